@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// A set of BED intervals per sequence, merged and sorted for lookup.
+#[derive(Debug, Default, Clone)]
+pub struct BedRegions {
+    intervals: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl BedRegions {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).context(format!("Failed to open BED file: {:?}", path.as_ref()))?;
+        let mut lines = Vec::new();
+        for line in BufReader::new(file).lines() {
+            lines.push(line.context("Failed to read BED line")?);
+        }
+        Self::from_lines(lines.iter().map(String::as_str))
+    }
+
+    pub fn from_lines<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Result<Self> {
+        let mut intervals: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 3 {
+                anyhow::bail!("BED line {} does not have enough fields", i + 1);
+            }
+            let start: usize = fields[1]
+                .parse()
+                .context(format!("Failed to parse BED start at line {}", i + 1))?;
+            let end: usize = fields[2]
+                .parse()
+                .context(format!("Failed to parse BED end at line {}", i + 1))?;
+            if end > start {
+                intervals
+                    .entry(fields[0].to_string())
+                    .or_default()
+                    .push((start, end));
+            }
+        }
+
+        for list in intervals.values_mut() {
+            list.sort();
+            let mut merged: Vec<(usize, usize)> = Vec::with_capacity(list.len());
+            for &(start, end) in list.iter() {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *list = merged;
+        }
+        Ok(BedRegions { intervals })
+    }
+
+    /// Merged intervals on `name` overlapping `[start, end)`, clipped to it.
+    pub fn overlapping(&self, name: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let list = match self.intervals.get(name) {
+            Some(list) => list,
+            None => return Vec::new(),
+        };
+        let first = list.partition_point(|&(_, e)| e <= start);
+        list[first..]
+            .iter()
+            .take_while(|&&(s, _)| s < end)
+            .map(|&(s, e)| (s.max(start), e.min(end)))
+            .collect()
+    }
+
+    pub fn overlaps(&self, name: &str, start: usize, end: usize) -> bool {
+        !self.overlapping(name, start, end).is_empty()
+    }
+
+    /// Number of bases of `[start, end)` on `name` covered by the regions.
+    pub fn covered_bases(&self, name: &str, start: usize, end: usize) -> usize {
+        self.overlapping(name, start, end)
+            .iter()
+            .map(|(s, e)| e - s)
+            .sum()
+    }
+
+    /// Per-base coverage of `[start, end)`, or `None` if nothing overlaps it.
+    pub fn local_mask(&self, name: &str, start: usize, end: usize) -> Option<Vec<bool>> {
+        let hits = self.overlapping(name, start, end);
+        if hits.is_empty() {
+            return None;
+        }
+        let mut mask = vec![false; end - start];
+        for (s, e) in hits {
+            mask[s - start..e - start]
+                .iter_mut()
+                .for_each(|m| *m = true);
+        }
+        Some(mask)
+    }
+}
+
+/// Writes BED6 lines (sequence, start, end, name, score, strand), in the
+/// order they come.
+pub struct BedWriter<W: Write> {
+    writer: W,
+    lines: usize,
+}
+
+impl<W: Write> BedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        BedWriter { writer, lines: 0 }
+    }
+
+    pub fn write(
+        &mut self,
+        sequence: &str,
+        start: usize,
+        end: usize,
+        name: &str,
+        score: usize,
+        strand: char,
+    ) -> Result<()> {
+        writeln!(
+            self.writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            sequence, start, end, name, score, strand
+        )?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Flushes the output and returns the number of lines written.
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush()?;
+        Ok(self.lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bed_regions_merge_and_overlap() {
+        let regions =
+            BedRegions::from_lines(["# mask", "chr1\t10\t20", "chr1\t15\t30", "chr2\t0\t5"])
+                .unwrap();
+        assert_eq!(regions.overlapping("chr1", 0, 100), vec![(10, 30)]);
+        assert_eq!(regions.covered_bases("chr1", 25, 40), 5);
+        assert!(!regions.overlaps("chr1", 30, 40));
+        assert!(regions.local_mask("chr3", 0, 10).is_none());
+        assert_eq!(
+            regions.local_mask("chr2", 3, 7).unwrap(),
+            vec![true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_bed_writer() {
+        let mut output = Vec::new();
+        let mut writer = BedWriter::new(&mut output);
+        writer.write("chr1", 10, 11, "Mismatch", 0, '+').unwrap();
+        writer.write("chr2", 0, 5, "window", 950, '-').unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr1\t10\t11\tMismatch\t0\t+\nchr2\t0\t5\twindow\t950\t-\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::paf_parser::PafRecord;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// One record of a query, as needed for the best-alignment summary.
+#[derive(Debug, Clone)]
+struct Placement {
+    line_number: usize,
+    target_name: String,
+    target_start: usize,
+    target_end: usize,
+    query_start: usize,
+    query_end: usize,
+    identity: f64,
+}
+
+impl Placement {
+    /// Ranking score: identity times aligned query length.
+    fn score(&self) -> f64 {
+        self.identity * (self.query_end - self.query_start) as f64
+    }
+}
+
+/// Collects every query's placements and picks the best one per query.
+#[derive(Debug, Default)]
+pub struct BestAlignmentTable {
+    queries: HashMap<String, Vec<Placement>>,
+}
+
+impl BestAlignmentTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, record: &PafRecord, line_number: usize, identity: f64) {
+        self.queries
+            .entry(record.query_name.clone())
+            .or_default()
+            .push(Placement {
+                line_number,
+                target_name: record.target_name.clone(),
+                target_start: record.target_start,
+                target_end: record.target_end,
+                query_start: record.query_start,
+                query_end: record.query_end,
+                identity,
+            });
+    }
+
+    /// Writes one TSV row per query, sorted by name: its best placement, the
+    /// number of alignments, and how many other placements overlap the best
+    /// one on the query.
+    pub fn write_tsv<W: Write>(&self, output: &mut W) -> Result<()> {
+        writeln!(
+            output,
+            "#query\talignments\tbest_line\ttarget\ttarget_start\ttarget_end\tquery_start\tquery_end\tidentity\toverlapping_secondary"
+        )?;
+        let mut names: Vec<&String> = self.queries.keys().collect();
+        names.sort();
+        for name in names {
+            let placements = &self.queries[name];
+            let (best_idx, best) = placements
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| {
+                    a.score()
+                        .total_cmp(&b.score())
+                        .then(a.line_number.cmp(&b.line_number))
+                })
+                .unwrap();
+            let overlapping = placements
+                .iter()
+                .enumerate()
+                .filter(|(i, p)| {
+                    *i != best_idx
+                        && p.query_start < best.query_end
+                        && best.query_start < p.query_end
+                })
+                .count();
+            writeln!(
+                output,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.6}\t{}",
+                name,
+                placements.len(),
+                best.line_number,
+                best.target_name,
+                best.target_start,
+                best.target_end,
+                best.query_start,
+                best.query_end,
+                best.identity,
+                overlapping
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(query_start: usize, query_end: usize, target: &str) -> PafRecord {
+        PafRecord {
+            query_name: "contig1".to_string(),
+            query_length: 1000,
+            query_start,
+            query_end,
+            strand: '+',
+            target_name: target.to_string(),
+            target_length: 5000,
+            target_start: 0,
+            target_end: query_end - query_start,
+            residue_matches: 0,
+            block_length: 0,
+            mapq: Some(60),
+            cigar: String::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_best_alignment_by_identity_times_length() {
+        let mut table = BestAlignmentTable::new();
+        table.add(&record(0, 1000, "chr1"), 1, 0.90);
+        table.add(&record(0, 400, "chr2"), 2, 1.00);
+        table.add(&record(900, 1000, "chr3"), 3, 0.99);
+        let mut output = Vec::new();
+        table.write_tsv(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let row = output.lines().nth(1).unwrap();
+        assert_eq!(row, "contig1\t3\t1\tchr1\t0\t1000\t0\t1000\t0.900000\t2");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::fasta_reader::{fai_path, read_fai};
+use crate::md5::Md5;
+use anyhow::{Context, Result};
+use rust_htslib::faidx;
+use std::collections::HashMap;
+
+/// Bases fetched per request while hashing a sequence.
+const HASH_CHUNK: usize = 1 << 20;
+
+/// Computes the SAM `M5` checksum (MD5 of the uppercased sequence) of every
+/// sequence in an indexed FASTA, in index order.
+pub fn fasta_md5s(fasta: &str) -> Result<Vec<(String, String)>> {
+    let reader = faidx::Reader::from_path(fasta)
+        .context(format!("Failed to open FASTA file: {:?}", fasta))?;
+    let mut checksums = Vec::new();
+    for (name, length) in read_fai(&fai_path(fasta))? {
+        let mut hasher = Md5::new();
+        let mut start = 0;
+        while start < length {
+            let end = (start + HASH_CHUNK).min(length);
+            let chunk = reader
+                .fetch_seq(&name, start, end - 1)
+                .context(format!("Failed to fetch sequence: {}", name))?;
+            let normalized: Vec<u8> = chunk
+                .iter()
+                .filter(|b| (33..=126).contains(*b))
+                .map(|b| b.to_ascii_uppercase())
+                .collect();
+            hasher.update(&normalized);
+            start = end;
+        }
+        checksums.push((name, hasher.hex_digest()));
+    }
+    Ok(checksums)
+}
+
+/// Loads expected checksums from a Picard sequence dictionary (`@SQ` lines
+/// with `SN` and `M5`) or a two-column `name<TAB>md5` table.
+pub fn load_expected_md5s(path: &str) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read checksum file: {:?}", path))?;
+    let mut expected = HashMap::new();
+    for line in content.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('@') {
+            if !line.starts_with("@SQ") {
+                continue;
+            }
+            let mut name = None;
+            let mut md5 = None;
+            for field in line.split('\t') {
+                if let Some(value) = field.strip_prefix("SN:") {
+                    name = Some(value);
+                } else if let Some(value) = field.strip_prefix("M5:") {
+                    md5 = Some(value);
+                }
+            }
+            if let (Some(name), Some(md5)) = (name, md5) {
+                expected.insert(name.to_string(), md5.to_ascii_lowercase());
+            }
+        } else {
+            let (name, md5) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow::anyhow!("Checksum line is not name<TAB>md5: {}", line))?;
+            expected.insert(name.to_string(), md5.trim().to_ascii_lowercase());
+        }
+    }
+    Ok(expected)
+}
+
+/// A sequence whose checksum differs from the expected one.
+#[derive(Debug, PartialEq)]
+pub struct Md5Mismatch {
+    pub name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Compares computed checksums with expected ones. Sequences without an
+/// expected value are not checked.
+pub fn verify_md5s(
+    actual: &[(String, String)],
+    expected: &HashMap<String, String>,
+) -> Vec<Md5Mismatch> {
+    actual
+        .iter()
+        .filter_map(|(name, md5)| match expected.get(name) {
+            Some(expected_md5) if expected_md5 != md5 => Some(Md5Mismatch {
+                name: name.clone(),
+                expected: expected_md5.clone(),
+                actual: md5.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_md5s() {
+        let actual = vec![
+            ("chr1".to_string(), "aaaa".to_string()),
+            ("chr2".to_string(), "bbbb".to_string()),
+            ("chr3".to_string(), "cccc".to_string()),
+        ];
+        let mut expected = HashMap::new();
+        expected.insert("chr1".to_string(), "aaaa".to_string());
+        expected.insert("chr2".to_string(), "ffff".to_string());
+
+        assert_eq!(
+            verify_md5s(&actual, &expected),
+            vec![Md5Mismatch {
+                name: "chr2".to_string(),
+                expected: "ffff".to_string(),
+                actual: "bbbb".to_string(),
+            }]
+        );
+    }
+}
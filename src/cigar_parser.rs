@@ -1,33 +1,323 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
+use smallvec::SmallVec;
+use std::fmt;
+use std::ops::Deref;
 
 #[derive(Debug)]
 pub enum CigarOp {
+    /// Aligned bases that may match or mismatch (`M`), as in a CIGAR that
+    /// doesn't use the extended `=`/`X` operations.
+    AlignmentMatch(u64),
     Match(u64),
     Mismatch(u64),
     Insertion(u64),
     Deletion(u64),
+    /// Skipped target bases (`N`), i.e. an intron in a spliced alignment.
+    Skip(u64),
+    /// Clipped query bases (`S`, `H`) outside the aligned query span. PAF
+    /// coordinates already exclude them, so they consume nothing here.
+    SoftClip(u64),
+    HardClip(u64),
 }
 
-pub fn parse_cigar(cigar: &str) -> Result<Vec<CigarOp>> {
-    let mut ops = Vec::new();
-    let mut num = String::new();
+impl CigarOp {
+    pub fn is_clip(&self) -> bool {
+        matches!(self, CigarOp::SoftClip(_) | CigarOp::HardClip(_))
+    }
+
+    pub fn is_indel(&self) -> bool {
+        matches!(self, CigarOp::Insertion(_) | CigarOp::Deletion(_))
+    }
+
+    pub fn count(&self) -> u64 {
+        match self {
+            CigarOp::AlignmentMatch(len)
+            | CigarOp::Match(len)
+            | CigarOp::Mismatch(len)
+            | CigarOp::Insertion(len)
+            | CigarOp::Deletion(len)
+            | CigarOp::Skip(len)
+            | CigarOp::SoftClip(len)
+            | CigarOp::HardClip(len) => *len,
+        }
+    }
+
+    /// Query and target bases consumed by the operation.
+    pub fn consumed(&self) -> (usize, usize) {
+        let count = self.count() as usize;
+        match self {
+            CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_) => (count, count),
+            CigarOp::Insertion(_) => (count, 0),
+            CigarOp::Deletion(_) | CigarOp::Skip(_) => (0, count),
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => (0, 0),
+        }
+    }
+
+    /// The same operation with another count.
+    pub fn with_count(&self, count: u64) -> CigarOp {
+        match self {
+            CigarOp::AlignmentMatch(_) => CigarOp::AlignmentMatch(count),
+            CigarOp::Match(_) => CigarOp::Match(count),
+            CigarOp::Mismatch(_) => CigarOp::Mismatch(count),
+            CigarOp::Insertion(_) => CigarOp::Insertion(count),
+            CigarOp::Deletion(_) => CigarOp::Deletion(count),
+            CigarOp::Skip(_) => CigarOp::Skip(count),
+            CigarOp::SoftClip(_) => CigarOp::SoftClip(count),
+            CigarOp::HardClip(_) => CigarOp::HardClip(count),
+        }
+    }
+
+    /// The operation's letter in a CIGAR string.
+    pub fn symbol(&self) -> char {
+        match self {
+            CigarOp::AlignmentMatch(_) => 'M',
+            CigarOp::Match(_) => '=',
+            CigarOp::Mismatch(_) => 'X',
+            CigarOp::Insertion(_) => 'I',
+            CigarOp::Deletion(_) => 'D',
+            CigarOp::Skip(_) => 'N',
+            CigarOp::SoftClip(_) => 'S',
+            CigarOp::HardClip(_) => 'H',
+        }
+    }
+}
+
+/// Total clipped bases (`S` and `H`) at the start and at the end of a CIGAR.
+pub fn clip_lengths(ops: &[CigarOp]) -> (usize, usize) {
+    let clipped = |op: &CigarOp| match op {
+        CigarOp::SoftClip(len) | CigarOp::HardClip(len) => Some(*len as usize),
+        _ => None,
+    };
+    let leading: usize = ops.iter().map_while(clipped).sum();
+    let trailing: usize = if ops.iter().all(CigarOp::is_clip) {
+        0
+    } else {
+        ops.iter().rev().map_while(clipped).sum()
+    };
+    (leading, trailing)
+}
+
+/// Indices of the first and last ops of a CIGAR that align bases (`=`, `X`,
+/// `M` or `N`), or `None` if there are none. The insertions and deletions
+/// outside them are the ones the alignment begins or ends with.
+pub fn aligned_range(ops: &[CigarOp]) -> Option<(usize, usize)> {
+    let is_aligned = |op: &CigarOp| !op.is_clip() && !op.is_indel();
+    Some((
+        ops.iter().position(is_aligned)?,
+        ops.iter().rposition(is_aligned)?,
+    ))
+}
+
+/// Parsed CIGAR operations. Typical CIGARs fit inline without a heap allocation.
+pub type CigarOps = SmallVec<[CigarOp; 16]>;
+
+/// A parsed CIGAR, written out as its CIGAR string by `Display`. It
+/// dereferences to its operations.
+#[derive(Debug, Default)]
+pub struct Cigar(pub CigarOps);
+
+impl Cigar {
+    /// Parses `cigar` with `parse_cigar_lenient`, so that CIGARs with
+    /// zero-length operations can be normalized.
+    pub fn parse_lenient(cigar: &str) -> Result<Self> {
+        parse_cigar_lenient(cigar).map(Cigar)
+    }
+
+    /// The normalized form: zero-length operations are dropped and adjacent
+    /// operations of the same type merged, so that `3=0X2=1I1I` becomes
+    /// `5=2I`. Written out, this is the canonical CIGAR string.
+    pub fn normalize(&self) -> Cigar {
+        let mut normalized = CigarOps::new();
+        for op in self.iter().filter(|op| op.count() > 0) {
+            match normalized.last_mut() {
+                Some(last) if last.symbol() == op.symbol() => {
+                    *last = last.with_count(last.count() + op.count())
+                }
+                _ => normalized.push(op.with_count(op.count())),
+            }
+        }
+        Cigar(normalized)
+    }
+
+    pub fn into_ops(self) -> CigarOps {
+        self.0
+    }
+}
+
+impl From<&[CigarOp]> for Cigar {
+    fn from(ops: &[CigarOp]) -> Self {
+        Cigar(ops.iter().map(|op| op.with_count(op.count())).collect())
+    }
+}
+
+impl Deref for Cigar {
+    type Target = [CigarOp];
+
+    fn deref(&self) -> &[CigarOp] {
+        &self.0
+    }
+}
 
-    for c in cigar.chars() {
-        if c.is_ascii_digit() {
-            num.push(c);
+impl fmt::Display for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_cigar(self))
+    }
+}
+
+/// Writes `ops` back out as a CIGAR string.
+pub fn format_cigar(ops: &[CigarOp]) -> String {
+    ops.iter()
+        .map(|op| format!("{}{}", op.count(), op.symbol()))
+        .collect()
+}
+
+pub fn parse_cigar(cigar: &str) -> Result<CigarOps> {
+    let mut ops = CigarOps::new();
+    parse_cigar_into(cigar, &mut ops)?;
+    Ok(ops)
+}
+
+/// Parses `cigar` into `ops`, reusing its storage. Callers that parse many
+/// records can keep one buffer so that very long CIGARs only allocate once.
+pub fn parse_cigar_into(cigar: &str, ops: &mut CigarOps) -> Result<()> {
+    parse_ops(cigar, ops, false)
+}
+
+/// Like `parse_cigar`, but accepts zero-length operations, so that CIGARs
+/// with them can be normalized.
+pub fn parse_cigar_lenient(cigar: &str) -> Result<CigarOps> {
+    let mut ops = CigarOps::new();
+    parse_ops(cigar, &mut ops, true)?;
+    Ok(ops)
+}
+
+fn parse_ops(cigar: &str, ops: &mut CigarOps, allow_zero: bool) -> Result<()> {
+    ops.clear();
+    // The count being read and the position of its first digit.
+    let mut num: Option<(u64, usize)> = None;
+
+    for (position, c) in cigar.char_indices() {
+        if let Some(digit) = c.to_digit(10) {
+            let (count, start) = num.unwrap_or((0, position));
+            let count = count
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(digit as u64))
+                .ok_or_else(|| CigarSyntaxError::new(start, "operation count overflows"))?;
+            num = Some((count, start));
         } else {
-            let count = num
-                .parse::<u64>()
-                .context("Failed to parse CIGAR operation count")?;
+            let (count, start) = num.ok_or_else(|| {
+                CigarSyntaxError::new(position, format!("operation {} has no count", c))
+            })?;
+            if count == 0 && !allow_zero {
+                return Err(
+                    CigarSyntaxError::new(start, format!("zero-length operation 0{}", c)).into(),
+                );
+            }
             match c {
+                'M' => ops.push(CigarOp::AlignmentMatch(count)),
                 '=' => ops.push(CigarOp::Match(count)),
                 'X' => ops.push(CigarOp::Mismatch(count)),
                 'I' => ops.push(CigarOp::Insertion(count)),
                 'D' => ops.push(CigarOp::Deletion(count)),
-                _ => anyhow::bail!("Unknown CIGAR operation: {}", c),
+                'N' => ops.push(CigarOp::Skip(count)),
+                'S' => ops.push(CigarOp::SoftClip(count)),
+                'H' => ops.push(CigarOp::HardClip(count)),
+                _ => {
+                    return Err(
+                        CigarSyntaxError::new(position, format!("unknown operation {}", c)).into(),
+                    )
+                }
             }
-            num.clear();
+            num = None;
         }
     }
-    Ok(ops)
+    if let Some((count, start)) = num {
+        return Err(
+            CigarSyntaxError::new(start, format!("count {} has no operation", count)).into(),
+        );
+    }
+    Ok(())
+}
+
+/// A CIGAR string that isn't a sequence of `<count><op>` pairs with positive
+/// counts and known operations.
+#[derive(Debug, thiserror::Error)]
+#[error("Malformed CIGAR at position {position}: {reason}")]
+pub struct CigarSyntaxError {
+    /// 0-based offset of the offending character in the CIGAR string.
+    pub position: usize,
+    pub reason: String,
+}
+
+impl CigarSyntaxError {
+    fn new(position: usize, reason: impl Into<String>) -> Self {
+        CigarSyntaxError {
+            position,
+            reason: reason.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_lengths() {
+        let ops = parse_cigar("5H3S10=2I4=7S").unwrap();
+        assert_eq!(clip_lengths(&ops), (8, 7));
+        assert_eq!(clip_lengths(&parse_cigar("10=").unwrap()), (0, 0));
+    }
+
+    #[test]
+    fn test_aligned_range() {
+        let ops = parse_cigar("2S2I3D10=1I4X1D3S").unwrap();
+        assert_eq!(aligned_range(&ops), Some((3, 5)));
+        assert_eq!(aligned_range(&parse_cigar("3I2D").unwrap()), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        for (cigar, normalized) in [
+            ("3=0X2=1I1I", "5=2I"),
+            ("010=2X", "10=2X"),
+            ("2S3S4M0D4M", "5S8M"),
+            ("5=1X5=", "5=1X5="),
+        ] {
+            let cigar = Cigar::parse_lenient(cigar).unwrap();
+            assert_eq!(cigar.normalize().to_string(), normalized);
+        }
+        assert!(parse_cigar("3=0X2=").is_err());
+    }
+
+    #[test]
+    fn test_format_cigar() {
+        for cigar in ["5H3S10=2I4X1M7D20N7S", ""] {
+            assert_eq!(format_cigar(&parse_cigar(cigar).unwrap()), cigar);
+        }
+    }
+
+    #[test]
+    fn test_cigar_syntax_errors() {
+        for (cigar, message) in [
+            (
+                "5=0X3=",
+                "Malformed CIGAR at position 2: zero-length operation 0X",
+            ),
+            (
+                "5==",
+                "Malformed CIGAR at position 2: operation = has no count",
+            ),
+            ("5=3Z", "Malformed CIGAR at position 3: unknown operation Z"),
+            (
+                "5=12",
+                "Malformed CIGAR at position 2: count 12 has no operation",
+            ),
+        ] {
+            let error = parse_cigar(cigar).unwrap_err();
+            let syntax = error.downcast_ref::<CigarSyntaxError>().unwrap();
+            assert_eq!(syntax.to_string(), message);
+        }
+        assert!(parse_cigar("").unwrap().is_empty());
+    }
 }
@@ -0,0 +1,112 @@
+/// DUST window length, in bases.
+pub const DUST_WINDOW: usize = 64;
+/// DUST score above which a window is considered low-complexity.
+pub const DUST_THRESHOLD: f64 = 20.0;
+
+/// Marks bases lying in at least one low-complexity window, using the
+/// symmetric DUST triplet score over a sliding window of `window` bases.
+pub fn low_complexity_mask(seq: &[u8], window: usize, threshold: f64) -> Vec<bool> {
+    let mut mask = vec![false; seq.len()];
+    if window < 4 || seq.len() < window {
+        return mask;
+    }
+    let triplets: Vec<Option<usize>> = seq
+        .windows(3)
+        .map(|w| {
+            let mut code = 0;
+            for &base in w {
+                code = code * 4 + base_code(base)?;
+            }
+            Some(code)
+        })
+        .collect();
+    let n_triplets = window - 2;
+
+    let mut counts = [0usize; 64];
+    let mut score = 0usize;
+    // coverage[i] > 0 once a masked window starts at i; closed at i + window
+    let mut coverage = vec![0i64; seq.len() + 1];
+    for (i, triplet) in triplets.iter().enumerate() {
+        if let Some(t) = triplet {
+            score += counts[*t];
+            counts[*t] += 1;
+        }
+        if i >= n_triplets {
+            if let Some(t) = triplets[i - n_triplets] {
+                counts[t] -= 1;
+                score -= counts[t];
+            }
+        }
+        if i + 1 >= n_triplets {
+            let start = i + 1 - n_triplets;
+            if score as f64 / (n_triplets - 1) as f64 > threshold {
+                coverage[start] += 1;
+                coverage[start + window] -= 1;
+            }
+        }
+    }
+
+    let mut depth = 0;
+    for (i, masked) in mask.iter_mut().enumerate() {
+        depth += coverage[i];
+        *masked = depth > 0;
+    }
+    mask
+}
+
+/// Width of the target window used for local GC content, in bases.
+pub const GC_WINDOW: usize = 100;
+
+/// Returns the GC fraction of the `window` bases of `seq` centred on `idx`,
+/// clamped to the sequence bounds. Only unambiguous bases are counted.
+pub fn local_gc_fraction(seq: &[u8], idx: usize, window: usize) -> Option<f64> {
+    let start = idx.saturating_sub(window / 2);
+    let end = (start + window).min(seq.len());
+    let (gc, acgt) = seq
+        .get(start..end)?
+        .iter()
+        .fold((0, 0), |(gc, acgt), &base| match base {
+            b'G' | b'g' | b'C' | b'c' => (gc + 1, acgt + 1),
+            b'A' | b'a' | b'T' | b't' => (gc, acgt + 1),
+            _ => (gc, acgt),
+        });
+    if acgt == 0 {
+        None
+    } else {
+        Some(gc as f64 / acgt as f64)
+    }
+}
+
+fn base_code(base: u8) -> Option<usize> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_low_complexity_mask() {
+        let mut seq = b"ACGTTGCATGCAAGTCCGATAGCTTACGGATCCTAGGCATCAGTTAGCCATGACTGAGTCAACG".to_vec();
+        let complex_len = seq.len();
+        seq.extend(std::iter::repeat_n(b'A', 100));
+
+        let mask = low_complexity_mask(&seq, DUST_WINDOW, DUST_THRESHOLD);
+        assert!(!mask[0]);
+        assert!(mask[complex_len + 20..].iter().all(|&m| m));
+    }
+
+    #[test]
+    fn test_local_gc_fraction() {
+        let seq = b"GGGGAAAANN";
+        assert_eq!(local_gc_fraction(seq, 0, 4), Some(1.0));
+        assert_eq!(local_gc_fraction(seq, 4, 8), Some(0.5));
+        assert_eq!(local_gc_fraction(seq, 9, 2), None);
+    }
+}
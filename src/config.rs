@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// A value in a config file. Numbers are kept as written and parsed by the
+/// option they are given for, like command-line values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+/// Options read from a TOML config file, keyed by their long command-line
+/// names (`max-indel-len`, or `max_indel_len`).
+///
+/// Only the part of TOML needed for options is supported: `key = value`
+/// lines with strings, numbers, booleans and single-line arrays of strings,
+/// `#` comments, and a `[check]` table holding the same keys.
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    entries: Vec<(String, ConfigValue)>,
+}
+
+impl Config {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = std::fs::read_to_string(&path)
+            .context(format!("Failed to read config file: {:?}", path.as_ref()))?;
+        Self::parse(&text).context(format!("Invalid config file: {:?}", path.as_ref()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries: Vec<(String, ConfigValue)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[') {
+                match table.strip_suffix(']').map(str::trim) {
+                    Some("check") => continue,
+                    Some(table) => anyhow::bail!("Unknown table [{}] at line {}", table, i + 1),
+                    None => anyhow::bail!("Malformed table header at line {}", i + 1),
+                }
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected key = value at line {}", i + 1))?;
+            let key = key.trim();
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                anyhow::bail!("Invalid key {:?} at line {}", key, i + 1);
+            }
+            let key = key.replace('_', "-");
+            if entries.iter().any(|(k, _)| *k == key) {
+                anyhow::bail!("Duplicate key {} at line {}", key, i + 1);
+            }
+            let value =
+                parse_value(value.trim()).context(format!("Invalid value at line {}", i + 1))?;
+            entries.push((key, value));
+        }
+        Ok(Config { entries })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &ConfigValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+/// Drops a `#` comment that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<ConfigValue> {
+    match value {
+        "true" => return Ok(ConfigValue::Bool(true)),
+        "false" => return Ok(ConfigValue::Bool(false)),
+        _ => {}
+    }
+    if let Some(items) = value.strip_prefix('[') {
+        let items = items
+            .strip_suffix(']')
+            .ok_or_else(|| anyhow::anyhow!("Arrays must be closed on the same line"))?;
+        let mut list = Vec::new();
+        let mut rest = items.trim();
+        while !rest.is_empty() {
+            let (item, tail) = parse_scalar_prefix(rest)?;
+            list.push(item);
+            rest = tail.trim_start();
+            rest = match rest.strip_prefix(',') {
+                Some(tail) => tail.trim_start(),
+                None if rest.is_empty() => rest,
+                None => anyhow::bail!("Expected , between array items"),
+            };
+        }
+        return Ok(ConfigValue::List(list));
+    }
+    let (scalar, tail) = parse_scalar_prefix(value)?;
+    if !tail.trim().is_empty() {
+        anyhow::bail!("Unexpected text after value: {}", tail.trim());
+    }
+    Ok(ConfigValue::String(scalar))
+}
+
+/// Parses a string or number at the start of `s`, returning it and the rest.
+fn parse_scalar_prefix(s: &str) -> Result<(String, &str)> {
+    if let Some(body) = s.strip_prefix('\'') {
+        let end = body
+            .find('\'')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated string"))?;
+        return Ok((body[..end].to_string(), &body[end + 1..]));
+    }
+    if let Some(body) = s.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = body.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((parsed, &body[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => parsed.push('\n'),
+                    Some('t') => parsed.push('\t'),
+                    Some('"') => parsed.push('"'),
+                    Some('\\') => parsed.push('\\'),
+                    other => anyhow::bail!("Unsupported escape \\{}", other.unwrap_or(' ')),
+                },
+                c => parsed.push(c),
+            }
+        }
+        anyhow::bail!("Unterminated string");
+    }
+    let end = s
+        .find(|c: char| c == ',' || c.is_whitespace())
+        .unwrap_or(s.len());
+    let number = &s[..end];
+    if number.is_empty()
+        || !number
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-._eE".contains(c))
+    {
+        anyhow::bail!("Expected a string, number, boolean or array: {}", s);
+    }
+    Ok((number.replace('_', ""), &s[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            "# QC setup\n\
+             query-fasta = \"asm.fa.gz\"\n\
+             [check]\n\
+             target_fasta = 'ref.fa.gz' # GRCh38\n\
+             paf = [\"a.paf\", \"b.paf\",]\n\
+             max-indel-len = 10_000\n\
+             check-splice-sites = true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.get("query-fasta"),
+            Some(&ConfigValue::String("asm.fa.gz".to_string()))
+        );
+        assert_eq!(
+            config.get("target-fasta"),
+            Some(&ConfigValue::String("ref.fa.gz".to_string()))
+        );
+        assert_eq!(
+            config.get("paf"),
+            Some(&ConfigValue::List(vec![
+                "a.paf".to_string(),
+                "b.paf".to_string()
+            ]))
+        );
+        assert_eq!(
+            config.get("max-indel-len"),
+            Some(&ConfigValue::String("10000".to_string()))
+        );
+        assert_eq!(
+            config.get("check-splice-sites"),
+            Some(&ConfigValue::Bool(true))
+        );
+
+        assert!(Config::parse("threads = 4\nthreads = 8\n").is_err());
+        assert!(Config::parse("[output]\nformat = \"json\"\n").is_err());
+        assert!(Config::parse("format = json\n").is_err());
+    }
+}
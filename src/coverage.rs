@@ -0,0 +1,83 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Union of aligned intervals per sequence, for coverage summaries.
+/// Overlapping intervals are merged as they are added, so memory grows with
+/// the covered runs rather than with the intervals.
+#[derive(Debug, Default)]
+pub struct CoverageTracker {
+    lengths: HashMap<String, usize>,
+    /// Disjoint covered runs per sequence, end by start.
+    runs: HashMap<String, BTreeMap<usize, usize>>,
+}
+
+impl CoverageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, length: usize, start: usize, end: usize) {
+        self.lengths.entry(name.to_string()).or_insert(length);
+        if end <= start {
+            return;
+        }
+        let runs = self.runs.entry(name.to_string()).or_default();
+        // The runs that overlap or touch the interval are the last ones
+        // starting at or before its end.
+        let merged: Vec<(usize, usize)> = runs
+            .range(..=end)
+            .rev()
+            .take_while(|(_, &run_end)| run_end >= start)
+            .map(|(&run_start, &run_end)| (run_start, run_end))
+            .collect();
+        let (mut start, mut end) = (start, end);
+        for (run_start, run_end) in merged {
+            runs.remove(&run_start);
+            start = start.min(run_start);
+            end = end.max(run_end);
+        }
+        runs.insert(start, end);
+    }
+
+    /// Number of bases of `name` covered by at least one interval.
+    pub fn covered_bases(&self, name: &str) -> usize {
+        self.runs
+            .get(name)
+            .map_or(0, |runs| runs.iter().map(|(start, end)| end - start).sum())
+    }
+
+    /// Sequences seen, with their lengths and covered bases, sorted by name.
+    pub fn sequences(&self) -> Vec<(&str, usize, usize)> {
+        let mut sequences: Vec<_> = self
+            .lengths
+            .iter()
+            .map(|(name, &length)| (name.as_str(), length, self.covered_bases(name)))
+            .collect();
+        sequences.sort_unstable();
+        sequences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covered_bases_merges_overlaps() {
+        let mut tracker = CoverageTracker::new();
+        tracker.add("contig1", 100, 0, 30);
+        tracker.add("contig1", 100, 20, 50);
+        tracker.add("contig1", 100, 60, 70);
+        tracker.add("contig2", 40, 0, 0);
+        assert_eq!(tracker.covered_bases("contig1"), 60);
+        assert_eq!(
+            tracker.sequences(),
+            vec![("contig1", 100, 60), ("contig2", 40, 0)]
+        );
+
+        // Bridges the gap between the two runs.
+        tracker.add("contig1", 100, 45, 65);
+        tracker.add("contig1", 100, 70, 80);
+        assert_eq!(tracker.covered_bases("contig1"), 80);
+        assert_eq!(tracker.runs["contig1"].len(), 1);
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::Result;
+
+use crate::cigar_parser::{CigarOp, CigarOps};
+
+/// One segment of a minimap2 `cs:Z` difference string. Bases are as written
+/// in the tag (lowercase in the short form); query bases of `-` strand
+/// records are reverse-complemented, like the query span they describe.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsOp {
+    /// `:n`, identical bases (short form).
+    Match(usize),
+    /// `=ACGT`, identical bases spelled out (long form).
+    Identical(String),
+    /// `*ac`, a target base substituted by a query base.
+    Substitution { target: u8, query: u8 },
+    /// `+ac`, bases inserted in the query.
+    Insertion(String),
+    /// `-ac`, bases deleted from the target.
+    Deletion(String),
+    /// `~gt10ag`, an intron of the given length with its flanking target
+    /// dinucleotides.
+    Intron {
+        donor: String,
+        len: usize,
+        acceptor: String,
+    },
+}
+
+impl CsOp {
+    /// Query and target bases consumed by the segment.
+    pub fn consumed(&self) -> (usize, usize) {
+        match self {
+            CsOp::Match(len) => (*len, *len),
+            CsOp::Identical(bases) => (bases.len(), bases.len()),
+            CsOp::Substitution { .. } => (1, 1),
+            CsOp::Insertion(bases) => (bases.len(), 0),
+            CsOp::Deletion(bases) => (0, bases.len()),
+            CsOp::Intron { len, .. } => (0, *len),
+        }
+    }
+}
+
+/// Parses a `cs:Z` value in the short or long form.
+pub fn parse_cs(cs: &str) -> Result<Vec<CsOp>> {
+    let bytes = cs.as_bytes();
+    let mut ops = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let op = bytes[i];
+        let start = i + 1;
+        let mut end = start;
+        match op {
+            b':' => {
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+                let len = cs[start..end]
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("cs segment at position {} has no length", i))?;
+                ops.push(CsOp::Match(len));
+            }
+            b'*' => {
+                end = start + 2;
+                match bytes.get(start..end) {
+                    Some([target, query])
+                        if target.is_ascii_alphabetic() && query.is_ascii_alphabetic() =>
+                    {
+                        ops.push(CsOp::Substitution {
+                            target: *target,
+                            query: *query,
+                        })
+                    }
+                    _ => anyhow::bail!("cs substitution at position {} needs two bases", i),
+                }
+            }
+            b'=' | b'+' | b'-' => {
+                while end < bytes.len() && bytes[end].is_ascii_alphabetic() {
+                    end += 1;
+                }
+                if end == start {
+                    anyhow::bail!("cs segment at position {} has no bases", i);
+                }
+                let bases = cs[start..end].to_string();
+                ops.push(match op {
+                    b'=' => CsOp::Identical(bases),
+                    b'+' => CsOp::Insertion(bases),
+                    _ => CsOp::Deletion(bases),
+                });
+            }
+            b'~' => {
+                let digits = cs[start..]
+                    .find(|c: char| c.is_ascii_digit())
+                    .map(|offset| start + offset);
+                let parsed = digits.and_then(|digits| {
+                    let len_end = cs[digits..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(cs.len(), |offset| digits + offset);
+                    let acceptor = cs.get(len_end..len_end + 2)?;
+                    Some((digits, len_end, acceptor))
+                });
+                match parsed {
+                    Some((digits, len_end, acceptor)) if digits == start + 2 => {
+                        ops.push(CsOp::Intron {
+                            donor: cs[start..digits].to_string(),
+                            len: cs[digits..len_end].parse()?,
+                            acceptor: acceptor.to_string(),
+                        });
+                        end = len_end + 2;
+                    }
+                    _ => anyhow::bail!("cs intron at position {} is malformed", i),
+                }
+            }
+            _ => anyhow::bail!("Unknown cs operation {:?} at position {}", op as char, i),
+        }
+        i = end;
+    }
+    Ok(ops)
+}
+
+/// Writes `ops` back out as a `cs` string.
+pub fn format_cs(ops: &[CsOp]) -> String {
+    ops.iter()
+        .map(|op| match op {
+            CsOp::Match(len) => format!(":{}", len),
+            CsOp::Identical(bases) => format!("={}", bases),
+            CsOp::Substitution { target, query } => {
+                format!("*{}{}", *target as char, *query as char)
+            }
+            CsOp::Insertion(bases) => format!("+{}", bases),
+            CsOp::Deletion(bases) => format!("-{}", bases),
+            CsOp::Intron {
+                donor,
+                len,
+                acceptor,
+            } => format!("~{}{}{}", donor, len, acceptor),
+        })
+        .collect()
+}
+
+/// The CIGAR equivalent of a `cs` string, with substitutions as `X` and
+/// introns as `N` ops. Adjacent segments of the same type are merged.
+pub fn cs_to_cigar(ops: &[CsOp]) -> CigarOps {
+    let mut cigar = CigarOps::new();
+    for op in ops {
+        let next = match op {
+            CsOp::Match(len) => CigarOp::Match(*len as u64),
+            CsOp::Identical(bases) => CigarOp::Match(bases.len() as u64),
+            CsOp::Substitution { .. } => CigarOp::Mismatch(1),
+            CsOp::Insertion(bases) => CigarOp::Insertion(bases.len() as u64),
+            CsOp::Deletion(bases) => CigarOp::Deletion(bases.len() as u64),
+            CsOp::Intron { len, .. } => CigarOp::Skip(*len as u64),
+        };
+        match (cigar.last_mut(), &next) {
+            (Some(CigarOp::Match(len)), CigarOp::Match(more))
+            | (Some(CigarOp::Mismatch(len)), CigarOp::Mismatch(more))
+            | (Some(CigarOp::Insertion(len)), CigarOp::Insertion(more))
+            | (Some(CigarOp::Deletion(len)), CigarOp::Deletion(more)) => *len += more,
+            _ => cigar.push(next),
+        }
+    }
+    cigar
+}
+
+/// The `cs` string of a CIGAR over the aligned (uppercased) sequences it
+/// spans, in the short form or, with `long`, the long form. Clips are left
+/// out, as minimap2 does.
+pub fn cigar_to_cs(ops: &[CigarOp], query_seq: &[u8], target_seq: &[u8], long: bool) -> Vec<CsOp> {
+    let lower = |bases: &[u8]| String::from_utf8_lossy(bases).to_ascii_lowercase();
+    let mut cs = Vec::new();
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops {
+        let (q_len, t_len) = op.consumed();
+        let query = &query_seq[q_idx..q_idx + q_len];
+        let target = &target_seq[t_idx..t_idx + t_len];
+        match op {
+            CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_) => {
+                for (&q, &t) in query.iter().zip(target) {
+                    match cs.last_mut() {
+                        _ if q != t => cs.push(CsOp::Substitution {
+                            target: t.to_ascii_lowercase(),
+                            query: q.to_ascii_lowercase(),
+                        }),
+                        Some(CsOp::Match(len)) => *len += 1,
+                        Some(CsOp::Identical(bases)) => bases.push(t as char),
+                        _ if long => cs.push(CsOp::Identical((t as char).to_string())),
+                        _ => cs.push(CsOp::Match(1)),
+                    }
+                }
+            }
+            CigarOp::Insertion(_) => cs.push(CsOp::Insertion(lower(query))),
+            CigarOp::Deletion(_) => cs.push(CsOp::Deletion(lower(target))),
+            CigarOp::Skip(len) => cs.push(CsOp::Intron {
+                donor: lower(&target[..2.min(target.len())]),
+                len: *len as usize,
+                acceptor: lower(&target[target.len().saturating_sub(2)..]),
+            }),
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    cs
+}
+
+/// Places where the bases written in a `cs` string differ from the aligned
+/// (uppercased) sequences, as the query and target offsets of the segment
+/// and a description. Segments past the end of the sequences are left to the
+/// length checks.
+pub fn cs_base_errors(
+    ops: &[CsOp],
+    query_seq: &[u8],
+    target_seq: &[u8],
+) -> Vec<(usize, usize, String)> {
+    let mut errors = Vec::new();
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for (op_idx, op) in ops.iter().enumerate() {
+        let (q_len, t_len) = op.consumed();
+        let (Some(query), Some(target)) = (
+            query_seq.get(q_idx..q_idx + q_len),
+            target_seq.get(t_idx..t_idx + t_len),
+        ) else {
+            break;
+        };
+        let written: Vec<(&str, &[u8], &[u8])> = match op {
+            CsOp::Match(_) => Vec::new(),
+            CsOp::Identical(bases) => vec![
+                ("query", bases.as_bytes(), query),
+                ("target", bases.as_bytes(), target),
+            ],
+            CsOp::Substitution {
+                target: target_base,
+                query: query_base,
+            } => vec![
+                ("query", std::slice::from_ref(query_base), query),
+                ("target", std::slice::from_ref(target_base), target),
+            ],
+            CsOp::Insertion(bases) => vec![("query", bases.as_bytes(), query)],
+            CsOp::Deletion(bases) => vec![("target", bases.as_bytes(), target)],
+            CsOp::Intron {
+                donor, acceptor, ..
+            } => vec![
+                ("target", donor.as_bytes(), &target[..2.min(target.len())]),
+                (
+                    "target",
+                    acceptor.as_bytes(),
+                    &target[target.len().saturating_sub(2)..],
+                ),
+            ],
+        };
+        for (role, bases, actual) in written {
+            if !bases.eq_ignore_ascii_case(actual) {
+                errors.push((
+                    q_idx,
+                    t_idx,
+                    format!(
+                        "cs segment {} has {} bases {}, but the {} has {}",
+                        op_idx,
+                        role,
+                        String::from_utf8_lossy(bases),
+                        role,
+                        String::from_utf8_lossy(actual)
+                    ),
+                ));
+            }
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cigar_to_cs() {
+        let ops = crate::cigar_parser::parse_cigar("2S3=1X1I2=1D3N2=").unwrap();
+        let (query, target) = (b"ACGTTACGT", b"ACGAACGGTCGT");
+        assert_eq!(
+            format_cs(&cigar_to_cs(&ops, query, target, false)),
+            ":3*at+t:2-g~gt3tc:2"
+        );
+        assert_eq!(
+            format_cs(&cigar_to_cs(&ops, query, target, true)),
+            "=ACG*at+t=AC-g~gt3tc=GT"
+        );
+    }
+
+    #[test]
+    fn test_parse_cs() {
+        let ops = parse_cs(":6*ag:4+ac-t~gt20ag=ACG").unwrap();
+        assert_eq!(
+            ops,
+            [
+                CsOp::Match(6),
+                CsOp::Substitution {
+                    target: b'a',
+                    query: b'g'
+                },
+                CsOp::Match(4),
+                CsOp::Insertion("ac".to_string()),
+                CsOp::Deletion("t".to_string()),
+                CsOp::Intron {
+                    donor: "gt".to_string(),
+                    len: 20,
+                    acceptor: "ag".to_string()
+                },
+                CsOp::Identical("ACG".to_string()),
+            ]
+        );
+        let cigar: Vec<String> = cs_to_cigar(&ops)
+            .iter()
+            .map(|op| format!("{}{}", op.count(), op.symbol()))
+            .collect();
+        assert_eq!(cigar.concat(), "6=1X4=2I1D20N3=");
+        assert_eq!(format_cs(&ops), ":6*ag:4+ac-t~gt20ag=ACG");
+
+        assert!(parse_cs(":").is_err());
+        assert!(parse_cs("*a").is_err());
+        assert!(parse_cs(":5/3").is_err());
+    }
+
+    #[test]
+    fn test_cs_base_errors() {
+        let ops = parse_cs(":2*ag+c-t:1").unwrap();
+        assert!(cs_base_errors(&ops, b"ACGCA", b"ACATA").is_empty());
+        let errors = cs_base_errors(&ops, b"ACGGA", b"ACAGA");
+        assert_eq!(
+            errors,
+            [
+                (
+                    3,
+                    3,
+                    "cs segment 2 has query bases c, but the query has G".to_string()
+                ),
+                (
+                    4,
+                    3,
+                    "cs segment 3 has target bases t, but the target has G".to_string()
+                ),
+            ]
+        );
+    }
+}
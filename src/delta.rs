@@ -0,0 +1,228 @@
+use crate::cigar_parser::{Cigar, CigarOp};
+use anyhow::{Context, Result};
+
+/// The sequence pair of the alignments that follow a `>` line.
+#[derive(Debug, Clone)]
+struct SequencePair {
+    reference: String,
+    query: String,
+    reference_length: usize,
+    query_length: usize,
+}
+
+/// Converts one nucmer alignment, its header line (`rs re qs qe errors
+/// similarity_errors stops`, 1-based and inclusive, with `qs > qe` for the
+/// reverse strand) and its delta integers, into a PAF line. Deltas don't say
+/// which aligned bases match, so the CIGAR has `M` operations; nucmer's
+/// error count becomes the `NM:i` tag, which validation checks against the
+/// sequences.
+fn alignment_to_paf(pair: &SequencePair, header: &[usize], deltas: &[i64]) -> Result<String> {
+    let [reference_start, reference_end, query_start, query_end, errors, ..] = *header else {
+        anyhow::bail!("Delta alignment header does not have 7 fields");
+    };
+    if reference_start == 0 || query_start == 0 || reference_start > reference_end {
+        anyhow::bail!("Invalid delta alignment coordinates");
+    }
+    let strand = if query_start > query_end { '-' } else { '+' };
+    let (query_start, query_end) = (query_start.min(query_end), query_start.max(query_end));
+    let reference_span = reference_end - reference_start + 1;
+
+    // Each delta d aligns |d| - 1 bases, then skips a query base (an
+    // insertion, for negative d) or a reference base (a deletion).
+    let mut ops = Vec::new();
+    let (mut aligned, mut indel_bases, mut reference_used) = (0, 0, 0);
+    for &delta in deltas {
+        let run = delta.unsigned_abs() as usize - 1;
+        ops.push(CigarOp::AlignmentMatch(run as u64));
+        ops.push(if delta < 0 {
+            CigarOp::Insertion(1)
+        } else {
+            CigarOp::Deletion(1)
+        });
+        aligned += run;
+        indel_bases += 1;
+        reference_used += run + usize::from(delta > 0);
+    }
+    // The rest of the reference interval is aligned; a query interval of
+    // another length is left for validation to report.
+    let rest = reference_span.saturating_sub(reference_used);
+    ops.push(CigarOp::AlignmentMatch(rest as u64));
+    aligned += rest;
+
+    let mismatches = errors.saturating_sub(indel_bases);
+    Ok(format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t255\tNM:i:{}\tcg:Z:{}",
+        pair.query,
+        pair.query_length,
+        query_start - 1,
+        query_end,
+        strand,
+        pair.reference,
+        pair.reference_length,
+        reference_start - 1,
+        reference_end,
+        aligned.saturating_sub(mismatches),
+        aligned + indel_bases,
+        errors,
+        Cigar::from(&ops[..]).normalize()
+    ))
+}
+
+/// The alignments of a nucmer `.delta` file as PAF lines, each numbered by
+/// the (0-based) line of its header. The reference is the target.
+pub struct DeltaRecords<I: Iterator<Item = Result<String>>> {
+    lines: std::iter::Enumerate<I>,
+    pair: Option<SequencePair>,
+}
+
+impl<I: Iterator<Item = Result<String>>> DeltaRecords<I> {
+    pub fn new(lines: I) -> Self {
+        DeltaRecords {
+            lines: lines.enumerate(),
+            pair: None,
+        }
+    }
+
+    /// Reads the delta integers of an alignment, up to the closing 0.
+    fn read_deltas(&mut self) -> Result<Vec<i64>> {
+        let mut deltas = Vec::new();
+        for (number, line) in self.lines.by_ref() {
+            let line = line?;
+            let delta: i64 = line.trim().parse().context(format!(
+                "Invalid delta at line {}: {}",
+                number + 1,
+                line
+            ))?;
+            if delta == 0 {
+                return Ok(deltas);
+            }
+            deltas.push(delta);
+        }
+        anyhow::bail!("Delta file ends inside an alignment")
+    }
+
+    fn convert(&mut self, line_number: usize, line: &str) -> Result<String> {
+        let header: Vec<usize> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .context(format!("Invalid delta alignment header: {}", line))?;
+        let deltas = self.read_deltas()?;
+        let pair = self.pair.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Delta alignment at line {} comes before any > line",
+                line_number + 1
+            )
+        })?;
+        alignment_to_paf(pair, &header, &deltas)
+    }
+}
+
+impl<I: Iterator<Item = Result<String>>> Iterator for DeltaRecords<I> {
+    type Item = (usize, Result<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (line_number, line) = self.lines.next()?;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some((line_number, Err(e))),
+            };
+            // Protein alignments have amino acid deltas and frame-shifted
+            // coordinates.
+            if line_number == 1 && line.trim() != "NUCMER" {
+                self.lines.by_ref().for_each(drop);
+                return Some((
+                    line_number,
+                    Err(anyhow::anyhow!(
+                        "Only nucmer delta files are supported, not {}",
+                        line.trim()
+                    )),
+                ));
+            }
+            // The file paths and program lines.
+            if line_number < 2 || line.trim().is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('>') {
+                let fields: Vec<&str> = header.split_whitespace().collect();
+                let lengths = match fields[..] {
+                    [_, _, reference_length, query_length] => {
+                        reference_length.parse().ok().zip(query_length.parse().ok())
+                    }
+                    _ => None,
+                };
+                let Some((reference_length, query_length)) = lengths else {
+                    return Some((
+                        line_number,
+                        Err(anyhow::anyhow!("Invalid delta sequence line: {}", line)),
+                    ));
+                };
+                self.pair = Some(SequencePair {
+                    reference: fields[0].to_string(),
+                    query: fields[1].to_string(),
+                    reference_length,
+                    query_length,
+                });
+                continue;
+            }
+            return Some((line_number, self.convert(line_number, &line)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_records() {
+        let delta = "/data/ref.fa /data/qry.fa\n\
+                     NUCMER\n\
+                     >chr1 ctg1 100 50\n\
+                     11 30 1 20 3 3 0\n\
+                     5\n\
+                     -3\n\
+                     0\n\
+                     41 50 50 41 0 0 0\n\
+                     0\n";
+        let records: Vec<(usize, String)> =
+            DeltaRecords::new(delta.lines().map(|line| Ok(line.to_string())))
+                .map(|(number, record)| (number, record.unwrap()))
+                .collect();
+        assert_eq!(
+            records,
+            [
+                // 4 aligned bases, a reference base, 2 aligned bases, a query
+                // base and the 13 remaining reference bases; 1 mismatch.
+                (
+                    3,
+                    "ctg1\t50\t0\t20\t+\tchr1\t100\t10\t30\t18\t21\t255\tNM:i:3\tcg:Z:4M1D2M1I13M"
+                        .to_string()
+                ),
+                (
+                    7,
+                    "ctg1\t50\t40\t50\t-\tchr1\t100\t40\t50\t10\t10\t255\tNM:i:0\tcg:Z:10M"
+                        .to_string()
+                ),
+            ]
+        );
+
+        let truncated: Vec<_> = DeltaRecords::new(
+            "a b\nNUCMER\n>r q 10 10\n1 10 1 10 0 0 0\n5\n"
+                .lines()
+                .map(|l| Ok(l.to_string())),
+        )
+        .collect();
+        assert!(truncated[0].1.is_err());
+
+        let promer: Vec<_> = DeltaRecords::new(
+            "a b\nPROMER\n>r q 10 10\n1 9 1 9 0 0 0\n0\n"
+                .lines()
+                .map(|l| Ok(l.to_string())),
+        )
+        .collect();
+        assert_eq!(promer.len(), 1);
+        assert!(promer[0].1.is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// Counts validation findings per fixed-size window along each target, for
+/// output as a browser track.
+#[derive(Debug)]
+pub struct ErrorDensity {
+    window: usize,
+    lengths: HashMap<String, usize>,
+    counts: HashMap<String, BTreeMap<usize, usize>>,
+}
+
+impl ErrorDensity {
+    pub fn new(window: usize) -> Self {
+        ErrorDensity {
+            window: window.max(1),
+            lengths: HashMap::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Registers a target so that it appears in the track even without findings.
+    pub fn observe_target(&mut self, name: &str, length: usize) {
+        self.lengths.entry(name.to_string()).or_insert(length);
+    }
+
+    pub fn add(&mut self, name: &str, pos: usize) {
+        *self
+            .counts
+            .entry(name.to_string())
+            .or_default()
+            .entry(pos / self.window)
+            .or_insert(0) += 1;
+    }
+
+    fn targets(&self) -> Vec<(&str, usize)> {
+        let mut targets: Vec<_> = self
+            .lengths
+            .iter()
+            .map(|(name, &length)| (name.as_str(), length))
+            .collect();
+        targets.sort_unstable();
+        targets
+    }
+
+    /// Writes windows with at least one finding as bedGraph.
+    pub fn write_bedgraph<W: Write>(&self, output: &mut W) -> Result<()> {
+        writeln!(
+            output,
+            "track type=bedGraph name=\"pafcheck errors\" description=\"validation findings per {} bp\"",
+            self.window
+        )?;
+        for (name, length) in self.targets() {
+            let Some(counts) = self.counts.get(name) else {
+                continue;
+            };
+            for (&bin, &count) in counts {
+                let start = bin * self.window;
+                let end = (start + self.window).min(length.max(start + 1));
+                writeln!(output, "{}\t{}\t{}\t{}", name, start, end, count)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every window of every observed target as fixedStep wiggle.
+    pub fn write_wig<W: Write>(&self, output: &mut W) -> Result<()> {
+        writeln!(
+            output,
+            "track type=wiggle_0 name=\"pafcheck errors\" description=\"validation findings per {} bp\"",
+            self.window
+        )?;
+        for (name, length) in self.targets() {
+            if length == 0 {
+                continue;
+            }
+            writeln!(
+                output,
+                "fixedStep chrom={} start=1 step={} span={}",
+                name, self.window, self.window
+            )?;
+            let counts = self.counts.get(name);
+            for bin in 0..length.div_ceil(self.window) {
+                let count = counts.and_then(|c| c.get(&bin)).copied().unwrap_or(0);
+                writeln!(output, "{}", count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_density_tracks() {
+        let mut density = ErrorDensity::new(100);
+        density.observe_target("chr1", 250);
+        density.observe_target("chr2", 50);
+        density.add("chr1", 10);
+        density.add("chr1", 99);
+        density.add("chr1", 240);
+
+        let mut bedgraph = Vec::new();
+        density.write_bedgraph(&mut bedgraph).unwrap();
+        let bedgraph = String::from_utf8(bedgraph).unwrap();
+        let rows: Vec<&str> = bedgraph.lines().skip(1).collect();
+        assert_eq!(rows, vec!["chr1\t0\t100\t2", "chr1\t200\t250\t1"]);
+
+        let mut wig = Vec::new();
+        density.write_wig(&mut wig).unwrap();
+        let wig = String::from_utf8(wig).unwrap();
+        let rows: Vec<&str> = wig.lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            vec![
+                "fixedStep chrom=chr1 start=1 step=100 span=100",
+                "2",
+                "0",
+                "1",
+                "fixedStep chrom=chr2 start=1 step=100 span=100",
+                "0"
+            ]
+        );
+    }
+}
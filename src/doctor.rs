@@ -0,0 +1,291 @@
+use crate::fasta_reader::{fai_path, read_fai};
+use crate::paf_input::{is_gzipped, PafLines};
+use rust_htslib::bgzf;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One diagnostic result, with a suggested fix for anything that isn't OK.
+#[derive(Debug)]
+pub struct Finding {
+    pub level: Level,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: String) -> Self {
+        Finding {
+            level: Level::Ok,
+            message,
+            fix: None,
+        }
+    }
+
+    fn problem(level: Level, message: String, fix: String) -> Self {
+        Finding {
+            level,
+            message,
+            fix: Some(fix),
+        }
+    }
+}
+
+/// Checks the inputs of a validation run without looking at alignment content.
+pub fn diagnose(query_fasta: &str, target_fasta: &str, paf_path: &str) -> Vec<Finding> {
+    let mut findings = vec![Finding::ok(format!("htslib version {}", htslib_version()))];
+
+    let query_names = check_fasta("query", query_fasta, &mut findings);
+    let target_names = if target_fasta == query_fasta {
+        query_names.clone()
+    } else {
+        check_fasta("target", target_fasta, &mut findings)
+    };
+
+    // `-` is stdin, which is read like a file below.
+    if paf_path == "-" {
+        findings.push(Finding::ok("PAF is read from stdin".to_string()));
+    } else if !Path::new(paf_path).is_file() {
+        findings.push(Finding::problem(
+            Level::Fail,
+            format!("PAF file {} does not exist", paf_path),
+            "check the -p path".to_string(),
+        ));
+        return findings;
+    }
+    let lines = match PafLines::open(paf_path, 1) {
+        Ok(lines) => lines,
+        Err(e) => {
+            findings.push(Finding::problem(
+                Level::Fail,
+                format!("PAF file {} cannot be read: {}", paf_path, e),
+                "check file permissions and compression".to_string(),
+            ));
+            return findings;
+        }
+    };
+
+    let mut paf_queries = HashSet::new();
+    let mut paf_targets = HashSet::new();
+    for (line_number, line) in lines.enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                findings.push(Finding::problem(
+                    Level::Fail,
+                    format!("PAF read failed at line {}: {}", line_number + 1, e),
+                    "re-generate or re-compress the PAF file".to_string(),
+                ));
+                break;
+            }
+        };
+        let mut fields = line.split('\t');
+        if let (Some(query), Some(target)) = (fields.next(), fields.nth(4)) {
+            paf_queries.insert(query.to_string());
+            paf_targets.insert(target.to_string());
+        }
+    }
+    findings.push(Finding::ok(format!(
+        "PAF references {} query and {} target sequences",
+        paf_queries.len(),
+        paf_targets.len()
+    )));
+
+    if let Some(names) = &query_names {
+        check_overlap("query", query_fasta, &paf_queries, names, &mut findings);
+    }
+    if let Some(names) = &target_names {
+        check_overlap("target", target_fasta, &paf_targets, names, &mut findings);
+    }
+    findings
+}
+
+fn htslib_version() -> String {
+    // SAFETY: hts_version returns a pointer to a static NUL-terminated string.
+    unsafe { CStr::from_ptr(rust_htslib::htslib::hts_version()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Checks one FASTA and its index, returning its sequence names if the index is readable.
+fn check_fasta(side: &str, fasta: &str, findings: &mut Vec<Finding>) -> Option<HashSet<String>> {
+    let fasta_meta = match std::fs::metadata(fasta) {
+        Ok(meta) => meta,
+        Err(_) => {
+            findings.push(Finding::problem(
+                Level::Fail,
+                format!("{} FASTA {} does not exist", side, fasta),
+                format!(
+                    "check the {} path",
+                    if side == "query" { "-q" } else { "-t" }
+                ),
+            ));
+            return None;
+        }
+    };
+
+    let gzipped = is_gzipped(fasta).unwrap_or(false);
+    if gzipped {
+        match bgzf::is_bgzip(fasta) {
+            Ok(true) => findings.push(Finding::ok(format!(
+                "{} FASTA {} is bgzip-compressed",
+                side, fasta
+            ))),
+            _ => findings.push(Finding::problem(
+                Level::Fail,
+                format!("{} FASTA {} is gzip- but not bgzip-compressed", side, fasta),
+                format!(
+                    "zcat {0} | bgzip > {0}.bgz && samtools faidx {0}.bgz",
+                    fasta
+                ),
+            )),
+        }
+    } else {
+        findings.push(Finding::ok(format!(
+            "{} FASTA {} is uncompressed",
+            side, fasta
+        )));
+    }
+
+    let mut indexes = vec![fai_path(fasta)];
+    if gzipped {
+        indexes.push(format!("{}.gzi", fasta));
+    }
+    for index in &indexes {
+        match std::fs::metadata(index) {
+            Err(_) => findings.push(Finding::problem(
+                Level::Fail,
+                format!("{} index {} is missing", side, index),
+                format!("samtools faidx {}", fasta),
+            )),
+            Ok(meta) => {
+                let stale = match (meta.modified(), fasta_meta.modified()) {
+                    (Ok(index_time), Ok(fasta_time)) => index_time < fasta_time,
+                    _ => false,
+                };
+                if stale {
+                    findings.push(Finding::problem(
+                        Level::Warn,
+                        format!("{} index {} is older than {}", side, index, fasta),
+                        format!("rm {} && samtools faidx {}", index, fasta),
+                    ));
+                } else {
+                    findings.push(Finding::ok(format!(
+                        "{} index {} is up to date",
+                        side, index
+                    )));
+                }
+            }
+        }
+    }
+
+    match read_fai(&fai_path(fasta)) {
+        Ok(sequences) => Some(sequences.into_iter().map(|(name, _)| name).collect()),
+        Err(_) => None,
+    }
+}
+
+fn check_overlap(
+    side: &str,
+    fasta: &str,
+    paf_names: &HashSet<String>,
+    fasta_names: &HashSet<String>,
+    findings: &mut Vec<Finding>,
+) {
+    let mut missing: Vec<&String> = paf_names.difference(fasta_names).collect();
+    if missing.is_empty() {
+        findings.push(Finding::ok(format!(
+            "all {} {} names in the PAF are present in {}",
+            paf_names.len(),
+            side,
+            fasta
+        )));
+        return;
+    }
+    missing.sort();
+    let examples: Vec<&str> = missing.iter().take(3).map(|s| s.as_str()).collect();
+    let fix = if missing.len() == paf_names.len() {
+        format!(
+            "no {} names match; check that -{} points at the FASTA the PAF was aligned against",
+            side,
+            if side == "query" { 'q' } else { 't' }
+        )
+    } else {
+        "check for renamed sequences or a different assembly version".to_string()
+    };
+    findings.push(Finding::problem(
+        Level::Fail,
+        format!(
+            "{} of {} {} names in the PAF are missing from {} (e.g. {})",
+            missing.len(),
+            paf_names.len(),
+            side,
+            fasta,
+            examples.join(", ")
+        ),
+        fix,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Writes `contents` to `name` in `dir`, returning its path.
+    fn write(dir: &Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name).to_str().unwrap().to_string();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn failures(findings: &[Finding]) -> Vec<&str> {
+        findings
+            .iter()
+            .filter(|finding| finding.level == Level::Fail)
+            .map(|finding| finding.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = write(dir.path(), "query.fa", ">q\nACGT\n");
+        let target = write(dir.path(), "target.fa", ">t\nACGT\n>u\nACGT\n");
+        let paf = write(
+            dir.path(),
+            "aln.paf",
+            "q\t4\t0\t4\t+\tt\t4\t0\t4\t4\t4\t60\n",
+        );
+
+        // Without indexes, and with a missing PAF.
+        let findings = diagnose(&query, &target, "missing.paf");
+        let failed = failures(&findings);
+        assert_eq!(failed.len(), 3, "{:?}", failed);
+        assert!(failed[0].starts_with("query index"));
+        assert_eq!(failed[2], "PAF file missing.paf does not exist");
+
+        for file in [&query, &target] {
+            std::fs::write(fai_path(file), "t\t4\t3\t4\t5\nu\t4\t12\t4\t5\n").unwrap();
+        }
+        let findings = diagnose(&query, &target, &paf);
+        // The query index names the wrong sequences.
+        assert_eq!(
+            failures(&findings),
+            [format!(
+                "1 of 1 query names in the PAF are missing from {} (e.g. q)",
+                query
+            )]
+        );
+        assert!(findings
+            .iter()
+            .any(|finding| finding.message == "PAF references 1 query and 1 target sequences"));
+    }
+}
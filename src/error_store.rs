@@ -0,0 +1,463 @@
+use anyhow::{Context, Result};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::provenance::InputFile;
+
+/// Prefix of the header lines recording input provenance.
+const INPUT_HEADER: &str = "#input\t";
+
+/// Number of entries buffered in memory before a sorted chunk is spilled to disk.
+const CHUNK_CAPACITY: usize = 1_000_000;
+/// Number of entries per indexed block in the final store.
+const BLOCK_SIZE: usize = 4096;
+
+/// One error occurrence, keyed by its target position.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StoreEntry {
+    pub target_name: String,
+    pub target_pos: usize,
+    pub line_number: usize,
+    pub error_type: String,
+    pub query_name: String,
+    pub query_pos: usize,
+}
+
+impl StoreEntry {
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.target_name,
+            self.target_pos,
+            self.target_pos + 1,
+            self.error_type,
+            self.query_name,
+            self.query_pos,
+            self.line_number
+        )
+    }
+
+    fn from_line(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            anyhow::bail!("Error store line does not have enough fields");
+        }
+        Ok(StoreEntry {
+            target_name: fields[0].to_string(),
+            target_pos: fields[1]
+                .parse()
+                .context("Failed to parse target position")?,
+            error_type: fields[3].to_string(),
+            query_name: fields[4].to_string(),
+            query_pos: fields[5]
+                .parse()
+                .context("Failed to parse query position")?,
+            line_number: fields[6].parse().context("Failed to parse line number")?,
+        })
+    }
+}
+
+/// Collects error entries and writes them as a position-sorted, block-indexed
+/// BED-like file. Entries beyond `CHUNK_CAPACITY` are sorted and spilled to
+/// temporary files, which are merged when the store is finished.
+pub struct ErrorStoreWriter {
+    path: PathBuf,
+    buffer: Vec<StoreEntry>,
+    chunks: Vec<NamedTempFile>,
+    inputs: Vec<InputFile>,
+}
+
+impl ErrorStoreWriter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ErrorStoreWriter {
+            path: path.as_ref().to_path_buf(),
+            buffer: Vec::new(),
+            chunks: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Records the run's input files as `#input` header lines of the store.
+    pub fn with_provenance(mut self, inputs: Vec<InputFile>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn push(&mut self, entry: StoreEntry) -> Result<()> {
+        self.buffer.push(entry);
+        if self.buffer.len() >= CHUNK_CAPACITY {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.buffer.sort();
+        let mut chunk = NamedTempFile::new().context("Failed to create error store chunk")?;
+        {
+            let mut writer = BufWriter::new(chunk.as_file_mut());
+            for entry in self.buffer.drain(..) {
+                writeln!(writer, "{}", entry.to_line())?;
+            }
+            writer.flush()?;
+        }
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    /// Writes the sorted store and its `.idx` block index, returning the number of entries.
+    pub fn finish(mut self) -> Result<usize> {
+        let file = File::create(&self.path)
+            .context(format!("Failed to create error store: {:?}", self.path))?;
+        let mut writer = BufWriter::new(file);
+        let mut header_len = 0;
+        for input in &self.inputs {
+            let line = format!("{}{}", INPUT_HEADER, input.to_fields());
+            writeln!(writer, "{}", line)?;
+            header_len += line.len() as u64 + 1;
+        }
+        let mut sink = IndexedSink::new(writer, header_len);
+
+        if self.chunks.is_empty() {
+            self.buffer.sort();
+            for entry in &self.buffer {
+                sink.write(entry)?;
+            }
+        } else {
+            if !self.buffer.is_empty() {
+                self.spill()?;
+            }
+            let mut readers = Vec::new();
+            for chunk in &self.chunks {
+                let file = chunk
+                    .reopen()
+                    .context("Failed to reopen error store chunk")?;
+                readers.push(BufReader::new(file).lines());
+            }
+            let mut heap = BinaryHeap::new();
+            for (i, reader) in readers.iter_mut().enumerate() {
+                if let Some(line) = reader.next() {
+                    heap.push(Reverse((StoreEntry::from_line(&line?)?, i)));
+                }
+            }
+            while let Some(Reverse((entry, i))) = heap.pop() {
+                sink.write(&entry)?;
+                if let Some(line) = readers[i].next() {
+                    heap.push(Reverse((StoreEntry::from_line(&line?)?, i)));
+                }
+            }
+        }
+
+        let (count, index) = sink.finish()?;
+        let index_path = index_path(&self.path);
+        let mut index_writer = BufWriter::new(File::create(&index_path).context(format!(
+            "Failed to create error store index: {:?}",
+            index_path
+        ))?);
+        for block in index {
+            writeln!(
+                index_writer,
+                "{}\t{}\t{}\t{}\t{}",
+                block.target_name, block.first_pos, block.last_pos, block.offset, block.entries
+            )?;
+        }
+        index_writer.flush()?;
+        Ok(count)
+    }
+}
+
+struct IndexBlock {
+    target_name: String,
+    first_pos: usize,
+    last_pos: usize,
+    offset: u64,
+    entries: usize,
+}
+
+struct IndexedSink<W: Write> {
+    writer: W,
+    offset: u64,
+    count: usize,
+    blocks: Vec<IndexBlock>,
+}
+
+impl<W: Write> IndexedSink<W> {
+    fn new(writer: W, offset: u64) -> Self {
+        IndexedSink {
+            writer,
+            offset,
+            count: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, entry: &StoreEntry) -> Result<()> {
+        let start_block = match self.blocks.last() {
+            Some(block) => block.entries >= BLOCK_SIZE || block.target_name != entry.target_name,
+            None => true,
+        };
+        if start_block {
+            self.blocks.push(IndexBlock {
+                target_name: entry.target_name.clone(),
+                first_pos: entry.target_pos,
+                last_pos: entry.target_pos,
+                offset: self.offset,
+                entries: 0,
+            });
+        }
+        let block = self.blocks.last_mut().unwrap();
+        block.last_pos = entry.target_pos;
+        block.entries += 1;
+
+        let line = entry.to_line();
+        writeln!(self.writer, "{}", line)?;
+        self.offset += line.len() as u64 + 1;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(usize, Vec<IndexBlock>)> {
+        self.writer.flush()?;
+        Ok((self.count, self.blocks))
+    }
+}
+
+fn index_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+/// Random access to an error store written by `ErrorStoreWriter`.
+pub struct ErrorStoreReader {
+    file: File,
+    blocks: Vec<IndexBlock>,
+    inputs: Vec<InputFile>,
+}
+
+impl ErrorStoreReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).context(format!("Failed to open error store: {:?}", path))?;
+        let index_path = index_path(path);
+        let index_file = File::open(&index_path).context(format!(
+            "Failed to open error store index: {:?}",
+            index_path
+        ))?;
+
+        let mut blocks = Vec::new();
+        for line in BufReader::new(index_file).lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                anyhow::bail!("Error store index line does not have enough fields");
+            }
+            blocks.push(IndexBlock {
+                target_name: fields[0].to_string(),
+                first_pos: fields[1].parse().context("Failed to parse block start")?,
+                last_pos: fields[2].parse().context("Failed to parse block end")?,
+                offset: fields[3].parse().context("Failed to parse block offset")?,
+                entries: fields[4].parse().context("Failed to parse block size")?,
+            });
+        }
+
+        let mut inputs = Vec::new();
+        for line in BufReader::new(&file).lines() {
+            let line = line?;
+            match line.strip_prefix(INPUT_HEADER) {
+                Some(fields) => inputs.push(InputFile::from_fields(fields)?),
+                None => break,
+            }
+        }
+        Ok(ErrorStoreReader {
+            file,
+            blocks,
+            inputs,
+        })
+    }
+
+    /// Input files recorded when the store was written.
+    pub fn inputs(&self) -> &[InputFile] {
+        &self.inputs
+    }
+
+    /// Returns the entries on `target_name` with `start <= target_pos < end`,
+    /// reading only the blocks that overlap the region.
+    pub fn query(
+        &mut self,
+        target_name: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<StoreEntry>> {
+        let mut entries = Vec::new();
+        for block in &self.blocks {
+            if block.target_name != target_name || block.last_pos < start || block.first_pos >= end
+            {
+                continue;
+            }
+            self.file.seek(SeekFrom::Start(block.offset))?;
+            let reader = BufReader::new(&self.file);
+            for line in reader.lines().take(block.entries) {
+                let entry = StoreEntry::from_line(&line?)?;
+                if entry.target_pos >= start && entry.target_pos < end {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Returns all entries matching `filter`, using the block index when the
+    /// filter is restricted to a region.
+    pub fn select(&mut self, filter: &EntryFilter) -> Result<Vec<StoreEntry>> {
+        let candidates = match &filter.region {
+            Some((target_name, start, end)) => self.query(target_name, *start, *end)?,
+            None => {
+                self.file.seek(SeekFrom::Start(0))?;
+                let mut entries = Vec::new();
+                for line in BufReader::new(&self.file).lines() {
+                    let line = line?;
+                    if !line.starts_with('#') {
+                        entries.push(StoreEntry::from_line(&line)?);
+                    }
+                }
+                entries
+            }
+        };
+        Ok(candidates
+            .into_iter()
+            .filter(|entry| filter.matches(entry))
+            .collect())
+    }
+}
+
+/// Criteria for selecting entries from an error store. Unset fields match everything.
+#[derive(Debug, Default)]
+pub struct EntryFilter {
+    pub error_type: Option<String>,
+    /// Matches either the query or the target name.
+    pub sequence: Option<String>,
+    pub region: Option<(String, usize, usize)>,
+    pub line_number: Option<usize>,
+}
+
+impl EntryFilter {
+    pub fn matches(&self, entry: &StoreEntry) -> bool {
+        if let Some(error_type) = &self.error_type {
+            if !entry.error_type.eq_ignore_ascii_case(error_type) {
+                return false;
+            }
+        }
+        if let Some(sequence) = &self.sequence {
+            if &entry.query_name != sequence && &entry.target_name != sequence {
+                return false;
+            }
+        }
+        if let Some((target_name, start, end)) = &self.region {
+            if &entry.target_name != target_name
+                || entry.target_pos < *start
+                || entry.target_pos >= *end
+            {
+                return false;
+            }
+        }
+        if let Some(line_number) = self.line_number {
+            if entry.line_number != line_number {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parses a samtools-style region (`chr1`, `chr1:1,200,000-1,300,000`) into a
+/// sequence name and 0-based half-open interval.
+pub fn parse_region(region: &str) -> Result<(String, usize, usize)> {
+    match region.rsplit_once(':') {
+        Some((name, range)) => {
+            let range = range.replace(',', "");
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (start, end),
+                None => (range.as_str(), range.as_str()),
+            };
+            let start: usize = start.parse().context("Failed to parse region start")?;
+            let end: usize = if end.is_empty() {
+                usize::MAX
+            } else {
+                end.parse().context("Failed to parse region end")?
+            };
+            if start == 0 || end < start {
+                anyhow::bail!("Invalid region: {}", region);
+            }
+            Ok((name.to_string(), start - 1, end))
+        }
+        None => Ok((region.to_string(), 0, usize::MAX)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(target_name: &str, target_pos: usize) -> StoreEntry {
+        StoreEntry {
+            target_name: target_name.to_string(),
+            target_pos,
+            line_number: 1,
+            error_type: "Mismatch".to_string(),
+            query_name: "query".to_string(),
+            query_pos: target_pos,
+        }
+    }
+
+    #[test]
+    fn test_store_round_trip_region_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("errors.bed");
+
+        let input = InputFile {
+            role: "paf".to_string(),
+            path: "aln.paf".to_string(),
+            size: 10,
+            mtime: 0,
+            checksum: None,
+        };
+        let mut writer = ErrorStoreWriter::new(&path).with_provenance(vec![input.clone()]);
+        for pos in (0..10_000).rev() {
+            writer.push(entry("chr2", pos)).unwrap();
+            writer.push(entry("chr1", pos * 2)).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 20_000);
+
+        let mut reader = ErrorStoreReader::open(&path).unwrap();
+        assert_eq!(reader.inputs(), &[input]);
+        let hits = reader.query("chr1", 1000, 1010).unwrap();
+        let positions: Vec<usize> = hits.iter().map(|e| e.target_pos).collect();
+        assert_eq!(positions, vec![1000, 1002, 1004, 1006, 1008]);
+        assert!(reader.query("chr3", 0, usize::MAX).unwrap().is_empty());
+
+        let filter = EntryFilter {
+            sequence: Some("chr2".to_string()),
+            line_number: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(reader.select(&filter).unwrap().len(), 10_000);
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(
+            parse_region("chr1:1,200,000-1,300,000").unwrap(),
+            ("chr1".to_string(), 1_199_999, 1_300_000)
+        );
+        assert_eq!(
+            parse_region("chr1").unwrap(),
+            ("chr1".to_string(), 0, usize::MAX)
+        );
+        assert!(parse_region("chr1:0-10").is_err());
+    }
+}
@@ -1,11 +1,122 @@
+use crate::fastq::{is_fastq, FastqFile};
+use crate::refget::{RefgetClient, REFGET_PREFIX};
+use crate::rgfa::RgfaGraph;
 use anyhow::{Context, Result};
 use rust_htslib::faidx;
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Largest FASTA that is read into memory from a non-seekable input.
+pub const DEFAULT_STREAM_LIMIT: u64 = 4 << 30;
+
+/// Where the sequences of one side of the alignments come from.
+pub enum SequenceSource {
+    Faidx(faidx::Reader),
+    Refget(RefgetClient),
+    /// Sequences slurped from a named pipe or other non-seekable input.
+    InMemory(Rc<HashMap<String, String>>),
+    /// Reads with base qualities.
+    Fastq(FastqFile),
+}
+
+impl SequenceSource {
+    /// Opens `location` as a refget server if it starts with `refget:`,
+    /// otherwise as an indexed FASTA or uncompressed FASTQ file.
+    pub fn open(location: &str, refget_aliases: Option<&str>) -> Result<Self> {
+        Self::open_with_stream_limit(location, refget_aliases, DEFAULT_STREAM_LIMIT)
+    }
+
+    /// Like `open`, but FASTAs on non-seekable inputs (named pipes, process
+    /// substitution) are read into memory, up to `stream_limit` bytes.
+    pub fn open_with_stream_limit(
+        location: &str,
+        refget_aliases: Option<&str>,
+        stream_limit: u64,
+    ) -> Result<Self> {
+        if is_stream(location) {
+            return Self::slurp(location, stream_limit);
+        }
+        match location.strip_prefix(REFGET_PREFIX) {
+            Some(base_url) => {
+                let mut client = RefgetClient::new(base_url);
+                if let Some(aliases) = refget_aliases {
+                    client = client.with_aliases(aliases)?;
+                }
+                Ok(SequenceSource::Refget(client))
+            }
+            None if is_fastq(location) => Ok(SequenceSource::Fastq(FastqFile::open(location)?)),
+            None => Ok(SequenceSource::Faidx(
+                faidx::Reader::from_path(location)
+                    .context(format!("Failed to open FASTA file: {:?}", location))?,
+            )),
+        }
+    }
+
+    fn slurp(location: &str, stream_limit: u64) -> Result<Self> {
+        let file = std::fs::File::open(location)
+            .context(format!("Failed to open FASTA input: {:?}", location))?;
+        let mut content = String::new();
+        file.take(stream_limit + 1)
+            .read_to_string(&mut content)
+            .context(format!("Failed to read FASTA input: {:?}", location))?;
+        if content.len() as u64 > stream_limit {
+            anyhow::bail!(
+                "FASTA input {:?} is not seekable and larger than {} bytes; write it to a file and index it instead",
+                location,
+                stream_limit
+            );
+        }
+        Ok(SequenceSource::InMemory(Rc::new(parse_fasta(&content)?)))
+    }
+
+    /// A second handle on the same sequences, for in-memory sources that
+    /// can't be opened twice.
+    pub fn share(&self) -> Option<Self> {
+        match self {
+            SequenceSource::InMemory(sequences) => {
+                Some(SequenceSource::InMemory(Rc::clone(sequences)))
+            }
+            _ => None,
+        }
+    }
+
+    fn fetch(&self, seq_name: &str, start: usize, end: usize) -> Result<String> {
+        match self {
+            SequenceSource::InMemory(sequences) => sequences
+                .get(seq_name)
+                .ok_or_else(|| anyhow::anyhow!("Sequence not found: {}", seq_name))?
+                .get(start..end)
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Interval {}-{} out of range for sequence: {}",
+                        start,
+                        end,
+                        seq_name
+                    )
+                }),
+            SequenceSource::Faidx(reader) => reader
+                .fetch_seq_string(seq_name, start, end - 1) // Adjust for 0-based indexing
+                .context(format!("Failed to fetch sequence: {}", seq_name)),
+            SequenceSource::Refget(client) => client.fetch(seq_name, start, end),
+            SequenceSource::Fastq(fastq) => fastq.fetch(seq_name, start, end),
+        }
+    }
+}
+
+/// Context of errors fetching query or target sequence, which tells them
+/// apart from problems with the alignment itself.
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to fetch {0} sequence")]
+pub struct FetchError(pub &'static str);
 
 pub struct MultiFastaReader {
-    query_reader: faidx::Reader,
-    target_reader: faidx::Reader,
+    query_reader: SequenceSource,
+    target_reader: SequenceSource,
+    target_graph: Option<Arc<RgfaGraph>>,
 }
 
 impl MultiFastaReader {
@@ -19,11 +130,20 @@ impl MultiFastaReader {
             target_fasta.as_ref()
         ))?;
         Ok(MultiFastaReader {
-            query_reader,
-            target_reader,
+            query_reader: SequenceSource::Faidx(query_reader),
+            target_reader: SequenceSource::Faidx(target_reader),
+            target_graph: None,
         })
     }
 
+    pub fn from_sources(query_source: SequenceSource, target_source: SequenceSource) -> Self {
+        MultiFastaReader {
+            query_reader: query_source,
+            target_reader: target_source,
+            target_graph: None,
+        }
+    }
+
     pub fn from_strings(query_fasta: &str, target_fasta: &str) -> Result<Self> {
         let query_map = parse_fasta(query_fasta)?;
         let target_map = parse_fasta(target_fasta)?;
@@ -32,39 +152,104 @@ impl MultiFastaReader {
         let target_reader = create_in_memory_reader(&target_map)?;
 
         Ok(MultiFastaReader {
-            query_reader,
-            target_reader,
+            query_reader: SequenceSource::Faidx(query_reader),
+            target_reader: SequenceSource::Faidx(target_reader),
+            target_graph: None,
         })
     }
 
+    /// Resolves target names against an rGFA graph (segment ids, oriented paths
+    /// and stable-sequence intervals) before falling back to the target FASTA.
+    pub fn with_target_graph(self, graph: RgfaGraph) -> Self {
+        self.with_shared_target_graph(Arc::new(graph))
+    }
+
+    /// Like `with_target_graph`, for a graph shared between several readers.
+    pub fn with_shared_target_graph(mut self, graph: Arc<RgfaGraph>) -> Self {
+        self.target_graph = Some(graph);
+        self
+    }
+
     pub fn fetch_query_sequence(&self, seq_name: &str, start: usize, end: usize) -> Result<String> {
-        self.fetch_sequence(&self.query_reader, seq_name, start, end)
-            .context("Failed to fetch query sequence")
+        self.query_reader
+            .fetch(seq_name, start, end)
+            .context(FetchError("query"))
     }
 
-    pub fn fetch_target_sequence(
+    /// Phred qualities of a query interval, if the queries come from a FASTQ.
+    pub fn fetch_query_qualities(
         &self,
         seq_name: &str,
         start: usize,
         end: usize,
-    ) -> Result<String> {
-        self.fetch_sequence(&self.target_reader, seq_name, start, end)
-            .context("Failed to fetch target sequence")
+    ) -> Result<Option<Vec<u8>>> {
+        match &self.query_reader {
+            SequenceSource::Fastq(fastq) => fastq
+                .fetch_qualities(seq_name, start, end)
+                .context(FetchError("query"))
+                .map(Some),
+            _ => Ok(None),
+        }
     }
 
-    fn fetch_sequence(
+    pub fn fetch_target_sequence(
         &self,
-        reader: &faidx::Reader,
         seq_name: &str,
         start: usize,
         end: usize,
     ) -> Result<String> {
-        reader
-            .fetch_seq_string(seq_name, start, end - 1) // Adjust for 0-based indexing
-            .context(format!("Failed to fetch sequence: {}", seq_name))
+        if let Some(graph) = &self.target_graph {
+            if let Some(sequence) = graph.fetch(seq_name, start, end) {
+                return Ok(sequence);
+            }
+            if graph.length(seq_name).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Target interval {}-{} out of range for rGFA sequence: {}",
+                    start,
+                    end,
+                    seq_name
+                )
+                .context(FetchError("target")));
+            }
+        }
+        self.target_reader
+            .fetch(seq_name, start, end)
+            .context(FetchError("target"))
     }
 }
 
+/// Whether `location` is an existing non-regular file (a FIFO, character
+/// device or `/dev/fd` entry) that can't be indexed or read at random.
+pub fn is_stream(location: &str) -> bool {
+    std::fs::metadata(location).is_ok_and(|metadata| {
+        let file_type = metadata.file_type();
+        !file_type.is_file() && !file_type.is_dir()
+    })
+}
+
+/// Path of the faidx index that belongs to a FASTA file.
+pub fn fai_path(fasta: &str) -> String {
+    format!("{}.fai", fasta)
+}
+
+/// Reads sequence names and lengths from a `.fai` index, in file order.
+pub fn read_fai(fai: &str) -> Result<Vec<(String, usize)>> {
+    let content =
+        std::fs::read_to_string(fai).context(format!("Failed to read FASTA index: {:?}", fai))?;
+    let mut sequences = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 2 {
+            anyhow::bail!("FASTA index line does not have enough fields: {}", line);
+        }
+        let length = fields[1]
+            .parse()
+            .context(format!("Failed to parse sequence length in {:?}", fai))?;
+        sequences.push((fields[0].to_string(), length));
+    }
+    Ok(sequences)
+}
+
 fn parse_fasta(fasta_content: &str) -> Result<HashMap<String, String>> {
     let mut sequences = HashMap::new();
     let mut current_seq_name = String::new();
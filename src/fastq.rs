@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::fasta_reader::fai_path;
+
+/// Location of one read in a FASTQ file, as in a `samtools fqidx` index.
+#[derive(Debug, Clone, PartialEq)]
+struct FastqEntry {
+    length: usize,
+    /// Byte offset of the first base.
+    offset: u64,
+    line_bases: usize,
+    line_width: usize,
+    /// Byte offset of the first quality.
+    quality_offset: u64,
+}
+
+/// An uncompressed FASTQ file read at random through its `.fai` index, which
+/// is built and written next to it (in the format of `samtools fqidx`) if it
+/// doesn't exist yet.
+pub struct FastqFile {
+    reader: RefCell<BufReader<File>>,
+    entries: HashMap<String, FastqEntry>,
+}
+
+impl FastqFile {
+    pub fn open(path: &str) -> Result<Self> {
+        let fai = fai_path(path);
+        let entries = if std::path::Path::new(&fai).exists() {
+            load_index(&fai)?
+        } else {
+            let entries = build_index(path)?;
+            // A read-only directory only costs rebuilding the index next time.
+            let _ = write_index(&fai, &entries);
+            entries.into_iter().collect()
+        };
+        let file = File::open(path).context(format!("Failed to open FASTQ file: {:?}", path))?;
+        Ok(FastqFile {
+            reader: RefCell::new(BufReader::new(file)),
+            entries,
+        })
+    }
+
+    pub fn fetch(&self, name: &str, start: usize, end: usize) -> Result<String> {
+        let entry = self.entry(name, start, end)?;
+        let bases = self.read(entry, entry.offset, start, end)?;
+        String::from_utf8(bases).context(format!("Invalid bases in FASTQ read: {}", name))
+    }
+
+    /// Phred base qualities (without the +33 offset) of `start..end`.
+    pub fn fetch_qualities(&self, name: &str, start: usize, end: usize) -> Result<Vec<u8>> {
+        let entry = self.entry(name, start, end)?;
+        let qualities = self.read(entry, entry.quality_offset, start, end)?;
+        Ok(qualities
+            .into_iter()
+            .map(|quality| quality.saturating_sub(33))
+            .collect())
+    }
+
+    fn entry(&self, name: &str, start: usize, end: usize) -> Result<&FastqEntry> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Sequence not found: {}", name))?;
+        if start > end || end > entry.length {
+            anyhow::bail!(
+                "Interval {}-{} out of range for sequence: {}",
+                start,
+                end,
+                name
+            );
+        }
+        Ok(entry)
+    }
+
+    /// Reads `start..end` of the wrapped lines beginning at `offset`.
+    fn read(&self, entry: &FastqEntry, offset: u64, start: usize, end: usize) -> Result<Vec<u8>> {
+        if start == end {
+            return Ok(Vec::new());
+        }
+        let position = |i: usize| {
+            offset + ((i / entry.line_bases) * entry.line_width + i % entry.line_bases) as u64
+        };
+        let first = position(start);
+        let last = position(end - 1);
+        let mut buffer = vec![0; (last - first + 1) as usize];
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(first))?;
+        reader.read_exact(&mut buffer)?;
+        buffer.retain(|&b| b != b'\n' && b != b'\r');
+        Ok(buffer)
+    }
+}
+
+/// Whether the file at `path` starts like a FASTQ file.
+pub fn is_fastq(path: &str) -> bool {
+    let mut first = [0];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut first))
+        .is_ok()
+        && first[0] == b'@'
+}
+
+/// Indexes a FASTQ file whose records each have one sequence and one quality
+/// line.
+fn build_index(path: &str) -> Result<Vec<(String, FastqEntry)>> {
+    let file = File::open(path).context(format!("Failed to open FASTQ file: {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    let mut lines = [String::new(), String::new(), String::new(), String::new()];
+    loop {
+        let mut lengths = [0; 4];
+        for (line, length) in lines.iter_mut().zip(&mut lengths) {
+            line.clear();
+            *length = reader.read_line(line)? as u64;
+        }
+        if lengths[0] == 0 {
+            break;
+        }
+        let [header, sequence, separator, qualities] = &lines;
+        let record = entries.len() + 1;
+        let name = header
+            .strip_prefix('@')
+            .and_then(|header| header.split_whitespace().next())
+            .ok_or_else(|| anyhow::anyhow!("FASTQ record {} has no @name line", record))?;
+        let bases = sequence.trim_end_matches(['\n', '\r']);
+        if !separator.starts_with('+')
+            || qualities.trim_end_matches(['\n', '\r']).len() != bases.len()
+        {
+            anyhow::bail!(
+                "FASTQ record {} ({}) is not a four-line record with as many qualities as bases; multi-line FASTQ needs a samtools fqidx index",
+                record,
+                name
+            );
+        }
+        entries.push((
+            name.to_string(),
+            FastqEntry {
+                length: bases.len(),
+                offset: offset + lengths[0],
+                line_bases: bases.len().max(1),
+                line_width: sequence.len().max(1),
+                quality_offset: offset + lengths[0] + lengths[1] + lengths[2],
+            },
+        ));
+        offset += lengths.iter().sum::<u64>();
+    }
+    Ok(entries)
+}
+
+fn write_index(fai: &str, entries: &[(String, FastqEntry)]) -> Result<()> {
+    let mut writer = std::io::BufWriter::new(File::create(fai)?);
+    for (name, entry) in entries {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            name,
+            entry.length,
+            entry.offset,
+            entry.line_bases,
+            entry.line_width,
+            entry.quality_offset
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn load_index(fai: &str) -> Result<HashMap<String, FastqEntry>> {
+    let content =
+        std::fs::read_to_string(fai).context(format!("Failed to read FASTQ index: {:?}", fai))?;
+    let mut entries = HashMap::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 6 {
+            anyhow::bail!("{:?} is not a FASTQ index: {}", fai, line);
+        }
+        let parse = |field: &str| -> Result<u64> {
+            field
+                .parse()
+                .context(format!("Invalid FASTQ index line in {:?}: {}", fai, line))
+        };
+        entries.insert(
+            fields[0].to_string(),
+            FastqEntry {
+                length: parse(fields[1])? as usize,
+                offset: parse(fields[2])?,
+                line_bases: (parse(fields[3])? as usize).max(1),
+                line_width: (parse(fields[4])? as usize).max(1),
+                quality_offset: parse(fields[5])?,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fastq_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reads.fq");
+        std::fs::write(
+            &path,
+            "@read1 extra\nACGTACGT\n+\nIIII####\n@read2\nGGCC\n+read2\n5555\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        assert!(is_fastq(path));
+
+        for _ in 0..2 {
+            // The second pass reads the index written by the first.
+            let fastq = FastqFile::open(path).unwrap();
+            assert_eq!(fastq.fetch("read1", 2, 6).unwrap(), "GTAC");
+            assert_eq!(
+                fastq.fetch_qualities("read1", 2, 6).unwrap(),
+                [40, 40, 2, 2]
+            );
+            assert_eq!(fastq.fetch("read2", 0, 4).unwrap(), "GGCC");
+            assert_eq!(fastq.fetch_qualities("read2", 3, 4).unwrap(), [20]);
+            assert!(fastq.fetch("read2", 0, 5).is_err());
+            assert!(fastq.fetch("read3", 0, 1).is_err());
+        }
+        assert!(std::fs::read_to_string(fai_path(path))
+            .unwrap()
+            .starts_with("read1\t8\t13\t8\t9\t24\n"));
+    }
+}
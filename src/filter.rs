@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::bed::BedRegions;
+use crate::paf_parser::PafRecord;
+
+/// Selects the records to validate by query and target name, and by target
+/// regions of interest.
+#[derive(Debug, Default, Clone)]
+pub struct RecordFilter {
+    query_name: Option<Regex>,
+    target_name: Option<Regex>,
+    regions: Option<BedRegions>,
+}
+
+impl RecordFilter {
+    /// Builds a filter from regular expressions that must match the whole
+    /// query or target name, so `chr6` doesn't select `chr16`.
+    pub fn from_patterns(query_name: Option<&str>, target_name: Option<&str>) -> Result<Self> {
+        let compile = |pattern: &str| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .context(format!("Invalid name pattern: {}", pattern))
+        };
+        Ok(RecordFilter {
+            query_name: query_name.map(compile).transpose()?,
+            target_name: target_name.map(compile).transpose()?,
+            regions: None,
+        })
+    }
+
+    /// Only selects records whose target interval overlaps `regions`.
+    pub fn with_regions(mut self, regions: BedRegions) -> Self {
+        self.regions = Some(regions);
+        self
+    }
+
+    /// Whether any records can be filtered out.
+    pub fn is_active(&self) -> bool {
+        self.query_name.is_some() || self.target_name.is_some() || self.regions.is_some()
+    }
+
+    pub fn selects(&self, record: &PafRecord) -> bool {
+        let matches =
+            |re: &Option<Regex>, name: &str| re.as_ref().is_none_or(|re| re.is_match(name));
+        matches(&self.query_name, &record.query_name)
+            && matches(&self.target_name, &record.target_name)
+            && self.regions.as_ref().is_none_or(|regions| {
+                regions.overlaps(&record.target_name, record.target_start, record.target_end)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_patterns() {
+        let record = |query: &str, target: &str| {
+            PafRecord::from_line(&format!(
+                "{}\t10\t0\t10\t+\t{}\t10\t0\t10\t10\t10\t60\tcg:Z:10=",
+                query, target
+            ))
+            .unwrap()
+        };
+        let filter = RecordFilter::from_patterns(None, Some("chr6.*")).unwrap();
+        assert!(filter.is_active());
+        assert!(filter.selects(&record("contig1", "chr6")));
+        assert!(filter.selects(&record("contig1", "chr6_GL000250v2_alt")));
+        assert!(!filter.selects(&record("contig1", "chr16")));
+
+        let filter = RecordFilter::from_patterns(Some("HG002#1#.*"), Some("chr6")).unwrap();
+        assert!(filter.selects(&record("HG002#1#h1tg7", "chr6")));
+        assert!(!filter.selects(&record("HG002#2#h2tg7", "chr6")));
+
+        assert!(!RecordFilter::from_patterns(None, None).unwrap().is_active());
+        assert!(RecordFilter::from_patterns(Some("chr6("), None).is_err());
+    }
+
+    #[test]
+    fn test_regions() {
+        let record = |target: &str, start: usize| {
+            PafRecord::from_line(&format!(
+                "q\t10\t0\t10\t+\t{}\t1000\t{}\t{}\t10\t10\t60\tcg:Z:10=",
+                target,
+                start,
+                start + 10
+            ))
+            .unwrap()
+        };
+        let regions = BedRegions::from_lines(["chr6\t100\t200"]).unwrap();
+        let filter = RecordFilter::default().with_regions(regions);
+        assert!(filter.is_active());
+        assert!(filter.selects(&record("chr6", 95)));
+        assert!(filter.selects(&record("chr6", 150)));
+        assert!(!filter.selects(&record("chr6", 90)));
+        assert!(!filter.selects(&record("chr6", 200)));
+        assert!(!filter.selects(&record("chr7", 150)));
+    }
+}
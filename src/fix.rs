@@ -0,0 +1,766 @@
+use anyhow::{Context, Result};
+
+use crate::cigar_parser::{aligned_range, clip_lengths, format_cigar, Cigar, CigarOp, CigarOps};
+use crate::cs_tag::{cigar_to_cs, format_cs, parse_cs, CsOp};
+use crate::fasta_reader::MultiFastaReader;
+use crate::paf_parser::PafRecord;
+use crate::validator::{
+    alignment_stats, cigar_stats, fetch_aligned_sequences, validate_record, AlignmentStats,
+    ValidationError,
+};
+
+/// Recomputes the `=`/`X` runs of `ops` by comparing the aligned (uppercased)
+/// sequences base by base. `M` ops are resolved into `=`/`X` runs the same
+/// way, so the result is an extended CIGAR; indels, introns and clips stay
+/// where they are. CIGARs that don't span the sequences exactly can't be
+/// fixed this way.
+pub fn resegment_cigar(ops: &[CigarOp], query_seq: &[u8], target_seq: &[u8]) -> Result<CigarOps> {
+    let mut fixed = CigarOps::new();
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops {
+        let (q_len, t_len) = op.consumed();
+        if q_idx + q_len > query_seq.len() || t_idx + t_len > target_seq.len() {
+            anyhow::bail!("CIGAR extends past the aligned sequences");
+        }
+        match op {
+            CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_) => {
+                let columns = query_seq[q_idx..q_idx + q_len]
+                    .iter()
+                    .zip(&target_seq[t_idx..t_idx + t_len]);
+                for (q, t) in columns {
+                    match (fixed.last_mut(), q == t) {
+                        (Some(CigarOp::Match(len)), true)
+                        | (Some(CigarOp::Mismatch(len)), false) => *len += 1,
+                        (_, true) => fixed.push(CigarOp::Match(1)),
+                        (_, false) => fixed.push(CigarOp::Mismatch(1)),
+                    }
+                }
+            }
+            CigarOp::Insertion(len) => fixed.push(CigarOp::Insertion(*len)),
+            CigarOp::Deletion(len) => fixed.push(CigarOp::Deletion(*len)),
+            CigarOp::Skip(len) => fixed.push(CigarOp::Skip(*len)),
+            CigarOp::SoftClip(len) => fixed.push(CigarOp::SoftClip(*len)),
+            CigarOp::HardClip(len) => fixed.push(CigarOp::HardClip(*len)),
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    if (q_idx, t_idx) != (query_seq.len(), target_seq.len()) {
+        anyhow::bail!(
+            "CIGAR covers {} query and {} target bases, but the record spans {} and {}",
+            q_idx,
+            t_idx,
+            query_seq.len(),
+            target_seq.len()
+        );
+    }
+    Ok(fixed)
+}
+
+/// Shifts each insertion and deletion of an extended CIGAR to its leftmost
+/// equivalent position, through the `=` run before it, like the
+/// normalization of indel variants. Aligners place indels in repeats
+/// differently; left-aligned CIGARs of the same alignment are identical.
+/// `ops` must span the aligned (uppercased) sequences, as after
+/// `resegment_cigar`.
+pub fn left_align_indels(ops: &[CigarOp], query_seq: &[u8], target_seq: &[u8]) -> CigarOps {
+    let mut aligned: Vec<CigarOp> = ops.iter().map(|op| op.with_count(op.count())).collect();
+    let first_aligned = aligned_range(&aligned).map(|(first, _)| first);
+    let (mut q_idx, mut t_idx) = (0, 0);
+    let mut i = 0;
+    while i < aligned.len() {
+        let (seq, start) = match aligned[i] {
+            CigarOp::Insertion(_) => (query_seq, q_idx),
+            CigarOp::Deletion(_) => (target_seq, t_idx),
+            _ => {
+                let (q_len, t_len) = aligned[i].consumed();
+                q_idx += q_len;
+                t_idx += t_len;
+                i += 1;
+                continue;
+            }
+        };
+        let len = aligned[i].count() as usize;
+        let before = match i.checked_sub(1).map(|j| &aligned[j]) {
+            Some(CigarOp::Match(before)) => *before as usize,
+            _ => 0,
+        };
+        // The first aligned column stays, or the indel would become a
+        // terminal one.
+        let movable = if Some(i.wrapping_sub(1)) == first_aligned {
+            before.saturating_sub(1)
+        } else {
+            before
+        };
+        // The indel can move left while the base it would uncover on the
+        // right matches the base it would cover on the left.
+        let shift = (0..movable.min(start))
+            .take_while(|k| seq[start - k - 1] == seq[start + len - k - 1])
+            .count();
+        let (q_len, t_len) = aligned[i].consumed();
+        if shift > 0 {
+            aligned[i - 1] = CigarOp::Match((before - shift) as u64);
+            aligned.insert(i + 1, CigarOp::Match(shift as u64));
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+        q_idx -= shift;
+        t_idx -= shift;
+        i += 1;
+    }
+    Cigar::from(&aligned[..]).normalize().into_ops()
+}
+
+/// The `cg:Z` CIGAR of a record, normalized and with its `=`/`X` runs
+/// recomputed from its aligned sequences (see `fetch_aligned_sequences`).
+pub fn fix_cigar(record: &PafRecord, query_seq: &[u8], target_seq: &[u8]) -> Result<CigarOps> {
+    let cigar = Cigar::parse_lenient(&record.cigar).context("Failed to parse CIGAR string")?;
+    resegment_cigar(&cigar.normalize(), query_seq, target_seq)
+}
+
+/// Options of `pafcheck fix`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixOptions {
+    /// Left-align indels, see `left_align_indels`.
+    pub left_align: bool,
+    /// Split records at longer indels, see `split_at_indels`.
+    pub split_at_indel: Option<usize>,
+}
+
+/// What `fix_line` changed in a record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineFixes {
+    /// The CIGAR was wrong, left-aligning and trimming aside.
+    pub cigar: bool,
+    /// The wrong CIGAR had `M` ops.
+    pub resolved: bool,
+    /// The wrong CIGAR wasn't normalized, e.g. `5=3=` or `5=0X3=`.
+    pub non_canonical: bool,
+    pub trimmed: bool,
+    pub left_aligned: bool,
+    pub columns: bool,
+    pub tags: bool,
+    /// Records the line was split into, if more than one.
+    pub pieces: Option<usize>,
+}
+
+impl LineFixes {
+    /// Whether the record was wrong, rather than only retagged or
+    /// transformed as asked.
+    pub fn corrected(&self) -> bool {
+        self.cigar || self.columns || self.trimmed
+    }
+}
+
+/// Fixes the CIGAR of one PAF line and everything that depends on it, as
+/// `pafcheck fix` does, returning the lines to write in its place (several
+/// if it was split) and what changed. Fields that didn't need a change are
+/// kept byte for byte. Lines without a CIGAR are returned as they are.
+pub fn fix_line(
+    line: &str,
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    options: &FixOptions,
+) -> Result<(Vec<String>, LineFixes)> {
+    let mut fixes = LineFixes::default();
+    if record.cigar.is_empty() {
+        return Ok((vec![line.to_string()], fixes));
+    }
+    let (query_seq, target_seq) = fetch_aligned_sequences(record, fasta_reader)?;
+    let ops = fix_cigar(record, &query_seq, &target_seq)?;
+    let fixed_cigar = format_cigar(&ops);
+    let ops = if options.left_align {
+        let shifted = left_align_indels(&ops, &query_seq, &target_seq);
+        fixes.left_aligned = format_cigar(&shifted) != fixed_cigar;
+        shifted
+    } else {
+        ops
+    };
+    if fixed_cigar != record.cigar {
+        fixes.cigar = true;
+        fixes.resolved = record.cigar.contains('M');
+        let canonical =
+            Cigar::parse_lenient(&record.cigar).map(|cigar| cigar.normalize().to_string());
+        fixes.non_canonical = canonical.is_ok_and(|canonical| canonical != record.cigar);
+    }
+
+    let (ops, trimmed) = trim_terminal_indels(&ops);
+    let cigar = format_cigar(&ops);
+    let mut fixed_line = line.to_string();
+    if cigar != record.cigar {
+        fixed_line = set_tag(&fixed_line, "cg:Z", &cigar);
+        // Rewritten from the final CIGAR, in the same form, so that the two
+        // agree.
+        if let Some(cs) = record.tag("cs:Z") {
+            let query_seq = &query_seq[trimmed.leading.0..query_seq.len() - trimmed.trailing.0];
+            let target_seq = &target_seq[trimmed.leading.1..target_seq.len() - trimmed.trailing.1];
+            let cs_ops = cigar_to_cs(&ops, query_seq, target_seq, cs.contains('='));
+            fixed_line = set_tag(&fixed_line, "cs:Z", &format_cs(&cs_ops));
+        }
+    }
+    if !trimmed.is_empty() {
+        fixed_line = trim_coordinates(&fixed_line, record, &trimmed);
+        fixes.trimmed = true;
+    }
+    // The fixed CIGAR's labels are exact, so its stats are the sequences'.
+    let stats = cigar_stats(&ops);
+    let (fixed_line, columns_changed) = set_count_columns(&fixed_line, &stats);
+    fixes.columns = columns_changed;
+    let (fixed_line, tags_changed) = set_metric_tags(&fixed_line, &stats);
+    fixes.tags = tags_changed;
+
+    if let Some(max_indel) = options.split_at_indel {
+        if let Some(pieces) = split_line(&fixed_line, &ops, max_indel)? {
+            fixes.pieces = Some(pieces.len());
+            return Ok((pieces, fixes));
+        }
+    }
+    Ok((vec![fixed_line], fixes))
+}
+
+/// Tags written by `annotate_line`; existing copies are replaced rather than
+/// duplicated.
+const QC_TAGS: [&str; 4] = ["pi:f:", "pg:f:", "pe:i:", "pf:f:"];
+
+/// A PAF line with QC tags appended, as `pafcheck annotate` writes it:
+/// recomputed BLAST-like identity (`pi:f`), gap-compressed identity
+/// (`pg:f`), number of validation errors (`pe:i`) and aligned fraction of the
+/// query (`pf:f`). The `NM:i`, `de:f`, `dv:f` and `gi:f` tags are set to the
+/// recomputed values; other fields are kept byte for byte.
+pub fn annotate_line(
+    line: &str,
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+) -> Result<String> {
+    let stats = alignment_stats(record, fasta_reader)?;
+    let mut sink = Vec::new();
+    let errors = match validate_record(record, fasta_reader, "omit", &mut sink) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<ValidationError>() {
+            Some(validation_error) => validation_error.errors.values().map(|e| e.count).sum(),
+            None => 1,
+        },
+    };
+    let covered = (record.query_end - record.query_start.min(record.query_end)) as f64
+        / record.query_length.max(1) as f64;
+
+    let fields: Vec<&str> = line
+        .split('\t')
+        .filter(|field| !QC_TAGS.iter().any(|tag| field.starts_with(tag)))
+        .collect();
+    let (line, _) = set_metric_tags(&fields.join("\t"), &stats);
+    Ok(format!(
+        "{}\tpi:f:{:.6}\tpg:f:{:.6}\tpe:i:{}\tpf:f:{:.6}",
+        line,
+        stats.blast_identity(),
+        stats.gap_compressed_identity(),
+        errors,
+        covered
+    ))
+}
+
+/// Query and target bases removed from each end of an alignment by
+/// `trim_terminal_indels`, in the orientation of its CIGAR.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrimmedIndels {
+    pub leading: (usize, usize),
+    pub trailing: (usize, usize),
+}
+
+impl TrimmedIndels {
+    pub fn is_empty(&self) -> bool {
+        *self == TrimmedIndels::default()
+    }
+}
+
+/// Strips the insertions and deletions an alignment begins or ends with.
+/// In a CIGAR with clips, trimmed query bases are soft-clipped instead, so
+/// that the clips still account for the whole query. CIGARs without any
+/// `=`, `X`, `M` or `N` op are returned as they are.
+pub fn trim_terminal_indels(ops: &[CigarOp]) -> (CigarOps, TrimmedIndels) {
+    let mut trimmed = TrimmedIndels::default();
+    let copy = |op: &CigarOp| op.with_count(op.count());
+    let Some((first, last)) = aligned_range(ops) else {
+        return (ops.iter().map(copy).collect(), trimmed);
+    };
+    let clipped = ops.iter().any(CigarOp::is_clip);
+    // Keeps the clips of one end, soft-clipping its trimmed query bases next
+    // to the alignment.
+    let trim_end = |end: &[CigarOp], removed: &mut (usize, usize), inner_first: bool| {
+        let mut kept: CigarOps = end.iter().filter(|op| !op.is_indel()).map(copy).collect();
+        for op in end.iter().filter(|op| op.is_indel()) {
+            let (q_len, t_len) = op.consumed();
+            removed.0 += q_len;
+            removed.1 += t_len;
+        }
+        if clipped && removed.0 > 0 {
+            let inner = if inner_first {
+                kept.first_mut()
+            } else {
+                kept.last_mut()
+            };
+            match inner {
+                Some(CigarOp::SoftClip(len)) => *len += removed.0 as u64,
+                _ if inner_first => kept.insert(0, CigarOp::SoftClip(removed.0 as u64)),
+                _ => kept.push(CigarOp::SoftClip(removed.0 as u64)),
+            }
+        }
+        kept
+    };
+    let mut fixed = trim_end(&ops[..first], &mut trimmed.leading, false);
+    fixed.extend(ops[first..=last].iter().map(copy));
+    fixed.extend(trim_end(&ops[last + 1..], &mut trimmed.trailing, true));
+    (fixed, trimmed)
+}
+
+fn strip_cs_indels(ops: &[CsOp]) -> &[CsOp] {
+    let is_indel = |op: &CsOp| matches!(op, CsOp::Insertion(_) | CsOp::Deletion(_));
+    let start = ops.iter().position(|op| !is_indel(op)).unwrap_or(ops.len());
+    let end = ops
+        .iter()
+        .rposition(|op| !is_indel(op))
+        .map_or(start, |i| i + 1);
+    &ops[start..end]
+}
+
+/// One of the records an alignment is split into by `split_at_indels`: its
+/// CIGAR, and the query and target offsets of its span within the
+/// alignment, in the orientation of the CIGAR.
+#[derive(Debug)]
+pub struct CigarPiece {
+    pub ops: CigarOps,
+    pub query: (usize, usize),
+    pub target: (usize, usize),
+}
+
+/// Splits an alignment at its insertions and deletions longer than
+/// `max_indel`, which are left out, as are indels that would begin or end a
+/// piece. In a CIGAR with clips, each piece is soft-clipped to the rest of
+/// the query.
+pub fn split_at_indels(ops: &[CigarOp], max_indel: usize) -> Vec<CigarPiece> {
+    let (leading_clip, trailing_clip) = clip_lengths(ops);
+    let clipped = ops.iter().any(CigarOp::is_clip);
+    let aligned_query: usize = ops.iter().map(|op| op.consumed().0).sum();
+    let is_indel = |op: &CigarOp| matches!(op, CigarOp::Insertion(_) | CigarOp::Deletion(_));
+
+    let mut pieces = Vec::new();
+    // Ops of the current piece with their query and target offsets.
+    let mut current: Vec<(&CigarOp, usize, usize)> = Vec::new();
+    let mut close = |current: &mut Vec<(&CigarOp, usize, usize)>| {
+        let start = current.iter().position(|(op, _, _)| !is_indel(op));
+        let end = current.iter().rposition(|(op, _, _)| !is_indel(op));
+        if let (Some(start), Some(end)) = (start, end) {
+            let span = &current[start..=end];
+            let (last, q_last, t_last) = span[span.len() - 1];
+            let (q_len, t_len) = last.consumed();
+            let query = (span[0].1, q_last + q_len);
+            let mut piece_ops = CigarOps::new();
+            if clipped && leading_clip + query.0 > 0 {
+                piece_ops.push(CigarOp::SoftClip((leading_clip + query.0) as u64));
+            }
+            piece_ops.extend(span.iter().map(|(op, _, _)| op.with_count(op.count())));
+            if clipped && trailing_clip + aligned_query - query.1 > 0 {
+                piece_ops.push(CigarOp::SoftClip(
+                    (trailing_clip + aligned_query - query.1) as u64,
+                ));
+            }
+            pieces.push(CigarPiece {
+                ops: piece_ops,
+                query,
+                target: (span[0].2, t_last + t_len),
+            });
+        }
+        current.clear();
+    };
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops.iter().filter(|op| !op.is_clip()) {
+        if is_indel(op) && op.count() as usize > max_indel {
+            close(&mut current);
+        } else {
+            current.push((op, q_idx, t_idx));
+        }
+        let (q_len, t_len) = op.consumed();
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    close(&mut current);
+    pieces
+}
+
+/// Splits a `cs` string like `split_at_indels` splits the CIGAR.
+pub fn split_cs(cs: &str, max_indel: usize) -> Result<Vec<String>> {
+    let ops = parse_cs(cs)?;
+    let pieces = ops.split(|op| match op {
+        CsOp::Insertion(bases) | CsOp::Deletion(bases) => bases.len() > max_indel,
+        _ => false,
+    });
+    Ok(pieces
+        .map(strip_cs_indels)
+        .filter(|piece| !piece.is_empty())
+        .map(format_cs)
+        .collect())
+}
+
+/// The lines of the records a fixed PAF line is split into by
+/// `split_at_indels`, given its CIGAR ops, or `None` if it has no indel
+/// longer than `max_indel`. Each gets its piece's coordinates, CIGAR, count
+/// columns and edit distance and divergence tags; its `cs:Z` tag is split
+/// likewise, or dropped if it doesn't split into the same pieces.
+pub fn split_line(line: &str, ops: &[CigarOp], max_indel: usize) -> Result<Option<Vec<String>>> {
+    let pieces = split_at_indels(ops, max_indel);
+    if pieces.len() < 2 {
+        return Ok(None);
+    }
+    let record = PafRecord::from_line(line)?;
+    let cs_pieces = record
+        .tag("cs:Z")
+        .and_then(|cs| split_cs(cs, max_indel).ok())
+        .filter(|cs_pieces| cs_pieces.len() == pieces.len());
+    let mut lines = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        let piece_line = set_piece_coordinates(line, &record, piece);
+        let piece_line = set_tag(&piece_line, "cg:Z", &format_cigar(&piece.ops));
+        let piece_line = match &cs_pieces {
+            Some(cs_pieces) => set_tag(&piece_line, "cs:Z", &cs_pieces[i]),
+            None => remove_tag(&piece_line, "cs:Z"),
+        };
+        let stats = cigar_stats(&piece.ops);
+        let (piece_line, _) = set_count_columns(&piece_line, &stats);
+        let (piece_line, _) = set_metric_tags(&piece_line, &stats);
+        lines.push(piece_line);
+    }
+    Ok(Some(lines))
+}
+
+/// Sets the query and target intervals of a PAF line to those of `piece`
+/// of the record's alignment.
+pub fn set_piece_coordinates(line: &str, record: &PafRecord, piece: &CigarPiece) -> String {
+    let (query_start, query_end) = if record.strand == '-' {
+        (
+            record.query_end - piece.query.1,
+            record.query_end - piece.query.0,
+        )
+    } else {
+        (
+            record.query_start + piece.query.0,
+            record.query_start + piece.query.1,
+        )
+    };
+    let mut fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+    if fields.len() < 12 {
+        return line.to_string();
+    }
+    fields[2] = query_start.to_string();
+    fields[3] = query_end.to_string();
+    fields[7] = (record.target_start + piece.target.0).to_string();
+    fields[8] = (record.target_start + piece.target.1).to_string();
+    fields.join("\t")
+}
+
+/// Moves the query and target intervals of a PAF line in by the bases
+/// `trimmed` from the alignment. On the `-` strand, the start of the CIGAR
+/// is the end of the query interval.
+pub fn trim_coordinates(line: &str, record: &PafRecord, trimmed: &TrimmedIndels) -> String {
+    let (query_before, query_after) = if record.strand == '-' {
+        (trimmed.trailing.0, trimmed.leading.0)
+    } else {
+        (trimmed.leading.0, trimmed.trailing.0)
+    };
+    let mut fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+    if fields.len() < 12 {
+        return line.to_string();
+    }
+    fields[2] = (record.query_start + query_before).to_string();
+    fields[3] = record.query_end.saturating_sub(query_after).to_string();
+    fields[7] = (record.target_start + trimmed.leading.1).to_string();
+    fields[8] = record
+        .target_end
+        .saturating_sub(trimmed.trailing.1)
+        .to_string();
+    fields.join("\t")
+}
+
+/// Sets columns 10 (matching bases) and 11 (block length) of a PAF line to
+/// the values of `stats`, unless they already have them. Also returns
+/// whether either changed.
+pub fn set_count_columns(line: &str, stats: &AlignmentStats) -> (String, bool) {
+    let mut fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 12 {
+        return (line.to_string(), false);
+    }
+    let matches = stats.identical.to_string();
+    let block_length = stats.block_length().to_string();
+    let mut changed = false;
+    for (field, value) in [(9, &matches), (10, &block_length)] {
+        if fields[field].parse::<usize>().ok() != value.parse().ok() {
+            fields[field] = value;
+            changed = true;
+        }
+    }
+    (fields.join("\t"), changed)
+}
+
+/// The edit distance and divergence tags of an alignment, with the
+/// definitions `tag_errors` checks them against.
+pub fn metric_tags(stats: &AlignmentStats) -> [(&'static str, String); 4] {
+    let gap_compressed = stats.gap_compressed_identity();
+    [
+        ("NM:i", stats.edit_distance().to_string()),
+        ("de:f", format!("{:.4}", 1.0 - gap_compressed)),
+        ("dv:f", format!("{:.4}", 1.0 - gap_compressed)),
+        ("gi:f", format!("{:.4}", gap_compressed)),
+    ]
+}
+
+/// Overwrites or inserts the `metric_tags` of a PAF line. Tags that already
+/// agree with `stats` to the written precision are kept as they are. Also
+/// returns whether any tag was changed.
+pub fn set_metric_tags(line: &str, stats: &AlignmentStats) -> (String, bool) {
+    let mut line = line.to_string();
+    let mut changed = false;
+    for (tag, value) in metric_tags(stats) {
+        let agrees = tag_value(&line, tag).is_some_and(|existing| {
+            match (existing.parse::<f64>(), value.parse::<f64>()) {
+                (Ok(existing), Ok(value)) => (existing - value).abs() < 0.5e-4,
+                _ => false,
+            }
+        });
+        if !agrees {
+            line = set_tag(&line, tag, &value);
+            changed = true;
+        }
+    }
+    (line, changed)
+}
+
+/// The value of the optional field `tag` of a PAF line.
+fn tag_value<'a>(line: &'a str, tag: &str) -> Option<&'a str> {
+    line.split('\t')
+        .skip(12)
+        .find_map(|field| field.strip_prefix(tag)?.strip_prefix(':'))
+}
+
+/// Replaces the optional field `tag` (e.g. `cg:Z`) of a PAF line with
+/// `value`, or appends it if the line has none. Other fields are kept as
+/// they are.
+pub fn set_tag(line: &str, tag: &str, value: &str) -> String {
+    let prefix = format!("{}:", tag);
+    let mut fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+    let field = format!("{}{}", prefix, value);
+    match fields
+        .iter_mut()
+        .skip(12)
+        .find(|field| field.starts_with(&prefix))
+    {
+        Some(existing) => *existing = field,
+        None => fields.push(field),
+    }
+    fields.join("\t")
+}
+
+/// Removes the optional field `tag` from a PAF line.
+pub fn remove_tag(line: &str, tag: &str) -> String {
+    let prefix = format!("{}:", tag);
+    line.split('\t')
+        .enumerate()
+        .filter(|(i, field)| *i < 12 || !field.starts_with(&prefix))
+        .map(|(_, field)| field)
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cigar_parser::parse_cigar;
+
+    #[test]
+    fn test_resegment_cigar() {
+        let fixed = |cigar: &str, query: &[u8], target: &[u8]| {
+            resegment_cigar(&parse_cigar(cigar).unwrap(), query, target)
+                .map(|ops| format_cigar(&ops))
+        };
+        assert_eq!(fixed("8=", b"ACGTACGT", b"ACGAACGT").unwrap(), "3=1X4=");
+        // Indels stay in place, and runs around a wrong `X` are merged.
+        assert_eq!(
+            fixed("2=1X1=2I2=1D", b"ACGTGGAC", b"ACGTTCG").unwrap(),
+            "4=2I1X1=1D"
+        );
+        assert_eq!(fixed("2S3M", b"ACG", b"AAG").unwrap(), "2S1=1X1=");
+        assert_eq!(fixed("2M1I1M1=", b"ACGTA", b"ACTA").unwrap(), "2=1I2=");
+        assert!(fixed("5=", b"ACGT", b"ACGT").is_err());
+        assert!(fixed("3=", b"ACGT", b"ACGT").is_err());
+    }
+
+    #[test]
+    fn test_set_metric_tags() {
+        let stats = AlignmentStats {
+            identical: 8,
+            aligned: 9,
+            insertions: 1,
+            inserted_bases: 2,
+            deletions: 0,
+            deleted_bases: 0,
+        };
+        let line = "q\t11\t0\t11\t+\tt\t9\t0\t9\t8\t11\t60\tNM:i:1\tde:f:0.2000\tcg:Z:4=1X4=2I";
+        let (fixed, changed) = set_metric_tags(line, &stats);
+        assert!(changed);
+        assert_eq!(
+            fixed,
+            "q\t11\t0\t11\t+\tt\t9\t0\t9\t8\t11\t60\tNM:i:3\tde:f:0.2000\tcg:Z:4=1X4=2I\tdv:f:0.2000\tgi:f:0.8000"
+        );
+        assert_eq!(set_metric_tags(&fixed, &stats), (fixed.clone(), false));
+    }
+
+    #[test]
+    fn test_left_align_indels() {
+        let left_aligned = |cigar: &str, query: &[u8], target: &[u8]| {
+            format_cigar(&left_align_indels(
+                &parse_cigar(cigar).unwrap(),
+                query,
+                target,
+            ))
+        };
+        // The deleted A of a homopolymer moves to its start.
+        assert_eq!(left_aligned("4=1D2=", b"GCAAAT", b"GCAAAAT"), "2=1D4=");
+        // A dinucleotide repeat insertion shifts by whole and partial units.
+        assert_eq!(left_aligned("6=2I2=", b"GACACACAGT", b"GACACAGT"), "1=2I7=");
+        // Mismatches stop the shift.
+        assert_eq!(left_aligned("2=1X1=1D1=", b"AACAA", b"AATAAA"), "2=1X1D2=");
+        // The first aligned column stays in place.
+        assert_eq!(left_aligned("3=1I", b"AAAA", b"AAA"), "1=1I2=");
+        assert_eq!(left_aligned("4=1D2=", b"AAAAAA", b"AAAAAAA"), "1=1D5=");
+        assert_eq!(left_aligned("2S1I3=1I", b"CAAAA", b"AAA"), "2S1I1=1I2=");
+    }
+
+    #[test]
+    fn test_trim_terminal_indels() {
+        let trim = |cigar: &str| {
+            let (ops, trimmed) = trim_terminal_indels(&parse_cigar(cigar).unwrap());
+            (format_cigar(&ops), trimmed)
+        };
+        assert_eq!(
+            trim("2I3D5=1X4=1D"),
+            (
+                "5=1X4=".to_string(),
+                TrimmedIndels {
+                    leading: (2, 3),
+                    trailing: (0, 1)
+                }
+            )
+        );
+        // Trimmed query bases become soft clips when the CIGAR has clips.
+        assert_eq!(trim("5H2S3I10=2I").0, "5H5S10=2S");
+        assert_eq!(trim("5H3I10=").0, "5H3S10=");
+        assert_eq!(trim("10=").1, TrimmedIndels::default());
+        assert_eq!(trim("3I2D").0, "3I2D");
+
+        let line = "q\t20\t2\t18\t-\tt\t30\t5\t20\t14\t16\t60";
+        let record = PafRecord::from_line(line).unwrap();
+        let trimmed = TrimmedIndels {
+            leading: (2, 0),
+            trailing: (0, 1),
+        };
+        assert_eq!(
+            trim_coordinates(line, &record, &trimmed),
+            "q\t20\t2\t16\t-\tt\t30\t5\t19\t14\t16\t60"
+        );
+    }
+
+    #[test]
+    fn test_split_at_indels() {
+        let split = |cigar: &str| {
+            split_at_indels(&parse_cigar(cigar).unwrap(), 5)
+                .into_iter()
+                .map(|piece| (format_cigar(&piece.ops), piece.query, piece.target))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            split("5=1I3=10D4=1X2="),
+            [
+                ("5=1I3=".to_string(), (0, 9), (0, 8)),
+                ("4=1X2=".to_string(), (9, 16), (18, 25)),
+            ]
+        );
+        // Small indels next to a split are left out with it.
+        assert_eq!(
+            split("5=6I2D4="),
+            [
+                ("5=".to_string(), (0, 5), (0, 5)),
+                ("4=".to_string(), (11, 15), (7, 11)),
+            ]
+        );
+        assert_eq!(
+            split("2S5=10I3="),
+            [
+                ("2S5=13S".to_string(), (0, 5), (0, 5)),
+                ("17S3=".to_string(), (15, 18), (5, 8)),
+            ]
+        );
+        assert_eq!(split("10=").len(), 1);
+        assert_eq!(
+            split_cs(":5+a:3-aaaaaaaaaa:4*ag:2", 5).unwrap(),
+            [":5+a:3", ":4*ag:2"]
+        );
+
+        let line = "q\t300\t100\t200\t-\tt\t500\t0\t100\t90\t100\t60";
+        let record = PafRecord::from_line(line).unwrap();
+        let piece = CigarPiece {
+            ops: parse_cigar("20=").unwrap(),
+            query: (10, 30),
+            target: (40, 60),
+        };
+        assert_eq!(
+            set_piece_coordinates(line, &record, &piece),
+            "q\t300\t170\t190\t-\tt\t500\t40\t60\t90\t100\t60"
+        );
+        assert_eq!(
+            remove_tag(&format!("{}\tcs:Z::20\tNM:i:0", line), "cs:Z"),
+            format!("{}\tNM:i:0", line)
+        );
+
+        let line = "q\t20\t0\t16\t+\tt\t30\t0\t25\t15\t26\t60\ttp:A:P\tcg:Z:5=1I3=10D4=1X2=\tcs:Z::5+a:3-aaaaaaaaaa:4*ag:2";
+        let ops = parse_cigar("5=1I3=10D4=1X2=").unwrap();
+        assert_eq!(
+            split_line(line, &ops, 5).unwrap().unwrap(),
+            [
+                "q\t20\t0\t9\t+\tt\t30\t0\t8\t8\t9\t60\ttp:A:P\tcg:Z:5=1I3=\tcs:Z::5+a:3\tNM:i:1\tde:f:0.1111\tdv:f:0.1111\tgi:f:0.8889",
+                "q\t20\t9\t16\t+\tt\t30\t18\t25\t6\t7\t60\ttp:A:P\tcg:Z:4=1X2=\tcs:Z::4*ag:2\tNM:i:1\tde:f:0.1429\tdv:f:0.1429\tgi:f:0.8571",
+            ]
+        );
+        assert_eq!(split_line(line, &ops, 10).unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_count_columns() {
+        let stats = AlignmentStats {
+            identical: 8,
+            aligned: 9,
+            insertions: 1,
+            inserted_bases: 2,
+            deletions: 1,
+            deleted_bases: 1,
+        };
+        let line = "q\t11\t0\t11\t+\tt\t10\t0\t10\t9\t12\t60\tcg:Z:4=1X4=2I1D";
+        let (fixed, changed) = set_count_columns(line, &stats);
+        assert!(changed);
+        assert_eq!(
+            fixed,
+            "q\t11\t0\t11\t+\tt\t10\t0\t10\t8\t12\t60\tcg:Z:4=1X4=2I1D"
+        );
+        assert_eq!(set_count_columns(&fixed, &stats), (fixed.clone(), false));
+    }
+
+    #[test]
+    fn test_set_tag() {
+        let line = "q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\ttp:A:P\tcg:Z:10=\tNM:i:0";
+        assert_eq!(
+            set_tag(line, "cg:Z", "4=1X5="),
+            "q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\ttp:A:P\tcg:Z:4=1X5=\tNM:i:0"
+        );
+        assert_eq!(
+            set_tag("q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60", "cg:Z", "10="),
+            "q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\tcg:Z:10="
+        );
+    }
+}
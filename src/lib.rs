@@ -1,4 +1,41 @@
+pub mod bed;
+pub mod best_alignment;
+pub mod checksums;
 pub mod cigar_parser;
+pub mod complexity;
+pub mod config;
+pub mod coverage;
+pub mod cs_tag;
+pub mod delta;
+pub mod density;
+pub mod doctor;
+pub mod error_store;
 pub mod fasta_reader;
+pub mod fastq;
+pub mod filter;
+pub mod fix;
+pub mod maf;
+pub mod md5;
+pub mod minimizer;
+pub mod overlap;
+pub mod paf_index;
+pub mod paf_input;
 pub mod paf_parser;
+pub mod pansn;
+pub mod parallel;
+pub mod primary;
+pub mod profile;
+pub mod progress;
+pub mod protein;
+pub mod provenance;
+pub mod refget;
+pub mod report;
+pub mod rgfa;
+pub mod sam;
+pub mod sampling;
+pub mod sequence_summary;
+pub mod stats;
 pub mod validator;
+pub mod vcf;
+pub mod verdict;
+pub mod windows;
@@ -0,0 +1,359 @@
+use crate::cigar_parser::CigarOp;
+use crate::cs_tag::{format_cs, CsOp};
+use crate::paf_parser::PafRecord;
+use anyhow::{Context, Result};
+use std::iter::Peekable;
+
+/// One `s` line of a MAF block.
+#[derive(Debug, Clone, PartialEq)]
+struct MafRow {
+    name: String,
+    start: usize,
+    size: usize,
+    strand: char,
+    source_size: usize,
+    text: Vec<u8>,
+}
+
+impl MafRow {
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 7 {
+            anyhow::bail!("MAF s line does not have 7 fields");
+        }
+        let number = |field: &str, what: &str| -> Result<usize> {
+            field
+                .parse()
+                .context(format!("Invalid MAF {}: {}", what, field))
+        };
+        let strand = match fields[4] {
+            "+" => '+',
+            "-" => '-',
+            other => anyhow::bail!("Invalid MAF strand: {}", other),
+        };
+        Ok(MafRow {
+            name: fields[1].to_string(),
+            start: number(fields[2], "start")?,
+            size: number(fields[3], "size")?,
+            strand,
+            source_size: number(fields[5], "source size")?,
+            text: fields[6].as_bytes().to_vec(),
+        })
+    }
+
+    /// Forward-strand interval of the row; MAF gives `-` strand starts on
+    /// the reverse complement.
+    fn interval(&self) -> (usize, usize) {
+        match self.strand {
+            '-' => (
+                self.source_size.saturating_sub(self.start + self.size),
+                self.source_size.saturating_sub(self.start),
+            ),
+            _ => (self.start, self.start + self.size),
+        }
+    }
+
+    fn reverse_complement(&mut self) {
+        self.text = self
+            .text
+            .iter()
+            .rev()
+            .map(|&base| complement(base))
+            .collect();
+        self.strand = if self.strand == '-' { '+' } else { '-' };
+        self.start = self.source_size.saturating_sub(self.start + self.size);
+    }
+}
+
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' => b'a',
+        other => other,
+    }
+}
+
+/// Converts a pairwise MAF block, its target (first) and query (second)
+/// `s` rows, into a PAF line. The alignment is written as a long-form
+/// `cs:Z` tag, which spells out the bases of the MAF text, so validating
+/// the record also checks them against the FASTA files.
+fn block_to_paf(mut target: MafRow, mut query: MafRow) -> Result<String> {
+    if target.text.len() != query.text.len() {
+        anyhow::bail!(
+            "MAF rows of {} and {} have different lengths",
+            target.name,
+            query.name
+        );
+    }
+    // PAF targets are on the forward strand.
+    if target.strand == '-' {
+        target.reverse_complement();
+        query.reverse_complement();
+    }
+
+    let mut ops: Vec<CsOp> = Vec::new();
+    let (mut matches, mut columns) = (0, 0);
+    for (&t, &q) in target.text.iter().zip(&query.text) {
+        let op = match (t, q) {
+            (b'-', b'-') => continue,
+            (b'-', q) => CsOp::Insertion((q.to_ascii_lowercase() as char).to_string()),
+            (t, b'-') => CsOp::Deletion((t.to_ascii_lowercase() as char).to_string()),
+            (t, q) if t.eq_ignore_ascii_case(&q) => {
+                matches += 1;
+                CsOp::Identical((t.to_ascii_uppercase() as char).to_string())
+            }
+            (t, q) => CsOp::Substitution {
+                target: t.to_ascii_lowercase(),
+                query: q.to_ascii_lowercase(),
+            },
+        };
+        columns += 1;
+        match (ops.last_mut(), op) {
+            (Some(CsOp::Identical(bases)), CsOp::Identical(more))
+            | (Some(CsOp::Insertion(bases)), CsOp::Insertion(more))
+            | (Some(CsOp::Deletion(bases)), CsOp::Deletion(more)) => bases.push_str(&more),
+            (_, op) => ops.push(op),
+        }
+    }
+
+    let (query_start, query_end) = query.interval();
+    let (target_start, target_end) = target.interval();
+    Ok(format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t255\tcs:Z:{}",
+        query.name,
+        query.source_size,
+        query_start,
+        query_end,
+        query.strand,
+        target.name,
+        target.source_size,
+        target_start,
+        target_end,
+        matches,
+        columns,
+        format_cs(&ops)
+    ))
+}
+
+/// A pairwise MAF block (without its blank separator line) of a record
+/// whose alignment is `ops` over its aligned sequences: the target row,
+/// then the query row, on the `-` strand for `-` strand records, as
+/// `block_to_paf` reads them. Insertions are gaps in the target row, and
+/// deletions and skips gaps in the query row; the fields of the two rows
+/// are padded to line up.
+pub fn maf_block(
+    record: &PafRecord,
+    ops: &[CigarOp],
+    query_seq: &[u8],
+    target_seq: &[u8],
+) -> Result<String> {
+    let (mut target_text, mut query_text) = (Vec::new(), Vec::new());
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops {
+        let (q_len, t_len) = op.consumed();
+        let (Some(query), Some(target)) = (
+            query_seq.get(q_idx..q_idx + q_len),
+            target_seq.get(t_idx..t_idx + t_len),
+        ) else {
+            anyhow::bail!("CIGAR extends past the aligned sequences");
+        };
+        let columns = q_len.max(t_len);
+        target_text.extend(target.iter().chain(std::iter::repeat(&b'-')).take(columns));
+        query_text.extend(query.iter().chain(std::iter::repeat(&b'-')).take(columns));
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+
+    let query_start = match record.strand {
+        '-' => record.query_length - record.query_end,
+        _ => record.query_start,
+    };
+    let rows = [
+        MafRow {
+            name: record.target_name.clone(),
+            start: record.target_start,
+            size: record.target_end - record.target_start,
+            strand: '+',
+            source_size: record.target_length,
+            text: target_text,
+        },
+        MafRow {
+            name: record.query_name.clone(),
+            start: query_start,
+            size: record.query_end - record.query_start,
+            strand: record.strand,
+            source_size: record.query_length,
+            text: query_text,
+        },
+    ];
+    let width =
+        |field: fn(&MafRow) -> String| rows.iter().map(|row| field(row).len()).max().unwrap_or(0);
+    let name_width = width(|row| row.name.clone());
+    let start_width = width(|row| row.start.to_string());
+    let size_width = width(|row| row.size.to_string());
+    let length_width = width(|row| row.source_size.to_string());
+    let mut block = String::from("a\n");
+    for row in &rows {
+        block.push_str(&format!(
+            "s {:<name_width$} {:>start_width$} {:>size_width$} {} {:>length_width$} {}\n",
+            row.name,
+            row.start,
+            row.size,
+            row.strand,
+            row.source_size,
+            String::from_utf8_lossy(&row.text)
+        ));
+    }
+    Ok(block)
+}
+
+/// Writes the header line of a MAF file.
+pub fn write_maf_header<W: std::io::Write>(output: &mut W) -> Result<()> {
+    writeln!(output, "##maf version=1 program=pafcheck")?;
+    Ok(())
+}
+
+/// Pairwise MAF blocks as PAF lines, each numbered by the (0-based) line of
+/// its `a` line.
+pub struct MafRecords<I: Iterator<Item = Result<String>>> {
+    lines: Peekable<std::iter::Enumerate<I>>,
+}
+
+impl<I: Iterator<Item = Result<String>>> MafRecords<I> {
+    pub fn new(lines: I) -> Self {
+        MafRecords {
+            lines: lines.enumerate().peekable(),
+        }
+    }
+
+    /// Reads the `s` rows of the block starting at `line_number`, up to the
+    /// blank line or next `a` line that ends it.
+    fn read_block(&mut self, line_number: usize) -> Result<String> {
+        let mut rows = Vec::new();
+        while let Some((_, line)) = self.lines.peek() {
+            let starts_block = match line {
+                Ok(line) => line.trim().is_empty() || line.starts_with('a'),
+                Err(_) => false,
+            };
+            if starts_block {
+                break;
+            }
+            let (number, line) = self.lines.next().unwrap();
+            let line = line?;
+            if line.starts_with('s') {
+                rows.push(
+                    MafRow::parse(&line).context(format!("Invalid MAF line {}", number + 1))?,
+                );
+            }
+        }
+        match <[MafRow; 2]>::try_from(rows) {
+            Ok([target, query]) => block_to_paf(target, query),
+            Err(rows) => anyhow::bail!(
+                "MAF block at line {} has {} sequence rows; only pairwise blocks are supported",
+                line_number + 1,
+                rows.len()
+            ),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<String>>> Iterator for MafRecords<I> {
+    type Item = (usize, Result<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (line_number, line) = self.lines.next()?;
+            match line {
+                Ok(line) if line.starts_with('a') => {
+                    return Some((line_number, self.read_block(line_number)))
+                }
+                // Headers, comments, blank lines and stray lines between blocks.
+                Ok(_) => continue,
+                Err(e) => return Some((line_number, Err(e))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(maf: &str) -> Vec<(usize, String)> {
+        MafRecords::new(maf.lines().map(|line| Ok(line.to_string())))
+            .map(|(number, record)| (number, record.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_maf_records() {
+        let maf = "##maf version=1\n\
+                   # lastz\n\
+                   \n\
+                   a score=10\n\
+                   s ref.chr1 2 9 + 20 ACG-TTAACG\n\
+                   s qry.ctg1 0 8 + 12 ACGGTT--Cc\n\
+                   \n\
+                   a score=5\n\
+                   s ref.chr1 0 4 - 20 ACGT\n\
+                   s qry.ctg1 2 4 + 12 ACTT\n";
+        assert_eq!(
+            records(maf),
+            [
+                (
+                    3,
+                    "qry.ctg1\t12\t0\t8\t+\tref.chr1\t20\t2\t11\t6\t10\t255\tcs:Z:=ACG+g=TT-aa=C*gc"
+                        .to_string()
+                ),
+                // The target row on the - strand is flipped to the + strand.
+                (
+                    7,
+                    "qry.ctg1\t12\t2\t6\t-\tref.chr1\t20\t16\t20\t3\t4\t255\tcs:Z:=A*ca=GT"
+                        .to_string()
+                ),
+            ]
+        );
+
+        let result: Vec<_> = MafRecords::new(
+            "##maf\na\ns a 0 1 + 1 A\n"
+                .lines()
+                .map(|l| Ok(l.to_string())),
+        )
+        .collect();
+        assert!(result[0].1.is_err());
+    }
+
+    #[test]
+    fn test_maf_block() {
+        // An insertion, a deletion, and the query on the - strand.
+        let record = PafRecord::from_line(
+            "qry.ctg1\t12\t0\t8\t-\tref.chr1\t120\t100\t109\t6\t10\t60\tcg:Z:3=1I2=2D1=1X",
+        )
+        .unwrap();
+        let ops = crate::cigar_parser::parse_cigar("3=1I2=2D1=1X").unwrap();
+        let block = maf_block(&record, &ops, b"ACGGTTCA", b"ACGTTAACG").unwrap();
+        assert_eq!(
+            block,
+            "a\n\
+             s ref.chr1 100 9 + 120 ACG-TTAACG\n\
+             s qry.ctg1   4 8 -  12 ACGGTT--CA\n"
+        );
+
+        // Read back, the block is the record with a cs tag.
+        let maf = format!("##maf\n{}", block);
+        assert_eq!(
+            records(&maf),
+            [(
+                1,
+                "qry.ctg1\t12\t0\t8\t-\tref.chr1\t120\t100\t109\t6\t10\t255\tcs:Z:=ACG+g=TT-aa=C*ga"
+                    .to_string()
+            )]
+        );
+    }
+}
@@ -1,122 +1,3777 @@
 use anyhow::{Context, Result};
-use clap::{App, Arg};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use clap::{App, AppSettings, Arg, SubCommand};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 
-use pafcheck::fasta_reader::MultiFastaReader;
-use pafcheck::paf_parser::PafRecord;
-use pafcheck::validator::{validate_record, ErrorType, ValidationError};
+use pafcheck::bed::{BedRegions, BedWriter};
+use pafcheck::best_alignment::BestAlignmentTable;
+use pafcheck::checksums::{fasta_md5s, load_expected_md5s, verify_md5s};
+use pafcheck::config::{Config, ConfigValue};
+use pafcheck::coverage::CoverageTracker;
+use pafcheck::density::ErrorDensity;
+use pafcheck::doctor::{diagnose, Level};
+use pafcheck::error_store::{
+    parse_region, EntryFilter, ErrorStoreReader, ErrorStoreWriter, StoreEntry,
+};
+use pafcheck::fasta_reader::{
+    fai_path, is_stream, read_fai, FetchError, MultiFastaReader, SequenceSource,
+};
+use pafcheck::filter::RecordFilter;
+use pafcheck::fix::{annotate_line, fix_line, FixOptions, LineFixes};
+use pafcheck::maf::{maf_block, write_maf_header};
+use pafcheck::overlap::{classify_overlap, OverlapClass, ReciprocalChecker};
+use pafcheck::paf_index::{index_path, IndexedLines, PafIndex};
+use pafcheck::paf_input::{is_gzipped, numbered_records, PafLines};
+use pafcheck::paf_parser::{negative_columns, PafRecord};
+use pafcheck::pansn::{haplotype_pairing, HaplotypePairing, PanSnName};
+use pafcheck::parallel::{ReaderSpec, ValidationPool};
+use pafcheck::primary::PrimaryTracker;
+use pafcheck::profile::ErrorContextProfile;
+use pafcheck::progress::StatusReporter;
+use pafcheck::protein::{validate_protein_record, ResidueCounts};
+use pafcheck::provenance::InputFile;
+use pafcheck::refget::REFGET_PREFIX;
+use pafcheck::report::{
+    context_lines, write_json_report, write_ndjson_summary, ErrorEvent, OutputFormat,
+    ReportSummary, Verbosity, TSV_HEADER,
+};
+use pafcheck::rgfa::RgfaGraph;
+use pafcheck::sam::{sam_record, write_sam_header};
+use pafcheck::sampling::{RateEstimate, SplitMix64};
+use pafcheck::sequence_summary::SequenceErrorSummary;
+use pafcheck::stats::AlignmentSummary;
+use pafcheck::validator::{
+    alignment_ops, cigar_errors, column_errors, coordinate_errors, cross_check_identity, cs_errors,
+    fetch_aligned_sequences, fetch_aligned_sequences_with_case, is_self_alignment, policy_errors,
+    recompute_identity, sample_record, spot_check_record, tag_errors, tag_syntax_errors,
+    validate_aligned_sequences, validate_record_with_identity, Check, CheckSet, ErrorSite,
+    ErrorType, LowercasePolicy, NPolicy, SelfAlignmentPolicy, Severity, ValidationError,
+    ValidationOptions,
+};
+use pafcheck::vcf::{alignment_variants, write_vcf, Variant, VariantRecord};
+use pafcheck::verdict::VerdictWriter;
+use pafcheck::windows::identity_windows;
+
+/// Set when stdout carries a structured report, so log lines go to stderr instead.
+static LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Prints an informational `[pafcheck]` line of a validation run.
+macro_rules! log_line {
+    ($($arg:tt)*) => {
+        if LOG_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
 
 fn main() {
-    let matches = App::new("PAF Validator")
+    let matches = cli().get_matches();
+    match matches.subcommand() {
+        Some(("check", check_matches)) => check(check_matches),
+        Some(("doctor", doctor_matches)) => doctor(doctor_matches),
+        Some(("report", report_matches)) => match report_matches.subcommand() {
+            Some(("query", query_matches)) => run(query_error_store(query_matches)),
+            Some(("view", view_matches)) => {
+                let store_path = view_matches.value_of("store").unwrap();
+                let region = view_matches.value_of("region").unwrap();
+                run(view_error_store(store_path, region));
+            }
+            _ => unreachable!("clap requires a report subcommand"),
+        },
+        Some(("annotate", annotate_matches)) => run(annotate_paf(annotate_matches)),
+        Some(("fix", fix_matches)) => run(fix_paf(fix_matches)),
+        Some(("stats", stats_matches)) => run(stats_paf(stats_matches)),
+        Some(("windows", windows_matches)) => run(identity_windows_paf(windows_matches)),
+        Some(("to-vcf", vcf_matches)) => run(paf_to_vcf(vcf_matches)),
+        Some(("to-sam", sam_matches)) => run(paf_to_sam(sam_matches)),
+        Some(("to-maf", maf_matches)) => run(paf_to_maf(maf_matches)),
+        Some(("index", index_matches)) => run(index_paf(index_matches.value_of("paf").unwrap())),
+        _ => check(&matches),
+    }
+}
+
+/// Exits if a subcommand failed, printing its error with the whole context
+/// chain: with status 2 for an unparsable PAF record, 3 for sequences that
+/// couldn't be fetched, and 1 otherwise.
+fn run(result: Result<()>) {
+    if let Err(e) = result {
+        eprintln!("[pafcheck] Error: {:#}", e);
+        let code = if let Some(failure) = e.downcast_ref::<RunFailure>() {
+            failure.exit_code()
+        } else if e.downcast_ref::<FetchError>().is_some() {
+            3
+        } else {
+            1
+        };
+        std::process::exit(code);
+    }
+}
+
+fn cli() -> App<'static> {
+    App::new("PAF Validator")
         .version("1.0")
         .author("Your Name")
         .about("Validates PAF CIGAR strings against FASTA files")
-        .arg(
-            Arg::with_name("query_fasta")
-                .short('q')
-                .long("query-fasta")
-                .value_name("QUERY_FASTA")
-                .help("Path to the bgzip-compressed and tabix-indexed query FASTA file")
-                .takes_value(true)
-                .required(true),
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .args(check_args())
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Validates PAF records against the FASTA files (the default when no subcommand is given)")
+                .args(check_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("index")
+                .about("Indexes an uncompressed PAF by target interval for --target-region")
+                .arg(
+                    Arg::with_name("paf")
+                        .value_name("PAF")
+                        .help("Path to the PAF file; the index is written to <PAF>.idx")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Checks input files, indexes and sequence names without validating alignments")
+                .arg(
+                    Arg::with_name("query_fasta")
+                        .short('q')
+                        .long("query-fasta")
+                        .value_name("QUERY_FASTA")
+                        .help("Path to the query FASTA file")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("target_fasta")
+                        .short('t')
+                        .long("target-fasta")
+                        .value_name("TARGET_FASTA")
+                        .help("Path to the target FASTA file")
+                        .takes_value(true)
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("paf")
+                        .short('p')
+                        .long("paf")
+                        .value_name("PAF")
+                        .help("Path to the PAF file, or - to read from standard input (the default)")
+                        .takes_value(true)
+                        .default_value("-"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("annotate")
+                .about("Writes the PAF back out with pafcheck-computed QC tags (pi:f, pg:f, pe:i, pf:f)")
+                .args(io_args("Where to write the annotated PAF (default: stdout)")),
+        )
+        .subcommand(
+            SubCommand::with_name("fix")
+                .about("Writes the PAF back out with the =/X runs of each CIGAR recomputed from the sequences")
+                .args(io_args("Where to write the fixed PAF (default: stdout)"))
+                .arg(
+                    Arg::with_name("left-align")
+                        .long("left-align")
+                        .help("Shift each insertion and deletion to its leftmost equivalent position, so that CIGARs from different aligners can be compared"),
+                )
+                .arg(
+                    Arg::with_name("split-at-indel")
+                        .long("split-at-indel")
+                        .value_name("N")
+                        .help("Split records into several at insertions and deletions longer than N bases")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Summarizes the alignments of a PAF: aligned bases, identity, block lengths, alignments per sequence")
+                .args(io_args("Where to write the statistics (default: stdout)"))
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format of the statistics: text, json, tsv")
+                        .possible_values(["text", "json", "tsv"])
+                        .default_value("text")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("per-query-indels")
+                        .long("per-query-indels")
+                        .help("Also break the insertion and deletion length histograms down per query"),
+                ),
         )
-        .arg(
-            Arg::with_name("target_fasta")
-                .short('t')
-                .long("target-fasta")
-                .value_name("TARGET_FASTA")
-                .help("Path to the bgzip-compressed and tabix-indexed target FASTA file")
-                .takes_value(true)
-                .required(false),
+        .subcommand(
+            SubCommand::with_name("windows")
+                .about("Computes identity in sliding windows along each alignment and writes the windows below a threshold as BED")
+                .args(io_args("Where to write the BED of windows (default: stdout)"))
+                .arg(
+                    Arg::with_name("window")
+                        .long("window")
+                        .value_name("BASES")
+                        .help("Window size in target bases")
+                        .takes_value(true)
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::with_name("step")
+                        .long("step")
+                        .value_name("BASES")
+                        .help("Distance between the starts of consecutive windows")
+                        .takes_value(true)
+                        .default_value("500"),
+                )
+                .arg(
+                    Arg::with_name("min-identity")
+                        .long("min-identity")
+                        .value_name("FRACTION")
+                        .help("Report windows whose identity is below this fraction")
+                        .takes_value(true)
+                        .default_value("0.9"),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Write every window, not only those below --min-identity"),
+                ),
         )
-        .arg(
-            Arg::with_name("paf")
-                .short('p')
-                .long("paf")
-                .value_name("PAF")
-                .help("Path to the PAF file")
-                .takes_value(true)
-                .required(true),
+        .subcommand(
+            SubCommand::with_name("to-vcf")
+                .about("Writes the SNVs and indels of the records that pass validation as a VCF against the target")
+                .args(io_args("Where to write the VCF (default: stdout)"))
+                .args(validation_policy_args()),
         )
-        .arg(
-            Arg::with_name("error-mode")
-                .short('e')
-                .long("error-mode")
-                .value_name("MODE")
-                .help("Error handling mode: omit, report")
-                .takes_value(true)
-                .required(false)
-                .default_value("omit"),
+        .subcommand(
+            SubCommand::with_name("to-sam")
+                .about("Converts the records that pass validation to SAM, with SEQ from the query FASTA and recomputed NM and MD tags")
+                .args(io_args("Where to write the SAM (default: stdout)"))
+                .args(validation_policy_args()),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("to-maf")
+                .about("Converts the records that pass validation to pairwise MAF blocks of their aligned sequences")
+                .args(io_args("Where to write the MAF (default: stdout)"))
+                .args(validation_policy_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Works with saved error reports")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("view")
+                        .about("Lists errors from an error store within a target region")
+                        .arg(
+                            Arg::with_name("store")
+                                .short('s')
+                                .long("store")
+                                .value_name("FILE")
+                                .help("Path to the error store written with --error-store")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("region")
+                                .value_name("REGION")
+                                .help("Target region, e.g. chr1:1,200,000-1,300,000")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("query")
+                        .about("Filters entries of an error store and re-renders them")
+                        .arg(
+                            Arg::with_name("store")
+                                .short('s')
+                                .long("store")
+                                .value_name("FILE")
+                                .help("Path to the error store written with --error-store")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("error-type")
+                                .long("error-type")
+                                .visible_alias("type")
+                                .value_name("TYPE")
+                                .help("Only show errors of this type, e.g. Mismatch")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("sequence")
+                                .long("sequence")
+                                .value_name("NAME")
+                                .help("Only show errors involving this query or target sequence")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("region")
+                                .long("region")
+                                .value_name("REGION")
+                                .help("Only show errors in this target region")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("line")
+                                .long("line")
+                                .value_name("N")
+                                .help("Only show errors from this PAF line")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("show-inputs")
+                                .long("show-inputs")
+                                .help("Print the input files recorded in the store first"),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("Output format: text, bed")
+                                .takes_value(true)
+                                .default_value("text"),
+                        ),
+                ),
+        )
+}
+
+/// The input and output options of the subcommands that read a PAF and its
+/// FASTA files and write a file of their own, see `open_inputs`.
+fn io_args(output_help: &'static str) -> Vec<Arg<'static>> {
+    vec![
+        Arg::with_name("query_fasta")
+            .short('q')
+            .long("query-fasta")
+            .value_name("QUERY_FASTA")
+            .help("Path to the query FASTA file")
+            .takes_value(true)
+            .required(true),
+        Arg::with_name("target_fasta")
+            .short('t')
+            .long("target-fasta")
+            .value_name("TARGET_FASTA")
+            .help("Path to the target FASTA file")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("paf")
+            .short('p')
+            .long("paf")
+            .value_name("PAF")
+            .help("Path to the PAF file")
+            .takes_value(true)
+            .required(true),
+        Arg::with_name("output")
+            .short('o')
+            .long("output")
+            .value_name("FILE")
+            .help(output_help)
+            .takes_value(true),
+    ]
+}
+
+/// Help of `--checks`, listing the names of `Check::ALL`.
+fn checks_help() -> &'static str {
+    static HELP: OnceLock<String> = OnceLock::new();
+    HELP.get_or_init(|| {
+        let names: Vec<&str> = Check::ALL.iter().map(|check| check.name()).collect();
+        format!(
+            "Comma-separated checks to run ({}), or -name to skip one",
+            names.join(", ")
+        )
+    })
+}
 
-    let query_fasta_path = matches.value_of("query_fasta").unwrap();
-    let target_fasta_path = matches.value_of("target_fasta").unwrap_or(query_fasta_path);
+/// Options that decide whether a record passes validation, see
+/// `ValidationPolicy`.
+fn validation_policy_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::with_name("checks")
+            .long("checks")
+            .value_name("LIST")
+            .help(checks_help())
+            .takes_value(true),
+        Arg::with_name("ignore")
+            .long("ignore")
+            .value_name("TYPES")
+            .help("Comma-separated error types (e.g. cigar-mismatch) that are not reported or counted as errors")
+            .takes_value(true),
+        Arg::with_name("lowercase")
+            .long("lowercase")
+            .value_name("POLICY")
+            .help("How soft-masked (lowercase) bases are compared: ignore (case-insensitive), mismatch (case differences are mismatches), warn (case-insensitive, but report alignments entirely in masked sequence)")
+            .takes_value(true)
+            .default_value("ignore"),
+        Arg::with_name("n-policy")
+            .long("n-policy")
+            .value_name("POLICY")
+            .help("How N bases are compared: match-any (N matches any base), mismatch (N only matches N), skip (positions with N aren't compared)")
+            .takes_value(true)
+            .default_value("mismatch"),
+    ]
+}
+
+/// Options of the `check` subcommand, which are also accepted without a
+/// subcommand.
+fn check_args() -> Vec<Arg<'static>> {
+    let mut args = vec![
+        Arg::with_name("config")
+            .long("config")
+            .value_name("FILE")
+            .help("TOML file with option values; options given on the command line take precedence")
+            .takes_value(true),
+        Arg::with_name("query_fasta")
+            .short('q')
+            .long("query-fasta")
+            .value_name("QUERY_FASTA")
+            .help("Path to the bgzip-compressed and tabix-indexed query FASTA file, or refget:<server URL> (required, here or in --config)")
+            .takes_value(true),
+        Arg::with_name("target_fasta")
+            .short('t')
+            .long("target-fasta")
+            .value_name("TARGET_FASTA")
+            .help("Path to the bgzip-compressed and tabix-indexed target FASTA file, or refget:<server URL>")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("paf")
+            .short('p')
+            .long("paf")
+            .value_name("PAF")
+            .help("Path to the PAF file, or - to read from standard input (the default); may be repeated")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .default_value("-"),
+        Arg::with_name("query-name")
+            .long("query-name")
+            .value_name("REGEX")
+            .help("Only validate records whose query name matches REGEX")
+            .takes_value(true),
+        Arg::with_name("target-name")
+            .long("target-name")
+            .value_name("REGEX")
+            .help("Only validate records whose target name matches REGEX")
+            .takes_value(true),
+        Arg::with_name("regions")
+            .long("regions")
+            .value_name("BED")
+            .help("Only validate records whose target interval overlaps a region in BED")
+            .takes_value(true),
+        Arg::with_name("threads")
+            .long("threads")
+            .value_name("N")
+            .help("Number of threads validating records in parallel")
+            .takes_value(true)
+            .default_value("1"),
+        Arg::with_name("max-stream-fasta")
+            .long("max-stream-fasta")
+            .value_name("BYTES")
+            .help("Largest FASTA read into memory from a named pipe or other non-seekable input")
+            .takes_value(true)
+            .default_value("4294967296"),
+        Arg::with_name("decompress-threads")
+            .long("decompress-threads")
+            .value_name("N")
+            .help("Threads for decompressing .paf.gz/.paf.bgz input")
+            .takes_value(true)
+            .required(false)
+            .default_value("4"),
+        Arg::with_name("error-mode")
+            .short('e')
+            .long("error-mode")
+            .value_name("MODE")
+            .help("Error handling mode: omit, report")
+            .takes_value(true)
+            .required(false)
+            .default_value("omit"),
+        Arg::with_name("max-errors")
+            .long("max-errors")
+            .value_name("N")
+            .help("Stop validating once N errors have been recorded")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("target-region")
+            .long("target-region")
+            .value_name("REGION")
+            .help("Only validate the records overlapping this target region, read through the index written by `pafcheck index`")
+            .takes_value(true),
+        Arg::with_name("limit")
+            .long("limit")
+            .value_name("N")
+            .help("Only read the first N records")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Print every error occurrence (-v), and a line for each error-free record (-vv)")
+            .multiple_occurrences(true)
+            .conflicts_with("quiet"),
+        Arg::with_name("quiet")
+            .long("quiet")
+            .help("Only print the final summary, not the errors of each record"),
+        Arg::with_name("annotated-output")
+            .long("annotated-output")
+            .value_name("FILE")
+            .help("Write the PAF to FILE with a vd:Z tag holding each record's validation result")
+            .takes_value(true),
+        Arg::with_name("failing-output")
+            .long("failing-output")
+            .value_name("FILE")
+            .help("Write the records with errors to FILE, with a vd:Z tag holding their errors")
+            .takes_value(true),
+        Arg::with_name("filter")
+            .long("filter")
+            .help("Write the records without errors to stdout, unchanged, and drop the others"),
+        Arg::with_name("per-sequence-summary")
+            .long("per-sequence-summary")
+            .help("Print the query and target sequences with the most errors"),
+        Arg::with_name("per-sequence-tsv")
+            .long("per-sequence-tsv")
+            .value_name("FILE")
+            .help("Write records and errors per query and target sequence to a TSV")
+            .takes_value(true),
+        Arg::with_name("summary-only")
+            .long("summary-only")
+            .help("Only print a table of errors per type and the totals")
+            .conflicts_with("verbose"),
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output format of validation results: text, json, tsv, ndjson")
+            .takes_value(true)
+            .default_value("text"),
+        Arg::with_name("color")
+            .long("color")
+            .value_name("WHEN")
+            .help("Show mismatches with the bases around them highlighted in color: auto (on a terminal), always, never")
+            .takes_value(true)
+            .default_value("auto"),
+        Arg::with_name("refget-aliases")
+            .long("refget-aliases")
+            .value_name("TSV")
+            .help("Name-to-identifier table for sequences fetched from refget: servers")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("rgfa")
+            .long("rgfa")
+            .value_name("GFA")
+            .help("Resolve minigraph target names (segments, paths, stable coordinates) against this rGFA")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("exclude-bed")
+            .long("exclude-bed")
+            .value_name("BED")
+            .help("Skip base-level checks inside these target regions")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("warnings-as-errors")
+            .long("warnings-as-errors")
+            .visible_alias("strict")
+            .help("Report the warning types, such as NonNormalizedCigar, BoundaryIndel and SelfAlignment, as errors, failing the run")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("flag-boundary-indels")
+            .long("flag-boundary-indels")
+            .help("Report alignments that begin or end with an insertion or deletion as errors rather than warnings")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("max-terminal-indel")
+            .long("max-terminal-indel")
+            .value_name("BASES")
+            .help("Report terminal insertions or deletions longer than BASES as errors, and no shorter ones")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("max-indel-len")
+            .long("max-indel-len")
+            .visible_alias("max-indel")
+            .value_name("BASES")
+            .help("Report single insertions or deletions longer than BASES")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("check-splice-sites")
+            .long("check-splice-sites")
+            .help("Report introns (N ops) without GT-AG, GC-AG or AT-AC boundary dinucleotides")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("min-base-quality")
+            .long("min-base-quality")
+            .value_name("QUAL")
+            .help("With a FASTQ query file, report mismatches on query bases below this Phred quality as LowQualityMismatch instead of Mismatch")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("require-extended-cigar")
+            .long("require-extended-cigar")
+            .help("Report M operations, for pipelines that need = and X to tell matches from mismatches")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("ava")
+            .long("ava")
+            .help("Check read-overlap (all-vs-all) semantics: classify overlaps and compare reciprocal records")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("max-hang")
+            .long("max-hang")
+            .value_name("BASES")
+            .help("Largest overhang tolerated at overlap ends in --ava mode")
+            .takes_value(true)
+            .default_value("1000"),
+        Arg::with_name("int-frac")
+            .long("int-frac")
+            .value_name("FRACTION")
+            .help("Overhangs above this fraction of the overlap length make it internal in --ava mode")
+            .takes_value(true)
+            .default_value("0.8"),
+        Arg::with_name("reciprocal-slack")
+            .long("reciprocal-slack")
+            .value_name("BASES")
+            .help("Coordinate difference allowed between reciprocal overlap records in --ava mode")
+            .takes_value(true)
+            .default_value("0"),
+        Arg::with_name("min-query-coverage-fraction")
+            .long("min-query-coverage-fraction")
+            .value_name("FRACTION")
+            .help("Report queries whose aligned fraction (union of their records) is below FRACTION")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("min-identity")
+            .long("min-identity")
+            .value_name("FRACTION")
+            .help("Report records whose identity (identical bases over alignment columns, recomputed from the sequences) is below FRACTION")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("min-block-length")
+            .long("min-block-length")
+            .value_name("BASES")
+            .help("Report records whose alignment block length (column 11) is below BASES as policy violations")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("min-query-coverage")
+            .long("min-query-coverage")
+            .value_name("FRACTION")
+            .help("Report records whose query interval covers less than FRACTION of the query as policy violations")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("best-alignments")
+            .long("best-alignments")
+            .value_name("FILE")
+            .help("Write a TSV with each query's best alignment (identity x length) and alignment counts")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("error-density")
+            .long("error-density")
+            .value_name("FILE")
+            .help("Write validation findings per target window as a bedGraph (or wiggle, for .wig files) track")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("error-bed")
+            .long("error-bed")
+            .value_name("FILE")
+            .help("Write the target position of each mismatch (Mismatch, CigarMismatch, LowQualityMismatch) to a BED file")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("query-error-bed")
+            .long("query-error-bed")
+            .value_name("FILE")
+            .help("Write the query position of each mismatch to a BED file, like --error-bed")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("density-window")
+            .long("density-window")
+            .value_name("BASES")
+            .help("Window size of the --error-density track")
+            .takes_value(true)
+            .default_value("1000"),
+        Arg::with_name("pansn")
+            .long("pansn")
+            .help("Summarize alignment coverage per PanSN (sample#haplotype#contig) haplotype")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("pansn-delim")
+            .long("pansn-delim")
+            .value_name("CHAR")
+            .help("PanSN field delimiter")
+            .takes_value(true)
+            .default_value("#"),
+        Arg::with_name("flag-inter-haplotype")
+            .long("flag-inter-haplotype")
+            .help("Report alignments between different haplotypes of the same PanSN sample")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("exclude-self-sample")
+            .long("exclude-self-sample")
+            .help("Report alignments of a PanSN haplotype to itself (as excluded by wfmash -Y)")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("self-alignments")
+            .long("self-alignments")
+            .value_name("POLICY")
+            .help("What to do with records aligning a sequence to an overlapping interval of itself: error (report them as errors), warn (report them as warnings) or skip (don't check them) [default: warn]")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("protein")
+            .long("protein")
+            .help("Treat the PAF as miniprot protein-to-genome alignments against a protein query FASTA")
+            .takes_value(false)
+            .conflicts_with_all(&["fast", "sample-bases"])
+            .required(false),
+        Arg::with_name("minimizer-check")
+            .long("minimizer-check")
+            .help("Compare each record's CIGAR identity with an estimate from shared minimizers and flag large disagreements")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("max-identity-diff")
+            .long("max-identity-diff")
+            .value_name("FRACTION")
+            .help("Identity difference above which --minimizer-check flags a record")
+            .takes_value(true)
+            .default_value("0.1"),
+        Arg::with_name("tag-epsilon")
+            .long("tag-epsilon")
+            .value_name("FRACTION")
+            .help("Largest difference between de:f, dv:f, gi:f or bi:f and the value recomputed from the CIGAR")
+            .takes_value(true)
+            .default_value("0.01"),
+        Arg::with_name("fast")
+            .long("fast")
+            .help("Spot-check anchor bases per record and fully validate only records that fail")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("fast-anchors")
+            .long("fast-anchors")
+            .value_name("K")
+            .help("Number of anchor positions sampled per record in --fast mode, besides both ends")
+            .takes_value(true)
+            .required(false)
+            .default_value("8"),
+        Arg::with_name("sample")
+            .long("sample")
+            .value_name("FRACTION")
+            .help("Validate only a random fraction of records and extrapolate error rates")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("sample-bases")
+            .long("sample-bases")
+            .value_name("FRACTION")
+            .help("Validate only a random fraction of bases per record and estimate error rates")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Seed for random sampling")
+            .takes_value(true)
+            .required(false)
+            .default_value("1"),
+        Arg::with_name("homopolymer-k")
+            .long("homopolymer-k")
+            .value_name("K")
+            .help("Minimum run length for an error to count as homopolymer context")
+            .takes_value(true)
+            .required(false)
+            .default_value("3"),
+        Arg::with_name("progress")
+            .long("progress")
+            .help("Print progress (records, bytes read, percent done and ETA) to stderr every 10 seconds"),
+        Arg::with_name("status-interval")
+            .long("status-interval")
+            .value_name("SECONDS")
+            .help("Print a one-line status to stderr every SECONDS seconds")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("status-records")
+            .long("status-records")
+            .value_name("N")
+            .help("Print a one-line status to stderr every N records")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("verify-md5")
+            .long("verify-md5")
+            .value_name("FILE")
+            .help("Verify FASTA sequence MD5s against a .dict or name<TAB>md5 file before validating")
+            .takes_value(true)
+            .required(false),
+        Arg::with_name("print-md5")
+            .long("print-md5")
+            .help("Print the MD5 (M5) checksum of every FASTA sequence")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("checksum-inputs")
+            .long("checksum-inputs")
+            .help("Record content checksums of the input files in structured reports")
+            .takes_value(false)
+            .required(false),
+        Arg::with_name("error-store")
+            .long("error-store")
+            .value_name("FILE")
+            .help("Write a position-sorted, block-indexed store of all errors to FILE")
+            .takes_value(true)
+            .required(false),
+    ];
+    args.extend(validation_policy_args());
+    args
+}
+
+fn doctor(matches: &clap::ArgMatches) {
+    let query_fasta = matches.value_of("query_fasta").unwrap();
+    let target_fasta = matches.value_of("target_fasta").unwrap_or(query_fasta);
     let paf_path = matches.value_of("paf").unwrap();
-    let error_mode = matches.value_of("error-mode").unwrap();
+    let findings = diagnose(query_fasta, target_fasta, paf_path);
+    for finding in &findings {
+        let label = match finding.level {
+            Level::Ok => "OK  ",
+            Level::Warn => "WARN",
+            Level::Fail => "FAIL",
+        };
+        println!("[pafcheck] {} {}", label, finding.message);
+        if let Some(fix) = &finding.fix {
+            println!("[pafcheck]      fix: {}", fix);
+        }
+    }
+    if findings.iter().any(|f| f.level == Level::Fail) {
+        std::process::exit(1);
+    }
+}
 
-    if let Err(e) = validate_paf(query_fasta_path, target_fasta_path, paf_path, error_mode) {
-        eprintln!("[pafcheck] Error: {}", e);
+/// Reads a `--config` file, keyed by the ids of the `check` options its keys name.
+fn load_check_config(path: &str) -> Result<HashMap<&'static str, ConfigValue>> {
+    let config = Config::from_path(path)?;
+    let args = check_args();
+    let mut values = HashMap::new();
+    for (key, value) in config.entries() {
+        let arg = args
+            .iter()
+            .find(|arg| {
+                let aliases = arg.get_all_aliases().unwrap_or_default();
+                (arg.get_long() == Some(key) || aliases.contains(&key)) && key != "config"
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown option in config file: {}", key))?;
+        let valid = match value {
+            ConfigValue::String(_) => arg.is_takes_value_set() || arg.is_multiple_occurrences_set(),
+            ConfigValue::List(_) => arg.is_multiple_occurrences_set(),
+            ConfigValue::Bool(_) => !arg.is_takes_value_set(),
+        };
+        if !valid {
+            anyhow::bail!("Invalid value for {} in config file: {:?}", key, value);
+        }
+        values.insert(arg.get_id(), value.clone());
+    }
+    Ok(values)
+}
+
+/// `check` options from the command line, falling back to the `--config`
+/// file for options not given there.
+struct CheckMatches<'a> {
+    matches: &'a clap::ArgMatches,
+    config: Option<&'a HashMap<&'static str, ConfigValue>>,
+}
+
+impl<'a> CheckMatches<'a> {
+    fn configured(&self, id: &str) -> Option<&'a ConfigValue> {
+        if self.matches.occurrences_of(id) > 0 {
+            return None;
+        }
+        self.config?.get(id)
+    }
+
+    fn value_of(&self, id: &str) -> Option<&'a str> {
+        match self.configured(id) {
+            Some(ConfigValue::String(value)) => Some(value),
+            Some(ConfigValue::List(values)) => values.first().map(String::as_str),
+            _ => self.matches.value_of(id),
+        }
+    }
+
+    fn values_of(&self, id: &str) -> Vec<&'a str> {
+        match self.configured(id) {
+            Some(ConfigValue::String(value)) => vec![value],
+            Some(ConfigValue::List(values)) => values.iter().map(String::as_str).collect(),
+            _ => self
+                .matches
+                .values_of(id)
+                .map(Iterator::collect)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn is_present(&self, id: &str) -> bool {
+        match self.configured(id) {
+            Some(ConfigValue::Bool(value)) => *value,
+            Some(_) => self.occurrences_of(id) > 0,
+            None => self.matches.is_present(id),
+        }
+    }
+
+    /// For counted flags like `-vv`, given as a number in the config file.
+    fn occurrences_of(&self, id: &str) -> u64 {
+        match self.configured(id) {
+            Some(ConfigValue::Bool(value)) => u64::from(*value),
+            Some(ConfigValue::String(value)) => value.parse().unwrap_or(0),
+            Some(ConfigValue::List(_)) | None => self.matches.occurrences_of(id),
+        }
+    }
+}
+
+/// The options that decide whether a record passes validation, which the
+/// converters take too: the checks to run, the error types to ignore, and
+/// how soft-masked and `N` bases compare.
+struct ValidationPolicy {
+    checks: CheckSet,
+    ignore: Vec<ErrorType>,
+    lowercase: LowercasePolicy,
+    n_policy: NPolicy,
+}
+
+impl ValidationPolicy {
+    fn from_matches(matches: &CheckMatches) -> Result<Self> {
+        let checks = match matches.value_of("checks") {
+            Some(list) => CheckSet::parse(list).context("--checks")?,
+            None => CheckSet::all(),
+        };
+        let ignore = match matches.value_of("ignore") {
+            Some(list) => list
+                .split(',')
+                .map(|name| name.trim().parse())
+                .collect::<Result<_>>()
+                .context("--ignore")?,
+            None => Vec::new(),
+        };
+        Ok(ValidationPolicy {
+            checks,
+            ignore,
+            lowercase: matches
+                .value_of("lowercase")
+                .unwrap()
+                .parse()
+                .context("--lowercase")?,
+            n_policy: matches
+                .value_of("n-policy")
+                .unwrap()
+                .parse()
+                .context("--n-policy")?,
+        })
+    }
+
+    /// The aligned (uppercased) sequences of a record that passes
+    /// validation, fetched once for validation and conversion. Warnings and
+    /// ignored error types don't fail a record.
+    fn valid_sequences(
+        &self,
+        record: &PafRecord,
+        fasta_reader: &mut MultiFastaReader,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let options = ValidationOptions {
+            checks: self.checks,
+            lowercase: self.lowercase,
+            n_policy: self.n_policy,
+            ..Default::default()
+        };
+        let (mut query_seq, mut target_seq) =
+            fetch_aligned_sequences_with_case(record, fasta_reader)?;
+        let mut sink = Vec::new();
+        if let Err(e) = validate_aligned_sequences(
+            record,
+            fasta_reader,
+            "omit",
+            &options,
+            &mut query_seq,
+            &mut target_seq,
+            &mut sink,
+        ) {
+            let Some(validation_error) = e.downcast_ref::<ValidationError>() else {
+                return Err(e);
+            };
+            let errors: usize = validation_error
+                .errors
+                .iter()
+                .filter(|(error_type, _)| {
+                    error_type.severity() == Severity::Error && !self.ignore.contains(error_type)
+                })
+                .map(|(_, error_info)| error_info.count)
+                .sum();
+            if errors > 0 {
+                anyhow::bail!("fails validation with {} errors", errors);
+            }
+        }
+        Ok((query_seq, target_seq))
+    }
+}
+
+/// Sequence names and lengths of a converter's target FASTA, for the
+/// headers of its output. Converters open local FASTA files only, so the
+/// index exists once the FASTA is open.
+fn target_contigs(target_fasta: &str) -> Result<Vec<(String, usize)>> {
+    read_fai(&fai_path(target_fasta))
+}
+
+/// Parses the validation options and runs the validation.
+fn check(cli_matches: &clap::ArgMatches) {
+    let config = match cli_matches.value_of("config").map(load_check_config) {
+        None => None,
+        Some(Ok(config)) => Some(config),
+        Some(Err(e)) => {
+            eprintln!("[pafcheck] Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let matches = CheckMatches {
+        matches: cli_matches,
+        config: config.as_ref(),
+    };
+    let query_fasta = match matches.value_of("query_fasta") {
+        Some(query_fasta) => query_fasta,
+        None => {
+            eprintln!("[pafcheck] Error: -q/--query-fasta is required, on the command line or in --config");
+            std::process::exit(1);
+        }
+    };
+    let homopolymer_k = match matches.value_of("homopolymer-k").unwrap().parse() {
+        Ok(k) => k,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --homopolymer-k must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let fast_anchors = match matches.value_of("fast-anchors").unwrap().parse() {
+        Ok(k) => k,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --fast-anchors must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let sample_bases = match matches.value_of("sample-bases").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(fraction)) if fraction > 0.0 && fraction <= 1.0 => Some(fraction),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --sample-bases must be a fraction in (0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let sample_records = match matches.value_of("sample").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(fraction)) if fraction > 0.0 && fraction <= 1.0 => Some(fraction),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --sample must be a fraction in (0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let seed = match matches.value_of("seed").unwrap().parse() {
+        Ok(seed) => seed,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --seed must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let decompress_threads = match matches.value_of("decompress-threads").unwrap().parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --decompress-threads must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let status_interval = match matches.value_of("status-interval").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(secs)) if secs > 0.0 => Some(std::time::Duration::from_secs_f64(secs)),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --status-interval must be a positive number of seconds");
+            std::process::exit(1);
+        }
+    };
+    let status_records = match matches.value_of("status-records").map(str::parse::<usize>) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --status-records must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let status_interval = match status_interval {
+        None if matches.is_present("progress") && status_records.is_none() => {
+            Some(PROGRESS_INTERVAL)
+        }
+        interval => interval,
+    };
+    let max_terminal_indel = match matches.value_of("max-terminal-indel").map(str::parse) {
+        None => None,
+        Some(Ok(n)) => Some(n),
+        Some(Err(_)) => {
+            eprintln!("[pafcheck] Error: --max-terminal-indel must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let min_base_quality = match matches.value_of("min-base-quality").map(str::parse) {
+        None => None,
+        Some(Ok(quality)) => Some(quality),
+        Some(Err(_)) => {
+            eprintln!("[pafcheck] Error: --min-base-quality must be an integer from 0 to 255");
+            std::process::exit(1);
+        }
+    };
+    let max_indel_len = match matches.value_of("max-indel-len").map(str::parse) {
+        None => None,
+        Some(Ok(n)) => Some(n),
+        Some(Err(_)) => {
+            eprintln!("[pafcheck] Error: --max-indel-len must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let ValidationPolicy {
+        checks,
+        ignore,
+        lowercase,
+        n_policy,
+    } = match ValidationPolicy::from_matches(&matches) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("[pafcheck] Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let record_filter = match RecordFilter::from_patterns(
+        matches.value_of("query-name"),
+        matches.value_of("target-name"),
+    )
+    .and_then(|filter| match matches.value_of("regions") {
+        Some(path) => Ok(filter.with_regions(BedRegions::from_path(path)?)),
+        None => Ok(filter),
+    }) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("[pafcheck] Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let max_errors = match matches.value_of("max-errors").map(str::parse) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --max-errors must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let limit = match matches.value_of("limit").map(str::parse) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --limit must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let max_hang = match matches.value_of("max-hang").unwrap().parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --max-hang must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let int_frac = match matches.value_of("int-frac").unwrap().parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => f,
+        _ => {
+            eprintln!("[pafcheck] Error: --int-frac must be a fraction in [0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let reciprocal_slack = match matches.value_of("reciprocal-slack").unwrap().parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --reciprocal-slack must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let max_identity_diff = match matches
+        .value_of("max-identity-diff")
+        .unwrap()
+        .parse::<f64>()
+    {
+        Ok(f) if (0.0..=1.0).contains(&f) => f,
+        _ => {
+            eprintln!("[pafcheck] Error: --max-identity-diff must be a fraction in [0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let tag_epsilon = match matches.value_of("tag-epsilon").unwrap().parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => f,
+        _ => {
+            eprintln!("[pafcheck] Error: --tag-epsilon must be a fraction in [0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let self_alignments = match matches.value_of("self-alignments") {
+        None => SelfAlignmentPolicy::default(),
+        Some(policy) => match policy.parse::<SelfAlignmentPolicy>() {
+            Ok(policy) => policy,
+            Err(e) => {
+                eprintln!("[pafcheck] Error: --self-alignments: {}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+    let pansn_delim = {
+        let mut chars = matches.value_of("pansn-delim").unwrap().chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => {
+                eprintln!("[pafcheck] Error: --pansn-delim must be a single character");
+                std::process::exit(1);
+            }
+        }
+    };
+    let min_query_coverage = match matches
+        .value_of("min-query-coverage-fraction")
+        .map(str::parse::<f64>)
+    {
+        None => None,
+        Some(Ok(f)) if (0.0..=1.0).contains(&f) => Some(f),
+        Some(_) => {
+            eprintln!(
+                "[pafcheck] Error: --min-query-coverage-fraction must be a fraction in [0, 1]"
+            );
+            std::process::exit(1);
+        }
+    };
+    let min_identity = match matches.value_of("min-identity").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(f)) if (0.0..=1.0).contains(&f) => Some(f),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --min-identity must be a fraction in [0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let min_block_length = match matches.value_of("min-block-length").map(str::parse) {
+        None => None,
+        Some(Ok(n)) => Some(n),
+        Some(Err(_)) => {
+            eprintln!("[pafcheck] Error: --min-block-length must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+    let min_record_query_coverage = match matches
+        .value_of("min-query-coverage")
+        .map(str::parse::<f64>)
+    {
+        None => None,
+        Some(Ok(f)) if (0.0..=1.0).contains(&f) => Some(f),
+        Some(_) => {
+            eprintln!("[pafcheck] Error: --min-query-coverage must be a fraction in [0, 1]");
+            std::process::exit(1);
+        }
+    };
+    let density_window = match matches.value_of("density-window").unwrap().parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("[pafcheck] Error: --density-window must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let max_stream_fasta = match matches.value_of("max-stream-fasta").unwrap().parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("[pafcheck] Error: --max-stream-fasta must be a number of bytes");
+            std::process::exit(1);
+        }
+    };
+    let threads = match matches.value_of("threads").unwrap().parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("[pafcheck] Error: --threads must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let format: OutputFormat = match matches.value_of("format").unwrap().parse() {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("[pafcheck] Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let summary_only = matches.is_present("summary-only");
+    let filter = matches.is_present("filter");
+    if filter && format != OutputFormat::Text {
+        eprintln!(
+            "[pafcheck] Error: --filter writes records to stdout and can't be used with --format"
+        );
         std::process::exit(1);
     }
+    let verbosity = if matches.is_present("quiet") || summary_only {
+        Verbosity::Quiet
+    } else {
+        match matches.occurrences_of("verbose") {
+            0 => Verbosity::Normal,
+            1 => Verbosity::Sites,
+            _ => Verbosity::Records,
+        }
+    };
+    // Mismatch context goes with the per-record errors of the text report,
+    // which `--filter` moves to stderr.
+    let color = match matches.value_of("color").unwrap() {
+        "always" => true,
+        "never" => false,
+        "auto" => {
+            std::env::var_os("NO_COLOR").is_none()
+                && if filter {
+                    std::io::stderr().is_terminal()
+                } else {
+                    std::io::stdout().is_terminal()
+                }
+        }
+        other => {
+            eprintln!(
+                "[pafcheck] Error: --color must be auto, always or never, not {}",
+                other
+            );
+            std::process::exit(1);
+        }
+    } && format == OutputFormat::Text
+        && verbosity != Verbosity::Quiet;
+    let options = RunOptions {
+        query_fasta,
+        target_fasta: matches.value_of("target_fasta").unwrap_or(query_fasta),
+        paf_paths: matches.values_of("paf"),
+        decompress_threads,
+        max_stream_fasta,
+        threads,
+        error_mode: matches.value_of("error-mode").unwrap(),
+        error_store_path: matches.value_of("error-store"),
+        rgfa_path: matches.value_of("rgfa"),
+        refget_aliases: matches.value_of("refget-aliases"),
+        exclude_bed: matches.value_of("exclude-bed"),
+        homopolymer_k,
+        check_splice_sites: matches.is_present("check-splice-sites"),
+        flag_boundary_indels: matches.is_present("flag-boundary-indels"),
+        warnings_as_errors: matches.is_present("warnings-as-errors"),
+        max_terminal_indel,
+        max_indel_len,
+        ava: matches.is_present("ava"),
+        max_hang,
+        int_frac,
+        reciprocal_slack,
+        min_query_coverage,
+        min_identity,
+        min_block_length,
+        min_record_query_coverage,
+        best_alignments: matches.value_of("best-alignments"),
+        error_density: matches.value_of("error-density"),
+        density_window,
+        error_bed: matches.value_of("error-bed"),
+        query_error_bed: matches.value_of("query-error-bed"),
+        pansn: matches.is_present("pansn"),
+        pansn_delim,
+        flag_inter_haplotype: matches.is_present("flag-inter-haplotype"),
+        exclude_self_sample: matches.is_present("exclude-self-sample"),
+        self_alignments,
+        protein: matches.is_present("protein"),
+        minimizer_check: matches.is_present("minimizer-check"),
+        max_identity_diff,
+        tag_epsilon,
+        lowercase,
+        n_policy,
+        require_extended_cigar: matches.is_present("require-extended-cigar"),
+        min_base_quality,
+        fast: matches.is_present("fast"),
+        fast_anchors,
+        sample_bases,
+        seed,
+        status_interval,
+        status_records,
+        checksum_inputs: matches.is_present("checksum-inputs"),
+        verify_md5: matches.value_of("verify-md5"),
+        print_md5: matches.is_present("print-md5"),
+        max_errors,
+        limit,
+        target_region: matches.value_of("target-region"),
+        per_sequence_summary: matches.is_present("per-sequence-summary"),
+        per_sequence_tsv: matches.value_of("per-sequence-tsv"),
+        sample_records,
+        annotated_output: matches.value_of("annotated-output"),
+        failing_output: matches.value_of("failing-output"),
+        filter,
+        color,
+        summary_only,
+        checks,
+        ignore,
+        record_filter,
+        format,
+        verbosity,
+    };
+    LOG_TO_STDERR.store(format != OutputFormat::Text || filter, Ordering::Relaxed);
+
+    if let Err(e) = validate_paf(&options) {
+        // As a filter, records with errors are dropped rather than failing
+        // the run.
+        if filter
+            && e.downcast_ref::<RunFailure>()
+                .is_some_and(|failure| !matches!(failure, RunFailure::Parse(_)))
+        {
+            return;
+        }
+        eprintln!("[pafcheck] Error: {}", e);
+        let code = e
+            .downcast_ref::<RunFailure>()
+            .map_or(1, RunFailure::exit_code);
+        std::process::exit(code);
+    }
 }
 
-fn validate_paf(
-    query_fasta: &str,
-    target_fasta: &str,
-    paf_path: &str,
-    error_mode: &str,
-) -> Result<()> {
-    let mut fasta_reader = MultiFastaReader::new(query_fasta, target_fasta)
-        .context("Failed to create FASTA readers")?;
-    let paf_file = File::open(paf_path).context("Failed to open PAF file")?;
-    let reader = BufReader::new(paf_file);
-
-    let mut total_error_count = 0;
-    let mut error_type_counts: HashMap<ErrorType, usize> = HashMap::new();
-
-    for (line_number, line) in reader.lines().enumerate() {
-        let line = line.context("Failed to read PAF line")?;
-        let record = PafRecord::from_line(&line).context(format!(
-            "Failed to parse PAF record at line {}",
-            line_number + 1
-        ))?;
+/// Outcomes of a validation run with their own exit codes; any other error
+/// (bad options, unreadable inputs) exits with 1.
+#[derive(Debug, thiserror::Error)]
+enum RunFailure {
+    #[error("Failed to parse PAF record at line {0}")]
+    Parse(usize),
+    #[error("PAF validation failed with {errors} errors; sequences of {records} records could not be fetched")]
+    Fetch { records: usize, errors: usize },
+    #[error("PAF validation failed with {0} errors")]
+    Validation(usize),
+}
+
+impl RunFailure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunFailure::Parse(_) => 2,
+            RunFailure::Fetch { .. } => 3,
+            RunFailure::Validation(_) => 4,
+        }
+    }
+}
+
+/// Settings for a validation run, as given on the command line.
+struct RunOptions<'a> {
+    query_fasta: &'a str,
+    target_fasta: &'a str,
+    paf_paths: Vec<&'a str>,
+    decompress_threads: u32,
+    max_stream_fasta: u64,
+    threads: usize,
+    error_mode: &'a str,
+    error_store_path: Option<&'a str>,
+    rgfa_path: Option<&'a str>,
+    refget_aliases: Option<&'a str>,
+    exclude_bed: Option<&'a str>,
+    homopolymer_k: usize,
+    check_splice_sites: bool,
+    flag_boundary_indels: bool,
+    warnings_as_errors: bool,
+    max_terminal_indel: Option<usize>,
+    max_indel_len: Option<usize>,
+    ava: bool,
+    max_hang: usize,
+    int_frac: f64,
+    reciprocal_slack: usize,
+    min_query_coverage: Option<f64>,
+    min_identity: Option<f64>,
+    min_block_length: Option<usize>,
+    min_record_query_coverage: Option<f64>,
+    best_alignments: Option<&'a str>,
+    error_density: Option<&'a str>,
+    density_window: usize,
+    error_bed: Option<&'a str>,
+    query_error_bed: Option<&'a str>,
+    pansn: bool,
+    pansn_delim: char,
+    flag_inter_haplotype: bool,
+    exclude_self_sample: bool,
+    self_alignments: SelfAlignmentPolicy,
+    protein: bool,
+    minimizer_check: bool,
+    max_identity_diff: f64,
+    tag_epsilon: f64,
+    lowercase: LowercasePolicy,
+    n_policy: NPolicy,
+    require_extended_cigar: bool,
+    min_base_quality: Option<u8>,
+    fast: bool,
+    fast_anchors: usize,
+    sample_bases: Option<f64>,
+    seed: u64,
+    status_interval: Option<std::time::Duration>,
+    status_records: Option<usize>,
+    checksum_inputs: bool,
+    verify_md5: Option<&'a str>,
+    print_md5: bool,
+    max_errors: Option<usize>,
+    /// Records read before stopping, across all PAF files.
+    limit: Option<usize>,
+    /// Target region whose records are read through the PAF index.
+    target_region: Option<&'a str>,
+    per_sequence_summary: bool,
+    per_sequence_tsv: Option<&'a str>,
+    sample_records: Option<f64>,
+    annotated_output: Option<&'a str>,
+    failing_output: Option<&'a str>,
+    /// Write the records without errors to stdout instead of a report.
+    filter: bool,
+    /// Show the highlighted bases around mismatches in the text report.
+    color: bool,
+    summary_only: bool,
+    checks: CheckSet,
+    ignore: Vec<ErrorType>,
+    record_filter: RecordFilter,
+    format: OutputFormat,
+    verbosity: Verbosity,
+}
+
+fn validate_paf(options: &RunOptions) -> Result<()> {
+    if options.verify_md5.is_some() || options.print_md5 {
+        check_sequence_md5s(options)?;
+    }
+    let error_mode = options.error_mode;
+    let error_store_path = options.error_store_path;
+    let query_source = SequenceSource::open_with_stream_limit(
+        options.query_fasta,
+        options.refget_aliases,
+        options.max_stream_fasta,
+    )
+    .context("Failed to open query sequences")?;
+    // A pipe can only be read once, so reuse the query sequences if they are also the targets.
+    let target_source = match query_source.share() {
+        Some(shared) if options.target_fasta == options.query_fasta => shared,
+        _ => SequenceSource::open_with_stream_limit(
+            options.target_fasta,
+            options.refget_aliases,
+            options.max_stream_fasta,
+        )
+        .context("Failed to open target sequences")?,
+    };
+    let mut fasta_reader = MultiFastaReader::from_sources(query_source, target_source);
+    let target_graph = match options.rgfa_path {
+        Some(rgfa_path) => Some(Arc::new(
+            RgfaGraph::from_path(rgfa_path).context("Failed to load rGFA")?,
+        )),
+        None => None,
+    };
+    if let Some(graph) = &target_graph {
+        fasta_reader = fasta_reader.with_shared_target_graph(Arc::clone(graph));
+    }
+    let validation_options = Arc::new(ValidationOptions {
+        checks: options.checks,
+        exclude_regions: options
+            .exclude_bed
+            .map(BedRegions::from_path)
+            .transpose()
+            .context("Failed to load exclusion BED")?,
+        check_splice_sites: options.check_splice_sites,
+        // Terminal indels are warnings unless --max-terminal-indel picks out
+        // the long ones.
+        flag_boundary_indels: options.flag_boundary_indels || options.max_terminal_indel.is_none(),
+        max_terminal_indel: options.max_terminal_indel,
+        max_indel_len: options.max_indel_len,
+        context_bases: if options.color { CONTEXT_BASES } else { 0 },
+        lowercase: options.lowercase,
+        n_policy: options.n_policy,
+        require_extended_cigar: options.require_extended_cigar,
+        min_base_quality: options.min_base_quality,
+        min_identity: options.min_identity,
+    });
+    let mut pool = if options.threads <= 1 {
+        None
+    } else if is_stream(options.query_fasta) || is_stream(options.target_fasta) {
+        eprintln!("[pafcheck] FASTA input is a pipe, which can only be read once; validating with one thread");
+        None
+    } else if options.max_errors.is_some() {
+        // Errors of records still on the workers aren't counted yet, so the
+        // run wouldn't stop at the same record.
+        eprintln!("[pafcheck] --max-errors stops after a record's errors are counted; validating with one thread");
+        None
+    } else {
+        let spec = ReaderSpec {
+            query: options.query_fasta.to_string(),
+            target: options.target_fasta.to_string(),
+            refget_aliases: options.refget_aliases.map(str::to_string),
+            target_graph: target_graph.clone(),
+        };
+        Some(ValidationPool::new(
+            options.threads,
+            spec,
+            Arc::clone(&validation_options),
+            error_mode,
+        ))
+    };
+    let mut pending: Vec<(usize, PafRecord)> = Vec::new();
+
+    let mut filtered_records = 0;
+    let mut unsampled_records = 0;
+    // Separate from `rng` so that --sample picks the same records with or without --sample-bases.
+    let mut record_rng = SplitMix64::new(options.seed ^ 0x5eed_5eed_5eed_5eed);
+    let mut escalated_records = 0;
+    let mut flagged_records = 0;
+    let mut excluded_bases = 0;
+    let mut rng = SplitMix64::new(options.seed);
+    let target_region = options.target_region.map(parse_region).transpose()?;
+    // With --target-region, only a part of the input is read.
+    let total_bytes = match target_region {
+        Some(_) => None,
+        None => uncompressed_size(&options.paf_paths),
+    };
+    let mut status = StatusReporter::new(options.status_interval, options.status_records)
+        .with_total_bytes(total_bytes);
+    let (mut records_read, mut bytes_read) = (0, 0);
+    let (mut sampled_bases, mut sampled_errors) = (0, 0);
+    let mut residues = ResidueCounts::default();
+    let mut reciprocal = options
+        .ava
+        .then(|| ReciprocalChecker::new(options.reciprocal_slack));
+    let mut overlap_counts: HashMap<OverlapClass, usize> = HashMap::new();
+    let mut query_coverage = CoverageTracker::new();
+    let mut query_lengths: HashMap<String, (usize, usize)> = HashMap::new();
+    // Also counted as filtered.
+    let mut skipped_self_alignments = 0;
+    let mut primaries = PrimaryTracker::new();
+    let (query_fai_lengths, target_fai_lengths) = if options.checks.contains(Check::Coordinates) {
+        (
+            fai_lengths(options.query_fasta),
+            fai_lengths(options.target_fasta),
+        )
+    } else {
+        (HashMap::new(), HashMap::new())
+    };
+    let mut best_alignments = options.best_alignments.map(|_| BestAlignmentTable::new());
+    if options.min_query_coverage.is_some() && !options.query_fasta.starts_with(REFGET_PREFIX) {
+        // Seed with every query so that queries without any record are reported too.
+        if let Ok(queries) = read_fai(&fai_path(options.query_fasta)) {
+            for (name, length) in queries {
+                query_coverage.add(&name, length, 0, 0);
+            }
+        }
+    }
+    let mut tally = ErrorTally {
+        total: 0,
+        by_type: BTreeMap::new(),
+        warnings: BTreeMap::new(),
+        warnings_as_errors: options.warnings_as_errors,
+        promoted: [
+            (
+                ErrorType::BoundaryIndel,
+                options.flag_boundary_indels || options.max_terminal_indel.is_some(),
+            ),
+            (
+                ErrorType::SelfAlignment,
+                options.self_alignments == SelfAlignmentPolicy::Error,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(error_type, promoted)| promoted.then_some(error_type))
+        .collect(),
+        context_profile: ErrorContextProfile::new(options.homopolymer_k),
+        density: options
+            .error_density
+            .map(|_| ErrorDensity::new(options.density_window)),
+        target_bed: options.error_bed.map(create_bed_writer).transpose()?,
+        query_bed: options.query_error_bed.map(create_bed_writer).transpose()?,
+        store: match error_store_path {
+            Some(path) => {
+                Some(ErrorStoreWriter::new(path).with_provenance(describe_inputs(options)?))
+            }
+            None => None,
+        },
+        unfetched: 0,
+        sequences: (options.per_sequence_summary || options.per_sequence_tsv.is_some())
+            .then(SequenceErrorSummary::new),
+        failed_records: 0,
+        last_failed: None,
+        file_index: 0,
+        verdicts: match options.annotated_output {
+            Some(path) => Some(VerdictWriter::new(Box::new(std::io::BufWriter::new(
+                std::fs::File::create(path)
+                    .context(format!("Failed to create annotated PAF: {:?}", path))?,
+            )))),
+            None => None,
+        },
+        failing: match options.failing_output {
+            Some(path) => Some(VerdictWriter::failing_only(Box::new(
+                std::io::BufWriter::new(
+                    std::fs::File::create(path)
+                        .context(format!("Failed to create failing-records PAF: {:?}", path))?,
+                ),
+            ))),
+            None => None,
+        },
+        passing: options.filter.then(|| {
+            VerdictWriter::passing_only(
+                Box::new(std::io::BufWriter::new(std::io::stdout())) as Box<dyn Write>
+            )
+        }),
+        ignored: options
+            .ignore
+            .iter()
+            .map(|error_type| (error_type.clone(), 0))
+            .collect(),
+        format: options.format,
+        verbosity: options.verbosity,
+        events: Vec::new(),
+        holding: false,
+        held: VecDeque::new(),
+    };
+
+    if options.format == OutputFormat::Tsv {
+        println!("{}", TSV_HEADER);
+    }
+    let mut file_summaries = Vec::new();
+    // Set to the file and line at which --max-errors stopped the run.
+    let mut stopped_at = None;
+    // Set when --limit left records unchecked.
+    let mut limited = false;
+    for (file_index, &paf_path) in options.paf_paths.iter().enumerate() {
+        tally.file_index = file_index;
+        if options.paf_paths.len() > 1 {
+            log_line!("[pafcheck] Validating {}", paf_path);
+        }
+        let lines: Box<dyn Iterator<Item = (usize, Result<String>)>> = match &target_region {
+            Some((target_name, start, end)) => {
+                let index = load_paf_index(paf_path)?;
+                let selected = index.overlapping(target_name, *start, *end);
+                log_line!(
+                    "[pafcheck] {} of {} records of {} overlap {}",
+                    selected.len(),
+                    index.len(),
+                    paf_path,
+                    options.target_region.unwrap()
+                );
+                Box::new(IndexedLines::open(paf_path, &selected)?)
+            }
+            None => numbered_records(PafLines::open(paf_path, options.decompress_threads)?),
+        };
+        let errors_before = tally.total;
+        let mut records = 0;
+        for (line_number, line) in lines {
+            // The output of a record comes after that of the records before
+            // it, which are still being validated.
+            tally.holding = !pending.is_empty();
+            if pending.is_empty() {
+                for verdicts in tally.verdict_writers() {
+                    verdicts.flush_queued()?;
+                }
+            }
+            if options.max_errors.is_some_and(|max| tally.total >= max) {
+                stopped_at = Some((paf_path, line_number + 1));
+                break;
+            }
+            let line = line?;
+            bytes_read += line.len() as u64 + 1;
+            if options.protein && line.starts_with("##") {
+                for verdicts in tally.verdict_writers() {
+                    verdicts.push_other(line_number + 1, &line);
+                }
+                continue; // miniprot --aln detail lines
+            }
+            if options.limit.is_some_and(|limit| records_read >= limit) {
+                limited = true;
+                break;
+            }
+            records += 1;
+            records_read += 1;
+            let record = match PafRecord::from_line(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    let negative = negative_columns(&line);
+                    if negative.is_empty() || !options.checks.contains(Check::Coordinates) {
+                        return Err(e.context(RunFailure::Parse(line_number + 1)));
+                    }
+                    for verdicts in tally.verdict_writers() {
+                        verdicts.push_record(line_number + 1, &line);
+                    }
+                    let mut fields = line.split('\t');
+                    let query = fields.next().map(str::to_string);
+                    let target = fields.nth(4).map(str::to_string);
+                    for (column, value) in negative {
+                        tally.add_line_error(
+                            line_number + 1,
+                            query.clone(),
+                            target.clone(),
+                            ErrorType::NegativeCoordinate,
+                            1,
+                            format!("The {} is negative: {}", column, value),
+                        );
+                    }
+                    continue;
+                }
+            };
+            let skipped_self = options.self_alignments == SelfAlignmentPolicy::Skip
+                && options.record_filter.selects(&record)
+                && is_self_alignment(&record);
+            let selected = options.record_filter.selects(&record) && !skipped_self;
+            let sampled = selected
+                && options
+                    .sample_records
+                    .is_none_or(|fraction| record_rng.next_f64() < fraction);
+            for verdicts in tally.verdict_writers() {
+                match sampled {
+                    true => verdicts.push_record(line_number + 1, &line),
+                    false => verdicts.push_skipped(line_number + 1, &line),
+                }
+            }
+            status.tick(
+                records_read,
+                bytes_read,
+                tally.total,
+                &record.query_name,
+                &record.target_name,
+            );
+            // Every record counts as a primary for its secondaries, checked or not.
+            let conflicting_primary = primaries.add(&record, line_number + 1);
+            if let Some(first_line) =
+                conflicting_primary.filter(|_| sampled && options.checks.contains(Check::Tags))
+            {
+                tally.add_error(
+                    line_number + 1,
+                    &record,
+                    ErrorType::ConflictingPrimary,
+                    format!(
+                        "Primary alignment of {}:{}-{} overlaps the primary at line {}",
+                        record.query_name, record.query_start, record.query_end, first_line
+                    ),
+                );
+            }
+            if !selected {
+                filtered_records += 1;
+                skipped_self_alignments += usize::from(skipped_self);
+                continue;
+            }
+            if !sampled {
+                unsampled_records += 1;
+                continue;
+            }
+            if let Some(sequences) = tally.sequences.as_mut() {
+                sequences.add_record(&record.query_name, &record.target_name);
+            }
+
+            if record.mapq.is_none() && options.checks.contains(Check::Coordinates) {
+                tally.add_error(
+                    line_number + 1,
+                    &record,
+                    ErrorType::InvalidMappingQuality,
+                    format!(
+                        "Column 12 is not a mapping quality from 0 to 255: {}",
+                        line.split('\t').nth(11).unwrap_or_default()
+                    ),
+                );
+            }
+            if options.checks.contains(Check::Coordinates) {
+                let errors = coordinate_errors(&record);
+                if !errors.is_empty() {
+                    for (error_type, message) in errors {
+                        tally.add_error(line_number + 1, &record, error_type, message);
+                    }
+                    continue;
+                }
+            }
+
+            if options.checks.contains(Check::Length) {
+                for (error_type, message) in column_errors(&record) {
+                    tally.add_error(line_number + 1, &record, error_type, message);
+                }
+            }
+            if options.checks.contains(Check::Tags) {
+                for (error_type, message) in tag_syntax_errors(&record)
+                    .into_iter()
+                    .chain(tag_errors(&record, options.tag_epsilon))
+                    .chain(cs_errors(&record))
+                {
+                    tally.add_error(line_number + 1, &record, error_type, message);
+                }
+            }
+            if options.checks.contains(Check::Cigar) {
+                let errors = cigar_errors(&record);
+                let malformed = errors
+                    .iter()
+                    .any(|(error_type, _)| *error_type == ErrorType::MalformedCigar);
+                for (error_type, message) in errors {
+                    tally.add_error(line_number + 1, &record, error_type, message);
+                }
+                // There is no alignment to validate.
+                if malformed {
+                    continue;
+                }
+            }
 
-        let mut output = Vec::new();
-        if let Err(e) = validate_record(&record, &mut fasta_reader, error_mode, &mut output) {
-            if let Some(validation_error) = e.downcast_ref::<ValidationError>() {
-                for (error_type, error_info) in &validation_error.errors {
-                    let count = error_info.count;
-                    *error_type_counts.entry(error_type.clone()).or_insert(0) += count;
-                    total_error_count += count;
-                    println!(
-                        "[pafcheck] Error at line {}: {:?}: {}",
+            // Each record must declare the same (unclipped) length for its query.
+            match query_lengths.get(&record.query_name) {
+                Some(&(length, first_line))
+                    if length != record.query_length
+                        && options.checks.contains(Check::Coordinates) =>
+                {
+                    tally.add_error(
                         line_number + 1,
-                        error_type,
-                        error_info.first_message
+                        &record,
+                        ErrorType::InconsistentQueryLength,
+                        format!(
+                            "{} has length {}, but {} at line {}",
+                            record.query_name, record.query_length, length, first_line
+                        ),
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    query_lengths.insert(
+                        record.query_name.clone(),
+                        (record.query_length, line_number + 1),
+                    );
+                }
+            }
+
+            // The length columns must match the FASTAs the records are checked against.
+            for (role, name, length, fai_lengths) in [
+                (
+                    "Query",
+                    &record.query_name,
+                    record.query_length,
+                    &query_fai_lengths,
+                ),
+                (
+                    "Target",
+                    &record.target_name,
+                    record.target_length,
+                    &target_fai_lengths,
+                ),
+            ] {
+                match fai_lengths.get(name) {
+                    Some(&fai_length) if fai_length != length => tally.add_error(
+                        line_number + 1,
+                        &record,
+                        ErrorType::SequenceLengthMismatch,
+                        format!(
+                            "{} {} has length {} in the PAF, but {} in the FASTA index",
+                            role, name, length, fai_length
+                        ),
+                    ),
+                    _ => {}
+                }
+            }
+
+            if let Some(checker) = reciprocal.as_mut() {
+                let class = classify_overlap(&record, options.max_hang, options.int_frac);
+                *overlap_counts.entry(class).or_insert(0) += 1;
+                if class == OverlapClass::Internal && options.verbosity > Verbosity::Quiet {
+                    tally.log(
+                        line_number + 1,
+                        format!(
+                            "[pafcheck] Internal match at line {}: {} and {} overlap without reaching read ends",
+                            line_number + 1,
+                            record.query_name,
+                            record.target_name
+                        ),
+                    );
+                }
+                if let Some(mismatch) = checker.add(&record, line_number + 1) {
+                    tally.add_error(
+                        mismatch.second_line,
+                        &record,
+                        ErrorType::ReciprocalMismatch,
+                        format!(
+                            "overlap of {} and {} disagrees with line {}",
+                            mismatch.reads.0, mismatch.reads.1, mismatch.first_line
+                        ),
+                    );
+                }
+            }
+
+            if let Some(density) = tally.density.as_mut() {
+                density.observe_target(&record.target_name, record.target_length);
+            }
+            if options.pansn || options.min_query_coverage.is_some() {
+                query_coverage.add(
+                    &record.query_name,
+                    record.query_length,
+                    record.query_start,
+                    record.query_end,
+                );
+            }
+            if options.flag_inter_haplotype || options.exclude_self_sample {
+                let pairing =
+                    haplotype_pairing(&record.query_name, &record.target_name, options.pansn_delim);
+                let error_type = match pairing {
+                    Some(HaplotypePairing::SameSample) if options.flag_inter_haplotype => {
+                        Some(ErrorType::InterHaplotypeMapping)
+                    }
+                    Some(HaplotypePairing::SameHaplotype) if options.exclude_self_sample => {
+                        Some(ErrorType::SelfHaplotypeMapping)
+                    }
+                    _ => None,
+                };
+                if let Some(error_type) = error_type {
+                    let message =
+                        format!("{} aligned to {}", record.query_name, record.target_name);
+                    tally.add_error(line_number + 1, &record, error_type, message);
+                }
+            }
+
+            // A warning, or an error with --self-alignments error.
+            if is_self_alignment(&record) {
+                tally.add_error(
+                    line_number + 1,
+                    &record,
+                    ErrorType::SelfAlignment,
+                    format!(
+                        "{}:{}-{} is aligned to an overlapping interval of itself, {}-{}",
+                        record.query_name,
+                        record.query_start,
+                        record.query_end,
+                        record.target_start,
+                        record.target_end
+                    ),
+                );
+            }
+            for (error_type, message) in policy_errors(
+                &record,
+                options.min_block_length,
+                options.min_record_query_coverage,
+            ) {
+                tally.add_error(line_number + 1, &record, error_type, message);
+            }
+
+            if let Some(fraction) = options.sample_bases {
+                match sample_record(&record, &mut fasta_reader, fraction, &mut rng) {
+                    Ok(estimate) => {
+                        sampled_bases += estimate.sampled;
+                        sampled_errors += estimate.errors;
+                        if estimate.errors > 0 && options.checks.contains(Check::Mismatch) {
+                            tally.add_errors(
+                                line_number + 1,
+                                &record,
+                                ErrorType::Mismatch,
+                                estimate.errors,
+                                format!(
+                                    "{} of {} sampled aligned bases disagree with the CIGAR",
+                                    estimate.errors, estimate.sampled
+                                ),
+                            );
+                        }
+                        if options.verbosity > Verbosity::Quiet {
+                            log_line!(
+                        "[pafcheck] Line {}: sampled {} bases, {} errors, estimated error rate {:.6} (95% CI {:.6}-{:.6})",
+                        line_number + 1,
+                        estimate.sampled,
+                        estimate.errors,
+                        estimate.rate,
+                        estimate.ci_low,
+                        estimate.ci_high
+                    );
+                        }
+                    }
+                    Err(e) => {
+                        tally.add_failure(line_number + 1, &record, &e);
+                    }
+                }
+                if !options.protein {
+                    add_identity(
+                        best_alignments.as_mut(),
+                        &record,
+                        line_number + 1,
+                        &mut fasta_reader,
                     );
-                    if count > 1 {
-                        println!("[pafcheck] {:?}: Total occurrences: {}", error_type, count);
+                }
+                continue;
+            }
+
+            let mut flagged = false;
+            if options.minimizer_check && !options.protein {
+                match cross_check_identity(&record, &mut fasta_reader) {
+                    Ok(check) if check.disagrees(options.max_identity_diff) => {
+                        flagged = true;
+                        flagged_records += 1;
+                        if options.verbosity > Verbosity::Quiet {
+                            tally.log(
+                                line_number + 1,
+                                format!(
+                                    "[pafcheck] Priority at line {}: CIGAR identity {:.4} but minimizer estimate {:.4} ({} -> {})",
+                                    line_number + 1,
+                                    check.cigar,
+                                    check.estimated.unwrap_or(0.0),
+                                    record.query_name,
+                                    record.target_name
+                                ),
+                            );
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tally.add_failure(line_number + 1, &record, &e);
+                        continue;
+                    }
+                }
+            }
+
+            if options.fast && !flagged {
+                match spot_check_record(&record, &mut fasta_reader, options.fast_anchors) {
+                    Ok(true) => {
+                        add_identity(
+                            best_alignments.as_mut(),
+                            &record,
+                            line_number + 1,
+                            &mut fasta_reader,
+                        );
+                        continue;
+                    }
+                    Ok(false) => escalated_records += 1,
+                    Err(e) => {
+                        tally.add_failure(line_number + 1, &record, &e);
+                        continue;
                     }
                 }
+            }
+
+            if let Some(regions) = &validation_options.exclude_regions {
+                excluded_bases += regions.covered_bases(
+                    &record.target_name,
+                    record.target_start,
+                    record.target_end,
+                );
+            }
+
+            let mut output = Vec::new();
+            let result = if options.protein {
+                validate_protein_record(&record, &mut fasta_reader, error_mode, &mut output).map(
+                    |counts| {
+                        residues.aligned += counts.aligned;
+                        residues.identical += counts.identical;
+                    },
+                )
+            } else if let Some(pool) = pool.as_mut() {
+                pending.push((line_number + 1, record));
+                if pending.len() >= PARALLEL_BATCH {
+                    validate_pending(pool, &mut pending, &mut tally, best_alignments.as_mut())?;
+                }
+                continue;
             } else {
-                total_error_count += 1;
-                println!("[pafcheck] Error at line {}: {}", line_number + 1, e);
+                let (result, identity) = validate_record_with_identity(
+                    &record,
+                    &mut fasta_reader,
+                    error_mode,
+                    &validation_options,
+                    &mut output,
+                );
+                if let (Some(table), Some(identity)) = (best_alignments.as_mut(), identity) {
+                    table.add(&record, line_number + 1, identity);
+                }
+                result
+            };
+            tally.record_result(line_number + 1, &record, result)?;
+        }
+        if let Some(pool) = pool.as_mut() {
+            validate_pending(pool, &mut pending, &mut tally, best_alignments.as_mut())?;
+        }
+        for verdicts in tally.verdict_writers() {
+            verdicts.flush_queued()?;
+        }
+        file_summaries.push((paf_path, records, tally.total - errors_before));
+        if stopped_at.is_some() || limited {
+            break;
+        }
+    }
+
+    if let Some((paf_path, line_number)) = stopped_at {
+        log_line!(
+            "[pafcheck] Stopped after {} errors (--max-errors {}); records from line {} of {} on were not checked",
+            tally.total,
+            options.max_errors.unwrap(),
+            line_number,
+            paf_path
+        );
+    }
+    if limited {
+        log_line!(
+            "[pafcheck] Stopped after the first {} records (--limit)",
+            records_read
+        );
+    }
+    let truncated = stopped_at.is_some() || limited;
+
+    if options.paf_paths.len() > 1 {
+        log_line!("[pafcheck] Per-file results:");
+        for (paf_path, records, errors) in &file_summaries {
+            log_line!(
+                "[pafcheck]   - {}: {} records, {} errors",
+                paf_path,
+                records,
+                errors
+            );
+        }
+    }
+
+    if options.record_filter.is_active() {
+        log_line!(
+            "[pafcheck] Records skipped by --query-name/--target-name/--regions: {}",
+            filtered_records - skipped_self_alignments
+        );
+    }
+    if skipped_self_alignments > 0 {
+        log_line!(
+            "[pafcheck] Self-alignments skipped by --self-alignments skip: {}",
+            skipped_self_alignments
+        );
+    }
+
+    if let Some(fraction) = options.sample_records {
+        let total_records: usize = file_summaries.iter().map(|(_, records, _)| records).sum();
+        let sampled = total_records - filtered_records - unsampled_records;
+        let estimate = RateEstimate::from_counts(tally.failed_records, sampled);
+        log_line!(
+            "[pafcheck] Sampled {} of {} records (--sample {}): {} with errors, estimated fraction of records with errors {:.6} (95% CI {:.6}-{:.6})",
+            sampled,
+            sampled + unsampled_records,
+            fraction,
+            tally.failed_records,
+            estimate.rate,
+            estimate.ci_low,
+            estimate.ci_high
+        );
+        log_line!(
+            "[pafcheck] Extrapolated errors in all {} records: {:.0} ({:.0}-{:.0} records with errors)",
+            sampled + unsampled_records,
+            tally.total as f64 * (sampled + unsampled_records) as f64 / sampled.max(1) as f64,
+            estimate.ci_low * (sampled + unsampled_records) as f64,
+            estimate.ci_high * (sampled + unsampled_records) as f64
+        );
+    }
+
+    // Primaries may lie outside a partial run.
+    if options.checks.contains(Check::Tags) && !truncated && options.target_region.is_none() {
+        for (name, first_line, count) in primaries.orphan_secondaries() {
+            tally.add_line_error(
+                first_line,
+                Some(name.to_string()),
+                None,
+                ErrorType::OrphanSecondary,
+                1,
+                format!(
+                    "{} has {} secondary alignments (tp:A:S), the first at line {}, but no primary",
+                    name, count, first_line
+                ),
+            );
+        }
+    }
+
+    // Coverage of an interrupted run is incomplete, so don't report it.
+    if let Some(min_fraction) = options.min_query_coverage.filter(|_| !truncated) {
+        for (name, length, covered) in query_coverage.sequences() {
+            let fraction = covered as f64 / length.max(1) as f64;
+            if fraction < min_fraction {
+                tally.add_sequence_error(
+                    name,
+                    ErrorType::LowQueryCoverage,
+                    format!(
+                        "{} has {} of {} bases aligned ({:.4} < {})",
+                        name, covered, length, fraction, min_fraction
+                    ),
+                );
             }
         }
     }
 
-    if total_error_count > 0 {
-        println!("[pafcheck] PAF validation completed with errors:");
-        for (error_type, count) in error_type_counts.iter() {
-            println!("[pafcheck]   - {:?}: {} errors", error_type, count);
+    if let (Some(density), Some(path)) = (tally.density.take(), options.error_density) {
+        let file = std::fs::File::create(path)
+            .context(format!("Failed to create error density track: {:?}", path))?;
+        let mut writer = std::io::BufWriter::new(file);
+        if path.ends_with(".wig") {
+            density.write_wig(&mut writer)?;
+        } else {
+            density.write_bedgraph(&mut writer)?;
+        }
+        writer.flush()?;
+        log_line!("[pafcheck] Wrote error density track to {}", path);
+    }
+
+    for (bed, path) in [
+        (tally.target_bed.take(), options.error_bed),
+        (tally.query_bed.take(), options.query_error_bed),
+    ] {
+        if let (Some(bed), Some(path)) = (bed, path) {
+            let sites = bed
+                .finish()
+                .context(format!("Failed to write {:?}", path))?;
+            log_line!("[pafcheck] Wrote {} mismatch positions to {}", sites, path);
+        }
+    }
+
+    if let (Some(table), Some(path)) = (best_alignments, options.best_alignments) {
+        let file = std::fs::File::create(path)
+            .context(format!("Failed to create best-alignment TSV: {:?}", path))?;
+        let mut writer = std::io::BufWriter::new(file);
+        table.write_tsv(&mut writer)?;
+        writer.flush()?;
+        log_line!("[pafcheck] Wrote best alignment per query to {}", path);
+    }
+
+    if let (Some(verdicts), Some(path)) = (tally.verdicts.take(), options.annotated_output) {
+        verdicts.finish().context("Failed to write annotated PAF")?;
+        log_line!(
+            "[pafcheck] Wrote records with vd:Z validation tags to {}",
+            path
+        );
+    }
+
+    if let (Some(failing), Some(path)) = (tally.failing.take(), options.failing_output) {
+        failing
+            .finish()
+            .context("Failed to write failing-records PAF")?;
+        log_line!(
+            "[pafcheck] Wrote {} records with errors to {}",
+            tally.failed_records,
+            path
+        );
+    }
+
+    if let Some(passing) = tally.passing.take() {
+        passing.finish().context("Failed to write filtered PAF")?;
+        log_line!(
+            "[pafcheck] Dropped {} of {} records: {} with errors, {} not checked",
+            tally.failed_records + filtered_records + unsampled_records,
+            records_read,
+            tally.failed_records,
+            filtered_records + unsampled_records
+        );
+    }
+
+    if let Some(sequences) = tally.sequences.take() {
+        if options.per_sequence_summary {
+            print_sequence_errors(&sequences);
+        }
+        if let Some(path) = options.per_sequence_tsv {
+            let file = std::fs::File::create(path)
+                .context(format!("Failed to create per-sequence TSV: {:?}", path))?;
+            let mut writer = std::io::BufWriter::new(file);
+            sequences.write_tsv(&mut writer)?;
+            writer.flush()?;
+            log_line!("[pafcheck] Wrote errors per sequence to {}", path);
+        }
+    }
+
+    if let Some(store) = tally.store.take() {
+        let stored = store.finish().context("Failed to write error store")?;
+        log_line!(
+            "[pafcheck] Wrote {} errors to {}",
+            stored,
+            error_store_path.unwrap()
+        );
+    }
+
+    if options.sample_bases.is_some() {
+        let estimate = RateEstimate::from_counts(sampled_errors, sampled_bases);
+        log_line!(
+            "[pafcheck] Sampled {} bases with {} errors: estimated error rate {:.6} (95% CI {:.6}-{:.6})",
+            estimate.sampled, estimate.errors, estimate.rate, estimate.ci_low, estimate.ci_high
+        );
+    }
+
+    if validation_options.exclude_regions.is_some() {
+        log_line!(
+            "[pafcheck] Target bases excluded from checks by --exclude-bed: {}",
+            excluded_bases
+        );
+    }
+
+    if options.ava {
+        log_line!("[pafcheck] Overlap classes:");
+        for class in [
+            OverlapClass::Dovetail,
+            OverlapClass::QueryContained,
+            OverlapClass::TargetContained,
+            OverlapClass::Internal,
+        ] {
+            log_line!(
+                "[pafcheck]   - {:?}: {}",
+                class,
+                overlap_counts.get(&class).copied().unwrap_or(0)
+            );
+        }
+    }
+
+    if options.pansn {
+        print_haplotype_coverage(&query_coverage, options.pansn_delim);
+    }
+
+    if options.protein {
+        log_line!(
+            "[pafcheck] Protein mode: {} of {} translated codons match the protein residue ({:.2}%)",
+            residues.identical,
+            residues.aligned,
+            100.0 * residues.identical as f64 / residues.aligned.max(1) as f64
+        );
+    }
+
+    if options.minimizer_check {
+        log_line!(
+            "[pafcheck] Minimizer cross-check: {} records with CIGAR and minimizer identities differing by more than {}",
+            flagged_records, options.max_identity_diff
+        );
+    }
+
+    if options.fast {
+        log_line!(
+            "[pafcheck] Fast mode: {} records failed spot checks and were fully validated",
+            escalated_records
+        );
+    }
+
+    let mut ignored: Vec<_> = tally
+        .ignored
+        .iter()
+        .filter(|(_, &count)| count > 0)
+        .collect();
+    if !ignored.is_empty() {
+        ignored.sort_by_key(|(error_type, _)| error_type.cli_name());
+        log_line!("[pafcheck] Ignored by --ignore:");
+        for (error_type, count) in ignored {
+            log_line!("[pafcheck]   - {:?}: {} errors", error_type, count);
+        }
+    }
+
+    if matches!(options.format, OutputFormat::Json | OutputFormat::Ndjson) {
+        let named_counts = |counts: &BTreeMap<ErrorType, usize>| {
+            let mut counts: Vec<(String, usize)> = counts
+                .iter()
+                .map(|(error_type, count)| (format!("{:?}", error_type), *count))
+                .collect();
+            counts.sort();
+            counts
+        };
+        let counts = named_counts(&tally.by_type);
+        let warning_counts = named_counts(&tally.warnings);
+        let records = file_summaries.iter().map(|(_, records, _)| records).sum();
+        let inputs = describe_inputs(options)?;
+        let summary = ReportSummary {
+            counts: &counts,
+            warning_counts: &warning_counts,
+            inputs: &inputs,
+            records,
+            total_errors: tally.total,
+            truncated,
+        };
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        if options.format == OutputFormat::Json {
+            write_json_report(&mut stdout, &tally.events, &summary)?;
+        } else {
+            write_ndjson_summary(&mut stdout, &summary)?;
+        }
+        stdout.flush()?;
+        if tally.total > 0 {
+            return Err(tally.failure().into());
+        }
+        return Ok(());
+    }
+
+    if options.summary_only {
+        let records: usize = file_summaries.iter().map(|(_, records, _)| records).sum();
+        let mut counts: Vec<(String, &str, usize)> = tally
+            .by_type
+            .iter()
+            .chain(&tally.warnings)
+            .map(|(error_type, count)| {
+                let category = if tally.is_warning(error_type) {
+                    "warning"
+                } else if error_type.is_policy() {
+                    "policy"
+                } else {
+                    "correctness"
+                };
+                (format!("{:?}", error_type), category, *count)
+            })
+            .collect();
+        counts.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        log_line!(
+            "[pafcheck] {:<24} {:<12} {:>12}",
+            "Error type",
+            "Category",
+            "Errors"
+        );
+        for (error_type, category, count) in &counts {
+            log_line!(
+                "[pafcheck] {:<24} {:<12} {:>12}",
+                error_type,
+                category,
+                count
+            );
+        }
+        log_line!("[pafcheck] {:<24} {:<12} {:>12}", "Total", "", tally.total);
+        if !tally.warnings.is_empty() {
+            let warnings: usize = tally.warnings.values().sum();
+            log_line!("[pafcheck] {:<24} {:<12} {:>12}", "Warnings", "", warnings);
+        }
+        log_line!(
+            "[pafcheck] Records checked: {}",
+            records - filtered_records - unsampled_records
+        );
+        return match tally.total {
+            0 => Ok(()),
+            _ => Err(tally.failure().into()),
+        };
+    }
+
+    // The context breakdown is only part of the human-readable report on
+    // stdout.
+    let text = options.format == OutputFormat::Text && !options.filter;
+    if tally.total > 0 {
+        // Policy violations are listed apart from the correctness errors.
+        let (policy, correctness): (Vec<_>, Vec<_>) = tally
+            .by_type
+            .iter()
+            .partition(|(error_type, _)| error_type.is_policy());
+        let policy_total: usize = policy.iter().map(|(_, count)| **count).sum();
+        // Records that couldn't be checked are counted as errors without a type.
+        if tally.total > policy_total {
+            log_line!("[pafcheck] PAF validation completed with errors:");
+        }
+        for (error_type, count) in correctness {
+            log_line!("[pafcheck]   - {:?}: {} errors", error_type, count);
+            if text {
+                tally.context_profile.print_type_context(error_type);
+            }
+        }
+        if !policy.is_empty() {
+            log_line!("[pafcheck] Policy violations:");
+        }
+        for (error_type, count) in policy {
+            log_line!("[pafcheck]   - {:?}: {} errors", error_type, count);
+        }
+        if text {
+            tally.context_profile.print();
+        }
+        print_warnings(&tally.warnings);
+        log_line!("[pafcheck] Total errors: {}", tally.total);
+        Err(tally.failure().into())
+    } else if !tally.warnings.is_empty() {
+        print_warnings(&tally.warnings);
+        log_line!(
+            "[pafcheck] PAF validation completed successfully with {} warnings. No errors found.",
+            tally.warnings.values().sum::<usize>()
+        );
+        Ok(())
+    } else {
+        log_line!("[pafcheck] PAF validation completed successfully. No errors found.");
+        Ok(())
+    }
+}
+
+/// Lists the warnings of a run by type in the summary.
+fn print_warnings(warnings: &BTreeMap<ErrorType, usize>) {
+    if warnings.is_empty() {
+        return;
+    }
+    log_line!("[pafcheck] Warnings (use --warnings-as-errors to fail on them):");
+    for (error_type, count) in warnings {
+        log_line!("[pafcheck]   - {:?}: {} warnings", error_type, count);
+    }
+}
+
+/// Sequence lengths from the `.fai` index of a FASTA, or none if it has no
+/// readable index (e.g. refget or streamed sequences).
+fn fai_lengths(fasta: &str) -> HashMap<String, usize> {
+    if fasta.starts_with(REFGET_PREFIX) {
+        return HashMap::new();
+    }
+    read_fai(&fai_path(fasta))
+        .map(|sequences| sequences.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Status interval used by `--progress` when none is given.
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Total size of the PAF inputs, if they are all uncompressed regular files
+/// (so that the bytes of the lines read measure progress through them).
+fn uncompressed_size(paf_paths: &[&str]) -> Option<u64> {
+    let mut total = 0;
+    for &path in paf_paths {
+        let metadata = std::fs::metadata(path).ok()?;
+        if path == "-" || !metadata.is_file() || is_gzipped(path).ok()? {
+            return None;
+        }
+        total += metadata.len();
+    }
+    Some(total)
+}
+
+/// Records handed to the validation workers at a time.
+const PARALLEL_BATCH: usize = 4096;
+
+/// Validates the queued records on the worker pool and tallies the results
+/// in line order.
+fn validate_pending(
+    pool: &mut ValidationPool,
+    pending: &mut Vec<(usize, PafRecord)>,
+    tally: &mut ErrorTally,
+    mut best_alignments: Option<&mut BestAlignmentTable>,
+) -> Result<()> {
+    let (line_numbers, records): (Vec<usize>, Vec<PafRecord>) = pending.drain(..).unzip();
+    tally.holding = false;
+    for (line_number, (record, result, identity)) in
+        line_numbers.into_iter().zip(pool.validate_batch(records)?)
+    {
+        if let (Some(table), Some(identity)) = (best_alignments.as_deref_mut(), identity) {
+            table.add(&record, line_number, identity);
+        }
+        tally.release(line_number);
+        tally.record_result(line_number, &record, result)?;
+    }
+    tally.release(usize::MAX);
+    Ok(())
+}
+
+/// Adds a record that skipped full validation to the best-alignment table.
+/// A record whose identity can't be computed is left out; its sequences
+/// were already reported by the check that fetched them.
+fn add_identity(
+    table: Option<&mut BestAlignmentTable>,
+    record: &PafRecord,
+    line_number: usize,
+    fasta_reader: &mut MultiFastaReader,
+) {
+    if let Some(table) = table {
+        if let Ok(identity) = recompute_identity(record, fasta_reader) {
+            table.add(record, line_number, identity);
+        }
+    }
+}
+
+fn create_bed_writer(path: &str) -> Result<BedWriter<Box<dyn Write>>> {
+    let file =
+        std::fs::File::create(path).context(format!("Failed to create BED file: {:?}", path))?;
+    Ok(BedWriter::new(Box::new(std::io::BufWriter::new(file))))
+}
+
+/// Forward-strand query coordinate of an error site. Sites of `-` strand
+/// records are offsets into the reverse-complemented query interval.
+fn forward_query_pos(record: &PafRecord, query_pos: usize) -> usize {
+    if record.strand == '-' {
+        record.query_end - 1 - (query_pos - record.query_start)
+    } else {
+        query_pos
+    }
+}
+
+/// Running error totals, and the per-site outputs fed by validation results.
+struct ErrorTally {
+    total: usize,
+    /// Occurrences per error type; listed in `ErrorType` order, so that
+    /// summaries don't change between runs.
+    by_type: BTreeMap<ErrorType, usize>,
+    /// Occurrences of the warning types, which are not counted in `total`.
+    warnings: BTreeMap<ErrorType, usize>,
+    warnings_as_errors: bool,
+    /// Warning types reported as errors by their own options
+    /// (`--flag-boundary-indels`, `--max-terminal-indel`, `--self-alignments
+    /// error`).
+    promoted: Vec<ErrorType>,
+    context_profile: ErrorContextProfile,
+    density: Option<ErrorDensity>,
+    /// Writers of the target and query positions of mismatches.
+    target_bed: Option<BedWriter<Box<dyn Write>>>,
+    query_bed: Option<BedWriter<Box<dyn Write>>>,
+    store: Option<ErrorStoreWriter>,
+    /// Records that failed because their sequences couldn't be fetched.
+    unfetched: usize,
+    sequences: Option<SequenceErrorSummary>,
+    /// Records with errors, and the (PAF file, line) of the last one.
+    failed_records: usize,
+    last_failed: Option<(usize, usize)>,
+    file_index: usize,
+    verdicts: Option<VerdictWriter<Box<dyn Write>>>,
+    /// Writer of the records with errors for `--failing-output`.
+    failing: Option<VerdictWriter<Box<dyn Write>>>,
+    /// Writer of the records without errors to stdout for `--filter`.
+    passing: Option<VerdictWriter<Box<dyn Write>>>,
+    /// Occurrences of the error types given to `--ignore`, which are
+    /// neither reported nor counted as errors.
+    ignored: HashMap<ErrorType, usize>,
+    format: OutputFormat,
+    verbosity: Verbosity,
+    /// Errors collected for the JSON report; other formats print them as they come.
+    events: Vec<ErrorEvent>,
+    /// Whether the output of records is held back, because records before
+    /// them are being validated on the worker pool.
+    holding: bool,
+    /// The output held back, by 1-based line, for `release`.
+    held: VecDeque<(usize, HeldOutput)>,
+}
+
+/// Output of a record held back by `ErrorTally`.
+enum HeldOutput {
+    Event(ErrorEvent),
+    Log(String),
+}
+
+impl ErrorTally {
+    /// The `--annotated-output`, `--failing-output` and `--filter` writers in
+    /// use.
+    fn verdict_writers(&mut self) -> impl Iterator<Item = &mut VerdictWriter<Box<dyn Write>>> {
+        self.verdicts
+            .iter_mut()
+            .chain(self.failing.iter_mut())
+            .chain(self.passing.iter_mut())
+    }
+
+    /// Prints a log line about the record at `line_number`, after the
+    /// output of the records before it.
+    fn log(&mut self, line_number: usize, message: String) {
+        if self.holding {
+            self.held.push_back((line_number, HeldOutput::Log(message)));
+        } else {
+            log_line!("{}", message);
+        }
+    }
+
+    /// Writes the held output of the records up to `line_number`.
+    fn release(&mut self, line_number: usize) {
+        let holding = std::mem::replace(&mut self.holding, false);
+        while self
+            .held
+            .front()
+            .is_some_and(|(line, _)| *line <= line_number)
+        {
+            match self.held.pop_front().unwrap().1 {
+                HeldOutput::Event(event) => self.emit(event),
+                HeldOutput::Log(message) => log_line!("{}", message),
+            }
+        }
+        self.holding = holding;
+    }
+
+    fn emit(&mut self, event: ErrorEvent) {
+        if let Some(line) = event.line.filter(|_| self.holding) {
+            self.held.push_back((line, HeldOutput::Event(event)));
+            return;
+        }
+        if let Some(sequences) = self.sequences.as_mut() {
+            sequences.add_errors(
+                event.query.as_deref(),
+                event.target.as_deref(),
+                event.error_type.as_deref().unwrap_or("Unchecked"),
+                event.count,
+            );
+        }
+        // Warnings don't fail a record.
+        if let Some(line) = event.line.filter(|_| !event.warning) {
+            // A record's errors are emitted one after another.
+            if self.last_failed != Some((self.file_index, line)) {
+                self.last_failed = Some((self.file_index, line));
+                self.failed_records += 1;
+            }
+            for verdicts in self.verdict_writers() {
+                verdicts.note(line, event.error_type.as_deref(), event.count);
+            }
+        }
+        match self.format {
+            OutputFormat::Json => self.events.push(event),
+            OutputFormat::Tsv => println!("{}", event.to_tsv()),
+            // Stdout is line-buffered, so each error is seen as it is found.
+            OutputFormat::Ndjson => println!("{}", event.to_json()),
+            OutputFormat::Text if self.verbosity == Verbosity::Quiet => {}
+            OutputFormat::Text => {
+                log_line!("{}", event.to_text());
+                if event.count > 1 {
+                    if let Some(error_type) = &event.error_type {
+                        log_line!(
+                            "[pafcheck] {}: Total occurrences: {}",
+                            error_type,
+                            event.count
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Counts and reports a single error of a known type in a record.
+    fn add_error(
+        &mut self,
+        line_number: usize,
+        record: &PafRecord,
+        error_type: ErrorType,
+        message: String,
+    ) {
+        self.add_errors(line_number, record, error_type, 1, message);
+    }
+
+    /// Counts and reports `count` errors of one type in a record.
+    fn add_errors(
+        &mut self,
+        line_number: usize,
+        record: &PafRecord,
+        error_type: ErrorType,
+        count: usize,
+        message: String,
+    ) {
+        self.add_line_error(
+            line_number,
+            Some(record.query_name.clone()),
+            Some(record.target_name.clone()),
+            error_type,
+            count,
+            message,
+        );
+    }
+
+    /// Like `add_errors`, for a line that couldn't be parsed as a record.
+    fn add_line_error(
+        &mut self,
+        line_number: usize,
+        query: Option<String>,
+        target: Option<String>,
+        error_type: ErrorType,
+        count: usize,
+        message: String,
+    ) {
+        if self.ignore(&error_type, count) {
+            return;
+        }
+        self.emit(ErrorEvent {
+            line: Some(line_number),
+            query,
+            target,
+            error_type: Some(format!("{:?}", error_type)),
+            policy: error_type.is_policy(),
+            warning: self.is_warning(&error_type),
+            count,
+            query_pos: None,
+            target_pos: None,
+            message,
+        });
+        self.count(error_type, count);
+    }
+
+    /// Whether `error_type` is reported as a warning.
+    fn is_warning(&self, error_type: &ErrorType) -> bool {
+        !self.warnings_as_errors
+            && error_type.severity() == Severity::Warning
+            && !self.promoted.contains(error_type)
+    }
+
+    /// Adds `count` occurrences of `error_type` to the errors or warnings.
+    fn count(&mut self, error_type: ErrorType, count: usize) {
+        if self.is_warning(&error_type) {
+            *self.warnings.entry(error_type).or_insert(0) += count;
+        } else {
+            *self.by_type.entry(error_type).or_insert(0) += count;
+            self.total += count;
+        }
+    }
+
+    /// Counts `count` errors of `error_type` as ignored if it was given to
+    /// `--ignore`, and returns whether it was.
+    fn ignore(&mut self, error_type: &ErrorType, count: usize) -> bool {
+        match self.ignored.get_mut(error_type) {
+            Some(ignored) => {
+                *ignored += count;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The failure reported for a run with errors.
+    fn failure(&self) -> RunFailure {
+        if self.unfetched > 0 {
+            RunFailure::Fetch {
+                records: self.unfetched,
+                errors: self.total,
+            }
+        } else {
+            RunFailure::Validation(self.total)
+        }
+    }
+
+    /// Counts and reports a record that could not be checked.
+    fn add_failure(&mut self, line_number: usize, record: &PafRecord, e: &anyhow::Error) {
+        if e.downcast_ref::<FetchError>().is_some() {
+            self.unfetched += 1;
+        }
+        self.emit(ErrorEvent {
+            line: Some(line_number),
+            query: Some(record.query_name.clone()),
+            target: Some(record.target_name.clone()),
+            error_type: None,
+            policy: false,
+            warning: false,
+            count: 1,
+            query_pos: None,
+            target_pos: None,
+            message: e.to_string(),
+        });
+        self.total += 1;
+    }
+
+    /// Counts and reports an error about a whole sequence rather than a record.
+    fn add_sequence_error(&mut self, name: &str, error_type: ErrorType, message: String) {
+        if self.ignore(&error_type, 1) {
+            return;
+        }
+        self.emit(ErrorEvent {
+            line: None,
+            query: Some(name.to_string()),
+            target: None,
+            error_type: Some(format!("{:?}", error_type)),
+            policy: error_type.is_policy(),
+            warning: self.is_warning(&error_type),
+            count: 1,
+            query_pos: None,
+            target_pos: None,
+            message,
+        });
+        self.count(error_type, 1);
+    }
+
+    /// Reports and counts the outcome of validating the record at
+    /// (1-based) `line_number`.
+    fn record_result(
+        &mut self,
+        line_number: usize,
+        record: &PafRecord,
+        result: Result<()>,
+    ) -> Result<()> {
+        let e = match result {
+            Ok(()) => {
+                if self.verbosity >= Verbosity::Records {
+                    log_line!(
+                        "[pafcheck] Line {}: {} -> {}: no errors",
+                        line_number,
+                        record.query_name,
+                        record.target_name
+                    );
+                }
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        let validation_error = match e.downcast_ref::<ValidationError>() {
+            Some(validation_error) => validation_error,
+            None => {
+                self.add_failure(line_number, record, &e);
+                return Ok(());
+            }
+        };
+        for (error_type, error_info) in &validation_error.errors {
+            let count = error_info.count;
+            if self.ignore(error_type, count) {
+                continue;
+            }
+            let first_site = error_info.sites.first();
+            self.emit(ErrorEvent {
+                line: Some(line_number),
+                query: Some(record.query_name.clone()),
+                target: Some(record.target_name.clone()),
+                error_type: Some(format!("{:?}", error_type)),
+                policy: error_type.is_policy(),
+                warning: self.is_warning(error_type),
+                count,
+                query_pos: first_site.map(|site| site.query_pos),
+                target_pos: first_site.map(|site| site.target_pos),
+                message: error_info.first_message.clone(),
+            });
+            if self.format == OutputFormat::Text && self.verbosity == Verbosity::Normal {
+                if let Some(site) = first_site {
+                    print_site_context(site);
+                }
+            }
+            if self.format == OutputFormat::Text && self.verbosity >= Verbosity::Sites {
+                for site in &error_info.sites {
+                    log_line!(
+                        "[pafcheck]   {:?} at query pos {}, target pos {} (CIGAR operation {})",
+                        error_type,
+                        site.query_pos,
+                        site.target_pos,
+                        site.op_index
+                    );
+                    print_site_context(site);
+                }
+            }
+            let warning = self.is_warning(error_type);
+            self.count(error_type.clone(), count);
+            // Warnings are left out of the error context breakdown and stores.
+            if warning {
+                continue;
+            }
+            for site in &error_info.sites {
+                self.context_profile.add_site(record, error_type, site);
+                if let Some(density) = self.density.as_mut() {
+                    density.add(&record.target_name, site.target_pos);
+                }
+                if !matches!(
+                    error_type,
+                    ErrorType::Mismatch | ErrorType::CigarMismatch | ErrorType::LowQualityMismatch
+                ) {
+                    continue;
+                }
+                let name = format!("{:?}", error_type);
+                if let Some(bed) = self.target_bed.as_mut() {
+                    let position = site.target_pos;
+                    bed.write(
+                        &record.target_name,
+                        position,
+                        position + 1,
+                        &name,
+                        0,
+                        record.strand,
+                    )?;
+                }
+                if let Some(bed) = self.query_bed.as_mut() {
+                    let position = forward_query_pos(record, site.query_pos);
+                    bed.write(
+                        &record.query_name,
+                        position,
+                        position + 1,
+                        &name,
+                        0,
+                        record.strand,
+                    )?;
+                }
+            }
+            if let Some(store) = self.store.as_mut() {
+                for site in &error_info.sites {
+                    store.push(StoreEntry {
+                        target_name: record.target_name.clone(),
+                        target_pos: site.target_pos,
+                        line_number,
+                        error_type: format!("{:?}", error_type),
+                        query_name: record.query_name.clone(),
+                        query_pos: site.query_pos,
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The query and target FASTA paths of `io_args`; the target defaults to
+/// the query.
+fn fasta_paths(matches: &clap::ArgMatches) -> (&str, &str) {
+    let query_fasta = matches.value_of("query_fasta").unwrap();
+    (
+        query_fasta,
+        matches.value_of("target_fasta").unwrap_or(query_fasta),
+    )
+}
+
+/// Opens the inputs and output given with `io_args`: the FASTA files, the
+/// PAF lines, and `-o` or stdout.
+fn open_inputs(matches: &clap::ArgMatches) -> Result<(MultiFastaReader, PafLines, Box<dyn Write>)> {
+    let (query_fasta, target_fasta) = fasta_paths(matches);
+    let fasta_reader = MultiFastaReader::new(query_fasta, target_fasta)?;
+    let reader = PafLines::open(matches.value_of("paf").unwrap(), 1)?;
+    let output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).context(format!("Failed to create {:?}", path))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+    };
+    Ok((fasta_reader, reader, output))
+}
+
+/// Copies the PAF with per-record QC tags appended, see `annotate_line`.
+fn annotate_paf(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+
+    let mut annotated = 0;
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            writeln!(output)?;
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        let annotated_line = annotate_line(&line, &record, &mut fasta_reader).context(format!(
+            "Failed to compute identity at line {}",
+            line_number + 1
+        ))?;
+        writeln!(output, "{}", annotated_line)?;
+        annotated += 1;
+    }
+    output.flush()?;
+    eprintln!("[pafcheck] Annotated {} records", annotated);
+    Ok(())
+}
+
+/// Writes per-file statistics of the records' alignments. Records without an
+/// alignment, or whose alignment doesn't fit their sequences, are counted as
+/// skipped.
+fn stats_paf(matches: &clap::ArgMatches) -> Result<()> {
+    let (query_fasta, target_fasta) = fasta_paths(matches);
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+    let format: OutputFormat = matches.value_of("format").unwrap().parse()?;
+
+    let mut summary = AlignmentSummary::new();
+    if matches.is_present("per-query-indels") {
+        summary = summary.with_query_indels();
+    }
+    // Seed with every sequence so that unaligned ones count towards coverage too.
+    for (name, length) in fai_lengths(target_fasta) {
+        summary.add_target(&name, length);
+    }
+    for (name, length) in fai_lengths(query_fasta) {
+        summary.add_query(&name, length);
+    }
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        if record.cigar.is_empty() && record.tag("cs:Z").is_none() {
+            summary.skip();
+            continue;
+        }
+        if let Err(e) = summary.add_record(&record, &mut fasta_reader) {
+            eprintln!("[pafcheck] Line {} skipped: {:#}", line_number + 1, e);
+            summary.skip();
+        }
+    }
+    summary.write(format, &mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the identity windows of each record (see `identity_windows`) as
+/// BED, only those below `--min-identity` unless `--all` is given.
+fn identity_windows_paf(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, output) = open_inputs(matches)?;
+    let positive = |name: &str| -> Result<usize> {
+        let value = matches.value_of(name).unwrap();
+        match value.parse() {
+            Ok(bases) if bases > 0 => Ok(bases),
+            _ => anyhow::bail!("--{} must be a positive integer: {}", name, value),
+        }
+    };
+    let window = positive("window")?;
+    let step = positive("step")?;
+    let min_identity: f64 = matches
+        .value_of("min-identity")
+        .unwrap()
+        .parse()
+        .context("Invalid --min-identity")?;
+    let all = matches.is_present("all");
+
+    let mut bed = BedWriter::new(output);
+    let (mut windows, mut low, mut low_records, mut skipped) = (0, 0, 0, 0);
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        let record_windows = fetch_aligned_sequences(&record, &mut fasta_reader).and_then(
+            |(query_seq, target_seq)| {
+                identity_windows(
+                    &record,
+                    &alignment_ops(&record)?,
+                    &query_seq,
+                    &target_seq,
+                    window,
+                    step,
+                )
+            },
+        );
+        let record_windows = match record_windows {
+            Ok(record_windows) => record_windows,
+            Err(e) => {
+                eprintln!("[pafcheck] Line {} skipped: {:#}", line_number + 1, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        let mut record_low = false;
+        for identity_window in &record_windows {
+            windows += 1;
+            let below = identity_window.identity < min_identity;
+            if below {
+                low += 1;
+                record_low = true;
+            }
+            if below || all {
+                bed.write(
+                    &record.target_name,
+                    identity_window.target_start,
+                    identity_window.target_end,
+                    &format!(
+                        "{}:{}-{}",
+                        record.query_name, identity_window.query_start, identity_window.query_end
+                    ),
+                    (identity_window.identity * 1000.0).round() as usize,
+                    record.strand,
+                )?;
+            }
+        }
+        low_records += usize::from(record_low);
+    }
+    bed.finish()?;
+    eprintln!(
+        "[pafcheck] {} of {} windows in {} records have identity below {}",
+        low, windows, low_records, min_identity
+    );
+    if skipped > 0 {
+        eprintln!("[pafcheck] {} records skipped", skipped);
+    }
+    Ok(())
+}
+
+/// Writes the variants of the records that pass validation as a sorted VCF,
+/// see `alignment_variants`. Records with validation errors are skipped.
+fn paf_to_vcf(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+    let mut contigs = target_contigs(fasta_paths(matches).1)?;
+
+    let policy = ValidationPolicy::from_matches(&CheckMatches {
+        matches,
+        config: None,
+    })?;
+    let mut variants = Vec::new();
+    let (mut converted, mut skipped) = (0, 0);
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        match record_variants(&record, &mut fasta_reader, &policy) {
+            Ok(record_variants) => {
+                if !contigs.iter().any(|(name, _)| *name == record.target_name) {
+                    contigs.push((record.target_name.clone(), record.target_length));
+                }
+                variants.extend(record_variants.into_iter().map(|variant| VariantRecord {
+                    target_name: record.target_name.clone(),
+                    variant,
+                    query_name: record.query_name.clone(),
+                    strand: record.strand,
+                }));
+                converted += 1;
+            }
+            Err(e) => {
+                eprintln!("[pafcheck] Line {} skipped: {:#}", line_number + 1, e);
+                skipped += 1;
+            }
+        }
+    }
+    let count = variants.len();
+    write_vcf(&mut output, fasta_paths(matches).1, &contigs, variants)?;
+    output.flush()?;
+    eprintln!(
+        "[pafcheck] Wrote {} variants of {} records; {} records skipped",
+        count, converted, skipped
+    );
+    Ok(())
+}
+
+/// The variants of a record that passes validation.
+fn record_variants(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    policy: &ValidationPolicy,
+) -> Result<Vec<Variant>> {
+    let (query_seq, target_seq) = policy.valid_sequences(record, fasta_reader)?;
+    let anchor = match record.target_start {
+        0 => None,
+        start => fasta_reader
+            .fetch_target_sequence(&record.target_name, start - 1, start)?
+            .bytes()
+            .next()
+            .map(|base| base.to_ascii_uppercase()),
+    };
+    alignment_variants(
+        &alignment_ops(record)?,
+        &query_seq,
+        &target_seq,
+        record.target_start,
+        anchor,
+    )
+}
+
+/// Writes the records that pass validation as SAM, see `sam_record`, with
+/// the target FASTA's sequences in the header. Records with validation
+/// errors are skipped.
+fn paf_to_sam(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+
+    let policy = ValidationPolicy::from_matches(&CheckMatches {
+        matches,
+        config: None,
+    })?;
+    // The header comes first, so the @SQ lines can't wait for the records.
+    write_sam_header(&mut output, &target_contigs(fasta_paths(matches).1)?)?;
+    let (mut converted, mut skipped) = (0, 0);
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        match record_sam(&record, &mut fasta_reader, &policy) {
+            Ok(sam_line) => {
+                writeln!(output, "{}", sam_line)?;
+                converted += 1;
+            }
+            Err(e) => {
+                eprintln!("[pafcheck] Line {} skipped: {:#}", line_number + 1, e);
+                skipped += 1;
+            }
+        }
+    }
+    output.flush()?;
+    eprintln!(
+        "[pafcheck] Wrote {} SAM records; {} records skipped",
+        converted, skipped
+    );
+    Ok(())
+}
+
+/// The SAM line of a record that passes validation.
+fn record_sam(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    policy: &ValidationPolicy,
+) -> Result<String> {
+    let (query_seq, target_seq) = policy.valid_sequences(record, fasta_reader)?;
+    let query = fasta_reader
+        .fetch_query_sequence(&record.query_name, 0, record.query_length)
+        .context(format!(
+            "Failed to fetch query sequence: {}",
+            record.query_name
+        ))?;
+    sam_record(
+        record,
+        &alignment_ops(record)?,
+        query.as_bytes(),
+        &query_seq,
+        &target_seq,
+    )
+}
+
+/// Writes the records that pass validation as pairwise MAF blocks, see
+/// `maf_block`. Records with validation errors are skipped.
+fn paf_to_maf(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+
+    let policy = ValidationPolicy::from_matches(&CheckMatches {
+        matches,
+        config: None,
+    })?;
+    write_maf_header(&mut output)?;
+    let (mut converted, mut skipped) = (0, 0);
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        let block = policy.valid_sequences(&record, &mut fasta_reader).and_then(
+            |(query_seq, target_seq)| {
+                maf_block(&record, &alignment_ops(&record)?, &query_seq, &target_seq)
+            },
+        );
+        match block {
+            Ok(block) => {
+                writeln!(output, "\n{}", block)?;
+                converted += 1;
+            }
+            Err(e) => {
+                eprintln!("[pafcheck] Line {} skipped: {:#}", line_number + 1, e);
+                skipped += 1;
+            }
+        }
+    }
+    output.flush()?;
+    eprintln!(
+        "[pafcheck] Wrote {} MAF blocks; {} records skipped",
+        converted, skipped
+    );
+    Ok(())
+}
+
+/// Rewrites each record with its CIGAR fixed, see `fix_line`. Records whose
+/// CIGAR can't be fixed are written unchanged.
+fn fix_paf(matches: &clap::ArgMatches) -> Result<()> {
+    let (mut fasta_reader, reader, mut output) = open_inputs(matches)?;
+
+    let options = FixOptions {
+        left_align: matches.is_present("left-align"),
+        split_at_indel: matches
+            .value_of("split-at-indel")
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .context(format!("Invalid --split-at-indel: {}", value))
+            })
+            .transpose()?,
+    };
+    let mut counts = FixCounts::default();
+    for (line_number, line) in reader.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            writeln!(output)?;
+            continue;
+        }
+        let record = PafRecord::from_line(&line).context(RunFailure::Parse(line_number + 1))?;
+        counts.records += 1;
+        match fix_line(&line, &record, &mut fasta_reader, &options) {
+            Ok((lines, fixes)) => {
+                counts.add(&fixes);
+                for line in lines {
+                    writeln!(output, "{}", line)?;
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "[pafcheck] Line {} left unchanged: {:#}",
+                    line_number + 1,
+                    e
+                );
+                writeln!(output, "{}", line)?;
+                counts.unfixable += 1;
+            }
+        }
+    }
+    output.flush()?;
+    counts.print();
+    Ok(())
+}
+
+/// Records changed by `pafcheck fix`, by kind of change.
+#[derive(Debug, Default)]
+struct FixCounts {
+    records: usize,
+    /// Records whose CIGAR or count columns were wrong.
+    corrected: usize,
+    cigars: usize,
+    /// Fixed CIGARs that had `M` ops.
+    resolved: usize,
+    /// Fixed CIGARs that weren't normalized, e.g. `5=3=` or `5=0X3=`.
+    non_canonical: usize,
+    /// Records whose terminal indels were trimmed.
+    trimmed: usize,
+    /// Records with indels moved by `--left-align`.
+    left_aligned: usize,
+    /// Records split by `--split-at-indel`, and the records written for them.
+    split: usize,
+    pieces: usize,
+    columns: usize,
+    retagged: usize,
+    unfixable: usize,
+}
+
+impl FixCounts {
+    fn add(&mut self, fixes: &LineFixes) {
+        let count = |changed: bool| changed as usize;
+        self.corrected += count(fixes.corrected());
+        self.cigars += count(fixes.cigar);
+        self.resolved += count(fixes.resolved);
+        self.non_canonical += count(fixes.non_canonical);
+        self.trimmed += count(fixes.trimmed);
+        self.left_aligned += count(fixes.left_aligned);
+        self.columns += count(fixes.columns);
+        self.retagged += count(fixes.tags);
+        if let Some(pieces) = fixes.pieces {
+            self.split += 1;
+            self.pieces += pieces;
+        }
+    }
+
+    fn print(&self) {
+        eprintln!(
+            "[pafcheck] {} of {} records needed correction",
+            self.corrected, self.records
+        );
+        eprintln!("[pafcheck]   - CIGARs fixed: {}", self.cigars);
+        if self.resolved > 0 {
+            eprintln!(
+                "[pafcheck]     ({} of them had M operations resolved into =/X)",
+                self.resolved
+            );
+        }
+        if self.non_canonical > 0 {
+            eprintln!(
+                "[pafcheck]     ({} of them were non-canonical and were normalized)",
+                self.non_canonical
+            );
+        }
+        eprintln!("[pafcheck]   - Terminal indels trimmed: {}", self.trimmed);
+        if self.left_aligned > 0 {
+            eprintln!(
+                "[pafcheck]   - Indels left-aligned (not a correction): {}",
+                self.left_aligned
+            );
+        }
+        eprintln!(
+            "[pafcheck]   - Columns 10 and 11 recomputed: {}",
+            self.columns
+        );
+        eprintln!(
+            "[pafcheck] Set the NM:i, de:f, dv:f and gi:f tags of {} records",
+            self.retagged
+        );
+        if self.split > 0 {
+            eprintln!(
+                "[pafcheck] Split {} records at long indels into {} records",
+                self.split, self.pieces
+            );
+        }
+        if self.unfixable > 0 {
+            eprintln!(
+                "[pafcheck] {} records could not be fixed and were written unchanged",
+                self.unfixable
+            );
+        }
+    }
+}
+
+/// Prints the bases around an error site, if they were kept for `--color`.
+fn print_site_context(site: &ErrorSite) {
+    if let Some(context) = &site.context {
+        for line in context_lines(context) {
+            log_line!("[pafcheck]     {}", line);
+        }
+    }
+}
+
+/// Bases shown on each side of mismatches in colored output.
+const CONTEXT_BASES: usize = 5;
+
+/// Sequences listed per role by `--per-sequence-summary`.
+const TOP_SEQUENCES: usize = 10;
+
+/// Prints the query and target sequences with the most errors.
+fn print_sequence_errors(sequences: &SequenceErrorSummary) {
+    for role in ["query", "target"] {
+        let ranked = sequences.ranked(role);
+        if ranked.is_empty() {
+            continue;
+        }
+        log_line!("[pafcheck] Errors per {} sequence:", role);
+        for (name, sequence) in ranked.iter().take(TOP_SEQUENCES) {
+            let mut types: Vec<(&String, &usize)> = sequence.by_type.iter().collect();
+            types.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            let types: Vec<String> = types
+                .iter()
+                .map(|(error_type, count)| format!("{} {}", error_type, count))
+                .collect();
+            log_line!(
+                "[pafcheck]   - {}: {} errors in {} records ({})",
+                name,
+                sequence.errors,
+                sequence.records,
+                types.join(", ")
+            );
+        }
+        if ranked.len() > TOP_SEQUENCES {
+            log_line!(
+                "[pafcheck]   ... and {} more {} sequences with errors",
+                ranked.len() - TOP_SEQUENCES,
+                role
+            );
+        }
+    }
+}
+
+/// Prints the fraction of each PanSN query haplotype covered by alignments.
+fn print_haplotype_coverage(coverage: &CoverageTracker, delimiter: char) {
+    let mut haplotypes: Vec<(String, usize, usize)> = Vec::new();
+    for (name, length, covered) in coverage.sequences() {
+        let key = match PanSnName::parse(name, delimiter) {
+            Some(pansn) => pansn.haplotype_key(delimiter),
+            None => "(non-PanSN)".to_string(),
+        };
+        match haplotypes.iter_mut().find(|(k, _, _)| *k == key) {
+            Some(entry) => {
+                entry.1 += length;
+                entry.2 += covered;
+            }
+            None => haplotypes.push((key, length, covered)),
+        }
+    }
+    haplotypes.sort();
+    log_line!("[pafcheck] Query coverage per haplotype:");
+    for (key, length, covered) in haplotypes {
+        log_line!(
+            "[pafcheck]   - {}: {} of {} bases aligned ({:.2}%)",
+            key,
+            covered,
+            length,
+            100.0 * covered as f64 / length.max(1) as f64
+        );
+    }
+}
+
+/// Computes per-sequence MD5s of the local FASTAs, printing and/or verifying
+/// them, and fails before validation if the reference build doesn't match.
+fn check_sequence_md5s(options: &RunOptions) -> Result<()> {
+    let mut fastas = vec![options.query_fasta];
+    if options.target_fasta != options.query_fasta {
+        fastas.push(options.target_fasta);
+    }
+    let mut checksums = Vec::new();
+    for fasta in fastas {
+        if fasta.starts_with(REFGET_PREFIX) {
+            continue;
+        }
+        checksums.extend(fasta_md5s(fasta)?);
+    }
+
+    if options.print_md5 {
+        for (name, md5) in &checksums {
+            log_line!("[pafcheck] M5 {}\t{}", name, md5);
+        }
+    }
+    if let Some(path) = options.verify_md5 {
+        let expected = load_expected_md5s(path)?;
+        let mismatches = verify_md5s(&checksums, &expected);
+        for mismatch in &mismatches {
+            log_line!(
+                "[pafcheck] MD5 mismatch for {}: expected {}, found {}",
+                mismatch.name,
+                mismatch.expected,
+                mismatch.actual
+            );
+        }
+        let unmatched = expected
+            .keys()
+            .filter(|name| !checksums.iter().any(|(n, _)| n == *name))
+            .count();
+        if unmatched > 0 {
+            log_line!(
+                "[pafcheck] {} sequences in {} are not present in the FASTAs",
+                unmatched,
+                path
+            );
+        }
+        if !mismatches.is_empty() {
+            anyhow::bail!(
+                "{} sequences do not match the expected checksums; wrong reference build?",
+                mismatches.len()
+            );
+        }
+        log_line!(
+            "[pafcheck] Sequence checksums match {} for {} sequences",
+            path,
+            checksums.len() - mismatches.len()
+        );
+    }
+    Ok(())
+}
+
+/// Provenance of the run's local input files; refget sources are recorded by URL only.
+fn describe_inputs(options: &RunOptions) -> Result<Vec<InputFile>> {
+    let mut inputs = Vec::new();
+    let mut files: Vec<(&str, &str)> = options
+        .paf_paths
+        .iter()
+        .map(|&path| ("paf", path))
+        .collect();
+    if !options.query_fasta.starts_with(REFGET_PREFIX) {
+        files.push(("query_fasta", options.query_fasta));
+    }
+    if options.target_fasta != options.query_fasta
+        && !options.target_fasta.starts_with(REFGET_PREFIX)
+    {
+        files.push(("target_fasta", options.target_fasta));
+    }
+    if let Some(rgfa_path) = options.rgfa_path {
+        files.push(("rgfa", rgfa_path));
+    }
+    for (role, path) in files {
+        inputs.push(InputFile::describe(role, path, options.checksum_inputs)?);
+    }
+    Ok(inputs)
+}
+
+fn index_paf(paf_path: &str) -> Result<()> {
+    let index = PafIndex::build(paf_path)?;
+    let path = index_path(paf_path);
+    index.write(&path)?;
+    eprintln!(
+        "[pafcheck] Indexed {} records of {} into {}",
+        index.len(),
+        paf_path,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Loads the index written by `pafcheck index`, refusing one that is missing
+/// or older than the PAF.
+fn load_paf_index(paf_path: &str) -> Result<PafIndex> {
+    let path = index_path(paf_path);
+    if !path.exists() {
+        anyhow::bail!(
+            "--target-region needs an index of {}; create it with `pafcheck index {}`",
+            paf_path,
+            paf_path
+        );
+    }
+    let index = PafIndex::load(&path)?;
+    if !index.is_current(paf_path)? {
+        anyhow::bail!(
+            "{} changed since it was indexed; recreate the index with `pafcheck index {}`",
+            paf_path,
+            paf_path
+        );
+    }
+    Ok(index)
+}
+
+fn view_error_store(store_path: &str, region: &str) -> Result<()> {
+    let (target_name, start, end) = parse_region(region)?;
+    let mut reader = ErrorStoreReader::open(store_path)?;
+    for entry in reader.query(&target_name, start, end)? {
+        println!("{}", entry.to_line());
+    }
+    Ok(())
+}
+
+/// The entries `report query` selects, from its filter options.
+fn entry_filter(matches: &clap::ArgMatches) -> Result<EntryFilter> {
+    Ok(EntryFilter {
+        error_type: matches.value_of("error-type").map(str::to_string),
+        sequence: matches.value_of("sequence").map(str::to_string),
+        region: matches.value_of("region").map(parse_region).transpose()?,
+        line_number: matches
+            .value_of("line")
+            .map(str::parse)
+            .transpose()
+            .context("Failed to parse --line")?,
+    })
+}
+
+fn query_error_store(matches: &clap::ArgMatches) -> Result<()> {
+    let filter = entry_filter(matches)?;
+    let format = matches.value_of("format").unwrap();
+    if format != "text" && format != "bed" {
+        anyhow::bail!("Unknown report format: {}", format);
+    }
+
+    let mut reader = ErrorStoreReader::open(matches.value_of("store").unwrap())?;
+    if matches.is_present("show-inputs") {
+        for input in reader.inputs() {
+            println!(
+                "# {} {} size={} mtime={} checksum={}",
+                input.role,
+                input.path,
+                input.size,
+                input.mtime,
+                input.checksum.as_deref().unwrap_or("-")
+            );
+        }
+    }
+    for entry in reader.select(&filter)? {
+        if format == "bed" {
+            println!(
+                "{}\t{}\t{}\t{}:{}:{}",
+                entry.target_name,
+                entry.target_pos,
+                entry.target_pos + 1,
+                entry.error_type,
+                entry.query_name,
+                entry.query_pos
+            );
+        } else {
+            println!(
+                "Line {}: {} at {}:{} (query {}:{})",
+                entry.line_number,
+                entry.error_type,
+                entry.target_name,
+                entry.target_pos,
+                entry.query_name,
+                entry.query_pos
+            );
         }
-        println!("[pafcheck] Total errors: {}", total_error_count);
-        anyhow::bail!("PAF validation failed with {} errors", total_error_count);
-    } else {
-        println!("[pafcheck] PAF validation completed successfully. No errors found.");
-        Ok(())
     }
+    Ok(())
 }
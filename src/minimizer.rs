@@ -0,0 +1,141 @@
+use crate::cigar_parser::CigarOp;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+/// k-mer size for the identity cross-check.
+pub const MINIMIZER_K: usize = 15;
+/// Window (in k-mers) from which each minimizer is picked.
+pub const MINIMIZER_W: usize = 10;
+
+/// Invertible integer hash (as used by minimap2) so that minimizers are not
+/// biased towards poly-A k-mers.
+fn hash64(key: u64, mask: u64) -> u64 {
+    let mut key = (!key).wrapping_add(key << 21) & mask;
+    key ^= key >> 24;
+    key = key.wrapping_add(key << 3).wrapping_add(key << 8) & mask;
+    key ^= key >> 14;
+    key = key.wrapping_add(key << 2).wrapping_add(key << 4) & mask;
+    key ^= key >> 28;
+    key.wrapping_add(key << 31) & mask
+}
+
+/// Hashes of the (w, k)-minimizers of `seq`. k-mers containing anything but
+/// ACGT are skipped.
+pub fn minimizers(seq: &[u8], k: usize, w: usize) -> HashSet<u64> {
+    let mask = if k >= 32 {
+        u64::MAX
+    } else {
+        (1u64 << (2 * k)) - 1
+    };
+    let mut result = HashSet::new();
+    let mut window: VecDeque<(usize, u64)> = VecDeque::new();
+    let mut kmer = 0u64;
+    let mut valid = 0;
+    for (i, &base) in seq.iter().enumerate() {
+        let code = match base.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => {
+                valid = 0;
+                window.clear();
+                continue;
+            }
+        };
+        kmer = ((kmer << 2) | code) & mask;
+        valid += 1;
+        if valid < k {
+            continue;
+        }
+        let hash = hash64(kmer, mask);
+        while window.back().is_some_and(|&(_, h)| h >= hash) {
+            window.pop_back();
+        }
+        window.push_back((i, hash));
+        while window.front().is_some_and(|&(pos, _)| pos + w <= i) {
+            window.pop_front();
+        }
+        if valid >= k + w - 1 {
+            result.insert(window.front().unwrap().1);
+        }
+    }
+    result
+}
+
+/// Identity estimated from the Jaccard index of the two spans' minimizer sets
+/// (the Mash distance), or `None` if either span is too short to have any.
+pub fn minimizer_identity(query: &[u8], target: &[u8], k: usize, w: usize) -> Option<f64> {
+    let query_set = minimizers(query, k, w);
+    let target_set = minimizers(target, k, w);
+    if query_set.is_empty() || target_set.is_empty() {
+        return None;
+    }
+    let shared = query_set.intersection(&target_set).count();
+    let union = query_set.len() + target_set.len() - shared;
+    let jaccard = shared as f64 / union as f64;
+    if jaccard == 0.0 {
+        return Some(0.0);
+    }
+    let distance = -(2.0 * jaccard / (1.0 + jaccard)).ln() / k as f64;
+    Some((1.0 - distance).max(0.0))
+}
+
+/// Gap-compressed identity implied by the CIGAR: `=` columns over all
+/// columns, counting each indel once. `M` columns are taken to match.
+pub fn cigar_identity(ops: &[CigarOp]) -> f64 {
+    let mut matches = 0;
+    let mut columns = 0;
+    for op in ops {
+        match op {
+            CigarOp::AlignmentMatch(len) | CigarOp::Match(len) => {
+                matches += len;
+                columns += len;
+            }
+            CigarOp::Mismatch(len) => columns += len,
+            CigarOp::Insertion(_) | CigarOp::Deletion(_) => columns += 1,
+            CigarOp::Skip(_) | CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    if columns == 0 {
+        0.0
+    } else {
+        matches as f64 / columns as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::SplitMix64;
+
+    fn random_sequence(len: usize, seed: u64) -> Vec<u8> {
+        let mut rng = SplitMix64::new(seed);
+        (0..len)
+            .map(|_| b"ACGT"[(rng.next_u64() % 4) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_minimizer_identity() {
+        let seq = random_sequence(5000, 7);
+        let identity = minimizer_identity(&seq, &seq, MINIMIZER_K, MINIMIZER_W).unwrap();
+        assert!((identity - 1.0).abs() < 1e-9);
+
+        let unrelated = random_sequence(5000, 8);
+        let identity = minimizer_identity(&seq, &unrelated, MINIMIZER_K, MINIMIZER_W).unwrap();
+        assert!(identity < 0.5);
+
+        assert!(minimizer_identity(b"ACGT", &seq, MINIMIZER_K, MINIMIZER_W).is_none());
+    }
+
+    #[test]
+    fn test_cigar_identity() {
+        let ops = [
+            CigarOp::Match(8),
+            CigarOp::Mismatch(1),
+            CigarOp::Deletion(5),
+        ];
+        assert!((cigar_identity(&ops) - 0.8).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::paf_parser::PafRecord;
+use std::collections::HashMap;
+
+/// How two reads overlap, following miniasm's classification of read-to-read
+/// mappings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlapClass {
+    /// The overlap stops short of both read ends: not a usable overlap.
+    Internal,
+    QueryContained,
+    TargetContained,
+    Dovetail,
+}
+
+/// Classifies an overlap from its coordinates. Overhangs up to
+/// `min(max_hang, int_frac * mapped length)` are tolerated at the ends.
+pub fn classify_overlap(record: &PafRecord, max_hang: usize, int_frac: f64) -> OverlapClass {
+    // Put the target on the query's strand so that both read left-to-right.
+    let (ts, te) = if record.strand == '-' {
+        (
+            record.target_length.saturating_sub(record.target_end),
+            record.target_length.saturating_sub(record.target_start),
+        )
+    } else {
+        (record.target_start, record.target_end)
+    };
+    let (qs, qe, ql, tl) = (
+        record.query_start,
+        record.query_end,
+        record.query_length,
+        record.target_length,
+    );
+    let q_tail = ql.saturating_sub(qe);
+    let t_tail = tl.saturating_sub(te);
+    let overhang = qs.min(ts) + q_tail.min(t_tail);
+    let map_len = (qe - qs.min(qe)).max(te - ts.min(te));
+    if overhang as f64 > (max_hang as f64).min(map_len as f64 * int_frac) {
+        OverlapClass::Internal
+    } else if qs <= ts && q_tail <= t_tail {
+        OverlapClass::QueryContained
+    } else if qs >= ts && q_tail >= t_tail {
+        OverlapClass::TargetContained
+    } else {
+        OverlapClass::Dovetail
+    }
+}
+
+/// Coordinates of the first record seen for an ordered read pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PairCoords {
+    line_number: usize,
+    strand: char,
+    query: (usize, usize),
+    target: (usize, usize),
+}
+
+/// Two records for the same read pair, in opposite directions, that disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReciprocalMismatch {
+    pub first_line: usize,
+    pub second_line: usize,
+    pub reads: (String, String),
+}
+
+/// Checks that when a read pair appears in both directions (A->B and B->A),
+/// the two records describe the same overlap.
+#[derive(Debug, Default)]
+pub struct ReciprocalChecker {
+    pairs: HashMap<(String, String), PairCoords>,
+    slack: usize,
+}
+
+impl ReciprocalChecker {
+    /// `slack` is the number of bases each coordinate may differ by.
+    pub fn new(slack: usize) -> Self {
+        ReciprocalChecker {
+            pairs: HashMap::new(),
+            slack,
+        }
+    }
+
+    /// Records an overlap; returns the earlier reciprocal record it
+    /// disagrees with, if any.
+    pub fn add(&mut self, record: &PafRecord, line_number: usize) -> Option<ReciprocalMismatch> {
+        if record.query_name == record.target_name {
+            return None;
+        }
+        let coords = PairCoords {
+            line_number,
+            strand: record.strand,
+            query: (record.query_start, record.query_end),
+            target: (record.target_start, record.target_end),
+        };
+        let reverse_key = (record.target_name.clone(), record.query_name.clone());
+        if let Some(reverse) = self.pairs.get(&reverse_key) {
+            let close = |a: (usize, usize), b: (usize, usize)| {
+                a.0.abs_diff(b.0) <= self.slack && a.1.abs_diff(b.1) <= self.slack
+            };
+            if reverse.strand != coords.strand
+                || !close(reverse.query, coords.target)
+                || !close(reverse.target, coords.query)
+            {
+                return Some(ReciprocalMismatch {
+                    first_line: reverse.line_number,
+                    second_line: line_number,
+                    reads: reverse_key,
+                });
+            }
+            return None;
+        }
+        self.pairs
+            .entry((record.query_name.clone(), record.target_name.clone()))
+            .or_insert(coords);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlap(
+        query: (&str, usize, usize, usize),
+        strand: char,
+        target: (&str, usize, usize, usize),
+    ) -> PafRecord {
+        PafRecord {
+            query_name: query.0.to_string(),
+            query_length: query.1,
+            query_start: query.2,
+            query_end: query.3,
+            strand,
+            target_name: target.0.to_string(),
+            target_length: target.1,
+            target_start: target.2,
+            target_end: target.3,
+            residue_matches: 0,
+            block_length: 0,
+            mapq: Some(60),
+            cigar: String::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_classify_overlap() {
+        let dovetail = overlap(("a", 10000, 6000, 10000), '+', ("b", 10000, 0, 4000));
+        assert_eq!(
+            classify_overlap(&dovetail, 1000, 0.8),
+            OverlapClass::Dovetail
+        );
+        let contained = overlap(("a", 3000, 0, 3000), '+', ("b", 10000, 2000, 5000));
+        assert_eq!(
+            classify_overlap(&contained, 1000, 0.8),
+            OverlapClass::QueryContained
+        );
+        let internal = overlap(("a", 10000, 3000, 6000), '+', ("b", 10000, 3000, 6000));
+        assert_eq!(
+            classify_overlap(&internal, 1000, 0.8),
+            OverlapClass::Internal
+        );
+        // On the reverse strand, the end of the query meets the end of the target.
+        let reverse = overlap(("a", 10000, 6000, 10000), '-', ("b", 10000, 6000, 10000));
+        assert_eq!(
+            classify_overlap(&reverse, 1000, 0.8),
+            OverlapClass::Dovetail
+        );
+    }
+
+    #[test]
+    fn test_reciprocal_checker() {
+        let mut checker = ReciprocalChecker::new(0);
+        let forward = overlap(("a", 10000, 6000, 10000), '+', ("b", 10000, 0, 4000));
+        let agreeing = overlap(("b", 10000, 0, 4000), '+', ("a", 10000, 6000, 10000));
+        let disagreeing = overlap(("b", 10000, 0, 3000), '+', ("a", 10000, 6000, 10000));
+        assert!(checker.add(&forward, 1).is_none());
+        assert!(checker.add(&agreeing, 2).is_none());
+        let mismatch = checker.add(&disagreeing, 3).unwrap();
+        assert_eq!((mismatch.first_line, mismatch.second_line), (1, 3));
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::paf_input::is_gzipped;
+use crate::provenance::InputFile;
+
+/// Prefix of the header line recording the indexed PAF.
+const INPUT_HEADER: &str = "#input\t";
+
+/// Location of one PAF record and the target interval it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub target_name: String,
+    pub target_start: usize,
+    pub target_end: usize,
+    /// Byte offset of the record's line in the PAF.
+    pub offset: u64,
+    /// 1-based line number of the record.
+    pub line_number: usize,
+}
+
+/// Index of an uncompressed PAF by target interval, written next to it as
+/// `<paf>.idx` by `pafcheck index`, so that the records overlapping a target
+/// region can be read without scanning the whole file.
+#[derive(Debug)]
+pub struct PafIndex {
+    input: InputFile,
+    /// Sorted by target name and start.
+    entries: Vec<IndexEntry>,
+    /// Longest target interval of a record on each target.
+    max_len: HashMap<String, usize>,
+}
+
+impl PafIndex {
+    pub fn build(paf_path: &str) -> Result<Self> {
+        if paf_path == "-" || is_gzipped(paf_path)? {
+            anyhow::bail!("Only uncompressed PAF files can be indexed: {:?}", paf_path);
+        }
+        let input = InputFile::describe("paf", paf_path, false)?;
+        let file =
+            File::open(paf_path).context(format!("Failed to open PAF file: {:?}", paf_path))?;
+        let mut reader = BufReader::new(file);
+        let mut entries = Vec::new();
+        let mut line = String::new();
+        let (mut offset, mut line_number) = (0, 0);
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+            let record = line.trim_end_matches(['\n', '\r']);
+            // Skip blank lines and miniprot --aln detail lines.
+            if !record.is_empty() && !record.starts_with("##") {
+                let fields: Vec<&str> = record.splitn(10, '\t').collect();
+                if fields.len() < 9 {
+                    anyhow::bail!("PAF line {} does not have enough fields", line_number);
+                }
+                let parse = |field: &str, name: &str| -> Result<usize> {
+                    field.parse().context(format!(
+                        "Failed to parse {} at PAF line {}",
+                        name, line_number
+                    ))
+                };
+                entries.push(IndexEntry {
+                    target_name: fields[5].to_string(),
+                    target_start: parse(fields[7], "target start")?,
+                    target_end: parse(fields[8], "target end")?,
+                    offset,
+                    line_number,
+                });
+            }
+            offset += read as u64;
+        }
+        Ok(Self::from_entries(input, entries))
+    }
+
+    fn from_entries(input: InputFile, mut entries: Vec<IndexEntry>) -> Self {
+        entries.sort_by(|a, b| {
+            (&a.target_name, a.target_start, a.offset).cmp(&(
+                &b.target_name,
+                b.target_start,
+                b.offset,
+            ))
+        });
+        let mut max_len: HashMap<String, usize> = HashMap::new();
+        for entry in &entries {
+            let len = entry.target_end.saturating_sub(entry.target_start);
+            let max = max_len.entry(entry.target_name.clone()).or_insert(0);
+            *max = (*max).max(len);
+        }
+        PafIndex {
+            input,
+            entries,
+            max_len,
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).context(format!("Failed to create PAF index: {:?}", path))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}{}", INPUT_HEADER, self.input.to_fields())?;
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                entry.target_name,
+                entry.target_start,
+                entry.target_end,
+                entry.offset,
+                entry.line_number
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).context(format!("Failed to open PAF index: {:?}", path))?;
+        let mut lines = BufReader::new(file).lines();
+        let input = match lines.next().transpose()? {
+            Some(line) => match line.strip_prefix(INPUT_HEADER) {
+                Some(fields) => InputFile::from_fields(fields)?,
+                None => anyhow::bail!("PAF index is missing its #input header: {:?}", path),
+            },
+            None => anyhow::bail!("PAF index is empty: {:?}", path),
+        };
+        let mut entries = Vec::new();
+        for line in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                anyhow::bail!("PAF index line does not have enough fields");
+            }
+            entries.push(IndexEntry {
+                target_name: fields[0].to_string(),
+                target_start: fields[1].parse().context("Failed to parse target start")?,
+                target_end: fields[2].parse().context("Failed to parse target end")?,
+                offset: fields[3].parse().context("Failed to parse record offset")?,
+                line_number: fields[4].parse().context("Failed to parse line number")?,
+            });
+        }
+        Ok(Self::from_entries(input, entries))
+    }
+
+    /// Whether the PAF at `paf_path` has the size and modification time it
+    /// had when it was indexed.
+    pub fn is_current(&self, paf_path: &str) -> Result<bool> {
+        let current = InputFile::describe("paf", paf_path, false)?;
+        Ok(current.size == self.input.size && current.mtime == self.input.mtime)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The records whose target interval overlaps `start..end` on
+    /// `target_name`, in file order.
+    pub fn overlapping(&self, target_name: &str, start: usize, end: usize) -> Vec<&IndexEntry> {
+        let Some(&max_len) = self.max_len.get(target_name) else {
+            return Vec::new();
+        };
+        // No record starting before this can reach `start`.
+        let min_start = start.saturating_sub(max_len);
+        let first = self.entries.partition_point(|entry| {
+            (entry.target_name.as_str(), entry.target_start) < (target_name, min_start)
+        });
+        let mut selected: Vec<&IndexEntry> = self.entries[first..]
+            .iter()
+            .take_while(|entry| entry.target_name == target_name && entry.target_start < end)
+            .filter(|entry| entry.target_end > start)
+            .collect();
+        selected.sort_by_key(|entry| entry.offset);
+        selected
+    }
+}
+
+/// Path of the index of the PAF at `paf_path`.
+pub fn index_path(paf_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.idx", paf_path))
+}
+
+/// Reads the lines of indexed records, seeking to each. Yields 0-based line
+/// numbers, like `enumerate()` over all lines of the file.
+pub struct IndexedLines {
+    reader: BufReader<File>,
+    entries: std::vec::IntoIter<(u64, usize)>,
+}
+
+impl IndexedLines {
+    pub fn open(paf_path: &str, entries: &[&IndexEntry]) -> Result<Self> {
+        let file =
+            File::open(paf_path).context(format!("Failed to open PAF file: {:?}", paf_path))?;
+        let entries: Vec<(u64, usize)> = entries
+            .iter()
+            .map(|entry| (entry.offset, entry.line_number))
+            .collect();
+        Ok(IndexedLines {
+            reader: BufReader::new(file),
+            entries: entries.into_iter(),
+        })
+    }
+}
+
+impl Iterator for IndexedLines {
+    type Item = (usize, Result<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, line_number) = self.entries.next()?;
+        let mut line = String::new();
+        let read = self
+            .reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_line(&mut line))
+            .context(format!("Failed to read PAF line {}", line_number))
+            .map(|_| line.trim_end_matches(['\n', '\r']).to_string());
+        Some((line_number - 1, read))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlapping_records() {
+        let mut paf = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            paf,
+            "q1\t10\t0\t10\t+\tchr1\t100\t0\t50\t10\t10\t60\n\
+             q2\t10\t0\t10\t+\tchr2\t100\t10\t20\t10\t10\t60\n\
+             q3\t10\t0\t10\t+\tchr1\t100\t60\t70\t10\t10\t60\n\
+             q4\t10\t0\t10\t+\tchr1\t100\t40\t45\t10\t10\t60\n"
+        )
+        .unwrap();
+        let paf_path = paf.path().to_str().unwrap();
+        let index = PafIndex::build(paf_path).unwrap();
+        let index_file = tempfile::NamedTempFile::new().unwrap();
+        index.write(index_file.path()).unwrap();
+        let index = PafIndex::load(index_file.path()).unwrap();
+        assert!(index.is_current(paf_path).unwrap());
+        assert_eq!(index.len(), 4);
+
+        let lines: Vec<usize> = index
+            .overlapping("chr1", 42, 65)
+            .iter()
+            .map(|entry| entry.line_number)
+            .collect();
+        assert_eq!(lines, [1, 3, 4]);
+        assert!(index.overlapping("chr1", 50, 60).is_empty());
+        assert!(index.overlapping("chr3", 0, 100).is_empty());
+
+        let selected = index.overlapping("chr1", 60, 61);
+        let read: Vec<(usize, String)> = IndexedLines::open(paf_path, &selected)
+            .unwrap()
+            .map(|(line_number, line)| (line_number, line.unwrap()))
+            .collect();
+        assert_eq!(
+            read,
+            [(
+                2,
+                "q3\t10\t0\t10\t+\tchr1\t100\t60\t70\t10\t10\t60".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::delta::DeltaRecords;
+use crate::maf::MafRecords;
+use anyhow::{Context, Result};
+use rust_htslib::bgzf;
+use rust_htslib::tpool::ThreadPool;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread::JoinHandle;
+
+/// Lines handed from the decompression stage to the validator per message.
+const BATCH_LINES: usize = 1024;
+/// Batches the decompression stage may run ahead of the validator.
+const QUEUED_BATCHES: usize = 16;
+
+/// Returns true if the file starts with the gzip magic bytes.
+pub fn is_gzipped<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let mut magic = [0u8; 2];
+    let mut file =
+        File::open(&path).context(format!("Failed to open PAF file: {:?}", path.as_ref()))?;
+    let n = file.read(&mut magic)?;
+    Ok(n == 2 && magic == [0x1f, 0x8b])
+}
+
+/// Iterator over the lines of a PAF file, or of standard input for `-`.
+/// Gzip/bgzip input and standard input (compressed or not) are read on a
+/// dedicated thread (with an htslib thread pool for bgzf blocks), so
+/// decompression overlaps with validation instead of serializing it.
+pub enum PafLines {
+    Plain(std::io::Lines<BufReader<File>>),
+    Decompressed {
+        batches: Receiver<Result<Vec<String>>>,
+        current: std::vec::IntoIter<String>,
+        worker: Option<JoinHandle<()>>,
+    },
+}
+
+impl PafLines {
+    pub fn open<P: AsRef<Path>>(path: P, decompress_threads: u32) -> Result<Self> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            return Ok(Self::threaded(None, decompress_threads));
+        }
+        if !is_gzipped(path)? {
+            let file = File::open(path).context("Failed to open PAF file")?;
+            return Ok(PafLines::Plain(BufReader::new(file).lines()));
+        }
+        Ok(Self::threaded(Some(path.to_path_buf()), decompress_threads))
+    }
+
+    /// Reads `path`, or standard input if `None`, through htslib's bgzf
+    /// layer, which passes uncompressed data through unchanged.
+    fn threaded(path: Option<PathBuf>, decompress_threads: u32) -> Self {
+        let (sender, batches) = sync_channel(QUEUED_BATCHES);
+        let worker = std::thread::spawn(move || {
+            let result = (|| -> Result<()> {
+                let mut reader = match &path {
+                    Some(path) => bgzf::Reader::from_path(path)
+                        .context(format!("Failed to open compressed PAF file: {:?}", path))?,
+                    None => bgzf::Reader::from_stdin()
+                        .context("Failed to read PAF from standard input")?,
+                };
+                if decompress_threads > 1 {
+                    let pool = ThreadPool::new(decompress_threads)
+                        .context("Failed to create decompression thread pool")?;
+                    reader
+                        .set_thread_pool(&pool)
+                        .context("Failed to attach decompression thread pool")?;
+                }
+                let mut lines = BufReader::new(reader).lines();
+                loop {
+                    let mut batch = Vec::with_capacity(BATCH_LINES);
+                    for line in lines.by_ref().take(BATCH_LINES) {
+                        batch.push(line.context("Failed to decompress PAF line")?);
+                    }
+                    if batch.is_empty() {
+                        return Ok(());
+                    }
+                    if sender.send(Ok(batch)).is_err() {
+                        return Ok(()); // consumer stopped early
+                    }
+                }
+            })();
+            if let Err(e) = result {
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        PafLines::Decompressed {
+            batches,
+            current: Vec::new().into_iter(),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Iterator for PafLines {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            PafLines::Plain(lines) => lines
+                .next()
+                .map(|line| line.context("Failed to read PAF line")),
+            PafLines::Decompressed {
+                batches,
+                current,
+                worker,
+            } => loop {
+                if let Some(line) = current.next() {
+                    return Some(Ok(line));
+                }
+                match batches.recv() {
+                    Ok(Ok(batch)) => *current = batch.into_iter(),
+                    Ok(Err(e)) => return Some(Err(e)),
+                    Err(_) => {
+                        if let Some(worker) = worker.take() {
+                            let _ = worker.join();
+                        }
+                        return None;
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Numbered records of `lines`: the lines themselves for a PAF file, or the
+/// converted alignments of a MAF file, which starts with a `##maf` line, or
+/// of a MUMmer delta file, whose second line is `NUCMER` (or `PROMER`,
+/// which is rejected).
+pub fn numbered_records<'a, I>(lines: I) -> Box<dyn Iterator<Item = (usize, Result<String>)> + 'a>
+where
+    I: Iterator<Item = Result<String>> + 'a,
+{
+    let mut lines = lines.peekable();
+    if matches!(lines.peek(), Some(Ok(line)) if line.starts_with("##maf")) {
+        return Box::new(MafRecords::new(lines));
+    }
+    let head: Vec<Result<String>> = lines.by_ref().take(2).collect();
+    let is_delta =
+        matches!(head.get(1), Some(Ok(line)) if matches!(line.trim(), "NUCMER" | "PROMER"));
+    let lines = head.into_iter().chain(lines);
+    if is_delta {
+        Box::new(DeltaRecords::new(lines))
+    } else {
+        Box::new(lines.enumerate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(text: &str) -> Vec<(usize, String)> {
+        numbered_records(text.lines().map(|line| Ok(line.to_string())))
+            .map(|(number, record)| (number, record.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_numbered_records() {
+        // PAF lines pass through.
+        assert_eq!(
+            records("q\t1\t0\t1\nr\t1\t0\t1\nNUCMER\n"),
+            [
+                (0, "q\t1\t0\t1".to_string()),
+                (1, "r\t1\t0\t1".to_string()),
+                (2, "NUCMER".to_string()),
+            ]
+        );
+        assert_eq!(
+            records("##maf\na\ns t 0 1 + 1 A\ns q 0 1 + 1 A\n"),
+            [(
+                1,
+                "q\t1\t0\t1\t+\tt\t1\t0\t1\t1\t1\t255\tcs:Z:=A".to_string()
+            )]
+        );
+        assert_eq!(
+            records("t.fa q.fa\nNUCMER\n>t q 4 4\n1 4 1 4 0 0 0\n0\n"),
+            [(
+                3,
+                "q\t4\t0\t4\t+\tt\t4\t0\t4\t4\t4\t255\tNM:i:0\tcg:Z:4M".to_string()
+            )]
+        );
+    }
+}
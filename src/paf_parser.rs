@@ -1,5 +1,8 @@
 use anyhow::{Context, Result};
 
+/// Mapping quality of records whose aligner didn't compute one.
+pub const MAPQ_UNAVAILABLE: u8 = 255;
+
 #[derive(Debug)]
 pub struct PafRecord {
     pub query_name: String,
@@ -11,7 +14,16 @@ pub struct PafRecord {
     pub target_length: usize,
     pub target_start: usize,
     pub target_end: usize,
+    /// Column 10: the number of matching bases.
+    pub residue_matches: usize,
+    /// Column 11: the alignment block length.
+    pub block_length: usize,
+    /// Column 12: the mapping quality (`MAPQ_UNAVAILABLE` if unknown), or
+    /// `None` if it isn't an integer from 0 to 255.
+    pub mapq: Option<u8>,
     pub cigar: String,
+    /// Optional `TAG:TYPE:VALUE` fields after column 12, including `cg:Z`.
+    pub tags: Vec<String>,
 }
 
 impl PafRecord {
@@ -39,7 +51,47 @@ impl PafRecord {
             target_length: fields[6].parse().context("Failed to parse target length")?,
             target_start: fields[7].parse().context("Failed to parse target start")?,
             target_end: fields[8].parse().context("Failed to parse target end")?,
+            residue_matches: fields[9]
+                .parse()
+                .context("Failed to parse residue matches")?,
+            block_length: fields[10].parse().context("Failed to parse block length")?,
+            mapq: fields[11].parse().ok(),
             cigar,
+            tags: fields[12..].iter().map(|field| field.to_string()).collect(),
+        })
+    }
+
+    /// The value of the optional field with the given `TAG:TYPE`, e.g. `NM:i`.
+    pub fn tag(&self, tag: &str) -> Option<&str> {
+        self.tags.iter().find_map(|field| {
+            field
+                .strip_prefix(tag)
+                .and_then(|rest| rest.strip_prefix(':'))
         })
     }
 }
+
+/// Coordinate and length columns of a PAF line, by index and name.
+const COORDINATE_COLUMNS: [(usize, &str); 6] = [
+    (1, "query length"),
+    (2, "query start"),
+    (3, "query end"),
+    (6, "target length"),
+    (7, "target start"),
+    (8, "target end"),
+];
+
+/// The coordinate and length columns of a PAF line that hold negative
+/// integers, with their values. Such lines can't be parsed as records.
+pub fn negative_columns(line: &str) -> Vec<(&'static str, &str)> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    COORDINATE_COLUMNS
+        .iter()
+        .filter_map(|&(index, name)| {
+            let field = *fields.get(index)?;
+            let digits = field.strip_prefix('-')?;
+            (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+                .then_some((name, field))
+        })
+        .collect()
+}
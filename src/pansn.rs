@@ -0,0 +1,84 @@
+/// A sequence name following the PanSN-spec: `sample#haplotype#contig`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanSnName<'a> {
+    pub sample: &'a str,
+    pub haplotype: &'a str,
+    pub contig: &'a str,
+}
+
+impl<'a> PanSnName<'a> {
+    /// Parses a PanSN name with the given delimiter, or `None` if the name
+    /// doesn't have the three fields.
+    pub fn parse(name: &'a str, delimiter: char) -> Option<Self> {
+        let mut fields = name.splitn(3, delimiter);
+        let sample = fields.next()?;
+        let haplotype = fields.next()?;
+        let contig = fields.next()?;
+        if sample.is_empty() || haplotype.is_empty() {
+            return None;
+        }
+        Some(PanSnName {
+            sample,
+            haplotype,
+            contig,
+        })
+    }
+
+    /// The `sample#haplotype` prefix identifying the haplotype.
+    pub fn haplotype_key(&self, delimiter: char) -> String {
+        format!("{}{}{}", self.sample, delimiter, self.haplotype)
+    }
+}
+
+/// How a record's query and target haplotypes relate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaplotypePairing {
+    SameHaplotype,
+    /// Same sample, different haplotypes.
+    SameSample,
+    DifferentSamples,
+}
+
+/// Relates two PanSN names, or `None` if either is not a PanSN name.
+pub fn haplotype_pairing(query: &str, target: &str, delimiter: char) -> Option<HaplotypePairing> {
+    let query = PanSnName::parse(query, delimiter)?;
+    let target = PanSnName::parse(target, delimiter)?;
+    Some(if query.sample != target.sample {
+        HaplotypePairing::DifferentSamples
+    } else if query.haplotype != target.haplotype {
+        HaplotypePairing::SameSample
+    } else {
+        HaplotypePairing::SameHaplotype
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_haplotype_pairing() {
+        assert_eq!(
+            PanSnName::parse("HG002#1#chr1#alt", '#'),
+            Some(PanSnName {
+                sample: "HG002",
+                haplotype: "1",
+                contig: "chr1#alt"
+            })
+        );
+        assert_eq!(PanSnName::parse("chr1", '#'), None);
+        assert_eq!(
+            haplotype_pairing("HG002#1#chr1", "HG002#2#chr1", '#'),
+            Some(HaplotypePairing::SameSample)
+        );
+        assert_eq!(
+            haplotype_pairing("HG002#1#chr1", "HG002#1#chr2", '#'),
+            Some(HaplotypePairing::SameHaplotype)
+        );
+        assert_eq!(
+            haplotype_pairing("HG002#1#chr1", "CHM13#0#chr1", '#'),
+            Some(HaplotypePairing::DifferentSamples)
+        );
+        assert_eq!(haplotype_pairing("HG002#1#chr1", "chr1", '#'), None);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::fasta_reader::{MultiFastaReader, SequenceSource};
+use crate::paf_parser::PafRecord;
+use crate::rgfa::RgfaGraph;
+use crate::validator::{validate_record_with_identity, ValidationOptions};
+use anyhow::{Context, Result};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Everything needed to open a sequence reader, so that each worker thread
+/// can open its own (htslib readers can't be shared between threads).
+#[derive(Debug, Clone)]
+pub struct ReaderSpec {
+    pub query: String,
+    pub target: String,
+    pub refget_aliases: Option<String>,
+    pub target_graph: Option<Arc<RgfaGraph>>,
+}
+
+impl ReaderSpec {
+    pub fn open(&self) -> Result<MultiFastaReader> {
+        let query = SequenceSource::open(&self.query, self.refget_aliases.as_deref())
+            .context("Failed to open query sequences")?;
+        let target = SequenceSource::open(&self.target, self.refget_aliases.as_deref())
+            .context("Failed to open target sequences")?;
+        let reader = MultiFastaReader::from_sources(query, target);
+        Ok(match &self.target_graph {
+            Some(graph) => reader.with_shared_target_graph(Arc::clone(graph)),
+            None => reader,
+        })
+    }
+}
+
+type Job = (usize, PafRecord);
+type Outcome = (usize, PafRecord, Result<()>, Option<f64>);
+/// A validated record with its result and gap-compressed identity.
+pub type Validated = (PafRecord, Result<()>, Option<f64>);
+
+/// Worker threads that validate records in parallel. Results are handed back
+/// per batch in input order, so output stays deterministic.
+pub struct ValidationPool {
+    jobs: Option<Sender<Job>>,
+    results: Receiver<Outcome>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ValidationPool {
+    pub fn new(
+        threads: usize,
+        spec: ReaderSpec,
+        options: Arc<ValidationOptions>,
+        error_mode: &str,
+    ) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = channel();
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                let spec = spec.clone();
+                let options = Arc::clone(&options);
+                let error_mode = error_mode.to_string();
+                std::thread::spawn(move || {
+                    let mut reader = spec.open();
+                    loop {
+                        let job = job_receiver.lock().unwrap().recv();
+                        let (index, record) = match job {
+                            Ok(job) => job,
+                            Err(_) => return, // pool dropped
+                        };
+                        // A panic is reported as the record's error, so that
+                        // the batch still gets all of its results.
+                        let (result, identity) = match reader.as_mut() {
+                            Ok(reader) => catch_unwind(AssertUnwindSafe(|| {
+                                validate_record_with_identity(
+                                    &record,
+                                    reader,
+                                    &error_mode,
+                                    &options,
+                                    &mut Vec::new(),
+                                )
+                            }))
+                            .unwrap_or_else(|_| {
+                                (
+                                    Err(anyhow::anyhow!("Validation of the record panicked")),
+                                    None,
+                                )
+                            }),
+                            Err(e) => (Err(anyhow::anyhow!("{:#}", e)), None),
+                        };
+                        if result_sender
+                            .send((index, record, result, identity))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        ValidationPool {
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+
+    /// Validates a batch of records, returning each with its result and
+    /// gap-compressed identity in the order given. Fails if the workers
+    /// stopped, i.e. one of them panicked.
+    pub fn validate_batch(&mut self, records: Vec<PafRecord>) -> Result<Vec<Validated>> {
+        let count = records.len();
+        let jobs = self.jobs.as_ref().unwrap();
+        for job in records.into_iter().enumerate() {
+            if jobs.send(job).is_err() {
+                anyhow::bail!("The validation workers stopped");
+            }
+        }
+        let mut outcomes = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.results.recv() {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(_) => anyhow::bail!("A validation worker stopped before finishing its records"),
+            }
+        }
+        outcomes.sort_by_key(|(index, _, _, _): &Outcome| *index);
+        Ok(outcomes
+            .into_iter()
+            .map(|(_, record, result, identity)| (record, result, identity))
+            .collect())
+    }
+}
+
+impl Drop for ValidationPool {
+    fn drop(&mut self) {
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
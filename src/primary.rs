@@ -0,0 +1,99 @@
+use crate::paf_parser::PafRecord;
+use std::collections::HashMap;
+
+/// Primary and secondary alignments of one query.
+#[derive(Debug, Default)]
+struct QueryAlignments {
+    /// Query interval and line number of each primary.
+    primaries: Vec<(usize, usize, usize)>,
+    /// Line number of the first secondary, and how many there are.
+    secondaries: Option<(usize, usize)>,
+}
+
+/// Cross-record bookkeeping of the `tp:A` alignment types: secondary (`S`)
+/// alignments need a primary (`P`) of the same query, and primaries of a
+/// query shouldn't claim the same part of it. Records without `tp:A`, and
+/// inversions (`I`, `i`), are not tracked.
+#[derive(Debug, Default)]
+pub struct PrimaryTracker {
+    queries: HashMap<String, QueryAlignments>,
+}
+
+impl PrimaryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the type of a record. For a primary that covers more than half
+    /// of an earlier primary of the same query, or is mostly covered by one,
+    /// returns that primary's line number.
+    pub fn add(&mut self, record: &PafRecord, line_number: usize) -> Option<usize> {
+        let alignments = match record.tag("tp:A") {
+            Some("P") | Some("S") => self.queries.entry(record.query_name.clone()).or_default(),
+            _ => return None,
+        };
+        if record.tag("tp:A") == Some("S") {
+            let (first, count) = alignments.secondaries.get_or_insert((line_number, 0));
+            *first = (*first).min(line_number);
+            *count += 1;
+            return None;
+        }
+        let (start, end) = (record.query_start, record.query_end);
+        let conflict = alignments
+            .primaries
+            .iter()
+            .find(|(other_start, other_end, _)| {
+                let overlap = end.min(*other_end).saturating_sub(start.max(*other_start));
+                let shorter = end
+                    .saturating_sub(start)
+                    .min(other_end.saturating_sub(*other_start));
+                2 * overlap > shorter
+            });
+        let conflict = conflict.map(|(_, _, line)| *line);
+        alignments.primaries.push((start, end, line_number));
+        conflict
+    }
+
+    /// Queries with secondary alignments but no primary, sorted by name, with
+    /// the line of their first secondary and how many they have.
+    pub fn orphan_secondaries(&self) -> Vec<(&str, usize, usize)> {
+        let mut orphans: Vec<(&str, usize, usize)> = self
+            .queries
+            .iter()
+            .filter(|(_, alignments)| alignments.primaries.is_empty())
+            .filter_map(|(name, alignments)| {
+                let (first, count) = alignments.secondaries?;
+                Some((name.as_str(), first, count))
+            })
+            .collect();
+        orphans.sort();
+        orphans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(query: &str, start: usize, end: usize, tp: &str) -> PafRecord {
+        PafRecord::from_line(&format!(
+            "{}\t100\t{}\t{}\t+\tt\t100\t0\t10\t10\t10\t60\ttp:A:{}",
+            query, start, end, tp
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_primary_tracking() {
+        let mut tracker = PrimaryTracker::new();
+        assert_eq!(tracker.add(&record("q1", 0, 50, "P"), 1), None);
+        // A supplementary primary on another part of the query is fine.
+        assert_eq!(tracker.add(&record("q1", 45, 100, "P"), 2), None);
+        assert_eq!(tracker.add(&record("q1", 10, 40, "P"), 3), Some(1));
+        assert_eq!(tracker.add(&record("q1", 0, 50, "S"), 4), None);
+        assert_eq!(tracker.add(&record("q2", 0, 50, "S"), 5), None);
+        assert_eq!(tracker.add(&record("q2", 0, 50, "S"), 6), None);
+        assert_eq!(tracker.add(&record("q3", 0, 50, "I"), 7), None);
+        assert_eq!(tracker.orphan_secondaries(), [("q2", 5, 2)]);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::paf_parser::PafRecord;
+use crate::validator::{ErrorSite, ErrorType};
+use std::collections::HashMap;
+
+/// Upper bounds of the distance-from-alignment-end buckets, in bases.
+const END_DISTANCE_BOUNDS: [usize; 4] = [10, 100, 1000, 10000];
+
+/// Aggregates the sequence and alignment context of individual error sites:
+/// homopolymer runs, low-complexity regions, local GC content, and where in
+/// the alignment the errors fall.
+#[derive(Debug)]
+pub struct ErrorContextProfile {
+    homopolymer_k: usize,
+    site_count: usize,
+    /// (in homopolymer context, outside) per error type
+    homopolymer_counts: HashMap<ErrorType, (usize, usize)>,
+    low_complexity_count: usize,
+    gc_bins: [usize; 10],
+    /// Relative position along the target span, in tenths.
+    position_bins: [usize; 10],
+    /// Distance to the nearest alignment end, bucketed by `END_DISTANCE_BOUNDS`.
+    end_distance_bins: [usize; 5],
+}
+
+impl ErrorContextProfile {
+    pub fn new(homopolymer_k: usize) -> Self {
+        ErrorContextProfile {
+            homopolymer_k,
+            site_count: 0,
+            homopolymer_counts: HashMap::new(),
+            low_complexity_count: 0,
+            gc_bins: [0; 10],
+            position_bins: [0; 10],
+            end_distance_bins: [0; 5],
+        }
+    }
+
+    pub fn add_site(&mut self, record: &PafRecord, error_type: &ErrorType, site: &ErrorSite) {
+        self.site_count += 1;
+        let context_counts = self
+            .homopolymer_counts
+            .entry(error_type.clone())
+            .or_insert((0, 0));
+        if site.homopolymer_len >= self.homopolymer_k {
+            context_counts.0 += 1;
+        } else {
+            context_counts.1 += 1;
+        }
+        if site.low_complexity {
+            self.low_complexity_count += 1;
+        }
+        if let Some(gc) = site.gc_fraction {
+            self.gc_bins[((gc * 10.0) as usize).min(9)] += 1;
+        }
+
+        let span = record.target_end.saturating_sub(record.target_start);
+        if span > 0 {
+            let offset = site
+                .target_pos
+                .saturating_sub(record.target_start)
+                .min(span);
+            let relative = offset as f64 / span as f64;
+            self.position_bins[((relative * 10.0) as usize).min(9)] += 1;
+            let end_distance = offset.min(span.saturating_sub(offset + 1));
+            let bucket = END_DISTANCE_BOUNDS
+                .iter()
+                .position(|&bound| end_distance < bound)
+                .unwrap_or(END_DISTANCE_BOUNDS.len());
+            self.end_distance_bins[bucket] += 1;
+        }
+    }
+
+    /// Prints the homopolymer split for one error type of the summary table.
+    pub fn print_type_context(&self, error_type: &ErrorType) {
+        if let Some((in_run, outside)) = self.homopolymer_counts.get(error_type) {
+            println!(
+                "[pafcheck]       homopolymer (>= {}): {}, other: {}",
+                self.homopolymer_k, in_run, outside
+            );
+        }
+    }
+
+    pub fn print(&self) {
+        if self.site_count == 0 {
+            return;
+        }
+        println!(
+            "[pafcheck] Errors in low-complexity regions: {} of {} ({:.2}%)",
+            self.low_complexity_count,
+            self.site_count,
+            100.0 * self.low_complexity_count as f64 / self.site_count as f64
+        );
+        println!("[pafcheck] Errors by local GC content:");
+        for (bin, count) in self.gc_bins.iter().enumerate() {
+            println!(
+                "[pafcheck]   - GC {:>3}-{:<3}%: {}",
+                bin * 10,
+                (bin + 1) * 10,
+                count
+            );
+        }
+        println!("[pafcheck] Errors by relative alignment position:");
+        for (bin, count) in self.position_bins.iter().enumerate() {
+            println!(
+                "[pafcheck]   - {:.1}-{:.1}: {}",
+                bin as f64 / 10.0,
+                (bin + 1) as f64 / 10.0,
+                count
+            );
+        }
+        println!("[pafcheck] Errors by distance from nearest alignment end:");
+        let mut lower = 0;
+        for (bucket, count) in self.end_distance_bins.iter().enumerate() {
+            match END_DISTANCE_BOUNDS.get(bucket) {
+                Some(&upper) => {
+                    println!("[pafcheck]   - {}-{} bp: {}", lower, upper - 1, count);
+                    lower = upper;
+                }
+                None => println!("[pafcheck]   - >= {} bp: {}", lower, count),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_and_end_distance_bins() {
+        let record =
+            PafRecord::from_line("q\t100\t0\t100\t+\tt\t200\t100\t200\t100\t100\t60").unwrap();
+        let mut profile = ErrorContextProfile::new(3);
+        for target_pos in [100, 150, 199] {
+            let site = ErrorSite::new(0, target_pos - 100, target_pos, 1);
+            profile.add_site(&record, &ErrorType::Mismatch, &site);
+        }
+        assert_eq!(profile.position_bins[0], 1);
+        assert_eq!(profile.position_bins[5], 1);
+        assert_eq!(profile.position_bins[9], 1);
+        assert_eq!(profile.end_distance_bins, [2, 1, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+/// Prints a one-line status to stderr every `interval` and/or every
+/// `every_records` records, so long runs show that they are progressing.
+pub struct StatusReporter {
+    start: Instant,
+    last_report: Instant,
+    last_records: usize,
+    interval: Option<Duration>,
+    every_records: Option<usize>,
+    /// Size of the input, if known, for reporting percent done and an ETA.
+    total_bytes: Option<u64>,
+}
+
+impl StatusReporter {
+    pub fn new(interval: Option<Duration>, every_records: Option<usize>) -> Self {
+        let now = Instant::now();
+        StatusReporter {
+            start: now,
+            last_report: now,
+            last_records: 0,
+            interval,
+            every_records,
+            total_bytes: None,
+        }
+    }
+
+    pub fn with_total_bytes(mut self, total_bytes: Option<u64>) -> Self {
+        self.total_bytes = total_bytes;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some() || self.every_records.is_some()
+    }
+
+    /// Called once per record with the records and input bytes read so far;
+    /// prints a status line when one is due.
+    pub fn tick(
+        &mut self,
+        records: usize,
+        bytes: u64,
+        errors: usize,
+        query_name: &str,
+        target_name: &str,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let due_by_records = self
+            .every_records
+            .is_some_and(|every| every > 0 && records - self.last_records >= every);
+        // Checking the clock on every record is cheap next to fetching sequences.
+        let due_by_time = self
+            .interval
+            .is_some_and(|interval| self.last_report.elapsed() >= interval);
+        if !(due_by_records || due_by_time) {
+            return;
+        }
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let throughput = if elapsed > 0.0 {
+            records as f64 / elapsed
+        } else {
+            0.0
+        };
+        let progress = match self.total_bytes {
+            Some(total) if total > 0 => {
+                let fraction = (bytes as f64 / total as f64).min(1.0);
+                let eta = if fraction > 0.0 {
+                    format_duration(elapsed * (1.0 - fraction) / fraction)
+                } else {
+                    "unknown".to_string()
+                };
+                format!(
+                    "{} of {} read ({:.1}%, ETA {})",
+                    format_bytes(bytes),
+                    format_bytes(total),
+                    100.0 * fraction,
+                    eta
+                )
+            }
+            _ => format!("{} read", format_bytes(bytes)),
+        };
+        eprintln!(
+            "[pafcheck] status: {} records, {}, {} errors, at {} -> {}, {:.1} records/s",
+            records, progress, errors, query_name, target_name, throughput
+        );
+        self.last_report = Instant::now();
+        self.last_records = records;
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 GiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats seconds as `1h02m03s`, `2m03s` or `3s`.
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_formatting() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 << 29), "1.5 GiB");
+        assert_eq!(format_duration(3723.4), "1h02m03s");
+        assert_eq!(format_duration(59.0), "59s");
+    }
+}
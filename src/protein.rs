@@ -0,0 +1,235 @@
+use crate::fasta_reader::MultiFastaReader;
+use crate::paf_parser::PafRecord;
+use crate::validator::{record_error, reverse_complement, ErrorSite, ErrorType, ValidationError};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// One operation of a miniprot extended CIGAR. Counts are codons for `M` and
+/// `D`, residues for `I`, and nucleotides for the rest.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProteinOp {
+    Match(usize),
+    Insertion(usize),
+    Deletion(usize),
+    FrameshiftDeletion(usize),
+    FrameshiftMatch(usize),
+    /// Intron of the given length and phase (0 for `N`, 1 for `U`, 2 for `V`).
+    Intron(usize, u8),
+}
+
+pub fn parse_protein_cigar(cigar: &str) -> Result<Vec<ProteinOp>> {
+    let mut ops = Vec::new();
+    let mut count = String::new();
+    for c in cigar.chars() {
+        if c.is_ascii_digit() {
+            count.push(c);
+            continue;
+        }
+        if count.is_empty() {
+            anyhow::bail!("Missing count before '{}' in protein CIGAR", c);
+        }
+        let len: usize = count
+            .parse()
+            .context(format!("Invalid count in protein CIGAR: {}", count))?;
+        count.clear();
+        ops.push(match c {
+            'M' => ProteinOp::Match(len),
+            'I' => ProteinOp::Insertion(len),
+            'D' => ProteinOp::Deletion(len),
+            'F' => ProteinOp::FrameshiftDeletion(len),
+            'G' => ProteinOp::FrameshiftMatch(len),
+            'N' => ProteinOp::Intron(len, 0),
+            'U' => ProteinOp::Intron(len, 1),
+            'V' => ProteinOp::Intron(len, 2),
+            _ => anyhow::bail!("Invalid protein CIGAR operation: {}", c),
+        });
+    }
+    if !count.is_empty() {
+        anyhow::bail!("Protein CIGAR ends with a count but no operation");
+    }
+    Ok(ops)
+}
+
+/// Translates a codon with the standard genetic code; codons containing
+/// anything but ACGT translate to `X`.
+pub fn translate_codon(codon: &[u8]) -> u8 {
+    const AMINO_ACIDS: &[u8; 64] =
+        b"KNKNTTTTRSRSIIMIQHQHPPPPRRRRLLLLEDEDAAAAGGGGVVVV*Y*YSSSS*CWCLFLF";
+    let mut index = 0;
+    for &base in codon {
+        let value = match base.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' | b'U' => 3,
+            _ => return b'X',
+        };
+        index = index * 4 + value;
+    }
+    AMINO_ACIDS[index]
+}
+
+/// Residues compared in a protein alignment and how many were identical.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResidueCounts {
+    pub aligned: usize,
+    pub identical: usize,
+}
+
+/// Validates a miniprot protein-to-genome record: the query span is in
+/// residues of the protein FASTA, the target span in nucleotides. Codons of
+/// `M` ops are translated and compared with the protein; substitutions are
+/// expected in protein alignments, so they are counted rather than reported.
+/// For phase-1 and phase-2 introns the split codon is formed from the one or
+/// two bases before the intron and the rest after it, and consumes a residue.
+/// Errors are raised when the CIGAR's spans disagree with the PAF coordinates.
+pub fn validate_protein_record<W: Write>(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    error_mode: &str,
+    output: &mut W,
+) -> Result<ResidueCounts> {
+    let protein = fasta_reader
+        .fetch_query_sequence(&record.query_name, record.query_start, record.query_end)
+        .context(format!(
+            "Failed to fetch protein sequence: {} ({}:{})",
+            record.query_name, record.query_start, record.query_end
+        ))?
+        .to_uppercase()
+        .into_bytes();
+    let target_seq = fasta_reader
+        .fetch_target_sequence(&record.target_name, record.target_start, record.target_end)
+        .context(format!(
+            "Failed to fetch target sequence: {} ({}:{})",
+            record.target_name, record.target_start, record.target_end
+        ))?;
+    let genome = if record.strand == '-' {
+        reverse_complement(&target_seq)
+    } else {
+        target_seq
+    }
+    .to_uppercase()
+    .into_bytes();
+
+    let ops = parse_protein_cigar(&record.cigar).context("Failed to parse protein CIGAR")?;
+
+    let mut q_idx = 0;
+    let mut t_idx = 0;
+    let mut counts = ResidueCounts::default();
+    let mut compare = |codon: &[u8], q_idx: usize| -> Result<()> {
+        let residue = protein
+            .get(q_idx)
+            .ok_or_else(|| anyhow::anyhow!("Protein sequence index out of range"))?;
+        counts.aligned += 1;
+        if translate_codon(codon) == *residue {
+            counts.identical += 1;
+        }
+        Ok(())
+    };
+    let codon_at = |start: usize, len: usize| -> Result<&[u8]> {
+        genome
+            .get(start..start + len)
+            .ok_or_else(|| anyhow::anyhow!("Target sequence index out of range"))
+    };
+
+    for op in &ops {
+        match *op {
+            ProteinOp::Match(len) => {
+                for _ in 0..len {
+                    compare(codon_at(t_idx, 3)?, q_idx)?;
+                    q_idx += 1;
+                    t_idx += 3;
+                }
+            }
+            ProteinOp::Insertion(len) => q_idx += len,
+            ProteinOp::Deletion(len) => t_idx += 3 * len,
+            ProteinOp::FrameshiftDeletion(len) => t_idx += len,
+            ProteinOp::FrameshiftMatch(len) => {
+                q_idx += 1;
+                t_idx += len;
+            }
+            ProteinOp::Intron(len, 0) => t_idx += len,
+            ProteinOp::Intron(len, phase) => {
+                let before = phase as usize;
+                let mut codon = codon_at(t_idx, before)?.to_vec();
+                codon.extend_from_slice(codon_at(t_idx + before + len, 3 - before)?);
+                compare(&codon, q_idx)?;
+                q_idx += 1;
+                t_idx += len + 3;
+            }
+        }
+    }
+
+    let mut errors = HashMap::new();
+    let end_site = ErrorSite::new(
+        ops.len(),
+        record.query_start + q_idx,
+        record.target_start + t_idx.min(genome.len()),
+        0,
+    );
+    if q_idx != protein.len() {
+        let message = format!(
+            "Protein length mismatch: CIGAR implies {} residues, aligned span is {}",
+            q_idx,
+            protein.len()
+        );
+        record_error(
+            &mut errors,
+            ErrorType::LengthMismatch,
+            message,
+            end_site.clone(),
+        );
+    }
+    if t_idx != genome.len() {
+        let message = format!(
+            "Target sequence length mismatch: CIGAR implies {} nucleotides, actual length {}",
+            t_idx,
+            genome.len()
+        );
+        record_error(&mut errors, ErrorType::LengthMismatch, message, end_site);
+    }
+
+    if errors.is_empty() {
+        Ok(counts)
+    } else if error_mode == "report" {
+        for (error_type, error_info) in &errors {
+            writeln!(output, "{:?}: {}", error_type, error_info.first_message)?;
+        }
+        Ok(counts)
+    } else {
+        Err(anyhow::anyhow!(ValidationError { errors }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_protein_cigar() {
+        assert_eq!(
+            parse_protein_cigar("10M2I1F3M120U4M2G").unwrap(),
+            vec![
+                ProteinOp::Match(10),
+                ProteinOp::Insertion(2),
+                ProteinOp::FrameshiftDeletion(1),
+                ProteinOp::Match(3),
+                ProteinOp::Intron(120, 1),
+                ProteinOp::Match(4),
+                ProteinOp::FrameshiftMatch(2),
+            ]
+        );
+        assert!(parse_protein_cigar("3X").is_err());
+        assert!(parse_protein_cigar("M").is_err());
+    }
+
+    #[test]
+    fn test_translate_codon() {
+        assert_eq!(translate_codon(b"ATG"), b'M');
+        assert_eq!(translate_codon(b"tgg"), b'W');
+        assert_eq!(translate_codon(b"TAA"), b'*');
+        assert_eq!(translate_codon(b"GCN"), b'X');
+        assert_eq!(translate_codon(b"TTT"), b'F');
+    }
+}
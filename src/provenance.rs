@@ -0,0 +1,135 @@
+use crate::report::json_string;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::time::UNIX_EPOCH;
+
+/// Identity of one input file of a run, recorded in structured reports so a
+/// report can be matched to the exact inputs it validated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFile {
+    pub role: String,
+    pub path: String,
+    pub size: u64,
+    /// Modification time, in seconds since the Unix epoch.
+    pub mtime: u64,
+    /// FNV-1a 64-bit hash of the file contents, when requested.
+    pub checksum: Option<String>,
+}
+
+impl InputFile {
+    pub fn describe(role: &str, path: &str, with_checksum: bool) -> Result<Self> {
+        if path == "-" {
+            // Standard input can be neither stat'ed meaningfully nor re-read.
+            return Ok(InputFile {
+                role: role.to_string(),
+                path: path.to_string(),
+                size: 0,
+                mtime: 0,
+                checksum: None,
+            });
+        }
+        let meta =
+            std::fs::metadata(path).context(format!("Failed to stat input file: {:?}", path))?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let checksum = if with_checksum && meta.is_file() {
+            Some(format!("fnv1a64:{:016x}", fnv1a_file(path)?))
+        } else {
+            None
+        };
+        Ok(InputFile {
+            role: role.to_string(),
+            path: path.to_string(),
+            size: meta.len(),
+            mtime,
+            checksum,
+        })
+    }
+
+    /// Tab-separated form used in report headers.
+    pub fn to_fields(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.role,
+            self.path,
+            self.size,
+            self.mtime,
+            self.checksum.as_deref().unwrap_or("-")
+        )
+    }
+
+    /// JSON object form used in the JSON and NDJSON summaries.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"role\": {}, \"path\": {}, \"size\": {}, \"mtime\": {}, \"checksum\": {}}}",
+            json_string(&self.role),
+            json_string(&self.path),
+            self.size,
+            self.mtime,
+            self.checksum
+                .as_deref()
+                .map_or("null".to_string(), json_string)
+        )
+    }
+
+    pub fn from_fields(fields: &str) -> Result<Self> {
+        let fields: Vec<&str> = fields.split('\t').collect();
+        if fields.len() < 5 {
+            anyhow::bail!("Input provenance line does not have enough fields");
+        }
+        Ok(InputFile {
+            role: fields[0].to_string(),
+            path: fields[1].to_string(),
+            size: fields[2].parse().context("Failed to parse input size")?,
+            mtime: fields[3].parse().context("Failed to parse input mtime")?,
+            checksum: match fields[4] {
+                "-" => None,
+                checksum => Some(checksum.to_string()),
+            },
+        })
+    }
+}
+
+fn fnv1a_file(path: &str) -> Result<u64> {
+    let file = File::open(path).context(format!("Failed to open input file: {:?}", path))?;
+    let mut reader = BufReader::with_capacity(1 << 20, file);
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(hash);
+        }
+        for &byte in &buffer[..n] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_describe_round_trip() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "a").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let input = InputFile::describe("paf", path, true).unwrap();
+        assert_eq!(input.size, 1);
+        assert_eq!(input.checksum.as_deref(), Some("fnv1a64:af63dc4c8601ec8c"));
+        assert_eq!(InputFile::from_fields(&input.to_fields()).unwrap(), input);
+        assert_eq!(
+            InputFile::describe("paf", "-", true).unwrap().to_json(),
+            "{\"role\": \"paf\", \"path\": \"-\", \"size\": 0, \"mtime\": 0, \"checksum\": null}"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use rust_htslib::bgzf;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use url::Url;
+
+/// Prefix marking a sequence location as a refget server rather than a FASTA path.
+pub const REFGET_PREFIX: &str = "refget:";
+
+/// Client for the GA4GH refget sequence API. Requests go through htslib's
+/// hFILE layer, so any scheme htslib was built with (https, s3, ...) works.
+#[derive(Debug)]
+pub struct RefgetClient {
+    base_url: String,
+    aliases: HashMap<String, String>,
+}
+
+impl RefgetClient {
+    pub fn new(base_url: &str) -> Self {
+        RefgetClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Maps sequence names to refget identifiers (e.g. `chr1` to a TRUNC512
+    /// or ga4gh digest), read from a two-column TSV. Names without an alias
+    /// are sent to the server as-is.
+    pub fn with_aliases<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let file = File::open(&path).context(format!(
+            "Failed to open refget alias file: {:?}",
+            path.as_ref()
+        ))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Failed to read refget alias line")?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, id) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow::anyhow!("Refget alias line is not name<TAB>id: {}", line))?;
+            self.aliases.insert(name.to_string(), id.trim().to_string());
+        }
+        Ok(self)
+    }
+
+    pub fn sequence_url(&self, seq_name: &str, start: usize, end: usize) -> String {
+        let id = self
+            .aliases
+            .get(seq_name)
+            .map(String::as_str)
+            .unwrap_or(seq_name);
+        format!(
+            "{}/sequence/{}?start={}&end={}",
+            self.base_url, id, start, end
+        )
+    }
+
+    /// Fetches the 0-based, half-open interval `[start, end)` of a sequence.
+    pub fn fetch(&self, seq_name: &str, start: usize, end: usize) -> Result<String> {
+        let url = self.sequence_url(seq_name, start, end);
+        let parsed = Url::parse(&url).context(format!("Invalid refget URL: {}", url))?;
+        let mut reader = bgzf::Reader::from_url(&parsed)
+            .context(format!("Failed to open refget URL: {}", url))?;
+        let mut sequence = String::new();
+        reader
+            .read_to_string(&mut sequence)
+            .context(format!("Failed to read refget response: {}", url))?;
+        let sequence = sequence.trim().to_string();
+        if sequence.len() != end - start {
+            anyhow::bail!(
+                "Refget server returned {} bases for {}:{}-{}",
+                sequence.len(),
+                seq_name,
+                start,
+                end
+            );
+        }
+        Ok(sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_url_uses_aliases() {
+        let mut client = RefgetClient::new("https://refget.example.org/");
+        client
+            .aliases
+            .insert("chr1".to_string(), "SQ.abc123".to_string());
+        assert_eq!(
+            client.sequence_url("chr1", 10, 20),
+            "https://refget.example.org/sequence/SQ.abc123?start=10&end=20"
+        );
+        assert_eq!(
+            client.sequence_url("chr2", 0, 5),
+            "https://refget.example.org/sequence/chr2?start=0&end=5"
+        );
+    }
+}
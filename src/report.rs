@@ -0,0 +1,359 @@
+use crate::provenance::InputFile;
+use crate::validator::SiteContext;
+use anyhow::Result;
+use std::io::Write;
+use std::str::FromStr;
+
+/// How validation results are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `[pafcheck]`-prefixed log lines.
+    Text,
+    /// One JSON document with all errors and the summary.
+    Json,
+    /// One tab-separated row per error under a `TSV_HEADER` line.
+    Tsv,
+    /// One JSON object per line: each error as it is found, then a summary.
+    Ndjson,
+}
+
+/// How much of each record's findings is logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only the final summary.
+    Quiet,
+    /// The first occurrence of each error type per record.
+    Normal,
+    /// Every error occurrence.
+    Sites,
+    /// Every error occurrence, and each record checked without errors.
+    Records,
+}
+
+/// Column names of the TSV output.
+pub const TSV_HEADER: &str = "line\tquery\ttarget\terror_type\tquery_pos\ttarget_pos\tdetail";
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => anyhow::bail!("Unknown output format: {}", s),
+        }
+    }
+}
+
+/// One reported error: a validation error type found in a record, or a
+/// record that couldn't be checked at all (`error_type` is `None`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorEvent {
+    /// 1-based PAF line, or `None` for errors about whole sequences.
+    pub line: Option<usize>,
+    pub query: Option<String>,
+    pub target: Option<String>,
+    pub error_type: Option<String>,
+    /// Whether the error type is a policy violation (`ErrorType::is_policy`).
+    pub policy: bool,
+    /// Whether it is reported as a warning rather than an error.
+    pub warning: bool,
+    /// Occurrences of the type in the record.
+    pub count: usize,
+    pub query_pos: Option<usize>,
+    pub target_pos: Option<usize>,
+    pub message: String,
+}
+
+impl ErrorEvent {
+    /// The log line used by the text output.
+    pub fn to_text(&self) -> String {
+        let kind = if self.warning {
+            "Warning"
+        } else if self.policy {
+            "Policy violation"
+        } else {
+            "Error"
+        };
+        let location = match self.line {
+            Some(line) => format!("{} at line {}", kind, line),
+            None => kind.to_string(),
+        };
+        match &self.error_type {
+            Some(error_type) => {
+                format!("[pafcheck] {}: {}: {}", location, error_type, self.message)
+            }
+            None => format!("[pafcheck] {}: {}", location, self.message),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let optional_string = |value: &Option<String>| match value {
+            Some(value) => json_string(value),
+            None => "null".to_string(),
+        };
+        let optional_number = |value: Option<usize>| match value {
+            Some(value) => value.to_string(),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"line\":{},\"query\":{},\"target\":{},\"type\":{},\"category\":{},\"severity\":{},\"count\":{},\"query_pos\":{},\"target_pos\":{},\"message\":{}}}",
+            optional_number(self.line),
+            optional_string(&self.query),
+            optional_string(&self.target),
+            optional_string(&self.error_type),
+            json_string(if self.policy { "policy" } else { "correctness" }),
+            json_string(if self.warning { "warning" } else { "error" }),
+            self.count,
+            optional_number(self.query_pos),
+            optional_number(self.target_pos),
+            json_string(&self.message)
+        )
+    }
+
+    /// The event as a `TSV_HEADER` row; missing values are written as `.`.
+    pub fn to_tsv(&self) -> String {
+        let field = |value: Option<String>| value.unwrap_or_else(|| ".".to_string());
+        let detail: String = self
+            .message
+            .chars()
+            .map(|c| {
+                if c == '\t' || c == '\n' || c == '\r' {
+                    ' '
+                } else {
+                    c
+                }
+            })
+            .collect();
+        [
+            field(self.line.map(|line| line.to_string())),
+            field(self.query.clone()),
+            field(self.target.clone()),
+            field(self.error_type.clone()),
+            field(self.query_pos.map(|pos| pos.to_string())),
+            field(self.target_pos.map(|pos| pos.to_string())),
+            detail,
+        ]
+        .join("\t")
+    }
+}
+
+/// ANSI escapes around the bases of an error site in colored output.
+const HIGHLIGHT: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// The query and target lines of an error site's context, padded so that the
+/// site's bases line up and highlighted in color.
+pub fn context_lines(context: &SiteContext) -> [String; 2] {
+    let pad = context.query_offset.max(context.target_offset);
+    let line = |label: &str, bases: &str, offset: usize| {
+        let (before, rest) = bases.split_at(offset.min(bases.len()));
+        let mut chars = rest.chars();
+        let site = chars.next().map(String::from).unwrap_or_default();
+        format!(
+            "{:<6} {:>width$}{}{}{}{}{}",
+            label,
+            "",
+            before,
+            HIGHLIGHT,
+            site,
+            RESET,
+            chars.as_str(),
+            width = pad - offset
+        )
+    };
+    [
+        line("query", &context.query, context.query_offset),
+        line("target", &context.target, context.target_offset),
+    ]
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// What the JSON and NDJSON outputs say about a run besides its errors.
+#[derive(Debug)]
+pub struct ReportSummary<'a> {
+    /// Errors per type name, sorted.
+    pub counts: &'a [(String, usize)],
+    /// Warnings per type name, sorted.
+    pub warning_counts: &'a [(String, usize)],
+    pub inputs: &'a [InputFile],
+    pub records: usize,
+    pub total_errors: usize,
+    /// Whether the run was stopped early by `--max-errors`.
+    pub truncated: bool,
+}
+
+/// Writes the JSON report: every error and warning, the per-type counts of
+/// each, the input files and a summary.
+pub fn write_json_report<W: Write>(
+    output: &mut W,
+    events: &[ErrorEvent],
+    summary: &ReportSummary,
+) -> Result<()> {
+    writeln!(output, "{{")?;
+    writeln!(output, "  \"errors\": [")?;
+    for (i, event) in events.iter().enumerate() {
+        let separator = if i + 1 < events.len() { "," } else { "" };
+        writeln!(output, "    {}{}", event.to_json(), separator)?;
+    }
+    writeln!(output, "  ],")?;
+    writeln!(
+        output,
+        "  \"error_counts\": {},",
+        json_counts(summary.counts)
+    )?;
+    writeln!(
+        output,
+        "  \"warning_counts\": {},",
+        json_counts(summary.warning_counts)
+    )?;
+    writeln!(output, "  \"inputs\": {},", json_inputs(summary.inputs))?;
+    writeln!(output, "  \"summary\": {}", json_summary(summary))?;
+    writeln!(output, "}}")?;
+    Ok(())
+}
+
+/// Writes the last line of the NDJSON output, after the error lines written
+/// by `ErrorEvent::to_json`: the summary, the per-type counts and the input
+/// files.
+pub fn write_ndjson_summary<W: Write>(output: &mut W, summary: &ReportSummary) -> Result<()> {
+    writeln!(
+        output,
+        "{{\"summary\": {}, \"error_counts\": {}, \"warning_counts\": {}, \"inputs\": {}}}",
+        json_summary(summary),
+        json_counts(summary.counts),
+        json_counts(summary.warning_counts),
+        json_inputs(summary.inputs)
+    )?;
+    Ok(())
+}
+
+fn json_inputs(inputs: &[InputFile]) -> String {
+    let inputs: Vec<String> = inputs.iter().map(InputFile::to_json).collect();
+    format!("[{}]", inputs.join(", "))
+}
+
+fn json_counts(counts: &[(String, usize)]) -> String {
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(error_type, count)| format!("{}: {}", json_string(error_type), count))
+        .collect();
+    format!("{{{}}}", counts.join(", "))
+}
+
+fn json_summary(summary: &ReportSummary) -> String {
+    format!(
+        "{{\"records\": {}, \"total_errors\": {}, \"total_warnings\": {}, \"passed\": {}, \"truncated\": {}}}",
+        summary.records,
+        summary.total_errors,
+        summary.warning_counts.iter().map(|(_, count)| count).sum::<usize>(),
+        summary.total_errors == 0,
+        summary.truncated
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_report() {
+        let event = ErrorEvent {
+            line: Some(3),
+            query: Some("q1".to_string()),
+            target: Some("t\"1".to_string()),
+            error_type: Some("Mismatch".to_string()),
+            policy: false,
+            warning: false,
+            count: 2,
+            query_pos: Some(5),
+            target_pos: None,
+            message: "bad\tbase".to_string(),
+        };
+        assert_eq!(
+            event.to_json(),
+            "{\"line\":3,\"query\":\"q1\",\"target\":\"t\\\"1\",\"type\":\"Mismatch\",\"category\":\"correctness\",\"severity\":\"error\",\"count\":2,\"query_pos\":5,\"target_pos\":null,\"message\":\"bad\\tbase\"}"
+        );
+
+        let mut output = Vec::new();
+        let summary = ReportSummary {
+            counts: &[("Mismatch".to_string(), 2)],
+            warning_counts: &[],
+            inputs: &[],
+            records: 10,
+            total_errors: 2,
+            truncated: false,
+        };
+        write_json_report(&mut output, &[event], &summary).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\"error_counts\": {\"Mismatch\": 2},"));
+        assert!(output.contains("\"warning_counts\": {},\n  \"inputs\": [],"));
+        assert!(output.contains("\"passed\": false, \"truncated\": false"));
+
+        let mut output = Vec::new();
+        let summary = ReportSummary {
+            warning_counts: &[("NonNormalizedCigar".to_string(), 1)],
+            inputs: &[InputFile::describe("paf", "-", false).unwrap()],
+            truncated: true,
+            ..summary
+        };
+        write_ndjson_summary(&mut output, &summary).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"summary\": {\"records\": 10, \"total_errors\": 2, \"total_warnings\": 1, \"passed\": false, \"truncated\": true}, \"error_counts\": {\"Mismatch\": 2}, \"warning_counts\": {\"NonNormalizedCigar\": 1}, \"inputs\": [{\"role\": \"paf\", \"path\": \"-\", \"size\": 0, \"mtime\": 0, \"checksum\": null}]}\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_row() {
+        let event = ErrorEvent {
+            line: None,
+            query: Some("q1".to_string()),
+            target: None,
+            error_type: Some("LowQueryCoverage".to_string()),
+            policy: true,
+            warning: false,
+            count: 1,
+            query_pos: None,
+            target_pos: None,
+            message: "q1 has\t0 bases".to_string(),
+        };
+        assert_eq!(
+            event.to_tsv(),
+            ".\tq1\t.\tLowQueryCoverage\t.\t.\tq1 has 0 bases"
+        );
+        assert_eq!(TSV_HEADER.split('\t').count(), 7);
+    }
+
+    #[test]
+    fn test_context_lines() {
+        let context = SiteContext::around(b"ACGTA", 1, b"TTACTTA", 3, 2);
+        assert_eq!(
+            context_lines(&context),
+            [
+                "query   A\x1b[1;31mC\x1b[0mGT".to_string(),
+                "target TA\x1b[1;31mC\x1b[0mTT".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use crate::validator::reverse_complement;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// A segment of an rGFA graph, with its stable-sequence coordinates.
+#[derive(Debug)]
+pub struct Segment {
+    pub sequence: String,
+    pub stable_name: Option<String>,
+    pub stable_offset: usize,
+}
+
+/// Segments that share a stable name, sorted by stable offset.
+#[derive(Debug, Default)]
+struct StableSequence {
+    // (stable offset, segment id)
+    segments: Vec<(usize, String)>,
+    // stable end of each segment
+    ends: Vec<usize>,
+    // index of the first segment of the gap-free run each segment belongs to
+    runs: Vec<usize>,
+}
+
+impl StableSequence {
+    fn finish(&mut self, segments: &HashMap<String, Segment>) {
+        self.segments.sort();
+        self.ends = self
+            .segments
+            .iter()
+            .map(|(offset, id)| offset + segments[id].sequence.len())
+            .collect();
+        self.runs = Vec::with_capacity(self.segments.len());
+        for i in 0..self.segments.len() {
+            let run = match i {
+                0 => 0,
+                _ if self.segments[i].0 > self.ends[i - 1] => i,
+                _ => self.runs[i - 1],
+            };
+            self.runs.push(run);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    /// Index of the first segment ending after `position`.
+    fn first_after(&self, position: usize) -> usize {
+        self.ends.partition_point(|&end| end <= position)
+    }
+
+    /// Whether `start..end` is non-empty and covered without gaps.
+    fn covers(&self, start: usize, end: usize) -> bool {
+        let first = self.first_after(start);
+        let last = self.segments.partition_point(|(offset, _)| *offset < end);
+        start < end
+            && first < last
+            && self.segments[first].0 <= start
+            && self.ends[last - 1] >= end
+            && self.runs[first] == self.runs[last - 1]
+    }
+}
+
+/// What one step of a target name refers to.
+enum Piece<'a> {
+    Segment(&'a str),
+    Stable(&'a StableSequence, usize, usize),
+}
+
+impl Piece<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Piece::Segment(sequence) => sequence.len(),
+            Piece::Stable(_, start, end) => end - start,
+        }
+    }
+}
+
+/// Sequences of an rGFA graph, addressable the way minigraph writes target names:
+/// by segment id (`s1`), oriented segment path (`>s1>s2<s3`), or stable-sequence
+/// interval (`chr1:1000-2000`, or a path of such intervals).
+#[derive(Debug, Default)]
+pub struct RgfaGraph {
+    segments: HashMap<String, Segment>,
+    stable_index: HashMap<String, StableSequence>,
+}
+
+impl RgfaGraph {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).context(format!("Failed to open rGFA file: {:?}", path.as_ref()))?;
+        let mut graph = RgfaGraph::default();
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Failed to read rGFA line")?;
+            graph.add_line(&line)?;
+        }
+        graph.finish();
+        Ok(graph)
+    }
+
+    pub fn from_string(content: &str) -> Result<Self> {
+        let mut graph = RgfaGraph::default();
+        for line in content.lines() {
+            graph.add_line(line)?;
+        }
+        graph.finish();
+        Ok(graph)
+    }
+
+    fn add_line(&mut self, line: &str) -> Result<()> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields[0] != "S" {
+            return Ok(());
+        }
+        if fields.len() < 3 {
+            anyhow::bail!("rGFA segment line does not have enough fields");
+        }
+        let mut stable_name = None;
+        let mut stable_offset = 0;
+        for field in &fields[3..] {
+            if let Some(name) = field.strip_prefix("SN:Z:") {
+                stable_name = Some(name.to_string());
+            } else if let Some(offset) = field.strip_prefix("SO:i:") {
+                stable_offset = offset.parse().context("Failed to parse SO:i tag")?;
+            }
+        }
+        self.segments.insert(
+            fields[1].to_string(),
+            Segment {
+                sequence: fields[2].to_string(),
+                stable_name,
+                stable_offset,
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        self.stable_index.clear();
+        for (id, segment) in &self.segments {
+            if let Some(name) = &segment.stable_name {
+                self.stable_index
+                    .entry(name.clone())
+                    .or_default()
+                    .segments
+                    .push((segment.stable_offset, id.clone()));
+            }
+        }
+        for stable in self.stable_index.values_mut() {
+            stable.finish(&self.segments);
+        }
+    }
+
+    /// Resolves a PAF target name to its full sequence, or `None` if the name
+    /// does not refer to this graph.
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        self.fetch(name, 0, self.length(name)?)
+    }
+
+    /// Length of the sequence a PAF target name refers to, or `None` if the
+    /// name does not refer to this graph.
+    pub fn length(&self, name: &str) -> Option<usize> {
+        Some(self.steps(name)?.iter().map(|(_, piece)| piece.len()).sum())
+    }
+
+    /// The interval `start..end` of the sequence a PAF target name refers to,
+    /// built from the segments it overlaps only. `None` if the name does not
+    /// refer to this graph or the interval is out of range.
+    pub fn fetch(&self, name: &str, start: usize, end: usize) -> Option<String> {
+        if start > end {
+            return None;
+        }
+        let mut sequence = String::with_capacity(end - start);
+        let mut step_start = 0;
+        for (reverse, piece) in self.steps(name)? {
+            let step_end = step_start + piece.len();
+            if step_start < end && step_end > start {
+                let from = start.max(step_start) - step_start;
+                let to = end.min(step_end) - step_start;
+                if reverse {
+                    let forward = self.piece_sequence(&piece, piece.len() - to, piece.len() - from);
+                    sequence.push_str(&reverse_complement(&forward));
+                } else {
+                    sequence.push_str(&self.piece_sequence(&piece, from, to));
+                }
+            }
+            step_start = step_end;
+        }
+        (end <= step_start).then_some(sequence)
+    }
+
+    /// The steps of a target name, each with whether it is reverse-complemented.
+    fn steps<'a>(&'a self, name: &str) -> Option<Vec<(bool, Piece<'a>)>> {
+        if name.starts_with('>') || name.starts_with('<') {
+            let mut steps = Vec::new();
+            let mut rest = name;
+            while !rest.is_empty() {
+                let orient = rest.chars().next()?;
+                rest = &rest[1..];
+                let end = rest.find(['>', '<']).unwrap_or(rest.len());
+                steps.push((orient == '<', self.piece(&rest[..end])?));
+                rest = &rest[end..];
+            }
+            Some(steps)
+        } else {
+            Some(vec![(false, self.piece(name)?)])
+        }
+    }
+
+    fn piece(&self, step: &str) -> Option<Piece<'_>> {
+        if let Some(segment) = self.segments.get(step) {
+            return Some(Piece::Segment(&segment.sequence));
+        }
+        let (stable_name, range) = match step.rsplit_once(':') {
+            Some((stable_name, range)) => (stable_name, Some(range)),
+            None => (step, None),
+        };
+        let stable = self.stable_index.get(stable_name)?;
+        let (start, end) = match range {
+            Some(range) => {
+                let (start, end) = range.split_once('-')?;
+                (start.parse().ok()?, end.parse().ok()?)
+            }
+            None => (0, stable.len()),
+        };
+        stable
+            .covers(start, end)
+            .then_some(Piece::Stable(stable, start, end))
+    }
+
+    /// Forward sequence of `from..to` within a piece.
+    fn piece_sequence(&self, piece: &Piece, from: usize, to: usize) -> String {
+        match piece {
+            Piece::Segment(sequence) => sequence[from..to].to_string(),
+            Piece::Stable(stable, start, _) => {
+                let (from, to) = (start + from, start + to);
+                let mut sequence = String::with_capacity(to - from);
+                for i in stable.first_after(from)..stable.segments.len() {
+                    let (offset, id) = &stable.segments[i];
+                    if *offset >= to {
+                        break;
+                    }
+                    let segment = &self.segments[id].sequence;
+                    sequence.push_str(
+                        &segment[from.max(*offset) - offset..to.min(stable.ends[i]) - offset],
+                    );
+                }
+                sequence
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = "S\ts1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\n\
+                         S\ts2\tGGCC\tSN:Z:chr1\tSO:i:4\tSR:i:0\n\
+                         S\ts3\tTTA\tSN:Z:alt1\tSO:i:0\tSR:i:1\n\
+                         L\ts1\t+\ts2\t+\t0M\n";
+
+    #[test]
+    fn test_resolve_segments_and_paths() {
+        let graph = RgfaGraph::from_string(GRAPH).unwrap();
+        assert_eq!(graph.resolve("s1").unwrap(), "ACGT");
+        assert_eq!(graph.resolve(">s1>s2").unwrap(), "ACGTGGCC");
+        assert_eq!(graph.resolve(">s1<s3").unwrap(), "ACGTTAA");
+        assert!(graph.resolve("s4").is_none());
+    }
+
+    #[test]
+    fn test_resolve_stable_coordinates() {
+        let graph = RgfaGraph::from_string(GRAPH).unwrap();
+        assert_eq!(graph.resolve("chr1").unwrap(), "ACGTGGCC");
+        assert_eq!(graph.resolve("chr1:2-6").unwrap(), "GTGG");
+        assert_eq!(graph.resolve(">chr1:0-4>alt1:0-3").unwrap(), "ACGTTTA");
+        assert!(graph.resolve("chr1:2-20").is_none());
+    }
+
+    #[test]
+    fn test_fetch_intervals() {
+        let graph = RgfaGraph::from_string(GRAPH).unwrap();
+        assert_eq!(graph.length(">s1<s3"), Some(7));
+        assert_eq!(graph.fetch("chr1", 3, 5).unwrap(), "TG");
+        assert_eq!(graph.fetch("chr1:2-8", 1, 4).unwrap(), "TGG");
+        assert_eq!(graph.fetch(">s1<s3", 3, 6).unwrap(), "TTA");
+        assert_eq!(graph.fetch(">s2<chr1:0-3", 2, 6).unwrap(), "CCCG");
+        assert!(graph.fetch("chr1", 4, 9).is_none());
+        // A reversed interval, e.g. of a record without coordinate checks.
+        assert!(graph.fetch("s1", 3, 1).is_none());
+        assert!(graph.fetch(">s1<s3", 5, 2).is_none());
+        assert!(graph.length("s4").is_none());
+
+        let gapped = RgfaGraph::from_string(
+            "S\ts1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\n\
+             S\ts2\tGGCC\tSN:Z:chr1\tSO:i:6\tSR:i:0\n",
+        )
+        .unwrap();
+        assert!(gapped.resolve("chr1").is_none());
+        assert!(gapped.resolve("chr1:2-8").is_none());
+        assert_eq!(gapped.resolve("chr1:6-9").unwrap(), "GGC");
+    }
+}
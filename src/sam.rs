@@ -0,0 +1,170 @@
+use crate::cigar_parser::{Cigar, CigarOp};
+use crate::paf_parser::PafRecord;
+use crate::validator::sequence_stats;
+use anyhow::Result;
+use std::io::Write;
+
+/// The `MD:Z` tag of the aligned (uppercased) sequences under `ops`: runs of
+/// identical bases, the target base of each differing column, and `^` with
+/// the deleted target bases. Adjacent mismatches are separated by a `0`, as
+/// samtools writes them.
+pub fn md_tag(ops: &[CigarOp], query_seq: &[u8], target_seq: &[u8]) -> Result<String> {
+    let mut md = String::new();
+    let mut run = 0;
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops {
+        let (q_len, t_len) = op.consumed();
+        if q_idx + q_len > query_seq.len() || t_idx + t_len > target_seq.len() {
+            anyhow::bail!("CIGAR extends past the aligned sequences");
+        }
+        match op {
+            CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_) => {
+                for i in 0..t_len {
+                    let t = target_seq[t_idx + i];
+                    if query_seq[q_idx + i] == t {
+                        run += 1;
+                    } else {
+                        md.push_str(&run.to_string());
+                        md.push(t as char);
+                        run = 0;
+                    }
+                }
+            }
+            CigarOp::Deletion(_) => {
+                md.push_str(&run.to_string());
+                md.push('^');
+                md.push_str(&String::from_utf8_lossy(&target_seq[t_idx..t_idx + t_len]));
+                run = 0;
+            }
+            _ => {}
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    md.push_str(&run.to_string());
+    Ok(md)
+}
+
+/// A SAM line for a record whose alignment is `ops` over its aligned
+/// (uppercased) sequences. `query` is the whole query sequence on its
+/// forward strand; SEQ is its reverse complement for `-` strand records,
+/// whose CIGAR already runs along the target, and the unaligned ends of the
+/// query are soft-clipped (replacing any clips of the PAF CIGAR). `NM:i` and
+/// `MD:Z` are recomputed from the sequences.
+pub fn sam_record(
+    record: &PafRecord,
+    ops: &[CigarOp],
+    query: &[u8],
+    query_seq: &[u8],
+    target_seq: &[u8],
+) -> Result<String> {
+    let (mut leading, mut trailing) = (
+        record.query_start,
+        record.query_length.saturating_sub(record.query_end),
+    );
+    let seq = if record.strand == '-' {
+        std::mem::swap(&mut leading, &mut trailing);
+        reverse_complement(query)
+    } else {
+        query.to_vec()
+    };
+    let mut cigar = Vec::with_capacity(ops.len() + 2);
+    cigar.push(CigarOp::SoftClip(leading as u64));
+    cigar.extend(
+        ops.iter()
+            .filter(|op| !op.is_clip())
+            .map(|op| op.with_count(op.count())),
+    );
+    cigar.push(CigarOp::SoftClip(trailing as u64));
+
+    let mut flag = 0;
+    if record.strand == '-' {
+        flag |= 0x10;
+    }
+    if record.tag("tp:A") == Some("S") {
+        flag |= 0x100;
+    }
+    let edit_distance = sequence_stats(ops, query_seq, target_seq)?.edit_distance();
+    Ok(format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t*\t0\t0\t{}\t*\tNM:i:{}\tMD:Z:{}",
+        record.query_name,
+        flag,
+        record.target_name,
+        record.target_start + 1,
+        record.mapq.unwrap_or(255),
+        // Drops the zero-length clips.
+        Cigar::from(&cigar[..]).normalize(),
+        String::from_utf8_lossy(&seq),
+        edit_distance,
+        md_tag(ops, query_seq, target_seq)?
+    ))
+}
+
+fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|&base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            b'a' => b't',
+            b'c' => b'g',
+            b'g' => b'c',
+            b't' => b'a',
+            other => other,
+        })
+        .collect()
+}
+
+/// Writes the header of a SAM file of alignments against the `contigs`
+/// (name, length) of the target FASTA.
+pub fn write_sam_header<W: Write>(output: &mut W, contigs: &[(String, usize)]) -> Result<()> {
+    writeln!(output, "@HD\tVN:1.6\tSO:unsorted")?;
+    for (name, length) in contigs {
+        writeln!(output, "@SQ\tSN:{}\tLN:{}", name, length)?;
+    }
+    writeln!(
+        output,
+        "@PG\tID:pafcheck\tPN:pafcheck\tVN:{}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cigar_parser::parse_cigar;
+
+    #[test]
+    fn test_md_tag() {
+        // Adjacent mismatches, an insertion and a deletion.
+        let ops = parse_cigar("6M1I2M2D3M").unwrap();
+        assert_eq!(
+            md_tag(&ops, b"ACGTTAGCCGGT", b"ACGAGACCTTGGT").unwrap(),
+            "3A0G3^TT3"
+        );
+        assert_eq!(
+            md_tag(&parse_cigar("2X").unwrap(), b"AA", b"CG").unwrap(),
+            "0C0G0"
+        );
+    }
+
+    #[test]
+    fn test_sam_record() {
+        // The query is aligned from 2 to 8 of 10 bases on the - strand.
+        let record =
+            PafRecord::from_line("q\t10\t2\t8\t-\tt\t100\t20\t26\t5\t6\t60\ttp:A:S\tcg:Z:3=1X2=")
+                .unwrap();
+        let query = b"AACCGGTTAC";
+        // Reverse complement of query[2..8].
+        let query_seq = b"AACCGG";
+        let target_seq = b"AACGGG";
+        let ops = parse_cigar("3=1X2=").unwrap();
+        assert_eq!(
+            sam_record(&record, &ops, query, query_seq, target_seq).unwrap(),
+            "q\t272\tt\t21\t60\t2S3=1X2=2S\t*\t0\t0\tGTAACCGGTT\t*\tNM:i:1\tMD:Z:3G2"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+/// Small seeded pseudo-random generator (SplitMix64), so sampled runs are
+/// reproducible without pulling in an RNG dependency.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Estimated error rate from a sample of bases.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateEstimate {
+    pub sampled: usize,
+    pub errors: usize,
+    pub rate: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+impl RateEstimate {
+    /// Point estimate with a 95% Wilson score interval.
+    pub fn from_counts(errors: usize, sampled: usize) -> Self {
+        let (ci_low, ci_high) = wilson_interval(errors, sampled, 1.96);
+        RateEstimate {
+            sampled,
+            errors,
+            rate: if sampled == 0 {
+                0.0
+            } else {
+                errors as f64 / sampled as f64
+            },
+            ci_low,
+            ci_high,
+        }
+    }
+}
+
+fn wilson_interval(successes: usize, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let denom = 1.0 + z * z / n;
+    let centre = (p + z * z / (2.0 * n)) / denom;
+    let half = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denom;
+    ((centre - half).max(0.0), (centre + half).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_seeded() {
+        let mut a = SplitMix64::new(7);
+        let mut b = SplitMix64::new(7);
+        for _ in 0..10 {
+            let x = a.next_f64();
+            assert_eq!(x, b.next_f64());
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_rate_estimate_interval() {
+        let estimate = RateEstimate::from_counts(5, 100);
+        assert_eq!(estimate.rate, 0.05);
+        assert!(estimate.ci_low < 0.05 && estimate.ci_high > 0.05);
+        assert_eq!(RateEstimate::from_counts(0, 0).ci_high, 1.0);
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Records and errors of one sequence.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SequenceErrors {
+    pub records: usize,
+    pub errors: usize,
+    /// Error counts per type name.
+    pub by_type: HashMap<String, usize>,
+}
+
+/// Error breakdown per query and per target name, to tell errors spread
+/// genome-wide from errors concentrated in a few (misassembled) sequences.
+#[derive(Debug, Default)]
+pub struct SequenceErrorSummary {
+    queries: HashMap<String, SequenceErrors>,
+    targets: HashMap<String, SequenceErrors>,
+}
+
+impl SequenceErrorSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a checked record of the query and target.
+    pub fn add_record(&mut self, query_name: &str, target_name: &str) {
+        entry(&mut self.queries, query_name).records += 1;
+        entry(&mut self.targets, target_name).records += 1;
+    }
+
+    /// Counts `count` errors of `error_type` for the query and/or target.
+    pub fn add_errors(
+        &mut self,
+        query_name: Option<&str>,
+        target_name: Option<&str>,
+        error_type: &str,
+        count: usize,
+    ) {
+        for (sequences, name) in [
+            (&mut self.queries, query_name),
+            (&mut self.targets, target_name),
+        ] {
+            if let Some(name) = name {
+                let sequence = entry(sequences, name);
+                sequence.errors += count;
+                *sequence.by_type.entry(error_type.to_string()).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Sequences with errors, most errors first, for `role` "query" or "target".
+    pub fn ranked(&self, role: &str) -> Vec<(&str, &SequenceErrors)> {
+        let sequences = match role {
+            "query" => &self.queries,
+            _ => &self.targets,
+        };
+        let mut ranked: Vec<(&str, &SequenceErrors)> = sequences
+            .iter()
+            .filter(|(_, sequence)| sequence.errors > 0)
+            .map(|(name, sequence)| (name.as_str(), sequence))
+            .collect();
+        ranked.sort_by(|a, b| b.1.errors.cmp(&a.1.errors).then_with(|| a.0.cmp(b.0)));
+        ranked
+    }
+
+    /// Writes one row per sequence and role, sorted by role and name, with
+    /// the error counts of each type as `Type:count` pairs.
+    pub fn write_tsv<W: Write>(&self, output: &mut W) -> Result<()> {
+        writeln!(output, "#sequence\trole\trecords\terrors\terror_types")?;
+        for (role, sequences) in [("query", &self.queries), ("target", &self.targets)] {
+            let mut names: Vec<&String> = sequences.keys().collect();
+            names.sort();
+            for name in names {
+                let sequence = &sequences[name];
+                let mut types: Vec<String> = sequence
+                    .by_type
+                    .iter()
+                    .map(|(error_type, count)| format!("{}:{}", error_type, count))
+                    .collect();
+                types.sort();
+                writeln!(
+                    output,
+                    "{}\t{}\t{}\t{}\t{}",
+                    name,
+                    role,
+                    sequence.records,
+                    sequence.errors,
+                    if types.is_empty() {
+                        ".".to_string()
+                    } else {
+                        types.join(",")
+                    }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn entry<'a>(
+    sequences: &'a mut HashMap<String, SequenceErrors>,
+    name: &str,
+) -> &'a mut SequenceErrors {
+    if !sequences.contains_key(name) {
+        sequences.insert(name.to_string(), SequenceErrors::default());
+    }
+    sequences.get_mut(name).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_summary() {
+        let mut summary = SequenceErrorSummary::new();
+        summary.add_record("ctg1", "chr1");
+        summary.add_record("ctg2", "chr1");
+        summary.add_record("ctg2", "chr2");
+        summary.add_errors(Some("ctg2"), Some("chr1"), "Mismatch", 5);
+        summary.add_errors(Some("ctg2"), Some("chr2"), "LengthMismatch", 1);
+        summary.add_errors(Some("ctg1"), None, "LowQueryCoverage", 1);
+
+        let queries = summary.ranked("query");
+        assert_eq!(queries[0].0, "ctg2");
+        assert_eq!(queries[0].1.records, 2);
+        assert_eq!(queries[0].1.errors, 6);
+        assert_eq!(queries[1].0, "ctg1");
+        let targets: Vec<&str> = summary.ranked("target").iter().map(|(n, _)| *n).collect();
+        assert_eq!(targets, ["chr1", "chr2"]);
+
+        let mut output = Vec::new();
+        summary.write_tsv(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("ctg2\tquery\t2\t6\tLengthMismatch:1,Mismatch:5\n"));
+        assert!(output.contains("chr2\ttarget\t1\t1\tLengthMismatch:1\n"));
+    }
+}
@@ -0,0 +1,943 @@
+use crate::cigar_parser::CigarOp;
+use crate::coverage::CoverageTracker;
+use crate::fasta_reader::MultiFastaReader;
+use crate::paf_parser::PafRecord;
+use crate::report::{json_string, OutputFormat};
+use crate::validator::{alignment_ops, fetch_aligned_sequences, sequence_stats, AlignmentStats};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+/// Lower bounds of the block identity histogram bins.
+const IDENTITY_BINS: [f64; 6] = [0.0, 0.9, 0.95, 0.98, 0.99, 0.999];
+
+/// Rows and columns of the substitution matrix; other bases count as `N`.
+const BASES: [&str; 5] = ["A", "C", "G", "T", "N"];
+
+/// Lower bounds of the indel length histogram bins.
+const INDEL_BINS: [usize; 12] = [1, 2, 3, 4, 5, 6, 11, 21, 51, 101, 1001, 10001];
+
+/// Labels of `INDEL_BINS`.
+const INDEL_BIN_LABELS: [&str; 12] = [
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6-10",
+    "11-20",
+    "21-50",
+    "51-100",
+    "101-1000",
+    "1001-10000",
+    ">10000",
+];
+
+/// Insertion and deletion counts per `INDEL_BINS` bin.
+type IndelHistogram = [[usize; 12]; 2];
+
+/// Minimum block lengths of the cumulative coverage curve.
+const COVERAGE_THRESHOLDS: [usize; 8] =
+    [0, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000];
+
+/// Bins per unit of block identity in its `Histogram`.
+const IDENTITY_RESOLUTION: f64 = 100_000.0;
+
+/// Sequences listed per table in the text output; JSON and TSV list all.
+const TOP_SEQUENCES: usize = 10;
+
+/// Column name of sections with one value per row.
+const VALUE: &[&str] = &["value"];
+
+/// One table of the statistics: named rows of values in output order. Values
+/// are already formatted as JSON numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSection {
+    /// Key in the JSON and TSV output.
+    pub name: &'static str,
+    /// Heading in the text output.
+    pub title: &'static str,
+    /// Names of the values of each row; `VALUE` for one unnamed value.
+    pub columns: &'static [&'static str],
+    pub rows: Vec<(String, Vec<String>)>,
+    /// Whether the text output lists only the first `TOP_SEQUENCES` rows.
+    pub truncate: bool,
+}
+
+impl StatsSection {
+    /// A section with one value per row.
+    fn values(
+        name: &'static str,
+        title: &'static str,
+        rows: Vec<(String, String)>,
+        truncate: bool,
+    ) -> Self {
+        StatsSection {
+            name,
+            title,
+            columns: VALUE,
+            rows: rows
+                .into_iter()
+                .map(|(row, value)| (row, vec![value]))
+                .collect(),
+            truncate,
+        }
+    }
+
+    fn is_single_valued(&self) -> bool {
+        self.columns == VALUE
+    }
+}
+
+/// Counts and sums of values by bin, for medians and N50s in memory bounded
+/// by the number of bins rather than of values. The values of a bin are
+/// represented by their mean, so statistics are exact as long as each bin
+/// holds one distinct value.
+#[derive(Debug, Default)]
+struct Histogram {
+    bins: BTreeMap<u64, (usize, f64)>,
+    count: usize,
+    sum: f64,
+    /// The smallest and largest value, exactly.
+    range: Option<(f64, f64)>,
+}
+
+impl Histogram {
+    /// Adds `value` to `bin`; bins must be ordered like their values.
+    fn add(&mut self, bin: u64, value: f64) {
+        let (count, sum) = self.bins.entry(bin).or_insert((0, 0.0));
+        *count += 1;
+        *sum += value;
+        self.count += 1;
+        self.sum += value;
+        self.range = Some(match self.range {
+            Some((min, max)) => (min.min(value), max.max(value)),
+            None => (value, value),
+        });
+    }
+
+    /// 0 if there are no values, like the other statistics.
+    fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// The value of 0-based `rank` in ascending order.
+    fn value_at(&self, rank: usize) -> f64 {
+        let mut seen = 0;
+        for &(count, sum) in self.bins.values() {
+            seen += count;
+            if rank < seen {
+                return sum / count as f64;
+            }
+        }
+        0.0
+    }
+
+    fn median(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            n if n % 2 == 1 => self.value_at(n / 2),
+            n => (self.value_at(n / 2 - 1) + self.value_at(n / 2)) / 2.0,
+        }
+    }
+
+    /// Value such that values at least this large hold `fraction` of the sum
+    /// (the N50 for 0.5), 0 if there are none.
+    fn n_x(&self, fraction: f64) -> f64 {
+        let target = fraction * self.sum;
+        let mut cumulative = 0.0;
+        for &(count, sum) in self.bins.values().rev() {
+            cumulative += sum;
+            if cumulative >= target {
+                return sum / count as f64;
+            }
+        }
+        0.0
+    }
+}
+
+/// `Histogram` bin of a block length: exact below 1024, and 128 bins per
+/// doubling above, so that a bin's lengths are within 1% of each other.
+fn length_bin(length: usize) -> u64 {
+    let length = length as u64;
+    if length < 1024 {
+        return length;
+    }
+    let bits = u64::from(63 - length.leading_zeros());
+    1024 + (bits - 10) * 128 + ((length >> (bits - 7)) - 128)
+}
+
+/// Per-file aggregates of the alignments of a PAF, computed from the
+/// sequences rather than from the aligner's tags.
+#[derive(Debug, Default)]
+pub struct AlignmentSummary {
+    records: usize,
+    /// Records without an alignment, or whose alignment doesn't fit their
+    /// sequences.
+    skipped: usize,
+    totals: AlignmentStats,
+    block_identities: Histogram,
+    /// Records per `IDENTITY_BINS` bin.
+    identity_bins: [usize; 6],
+    block_lengths: Histogram,
+    queries: HashMap<String, usize>,
+    targets: HashMap<String, usize>,
+    target_coverage: CoverageTracker,
+    /// Per target, its bases under alignments (counted once per alignment)
+    /// and the queries aligned to it.
+    target_depth: HashMap<String, (usize, HashSet<String>)>,
+    /// Aligned columns by query base (rows) and target base, in `BASES` order.
+    substitutions: [[usize; 5]; 5],
+    indel_lengths: IndelHistogram,
+    query_lengths: HashMap<String, usize>,
+    /// Per query, its bases under alignments (counted once per alignment).
+    query_spans: HashMap<String, usize>,
+    /// Query and target coverage by the alignments with at least each of
+    /// `COVERAGE_THRESHOLDS` block length.
+    coverage_by_length: [(CoverageTracker, CoverageTracker); 8],
+    /// Indel length histograms per query, if asked for.
+    query_indel_lengths: Option<HashMap<String, IndelHistogram>>,
+}
+
+impl AlignmentSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also breaks the indel length histograms down per query.
+    pub fn with_query_indels(mut self) -> Self {
+        self.query_indel_lengths = Some(HashMap::new());
+        self
+    }
+
+    /// Lists a target in the coverage table even if nothing aligns to it.
+    pub fn add_target(&mut self, name: &str, length: usize) {
+        self.target_coverage.add(name, length, 0, 0);
+    }
+
+    /// Counts a query in the cumulative coverage even if nothing of it aligns.
+    pub fn add_query(&mut self, name: &str, length: usize) {
+        self.query_lengths.entry(name.to_string()).or_insert(length);
+    }
+
+    /// Adds a record, with its column counts and substitutions computed from
+    /// its aligned sequences.
+    pub fn add_record(
+        &mut self,
+        record: &PafRecord,
+        fasta_reader: &mut MultiFastaReader,
+    ) -> Result<()> {
+        let (query_seq, target_seq) = fetch_aligned_sequences(record, fasta_reader)?;
+        let ops = alignment_ops(record)?;
+        let stats = sequence_stats(&ops, &query_seq, &target_seq)?;
+        self.add_substitutions(&ops, &query_seq, &target_seq);
+        self.add_indels(&record.query_name, &ops);
+        self.add(record, &stats);
+        Ok(())
+    }
+
+    /// Tallies the query and target base of each `=`/`X`/`M` column.
+    fn add_substitutions(&mut self, ops: &[CigarOp], query_seq: &[u8], target_seq: &[u8]) {
+        let (mut q_idx, mut t_idx) = (0, 0);
+        for op in ops {
+            let (query_len, target_len) = op.consumed();
+            if matches!(
+                op,
+                CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_)
+            ) {
+                let query_bases = query_seq.iter().skip(q_idx).take(query_len);
+                let target_bases = target_seq.iter().skip(t_idx).take(target_len);
+                for (&q, &t) in query_bases.zip(target_bases) {
+                    self.substitutions[base_index(q)][base_index(t)] += 1;
+                }
+            }
+            q_idx += query_len;
+            t_idx += target_len;
+        }
+    }
+
+    /// Tallies the lengths of the insertions and deletions (not introns).
+    fn add_indels(&mut self, query_name: &str, ops: &[CigarOp]) {
+        let mut query_histogram = self
+            .query_indel_lengths
+            .as_mut()
+            .map(|queries| queries.entry(query_name.to_string()).or_default());
+        for op in ops {
+            let kind = match op {
+                CigarOp::Insertion(_) => 0,
+                CigarOp::Deletion(_) => 1,
+                _ => continue,
+            };
+            let length = op.count() as usize;
+            let bin = INDEL_BINS
+                .iter()
+                .rposition(|&low| length >= low)
+                .unwrap_or(0);
+            self.indel_lengths[kind][bin] += 1;
+            if let Some(histogram) = query_histogram.as_mut() {
+                histogram[kind][bin] += 1;
+            }
+        }
+    }
+
+    /// Adds a record with the column counts of its alignment.
+    pub fn add(&mut self, record: &PafRecord, stats: &AlignmentStats) {
+        self.records += 1;
+        self.totals.identical += stats.identical;
+        self.totals.aligned += stats.aligned;
+        self.totals.insertions += stats.insertions;
+        self.totals.inserted_bases += stats.inserted_bases;
+        self.totals.deletions += stats.deletions;
+        self.totals.deleted_bases += stats.deleted_bases;
+        let identity = stats.blast_identity();
+        self.block_identities
+            .add((identity * IDENTITY_RESOLUTION) as u64, identity);
+        let bin = IDENTITY_BINS
+            .iter()
+            .rposition(|&low| identity >= low)
+            .unwrap_or(0);
+        self.identity_bins[bin] += 1;
+        let block_length = stats.block_length();
+        self.block_lengths
+            .add(length_bin(block_length), block_length as f64);
+        *self.queries.entry(record.query_name.clone()).or_insert(0) += 1;
+        *self.targets.entry(record.target_name.clone()).or_insert(0) += 1;
+        self.target_coverage.add(
+            &record.target_name,
+            record.target_length,
+            record.target_start,
+            record.target_end,
+        );
+        let (depth, queries) = self
+            .target_depth
+            .entry(record.target_name.clone())
+            .or_default();
+        *depth += record.target_end.saturating_sub(record.target_start);
+        queries.insert(record.query_name.clone());
+        self.add_query(&record.query_name, record.query_length);
+        *self
+            .query_spans
+            .entry(record.query_name.clone())
+            .or_insert(0) += record.query_end.saturating_sub(record.query_start);
+        for (threshold, (queries, targets)) in
+            COVERAGE_THRESHOLDS.iter().zip(&mut self.coverage_by_length)
+        {
+            if block_length < *threshold {
+                break;
+            }
+            queries.add(&record.query_name, 0, record.query_start, record.query_end);
+            targets.add(
+                &record.target_name,
+                0,
+                record.target_start,
+                record.target_end,
+            );
+        }
+    }
+
+    /// Counts a record whose alignment couldn't be computed.
+    pub fn skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    pub fn sections(&self) -> Vec<StatsSection> {
+        let lengths = &self.block_lengths;
+        let summary = vec![
+            ("records", self.records.to_string()),
+            ("skipped", self.skipped.to_string()),
+            ("aligned_bases", self.totals.aligned.to_string()),
+            ("identical_bases", self.totals.identical.to_string()),
+            ("inserted_bases", self.totals.inserted_bases.to_string()),
+            ("deleted_bases", self.totals.deleted_bases.to_string()),
+            (
+                "gap_compressed_identity",
+                format_float(self.totals.gap_compressed_identity()),
+            ),
+            ("blast_identity", format_float(self.totals.blast_identity())),
+            ("mean_block_length", format_float(lengths.mean())),
+            ("median_block_length", format_float(lengths.median())),
+            ("block_n50", (lengths.n_x(0.5).round() as usize).to_string()),
+            ("block_n90", (lengths.n_x(0.9).round() as usize).to_string()),
+            ("queries", self.queries.len().to_string()),
+            ("targets", self.targets.len().to_string()),
+        ];
+
+        let identities = &self.block_identities;
+        let (min, max) = identities.range.unwrap_or((0.0, 0.0));
+        let mut distribution = vec![
+            ("min".to_string(), format_float(min)),
+            ("median".to_string(), format_float(identities.median())),
+            ("max".to_string(), format_float(max)),
+        ];
+        for (i, (low, count)) in IDENTITY_BINS.iter().zip(self.identity_bins).enumerate() {
+            let label = match IDENTITY_BINS.get(i + 1) {
+                Some(high) => format!("[{}, {})", low, high),
+                None => format!("[{}, 1]", low),
+            };
+            distribution.push((label, count.to_string()));
+        }
+
+        let mut sections = vec![
+            StatsSection::values(
+                "summary",
+                "Summary",
+                summary
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value))
+                    .collect(),
+                false,
+            ),
+            StatsSection::values(
+                "block_identity",
+                "Block identity distribution",
+                distribution,
+                false,
+            ),
+            StatsSection::values(
+                "alignments_per_query",
+                "Alignments per query",
+                ranked_counts(&self.queries),
+                true,
+            ),
+            StatsSection::values(
+                "alignments_per_target",
+                "Alignments per target",
+                ranked_counts(&self.targets),
+                true,
+            ),
+            StatsSection::values(
+                "span_per_query",
+                "Aligned span per query",
+                ranked_counts(&self.query_spans),
+                true,
+            ),
+            StatsSection::values(
+                "span_per_target",
+                "Aligned span per target",
+                ranked_counts(
+                    &self
+                        .target_depth
+                        .iter()
+                        .map(|(name, (span, _))| (name.clone(), *span))
+                        .collect(),
+                ),
+                true,
+            ),
+            self.target_coverage(),
+            self.cumulative_coverage(),
+            StatsSection {
+                name: "substitutions",
+                title: "Substitutions (query base by row, target base by column)",
+                columns: &BASES,
+                rows: BASES
+                    .iter()
+                    .zip(&self.substitutions)
+                    .map(|(base, counts)| {
+                        (
+                            base.to_string(),
+                            counts.iter().map(usize::to_string).collect(),
+                        )
+                    })
+                    .collect(),
+                truncate: false,
+            },
+            self.substitution_types(),
+            StatsSection {
+                name: "indel_lengths",
+                title: "Indel lengths",
+                columns: &["insertions", "deletions"],
+                rows: INDEL_BIN_LABELS
+                    .iter()
+                    .enumerate()
+                    .map(|(bin, label)| {
+                        (
+                            label.to_string(),
+                            vec![
+                                self.indel_lengths[0][bin].to_string(),
+                                self.indel_lengths[1][bin].to_string(),
+                            ],
+                        )
+                    })
+                    .collect(),
+                truncate: false,
+            },
+        ];
+        if let Some(queries) = &self.query_indel_lengths {
+            sections.push(query_indel_section(
+                "insertion_lengths_per_query",
+                "Insertion lengths per query",
+                queries,
+                0,
+            ));
+            sections.push(query_indel_section(
+                "deletion_lengths_per_query",
+                "Deletion lengths per query",
+                queries,
+                1,
+            ));
+        }
+        sections
+    }
+
+    /// Fraction of each target covered by at least one alignment, its mean
+    /// depth and the number of queries aligned to it, by target name.
+    fn target_coverage(&self) -> StatsSection {
+        let rows = self
+            .target_coverage
+            .sequences()
+            .into_iter()
+            .map(|(name, length, covered)| {
+                let (depth, queries) = self
+                    .target_depth
+                    .get(name)
+                    .map_or((0, 0), |(depth, queries)| (*depth, queries.len()));
+                let fraction = |bases: usize| {
+                    if length == 0 {
+                        0.0
+                    } else {
+                        bases as f64 / length as f64
+                    }
+                };
+                (
+                    name.to_string(),
+                    vec![
+                        length.to_string(),
+                        covered.to_string(),
+                        format_float(fraction(covered)),
+                        format_float(fraction(depth)),
+                        queries.to_string(),
+                    ],
+                )
+            })
+            .collect();
+        StatsSection {
+            name: "target_coverage",
+            title: "Target coverage",
+            columns: &[
+                "length",
+                "covered_bases",
+                "covered_fraction",
+                "mean_depth",
+                "queries",
+            ],
+            rows,
+            truncate: false,
+        }
+    }
+
+    /// Fractions of all query and target bases covered by alignments with at
+    /// least each of `COVERAGE_THRESHOLDS` block length.
+    fn cumulative_coverage(&self) -> StatsSection {
+        let query_total: usize = self.query_lengths.values().sum();
+        let target_total: usize = self
+            .target_coverage
+            .sequences()
+            .iter()
+            .map(|(_, length, _)| length)
+            .sum();
+        let fraction = |bases: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                bases as f64 / total as f64
+            }
+        };
+        let rows = COVERAGE_THRESHOLDS
+            .iter()
+            .zip(&self.coverage_by_length)
+            .map(|(threshold, (queries, targets))| {
+                let covered = |tracker: &CoverageTracker| -> usize {
+                    tracker
+                        .sequences()
+                        .iter()
+                        .map(|(_, _, covered)| covered)
+                        .sum()
+                };
+                (
+                    format!(">={}", threshold),
+                    vec![
+                        format_float(fraction(covered(queries), query_total)),
+                        format_float(fraction(covered(targets), target_total)),
+                    ],
+                )
+            })
+            .collect();
+        StatsSection {
+            name: "cumulative_coverage",
+            title: "Coverage by alignments of at least this block length",
+            columns: &["query_fraction", "target_fraction"],
+            rows,
+            truncate: false,
+        }
+    }
+
+    /// Transitions (A<->G, C<->T) and transversions among the mismatches
+    /// between A, C, G and T, and their ratio (0 without transversions).
+    fn substitution_types(&self) -> StatsSection {
+        let (mut transitions, mut transversions) = (0, 0);
+        for (q, counts) in self.substitutions.iter().enumerate().take(4) {
+            for (t, &count) in counts.iter().enumerate().take(4) {
+                if q == t {
+                    continue;
+                }
+                // A, C, G, T are 0-3: transitions keep the purine/pyrimidine parity.
+                if q % 2 == t % 2 {
+                    transitions += count;
+                } else {
+                    transversions += count;
+                }
+            }
+        }
+        let ratio = if transversions == 0 {
+            0.0
+        } else {
+            transitions as f64 / transversions as f64
+        };
+        StatsSection::values(
+            "substitution_types",
+            "Substitution types",
+            vec![
+                ("transitions".to_string(), transitions.to_string()),
+                ("transversions".to_string(), transversions.to_string()),
+                ("ti_tv_ratio".to_string(), format_float(ratio)),
+            ],
+            false,
+        )
+    }
+
+    pub fn write<W: Write>(&self, format: OutputFormat, output: &mut W) -> Result<()> {
+        let sections = self.sections();
+        match format {
+            OutputFormat::Text => write_text(&sections, output),
+            OutputFormat::Json => write_json(&sections, output),
+            OutputFormat::Tsv => write_tsv(&sections, output),
+            OutputFormat::Ndjson => anyhow::bail!("stats output format must be text, json or tsv"),
+        }
+    }
+}
+
+fn write_text<W: Write>(sections: &[StatsSection], output: &mut W) -> Result<()> {
+    for section in sections {
+        writeln!(output, "[pafcheck] {}:", section.title)?;
+        let shown = if section.truncate {
+            TOP_SEQUENCES.min(section.rows.len())
+        } else {
+            section.rows.len()
+        };
+        let rows = &section.rows[..shown];
+        let name_width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let widths: Vec<usize> = section
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                rows.iter()
+                    .map(|(_, values)| values[i].len())
+                    .chain((!section.is_single_valued()).then_some(column.len()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |name: &str, values: Vec<&str>| {
+            let values: Vec<String> = values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{:>width$}", value, width = width))
+                .collect();
+            format!(
+                "[pafcheck]   {:<width$}  {}",
+                name,
+                values.join("  "),
+                width = name_width
+            )
+        };
+        if !section.is_single_valued() {
+            writeln!(output, "{}", line("", section.columns.to_vec()))?;
+        }
+        for (name, values) in rows {
+            let values = values.iter().map(String::as_str).collect();
+            writeln!(output, "{}", line(name, values).trim_end())?;
+        }
+        if shown < section.rows.len() {
+            writeln!(
+                output,
+                "[pafcheck]   ... and {} more",
+                section.rows.len() - shown
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// One object per section; rows of one value map to it, other rows to an
+/// object of their columns.
+fn write_json<W: Write>(sections: &[StatsSection], output: &mut W) -> Result<()> {
+    let sections: Vec<String> = sections
+        .iter()
+        .map(|section| {
+            let rows: Vec<String> = section
+                .rows
+                .iter()
+                .map(|(name, values)| {
+                    let value = if section.is_single_valued() {
+                        values[0].clone()
+                    } else {
+                        let fields: Vec<String> = section
+                            .columns
+                            .iter()
+                            .zip(values)
+                            .map(|(column, value)| format!("{}: {}", json_string(column), value))
+                            .collect();
+                        format!("{{{}}}", fields.join(", "))
+                    };
+                    format!("{}: {}", json_string(name), value)
+                })
+                .collect();
+            format!("{}: {{{}}}", json_string(section.name), rows.join(", "))
+        })
+        .collect();
+    writeln!(output, "{{{}}}", sections.join(", "))?;
+    Ok(())
+}
+
+/// One row per value, with the name of its column as `field`.
+fn write_tsv<W: Write>(sections: &[StatsSection], output: &mut W) -> Result<()> {
+    writeln!(output, "section\tname\tfield\tvalue")?;
+    for section in sections {
+        for (name, values) in &section.rows {
+            for (column, value) in section.columns.iter().zip(values) {
+                writeln!(output, "{}\t{}\t{}\t{}", section.name, name, column, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Histograms of one indel `kind` (0 for insertions, 1 for deletions) per
+/// query, queries with most of them first.
+fn query_indel_section(
+    name: &'static str,
+    title: &'static str,
+    queries: &HashMap<String, IndelHistogram>,
+    kind: usize,
+) -> StatsSection {
+    let mut ranked: Vec<(&String, &[usize; 12])> = queries
+        .iter()
+        .map(|(query, histogram)| (query, &histogram[kind]))
+        .collect();
+    ranked.sort_by(|a, b| {
+        let total = |histogram: &[usize; 12]| histogram.iter().sum::<usize>();
+        total(b.1).cmp(&total(a.1)).then(a.0.cmp(b.0))
+    });
+    StatsSection {
+        name,
+        title,
+        columns: &INDEL_BIN_LABELS,
+        rows: ranked
+            .into_iter()
+            .map(|(query, counts)| (query.clone(), counts.iter().map(usize::to_string).collect()))
+            .collect(),
+        truncate: true,
+    }
+}
+
+fn base_index(base: u8) -> usize {
+    match base {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+/// Counts per sequence name, most first.
+fn ranked_counts(counts: &HashMap<String, usize>) -> Vec<(String, String)> {
+    let mut ranked: Vec<(&String, &usize)> = counts.iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .map(|(name, count)| (name.clone(), count.to_string()))
+        .collect()
+}
+
+fn format_float(value: f64) -> String {
+    format!("{:.6}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(query: &str, target: &str) -> PafRecord {
+        PafRecord::from_line(&format!(
+            "{}\t100\t0\t10\t+\t{}\t100\t0\t10\t10\t10\t60",
+            query, target
+        ))
+        .unwrap()
+    }
+
+    fn value<'a>(sections: &'a [StatsSection], section: &str, name: &str) -> &'a str {
+        let section = sections.iter().find(|s| s.name == section).unwrap();
+        &section.rows.iter().find(|(n, _)| n == name).unwrap().1[0]
+    }
+
+    #[test]
+    fn test_alignment_summary() {
+        let mut summary = AlignmentSummary::new();
+        summary.add(
+            &record("q1", "t1"),
+            &AlignmentStats {
+                identical: 10,
+                aligned: 10,
+                ..Default::default()
+            },
+        );
+        summary.add(
+            &record("q1", "t2"),
+            &AlignmentStats {
+                identical: 8,
+                aligned: 10,
+                insertions: 1,
+                inserted_bases: 10,
+                ..Default::default()
+            },
+        );
+        summary.skip();
+        summary.add_target("t3", 50);
+        let sections = summary.sections();
+        assert_eq!(value(&sections, "summary", "records"), "2");
+        assert_eq!(value(&sections, "summary", "skipped"), "1");
+        assert_eq!(value(&sections, "summary", "aligned_bases"), "20");
+        // 18 identical of 20 aligned columns and one insertion.
+        assert_eq!(
+            value(&sections, "summary", "gap_compressed_identity"),
+            "0.857143"
+        );
+        assert_eq!(
+            value(&sections, "summary", "median_block_length"),
+            "15.000000"
+        );
+        assert_eq!(value(&sections, "summary", "block_n50"), "20");
+        assert_eq!(value(&sections, "summary", "block_n90"), "10");
+        assert_eq!(value(&sections, "span_per_query", "q1"), "20");
+        assert_eq!(value(&sections, "span_per_target", "t2"), "10");
+        // Of 100 query bases, 10 are covered; of 250 target bases, 20.
+        let cumulative = sections
+            .iter()
+            .find(|s| s.name == "cumulative_coverage")
+            .unwrap();
+        assert_eq!(cumulative.rows[0].1, ["0.100000", "0.080000"]);
+        assert_eq!(cumulative.rows[1].1, ["0.000000", "0.000000"]);
+        assert_eq!(value(&sections, "block_identity", "min"), "0.400000");
+        assert_eq!(value(&sections, "block_identity", "[0, 0.9)"), "1");
+        assert_eq!(value(&sections, "block_identity", "[0.999, 1]"), "1");
+        assert_eq!(value(&sections, "alignments_per_query", "q1"), "2");
+        assert_eq!(
+            sections[3].rows,
+            [
+                ("t1".to_string(), vec!["1".to_string()]),
+                ("t2".to_string(), vec!["1".to_string()])
+            ]
+        );
+
+        let coverage = sections
+            .iter()
+            .find(|s| s.name == "target_coverage")
+            .unwrap();
+        assert_eq!(
+            coverage.rows[0].1,
+            ["100", "10", "0.100000", "0.100000", "1"]
+        );
+        // A target without alignments is listed as uncovered.
+        assert_eq!(coverage.rows[2].1, ["50", "0", "0.000000", "0.000000", "0"]);
+
+        let mut tsv = Vec::new();
+        summary.write(OutputFormat::Tsv, &mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        assert!(tsv.starts_with("section\tname\tfield\tvalue\nsummary\trecords\tvalue\t2\n"));
+        let mut json = Vec::new();
+        summary.write(OutputFormat::Json, &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"summary\": {\"records\": 2, \"skipped\": 1,"));
+        assert!(json.contains("\"alignments_per_query\": {\"q1\": 2}"));
+        assert!(json.contains("\"t3\": {\"length\": 50, \"covered_bases\": 0,"));
+    }
+
+    #[test]
+    fn test_histogram() {
+        assert_eq!(length_bin(1023), 1023);
+        assert_eq!(length_bin(1024), 1024);
+        assert_eq!(length_bin(1031), 1024);
+        assert_eq!(length_bin(1032), 1025);
+        assert_eq!(length_bin(2048), 1152);
+        assert!(length_bin(usize::MAX) > length_bin(usize::MAX / 2));
+
+        let mut lengths = Histogram::default();
+        for length in [100, 300, 1_000_000, 1_000_001, 200] {
+            lengths.add(length_bin(length), length as f64);
+        }
+        assert_eq!(lengths.median(), 300.0);
+        // The two long blocks share a bin, represented by their mean.
+        assert_eq!(lengths.value_at(4), 1_000_000.5);
+        assert_eq!(lengths.n_x(0.5), 1_000_000.5);
+        assert_eq!(lengths.n_x(1.0), 100.0);
+        assert_eq!(lengths.range, Some((100.0, 1_000_001.0)));
+        assert_eq!(Histogram::default().median(), 0.0);
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let mut fasta_reader =
+            MultiFastaReader::from_strings(">q\nACGTNACGTA\n", ">t\nGCATAACTTA\n").unwrap();
+        let record =
+            PafRecord::from_line("q\t10\t0\t10\t+\tt\t10\t0\t10\t5\t10\t60\tcg:Z:3M1X2=1I1D3=")
+                .unwrap();
+        let mut summary = AlignmentSummary::new();
+        summary.add_record(&record, &mut fasta_reader).unwrap();
+        let sections = summary.sections();
+        let matrix = sections.iter().find(|s| s.name == "substitutions").unwrap();
+        // A>G and G>A are transitions, G>T a transversion; N>A is in the N
+        // row. The 1X is tallied by its bases, T and T.
+        assert_eq!(matrix.rows[0].1, ["2", "0", "1", "0", "0"]);
+        assert_eq!(matrix.rows[2].1, ["1", "0", "0", "1", "0"]);
+        assert_eq!(matrix.rows[3].1, ["0", "0", "0", "2", "0"]);
+        assert_eq!(matrix.rows[4].1, ["1", "0", "0", "0", "0"]);
+        assert_eq!(value(&sections, "substitution_types", "transitions"), "2");
+        assert_eq!(value(&sections, "substitution_types", "transversions"), "1");
+        assert_eq!(
+            value(&sections, "substitution_types", "ti_tv_ratio"),
+            "2.000000"
+        );
+    }
+
+    #[test]
+    fn test_indel_lengths() {
+        let ops = crate::cigar_parser::parse_cigar("5=1I5=1D5=7I5=12D5=100N5=1I5=").unwrap();
+        let mut summary = AlignmentSummary::new().with_query_indels();
+        summary.add_indels("q1", &ops);
+        summary.add_indels("q2", &ops[..4]);
+        let sections = summary.sections();
+        let lengths = sections.iter().find(|s| s.name == "indel_lengths").unwrap();
+        assert_eq!(
+            lengths.rows[0],
+            ("1".to_string(), vec!["3".to_string(), "2".to_string()])
+        );
+        assert_eq!(lengths.rows[5].1, ["1", "0"]);
+        // The intron is not a deletion.
+        assert_eq!(lengths.rows[6].1, ["0", "1"]);
+        assert_eq!(lengths.rows[8].1, ["0", "0"]);
+        let insertions = sections
+            .iter()
+            .find(|s| s.name == "insertion_lengths_per_query")
+            .unwrap();
+        assert_eq!(insertions.columns[5], "6-10");
+        assert_eq!(insertions.rows[0].0, "q1");
+        assert_eq!(insertions.rows[0].1[..2], ["2", "0"]);
+        assert_eq!(insertions.rows[1].1[..2], ["1", "0"]);
+    }
+}
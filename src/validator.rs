@@ -1,16 +1,162 @@
-use crate::cigar_parser::{parse_cigar, CigarOp};
+use crate::bed::BedRegions;
+use crate::cigar_parser::{
+    aligned_range, clip_lengths, parse_cigar, CigarOp, CigarOps, CigarSyntaxError,
+};
+use crate::complexity::{
+    local_gc_fraction, low_complexity_mask, DUST_THRESHOLD, DUST_WINDOW, GC_WINDOW,
+};
+use crate::cs_tag::{cs_base_errors, cs_to_cigar, parse_cs};
 use crate::fasta_reader::MultiFastaReader;
+use crate::minimizer::{cigar_identity, minimizer_identity, MINIMIZER_K, MINIMIZER_W};
 use crate::paf_parser::PafRecord;
+use crate::sampling::{RateEstimate, SplitMix64};
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::io::Write;
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ErrorType {
     Mismatch,
     LengthMismatch,
     CigarMismatch,
+    NonCanonicalSplice,
+    BoundaryIndel,
+    LongIndel,
+    ReciprocalMismatch,
+    InterHaplotypeMapping,
+    SelfHaplotypeMapping,
+    LowQueryCoverage,
+    ClipMismatch,
+    InconsistentQueryLength,
+    SequenceLengthMismatch,
+    NegativeCoordinate,
+    InvalidInterval,
+    CoordinateOutOfRange,
+    MatchCountMismatch,
+    BlockLengthMismatch,
+    TagMismatch,
+    InvalidMappingQuality,
+    MaskedAlignment,
+    NonExtendedCigar,
+    NonNormalizedCigar,
+    MalformedCigar,
+    CigarOverrunsQuery,
+    CigarOverrunsTarget,
+    CsBaseMismatch,
+    CsCigarMismatch,
+    ConflictingPrimary,
+    OrphanSecondary,
+    TagSyntax,
+    LowQualityMismatch,
+    SelfAlignment,
+    LowIdentity,
+    ShortBlock,
+    LowRecordQueryCoverage,
+}
+
+impl ErrorType {
+    pub const ALL: [ErrorType; 36] = [
+        ErrorType::Mismatch,
+        ErrorType::LengthMismatch,
+        ErrorType::CigarMismatch,
+        ErrorType::NonCanonicalSplice,
+        ErrorType::BoundaryIndel,
+        ErrorType::LongIndel,
+        ErrorType::ReciprocalMismatch,
+        ErrorType::InterHaplotypeMapping,
+        ErrorType::SelfHaplotypeMapping,
+        ErrorType::LowQueryCoverage,
+        ErrorType::ClipMismatch,
+        ErrorType::InconsistentQueryLength,
+        ErrorType::SequenceLengthMismatch,
+        ErrorType::NegativeCoordinate,
+        ErrorType::InvalidInterval,
+        ErrorType::CoordinateOutOfRange,
+        ErrorType::MatchCountMismatch,
+        ErrorType::BlockLengthMismatch,
+        ErrorType::TagMismatch,
+        ErrorType::InvalidMappingQuality,
+        ErrorType::MaskedAlignment,
+        ErrorType::NonExtendedCigar,
+        ErrorType::NonNormalizedCigar,
+        ErrorType::MalformedCigar,
+        ErrorType::CigarOverrunsQuery,
+        ErrorType::CigarOverrunsTarget,
+        ErrorType::CsBaseMismatch,
+        ErrorType::CsCigarMismatch,
+        ErrorType::ConflictingPrimary,
+        ErrorType::OrphanSecondary,
+        ErrorType::TagSyntax,
+        ErrorType::LowQualityMismatch,
+        ErrorType::SelfAlignment,
+        ErrorType::LowIdentity,
+        ErrorType::ShortBlock,
+        ErrorType::LowRecordQueryCoverage,
+    ];
+
+    /// The type's name on the command line, e.g. `cigar-mismatch`.
+    pub fn cli_name(&self) -> String {
+        let mut name = String::new();
+        for c in format!("{:?}", self).chars() {
+            if c.is_ascii_uppercase() && !name.is_empty() {
+                name.push('-');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+
+    /// The severity of the type, unless warnings are treated as errors.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ErrorType::NonNormalizedCigar
+            | ErrorType::MaskedAlignment
+            | ErrorType::LowQualityMismatch
+            | ErrorType::BoundaryIndel
+            | ErrorType::SelfAlignment => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Whether the type is a policy violation: a record or sequence that
+    /// fails a threshold set on the command line (`--min-identity`,
+    /// `--max-indel-len`, ...) rather than a PAF that is incorrect.
+    pub fn is_policy(&self) -> bool {
+        matches!(
+            self,
+            ErrorType::LongIndel
+                | ErrorType::InterHaplotypeMapping
+                | ErrorType::SelfHaplotypeMapping
+                | ErrorType::LowQueryCoverage
+                | ErrorType::SelfAlignment
+                | ErrorType::LowIdentity
+                | ErrorType::ShortBlock
+                | ErrorType::LowRecordQueryCoverage
+        )
+    }
+}
+
+/// Whether an error type fails the validation, or is only reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// Suspicious but legal, such as a CIGAR that isn't normalized; doesn't
+    /// fail the run unless warnings are treated as errors.
+    Warning,
+}
+
+impl std::str::FromStr for ErrorType {
+    type Err = anyhow::Error;
+
+    /// Accepts command-line names (`cigar-mismatch`) and report names (`CigarMismatch`).
+    fn from_str(s: &str) -> Result<Self> {
+        ErrorType::ALL
+            .iter()
+            .find(|error_type| error_type.cli_name() == s || format!("{:?}", error_type) == s)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown error type: {}", s))
+    }
 }
 
 #[derive(Error, Debug)]
@@ -22,6 +168,76 @@ pub struct ValidationError {
 pub struct ErrorInfo {
     pub first_message: String,
     pub count: usize,
+    pub sites: Vec<ErrorSite>,
+}
+
+/// Location of a single error occurrence, in absolute sequence coordinates.
+#[derive(Debug, Clone)]
+pub struct ErrorSite {
+    pub op_index: usize,
+    pub query_pos: usize,
+    pub target_pos: usize,
+    /// Length of the target homopolymer run covering the site (0 past the end).
+    pub homopolymer_len: usize,
+    /// Whether the site lies in a DUST low-complexity region of the target.
+    pub low_complexity: bool,
+    /// GC fraction of the target window around the site, if it has any ACGT bases.
+    pub gc_fraction: Option<f64>,
+    /// Bases around a mismatch, if `ValidationOptions::context_bases` is set.
+    pub context: Option<SiteContext>,
+}
+
+impl ErrorSite {
+    /// A site without the context fields, which are filled in after
+    /// validation if requested.
+    pub fn new(
+        op_index: usize,
+        query_pos: usize,
+        target_pos: usize,
+        homopolymer_len: usize,
+    ) -> Self {
+        ErrorSite {
+            op_index,
+            query_pos,
+            target_pos,
+            homopolymer_len,
+            low_complexity: false,
+            gc_fraction: None,
+            context: None,
+        }
+    }
+}
+
+/// The query and target bases around an error site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteContext {
+    pub query: String,
+    pub target: String,
+    /// Index of the site's base in `query` and in `target`.
+    pub query_offset: usize,
+    pub target_offset: usize,
+}
+
+impl SiteContext {
+    /// The bases within `flank` of index `q` of `query` and `t` of `target`.
+    pub fn around(query: &[u8], q: usize, target: &[u8], t: usize, flank: usize) -> Self {
+        let window = |seq: &[u8], idx: usize| {
+            let start = idx.saturating_sub(flank).min(seq.len());
+            let end = (idx + flank + 1).min(seq.len());
+            (
+                String::from_utf8_lossy(&seq[start..end]).into_owned(),
+                idx - start,
+            )
+        };
+        let (query, query_offset) = window(query, q);
+        let (target, target_offset) = window(target, t);
+        SiteContext {
+            query,
+            target,
+            query_offset,
+            target_offset,
+        }
+    }
 }
 
 impl std::fmt::Display for ValidationError {
@@ -41,122 +257,1201 @@ impl std::fmt::Display for ValidationError {
     }
 }
 
+/// Checks that can be turned on and off individually with `--checks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// `=` bases that differ between query and target, and `cs` tag bases
+    /// that differ from the sequences (`Mismatch`, `LowQualityMismatch`,
+    /// `CsBaseMismatch`).
+    Mismatch,
+    /// `X` bases that are identical (`CigarMismatch`).
+    CigarMismatch,
+    /// CIGAR lengths against the record coordinates and columns
+    /// (`LengthMismatch`, `CigarOverrunsQuery`, `CigarOverrunsTarget`,
+    /// `MatchCountMismatch`, `BlockLengthMismatch`).
+    Length,
+    /// Record coordinates on their own and against clips, other records and
+    /// the FASTA indexes, and the mapping quality (`NegativeCoordinate`,
+    /// `InvalidInterval`, `CoordinateOutOfRange`, `ClipMismatch`,
+    /// `InconsistentQueryLength`, `SequenceLengthMismatch`,
+    /// `InvalidMappingQuality`).
+    Coordinates,
+    /// Optional PAF tags against the alignment and other records
+    /// (`TagSyntax`, `TagMismatch`, `CsCigarMismatch`, `ConflictingPrimary`,
+    /// `OrphanSecondary`).
+    Tags,
+    /// The form of the CIGAR itself (`MalformedCigar`, `NonNormalizedCigar`).
+    Cigar,
+}
+
+impl Check {
+    pub const ALL: [Check; 6] = [
+        Check::Mismatch,
+        Check::CigarMismatch,
+        Check::Length,
+        Check::Coordinates,
+        Check::Tags,
+        Check::Cigar,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Mismatch => "mismatch",
+            Check::CigarMismatch => "cigar-mismatch",
+            Check::Length => "length",
+            Check::Coordinates => "coordinates",
+            Check::Tags => "tags",
+            Check::Cigar => "cigar",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << Check::ALL.iter().position(|&c| c == self).unwrap()
+    }
+}
+
+impl std::str::FromStr for Check {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Check::ALL
+            .iter()
+            .copied()
+            .find(|check| check.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Check::ALL.iter().map(|check| check.name()).collect();
+                anyhow::anyhow!(
+                    "Unknown check: {} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The set of enabled checks; all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckSet(u8);
+
+impl CheckSet {
+    pub fn all() -> Self {
+        CheckSet(Check::ALL.iter().fold(0, |bits, check| bits | check.bit()))
+    }
+
+    pub fn none() -> Self {
+        CheckSet(0)
+    }
+
+    /// Parses a comma-separated list like `mismatch,length`, which enables
+    /// only the listed checks, or `-tags`, which disables checks from all.
+    pub fn parse(list: &str) -> Result<Self> {
+        let items: Vec<&str> = list.split(',').map(str::trim).collect();
+        let mut set = if items.iter().all(|item| item.starts_with('-')) {
+            CheckSet::all()
+        } else {
+            CheckSet::none()
+        };
+        for item in items {
+            match item.strip_prefix('-') {
+                Some(name) => set.0 &= !name.parse::<Check>()?.bit(),
+                None if item == "all" => set = CheckSet::all(),
+                None => set.0 |= item.parse::<Check>()?.bit(),
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn contains(self, check: Check) -> bool {
+        self.0 & check.bit() != 0
+    }
+}
+
+impl Default for CheckSet {
+    fn default() -> Self {
+        CheckSet::all()
+    }
+}
+
+/// How soft-masked (lowercase) bases are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LowercasePolicy {
+    /// Compare bases regardless of case.
+    #[default]
+    Ignore,
+    /// Count a difference in case as a mismatch.
+    Mismatch,
+    /// Compare bases regardless of case, but report alignments whose query
+    /// or target span is entirely soft-masked.
+    Warn,
+}
+
+impl LowercasePolicy {
+    pub const ALL: [LowercasePolicy; 3] = [
+        LowercasePolicy::Ignore,
+        LowercasePolicy::Mismatch,
+        LowercasePolicy::Warn,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LowercasePolicy::Ignore => "ignore",
+            LowercasePolicy::Mismatch => "mismatch",
+            LowercasePolicy::Warn => "warn",
+        }
+    }
+}
+
+impl std::str::FromStr for LowercasePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        LowercasePolicy::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown lowercase policy: {} (expected ignore, mismatch or warn)",
+                    s
+                )
+            })
+    }
+}
+
+/// What to do with records that align a sequence to an overlapping interval
+/// of itself, such as the self-mappings of an all-vs-all run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelfAlignmentPolicy {
+    /// Report them as `SelfAlignment` errors.
+    Error,
+    /// Validate them, and count them in a warning at the end of the run.
+    #[default]
+    Warn,
+    /// Leave them out, like records excluded by the record filters.
+    Skip,
+}
+
+impl SelfAlignmentPolicy {
+    pub const ALL: [SelfAlignmentPolicy; 3] = [
+        SelfAlignmentPolicy::Error,
+        SelfAlignmentPolicy::Warn,
+        SelfAlignmentPolicy::Skip,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SelfAlignmentPolicy::Error => "error",
+            SelfAlignmentPolicy::Warn => "warn",
+            SelfAlignmentPolicy::Skip => "skip",
+        }
+    }
+}
+
+impl std::str::FromStr for SelfAlignmentPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SelfAlignmentPolicy::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown self-alignment policy: {} (expected error, warn or skip)",
+                    s
+                )
+            })
+    }
+}
+
+/// How `N` bases are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NPolicy {
+    /// `N` matches any base, so it may sit under `=` but not under `X`.
+    MatchAny,
+    /// `N` is compared like any other base: it only matches another `N`.
+    #[default]
+    Mismatch,
+    /// Positions with an `N` in either sequence aren't compared.
+    Skip,
+}
+
+impl NPolicy {
+    pub const ALL: [NPolicy; 3] = [NPolicy::MatchAny, NPolicy::Mismatch, NPolicy::Skip];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NPolicy::MatchAny => "match-any",
+            NPolicy::Mismatch => "mismatch",
+            NPolicy::Skip => "skip",
+        }
+    }
+}
+
+impl std::str::FromStr for NPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        NPolicy::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown N policy: {} (expected match-any, mismatch or skip)",
+                    s
+                )
+            })
+    }
+}
+
+/// Settings that change how records are checked.
+#[derive(Debug, Default)]
+pub struct ValidationOptions {
+    /// The checks to run.
+    pub checks: CheckSet,
+    /// Target regions in which base-level comparisons are skipped.
+    pub exclude_regions: Option<BedRegions>,
+    /// Whether to check the dinucleotides at the ends of `N` (intron) ops.
+    pub check_splice_sites: bool,
+    /// Flag alignments that begin or end with an insertion or deletion
+    /// (`BoundaryIndel` warnings, unless promoted to errors).
+    pub flag_boundary_indels: bool,
+    /// Flag terminal insertions or deletions longer than this many bases.
+    pub max_terminal_indel: Option<usize>,
+    /// Flag any single insertion or deletion longer than this many bases.
+    pub max_indel_len: Option<usize>,
+    /// Bases of context kept on each side of mismatch sites (0 for none).
+    pub context_bases: usize,
+    /// How soft-masked bases are compared.
+    pub lowercase: LowercasePolicy,
+    /// How `N` bases are compared.
+    pub n_policy: NPolicy,
+    /// Flag `M` ops, which don't say whether their bases match.
+    pub require_extended_cigar: bool,
+    /// Report mismatches on query bases with a lower FASTQ quality as
+    /// `LowQualityMismatch` rather than `Mismatch`.
+    pub min_base_quality: Option<u8>,
+    /// Report records whose BLAST-like identity, recomputed from their
+    /// sequences, is below this fraction.
+    pub min_identity: Option<f64>,
+}
+
+/// Whether the record aligns a sequence to itself, on overlapping intervals.
+pub fn is_self_alignment(record: &PafRecord) -> bool {
+    record.query_name == record.target_name
+        && record.query_start < record.target_end
+        && record.target_start < record.query_end
+}
+
+/// Records shorter than `min_block_length` (column 11) or whose query
+/// interval covers less than `min_query_coverage` of the query.
+pub fn policy_errors(
+    record: &PafRecord,
+    min_block_length: Option<usize>,
+    min_query_coverage: Option<f64>,
+) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    if let Some(min_block_length) = min_block_length.filter(|&min| record.block_length < min) {
+        errors.push((
+            ErrorType::ShortBlock,
+            format!(
+                "Alignment block length {} is below the minimum of {}",
+                record.block_length, min_block_length
+            ),
+        ));
+    }
+    let aligned = record.query_end.saturating_sub(record.query_start);
+    let coverage = aligned as f64 / record.query_length as f64;
+    if let Some(min_query_coverage) = min_query_coverage.filter(|&min| coverage < min) {
+        errors.push((
+            ErrorType::LowRecordQueryCoverage,
+            format!(
+                "Alignment covers {:.4} of {} ({} of {} bases), below the minimum of {}",
+                coverage, record.query_name, aligned, record.query_length, min_query_coverage
+            ),
+        ));
+    }
+    errors
+}
+
+/// Intervals of a record that are empty or reversed, or that end past the
+/// length column of their sequence. The sequences of such records can't be
+/// fetched, so they are reported instead of being validated.
+pub fn coordinate_errors(record: &PafRecord) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    for (role, start, end, length) in [
+        (
+            "Query",
+            record.query_start,
+            record.query_end,
+            record.query_length,
+        ),
+        (
+            "Target",
+            record.target_start,
+            record.target_end,
+            record.target_length,
+        ),
+    ] {
+        if start >= end {
+            errors.push((
+                ErrorType::InvalidInterval,
+                format!("{} interval {}-{} is empty or reversed", role, start, end),
+            ));
+        }
+        if end > length {
+            errors.push((
+                ErrorType::CoordinateOutOfRange,
+                format!(
+                    "{} interval {}-{} ends past the {} length {}",
+                    role,
+                    start,
+                    end,
+                    role.to_lowercase(),
+                    length
+                ),
+            ));
+        }
+    }
+    errors
+}
+
+/// Disagreements between the count columns of a record and its CIGAR.
+/// Records without a CIGAR, or with one that doesn't parse, are left to the
+/// base-level validation. Column 10 isn't checked against CIGARs with `M`
+/// ops, which don't say how many of their bases match.
+pub fn column_errors(record: &PafRecord) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    if record.cigar.is_empty() {
+        return errors;
+    }
+    let Ok(ops) = parse_cigar(&record.cigar) else {
+        return errors;
+    };
+    let (mut matches, mut block_length) = (0, 0);
+    let mut extended = true;
+    for op in &ops {
+        match op {
+            CigarOp::AlignmentMatch(len) => {
+                extended = false;
+                block_length += len;
+            }
+            CigarOp::Match(len) => {
+                matches += len;
+                block_length += len;
+            }
+            CigarOp::Mismatch(len) | CigarOp::Insertion(len) | CigarOp::Deletion(len) => {
+                block_length += len
+            }
+            CigarOp::Skip(_) | CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    if extended && matches != record.residue_matches as u64 {
+        errors.push((
+            ErrorType::MatchCountMismatch,
+            format!(
+                "Column 10 has {} matching bases, but the CIGAR has {} = bases",
+                record.residue_matches, matches
+            ),
+        ));
+    }
+    if block_length != record.block_length as u64 {
+        errors.push((
+            ErrorType::BlockLengthMismatch,
+            format!(
+                "Column 11 has a block length of {}, but the CIGAR has {} M, =, X, I and D bases",
+                record.block_length, block_length
+            ),
+        ));
+    }
+    errors
+}
+
+/// Problems with the form of a record's CIGAR: syntax errors, which make the
+/// record impossible to validate, and legal but non-normalized forms, which
+/// usually come from a buggy step that produced or edited it: adjacent
+/// operations of the same type, like `5=3=`, and clips that aren't at the
+/// ends, or hard clips inside soft clips.
+pub fn cigar_errors(record: &PafRecord) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    let ops = match parse_cigar(&record.cigar) {
+        Ok(ops) => ops,
+        Err(e) => {
+            if let Some(syntax) = e.downcast_ref::<CigarSyntaxError>() {
+                errors.push((ErrorType::MalformedCigar, syntax.to_string()));
+            }
+            return errors;
+        }
+    };
+    for (i, pair) in ops.windows(2).enumerate() {
+        if pair[0].symbol() == pair[1].symbol() {
+            errors.push((
+                ErrorType::NonNormalizedCigar,
+                format!(
+                    "Operations {} and {} ({}{}{}{}) have the same type and should be merged",
+                    i,
+                    i + 1,
+                    pair[0].count(),
+                    pair[0].symbol(),
+                    pair[1].count(),
+                    pair[1].symbol()
+                ),
+            ));
+        }
+    }
+    // Clips may only be outermost, with hard clips outside soft clips.
+    let leading = ops.iter().take_while(|op| op.is_clip()).count();
+    let trailing = if leading == ops.len() {
+        0
+    } else {
+        ops.iter().rev().take_while(|op| op.is_clip()).count()
+    };
+    for (i, op) in ops.iter().enumerate() {
+        let misplaced = if i < leading {
+            matches!(op, CigarOp::HardClip(_))
+                && ops[..i].iter().any(|op| matches!(op, CigarOp::SoftClip(_)))
+        } else if i >= ops.len() - trailing {
+            matches!(op, CigarOp::HardClip(_))
+                && ops[i + 1..]
+                    .iter()
+                    .any(|op| matches!(op, CigarOp::SoftClip(_)))
+        } else {
+            op.is_clip()
+        };
+        if misplaced {
+            errors.push((
+                ErrorType::NonNormalizedCigar,
+                format!(
+                    "Clip {}{} at operation {} is not at an end of the CIGAR",
+                    op.count(),
+                    op.symbol(),
+                    i
+                ),
+            ));
+        }
+    }
+    errors
+}
+
+/// Disagreement between the `cs:Z` tag and the `cg:Z` CIGAR of a record
+/// that has both, e.g. because a post-processing step rewrote only one of
+/// them: the first aligned column where their operations differ, or the
+/// lengths they cover. `M` ops agree with both matches and substitutions.
+/// Tags that don't parse are left to the base-level validation.
+pub fn cs_errors(record: &PafRecord) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    let Some(cs) = record.tag("cs:Z") else {
+        return errors;
+    };
+    if record.cigar.is_empty() {
+        return errors;
+    }
+    let (Ok(cs_ops), Ok(cigar_ops)) = (parse_cs(cs), parse_cigar(&record.cigar)) else {
+        return errors;
+    };
+    let cs_ops = cs_to_cigar(&cs_ops);
+    let cigar_ops: Vec<&CigarOp> = cigar_ops.iter().filter(|op| !op.is_clip()).collect();
+    let consumed = |ops: &mut dyn Iterator<Item = &CigarOp>| {
+        ops.map(CigarOp::consumed)
+            .fold((0, 0), |(q, t), (dq, dt)| (q + dq, t + dt))
+    };
+    let cs_len = consumed(&mut cs_ops.iter());
+    let cigar_len = consumed(&mut cigar_ops.iter().copied());
+    if cs_len != cigar_len {
+        errors.push((
+            ErrorType::CsCigarMismatch,
+            format!(
+                "cs:Z covers {} query and {} target bases, but cg:Z covers {} and {}",
+                cs_len.0, cs_len.1, cigar_len.0, cigar_len.1
+            ),
+        ));
+        return errors;
+    }
+
+    // Walk both column by column, as (op, bases left in it).
+    let mut cs_iter = cs_ops.iter().filter(|op| op.count() > 0);
+    let mut cigar_iter = cigar_ops.into_iter();
+    let (mut cs_op, mut cigar_op) = (None, None);
+    let (mut q_idx, mut t_idx) = (0, 0);
+    loop {
+        if cs_op.is_none_or(|(_, left)| left == 0) {
+            cs_op = cs_iter.next().map(|op| (op, op.count()));
+        }
+        if cigar_op.is_none_or(|(_, left)| left == 0) {
+            cigar_op = cigar_iter.next().map(|op| (op, op.count()));
+        }
+        let (Some((cs_next, cs_left)), Some((cigar_next, cigar_left))) = (cs_op, cigar_op) else {
+            break;
+        };
+        let agree = cs_next.symbol() == cigar_next.symbol()
+            || (matches!(cigar_next, CigarOp::AlignmentMatch(_))
+                && matches!(cs_next, CigarOp::Match(_) | CigarOp::Mismatch(_)));
+        if !agree {
+            errors.push((
+                ErrorType::CsCigarMismatch,
+                format!(
+                    "cs:Z and cg:Z differ at query offset {} and target offset {}: cs:Z has {} where cg:Z has {}",
+                    q_idx,
+                    t_idx,
+                    cs_next.symbol(),
+                    cigar_next.symbol()
+                ),
+            ));
+            break;
+        }
+        let step = cs_left.min(cigar_left);
+        let (dq, dt) = cs_next.consumed();
+        q_idx += if dq > 0 { step as usize } else { 0 };
+        t_idx += if dt > 0 { step as usize } else { 0 };
+        cs_op = Some((cs_next, cs_left - step));
+        cigar_op = Some((cigar_next, cigar_left - step));
+    }
+    errors
+}
+
+/// Optional fields of a record that don't follow the SAM-style
+/// `TAG:TYPE:VALUE` convention: a two-character tag, a known type and a
+/// value of that type (`A` a single character, `i` an integer, `f` a float,
+/// `Z` a printable string, `H` hex bytes, `B` a typed numeric array), or
+/// tags that appear more than once.
+pub fn tag_syntax_errors(record: &PafRecord) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for (i, field) in record.tags.iter().enumerate() {
+        let column = 13 + i;
+        let mut parts = field.splitn(3, ':');
+        let (Some(tag), Some(kind), Some(value)) = (parts.next(), parts.next(), parts.next())
+        else {
+            errors.push((
+                ErrorType::TagSyntax,
+                format!("Column {} is not TAG:TYPE:VALUE: {}", column, field),
+            ));
+            continue;
+        };
+        let tag_ok = tag.len() == 2
+            && tag.as_bytes()[0].is_ascii_alphabetic()
+            && tag.as_bytes()[1].is_ascii_alphanumeric();
+        if !tag_ok {
+            errors.push((
+                ErrorType::TagSyntax,
+                format!("Column {} has an invalid tag name: {}", column, field),
+            ));
+            continue;
+        }
+        let value_ok = match kind {
+            "A" => value.len() == 1 && value.bytes().all(|b| b.is_ascii_graphic()),
+            "i" => is_integer(value),
+            "f" => is_float(value),
+            "Z" => value.bytes().all(|b| b == b' ' || b.is_ascii_graphic()),
+            "H" => value.len() % 2 == 0 && value.bytes().all(|b| b.is_ascii_hexdigit()),
+            "B" => {
+                let mut items = value.split(',');
+                match items.next() {
+                    Some("f") => items.all(is_float),
+                    Some("c" | "C" | "s" | "S" | "i" | "I") => items.all(is_integer),
+                    _ => false,
+                }
+            }
+            _ => {
+                errors.push((
+                    ErrorType::TagSyntax,
+                    format!("Column {} has an unknown type {}: {}", column, kind, field),
+                ));
+                continue;
+            }
+        };
+        if !value_ok {
+            errors.push((
+                ErrorType::TagSyntax,
+                format!(
+                    "Column {} has a value that isn't of type {}: {}",
+                    column, kind, field
+                ),
+            ));
+        }
+        if seen.contains(&tag) {
+            errors.push((
+                ErrorType::TagSyntax,
+                format!("Column {} repeats the tag {}", column, tag),
+            ));
+        }
+        seen.push(tag);
+    }
+    errors
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether `value` is a decimal float; `inf` and `nan`, which Rust would
+/// parse, are not valid in SAM tags.
+fn is_float(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+        && value.parse::<f64>().is_ok()
+}
+
+/// Disagreements between the optional tags of a record and its CIGAR.
+/// Identity and divergence tags may differ from the values recomputed from
+/// the CIGAR by up to `epsilon`. CIGARs with `M` ops don't determine these
+/// values, so their records aren't checked.
+pub fn tag_errors(record: &PafRecord, epsilon: f64) -> Vec<(ErrorType, String)> {
+    let mut errors = Vec::new();
+    if record.cigar.is_empty() {
+        return errors;
+    }
+    let Ok(ops) = parse_cigar(&record.cigar) else {
+        return errors;
+    };
+    if ops
+        .iter()
+        .any(|op| matches!(op, CigarOp::AlignmentMatch(_)))
+    {
+        return errors;
+    }
+    let stats = cigar_stats(&ops);
+    if let Some(nm) = record.tag("NM:i") {
+        let edit_distance = stats.edit_distance();
+        if nm.parse::<usize>().ok() != Some(edit_distance) {
+            errors.push((
+                ErrorType::TagMismatch,
+                format!(
+                    "NM:i is {}, but the CIGAR has an edit distance of {}",
+                    nm, edit_distance
+                ),
+            ));
+        }
+    }
+
+    let gap_compressed = stats.gap_compressed_identity();
+    for (tag, expected, description) in [
+        ("de:f", 1.0 - gap_compressed, "gap-compressed divergence"),
+        ("dv:f", 1.0 - gap_compressed, "gap-compressed divergence"),
+        ("gi:f", gap_compressed, "gap-compressed identity"),
+        ("bi:f", stats.blast_identity(), "block identity"),
+    ] {
+        let Some(value) = record.tag(tag) else {
+            continue;
+        };
+        if value
+            .parse::<f64>()
+            .map_or(true, |value| (value - expected).abs() > epsilon)
+        {
+            errors.push((
+                ErrorType::TagMismatch,
+                format!(
+                    "{} is {}, but the CIGAR has a {} of {:.4}",
+                    tag, value, description, expected
+                ),
+            ));
+        }
+    }
+    errors
+}
+
+/// Column counts of a CIGAR, taking its `=`/`X` labels at their word. `M`
+/// columns are counted as identical.
+pub fn cigar_stats(ops: &[CigarOp]) -> AlignmentStats {
+    let mut stats = AlignmentStats::default();
+    for op in ops {
+        match op {
+            CigarOp::AlignmentMatch(len) | CigarOp::Match(len) => {
+                stats.identical += *len as usize;
+                stats.aligned += *len as usize;
+            }
+            CigarOp::Mismatch(len) => stats.aligned += *len as usize,
+            CigarOp::Insertion(len) => {
+                stats.insertions += 1;
+                stats.inserted_bases += *len as usize;
+            }
+            CigarOp::Deletion(len) => {
+                stats.deletions += 1;
+                stats.deleted_bases += *len as usize;
+            }
+            CigarOp::Skip(_) | CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    stats
+}
+
+/// The alignment of a record as CIGAR ops: its `cg:Z` CIGAR or, for
+/// records without one, the CIGAR equivalent of its `cs:Z` tag.
+pub fn alignment_ops(record: &PafRecord) -> Result<CigarOps> {
+    if record.cigar.is_empty() {
+        if let Some(cs) = record.tag("cs:Z") {
+            let ops = parse_cs(cs).context("Failed to parse cs tag")?;
+            return Ok(cs_to_cigar(&ops));
+        }
+    }
+    parse_cigar(&record.cigar).context("Failed to parse CIGAR string")
+}
+
 pub fn validate_record<W: Write>(
     record: &PafRecord,
     fasta_reader: &mut MultiFastaReader,
     error_mode: &str,
     output: &mut W,
 ) -> Result<()> {
-    let query_seq = fasta_reader
-        .fetch_query_sequence(&record.query_name, record.query_start, record.query_end)
-        .context(format!(
-            "Failed to fetch query sequence: {} ({}:{})",
-            record.query_name, record.query_start, record.query_end
-        ))?;
-    let target_seq = fasta_reader
-        .fetch_target_sequence(&record.target_name, record.target_start, record.target_end)
-        .context(format!(
-            "Failed to fetch target sequence: {} ({}:{})",
-            record.target_name, record.target_start, record.target_end
-        ))?;
+    validate_record_with_options(
+        record,
+        fasta_reader,
+        error_mode,
+        &ValidationOptions::default(),
+        output,
+    )
+}
 
-    let query_seq = if record.strand == '-' {
-        reverse_complement(&query_seq)
-    } else {
-        query_seq
+pub fn validate_record_with_options<W: Write>(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    error_mode: &str,
+    options: &ValidationOptions,
+    output: &mut W,
+) -> Result<()> {
+    let (mut query, mut target) = fetch_aligned_sequences_with_case(record, fasta_reader)?;
+    validate_aligned_sequences(
+        record,
+        fasta_reader,
+        error_mode,
+        options,
+        &mut query,
+        &mut target,
+        output,
+    )
+}
+
+/// Like `validate_record_with_options`, also returning the record's
+/// gap-compressed identity (see `recompute_identity`) computed from the same
+/// fetch. The identity is `None` if the sequences couldn't be fetched or
+/// don't fit the alignment.
+pub fn validate_record_with_identity<W: Write>(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    error_mode: &str,
+    options: &ValidationOptions,
+    output: &mut W,
+) -> (Result<()>, Option<f64>) {
+    let (mut query, mut target) = match fetch_aligned_sequences_with_case(record, fasta_reader) {
+        Ok(sequences) => sequences,
+        Err(e) => return (Err(e), None),
     };
+    let result = validate_aligned_sequences(
+        record,
+        fasta_reader,
+        error_mode,
+        options,
+        &mut query,
+        &mut target,
+        output,
+    );
+    let identity = alignment_ops(record)
+        .and_then(|ops| sequence_stats(&ops, &query, &target))
+        .ok()
+        .map(|stats| stats.gap_compressed_identity());
+    (result, identity)
+}
 
-    let query_seq = query_seq.to_uppercase().into_bytes();
-    let target_seq = target_seq.to_uppercase().into_bytes();
+/// Like `validate_record_with_options`, for the aligned sequences of the
+/// record as fetched by `fetch_aligned_sequences_with_case`. They are
+/// uppercased in place, so that callers that go on to use them needn't fetch
+/// them again.
+pub fn validate_aligned_sequences<W: Write>(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    error_mode: &str,
+    options: &ValidationOptions,
+    query: &mut Vec<u8>,
+    target: &mut Vec<u8>,
+    output: &mut W,
+) -> Result<()> {
+    let masked_spans = if options.lowercase == LowercasePolicy::Warn {
+        masked_span_errors(record, query, target)
+    } else {
+        Vec::new()
+    };
+    // The original case is only compared under LowercasePolicy::Mismatch.
+    let cased =
+        (options.lowercase == LowercasePolicy::Mismatch).then(|| (query.clone(), target.clone()));
+    query.make_ascii_uppercase();
+    target.make_ascii_uppercase();
+    let (query_seq, target_seq): (&Vec<u8>, &Vec<u8>) = (query, target);
+    let (compared_query, compared_target) = match &cased {
+        Some((query, target)) => (query.as_slice(), target.as_slice()),
+        None => (query_seq.as_slice(), target_seq.as_slice()),
+    };
+    let excluded = options.exclude_regions.as_ref().and_then(|regions| {
+        regions.local_mask(&record.target_name, record.target_start, record.target_end)
+    });
 
-    let cigar_ops = parse_cigar(&record.cigar).context("Failed to parse CIGAR string")?;
+    let cigar_ops = alignment_ops(record)?;
+    // Base qualities, in the orientation of `query_seq`.
+    let qualities = match options.min_base_quality {
+        Some(_) => fasta_reader
+            .fetch_query_qualities(&record.query_name, record.query_start, record.query_end)?
+            .map(|mut qualities| {
+                if record.strand == '-' {
+                    qualities.reverse();
+                }
+                qualities
+            }),
+        None => None,
+    };
 
     let mut q_idx: usize = 0;
     let mut t_idx: usize = 0;
     let mut errors: HashMap<ErrorType, ErrorInfo> = HashMap::new();
+    // The CIGAR walk below checks a cs tag's segment types; its bases are
+    // checked here.
+    if let Some(cs) = record.tag("cs:Z").filter(|_| record.cigar.is_empty()) {
+        if options.checks.contains(Check::Mismatch) {
+            let cs_ops = parse_cs(cs).context("Failed to parse cs tag")?;
+            for (cs_q_idx, cs_t_idx, message) in cs_base_errors(&cs_ops, query_seq, target_seq) {
+                let site = ErrorSite::new(
+                    0,
+                    record.query_start + cs_q_idx,
+                    record.target_start + cs_t_idx,
+                    homopolymer_run(target_seq, cs_t_idx),
+                );
+                record_error(&mut errors, ErrorType::CsBaseMismatch, message, site);
+            }
+        }
+    }
+    for message in masked_spans {
+        let site = ErrorSite::new(
+            0,
+            record.query_start,
+            record.target_start,
+            homopolymer_run(target_seq, 0),
+        );
+        record_error(&mut errors, ErrorType::MaskedAlignment, message, site);
+    }
 
+    let mut overrun = false;
     for (op_idx, op) in cigar_ops.iter().enumerate() {
+        // The rest of the CIGAR can't be compared once it runs past either span.
+        let (q_len, t_len) = op.consumed();
+        for (error_type, role, name, start, end, idx, len, seq_len) in [
+            (
+                ErrorType::CigarOverrunsQuery,
+                "query",
+                &record.query_name,
+                record.query_start,
+                record.query_end,
+                q_idx,
+                q_len,
+                query_seq.len(),
+            ),
+            (
+                ErrorType::CigarOverrunsTarget,
+                "target",
+                &record.target_name,
+                record.target_start,
+                record.target_end,
+                t_idx,
+                t_len,
+                target_seq.len(),
+            ),
+        ] {
+            if idx + len <= seq_len {
+                continue;
+            }
+            overrun = true;
+            if options.checks.contains(Check::Length) {
+                let error_message = format!(
+                    "CIGAR overruns the {} at operation {} ({}{}): it reaches {} of the {} bases of {}:{}-{}",
+                    role,
+                    op_idx,
+                    op.count(),
+                    op.symbol(),
+                    idx + len,
+                    seq_len,
+                    name,
+                    start,
+                    end
+                );
+                let site = ErrorSite::new(
+                    op_idx,
+                    record.query_start + q_idx.min(query_seq.len()),
+                    record.target_start + t_idx.min(target_seq.len()),
+                    homopolymer_run(target_seq, t_idx),
+                );
+                record_error(&mut errors, error_type, error_message, site);
+            }
+        }
+        if overrun {
+            break;
+        }
         match op {
             CigarOp::Match(len) | CigarOp::Mismatch(len) => {
                 let len = *len as usize;
-                let q_slice = query_seq
-                    .get(q_idx..q_idx + len)
-                    .ok_or_else(|| anyhow::anyhow!("Query sequence index out of range"))?;
-                let t_slice = target_seq
-                    .get(t_idx..t_idx + len)
-                    .ok_or_else(|| anyhow::anyhow!("Target sequence index out of range"))?;
+                let q_slice = &compared_query[q_idx..q_idx + len];
+                let t_slice = &compared_target[t_idx..t_idx + len];
 
+                let check = if matches!(op, CigarOp::Match(_)) {
+                    Check::Mismatch
+                } else {
+                    Check::CigarMismatch
+                };
                 for i in 0..len {
+                    if let Some(excluded) = &excluded {
+                        if excluded.get(t_idx + i).copied().unwrap_or(false) {
+                            continue;
+                        }
+                    }
                     let q = q_slice[i];
                     let t = t_slice[i];
-                    let is_match = q == t;
+                    let has_n = q.eq_ignore_ascii_case(&b'N') || t.eq_ignore_ascii_case(&b'N');
+                    let is_match = match options.n_policy {
+                        NPolicy::Skip if has_n => continue,
+                        NPolicy::MatchAny if has_n => true,
+                        _ => q == t,
+                    };
                     let expected_match = matches!(op, CigarOp::Match(_));
 
-                    if is_match != expected_match {
-                        let error_type = if expected_match {
-                            ErrorType::Mismatch
-                        } else {
-                            ErrorType::CigarMismatch
+                    if is_match != expected_match && options.checks.contains(check) {
+                        let low_quality = qualities
+                            .as_ref()
+                            .zip(options.min_base_quality)
+                            .and_then(|(qualities, min)| {
+                                qualities.get(q_idx + i).filter(|&&quality| quality < min)
+                            });
+                        let error_type = match (expected_match, low_quality) {
+                            (true, None) => ErrorType::Mismatch,
+                            (true, Some(_)) => ErrorType::LowQualityMismatch,
+                            (false, _) => ErrorType::CigarMismatch,
                         };
 
-                        let error_message = format!(
+                        let mut error_message = format!(
                             "CIGAR mismatch at operation {}: query char '{}' at pos {} vs target char '{}' at pos {}",
                             op_idx, q as char, record.query_start + q_idx + i, t as char, record.target_start + t_idx + i
                         );
+                        if let (true, Some(quality)) = (expected_match, low_quality) {
+                            error_message.push_str(&format!(" (query base quality {})", quality));
+                        }
 
-                        errors
-                            .entry(error_type)
-                            .and_modify(|e| e.count += 1)
-                            .or_insert(ErrorInfo {
-                                first_message: error_message,
-                                count: 1,
-                            });
+                        let site = ErrorSite::new(
+                            op_idx,
+                            record.query_start + q_idx + i,
+                            record.target_start + t_idx + i,
+                            homopolymer_run(target_seq, t_idx + i),
+                        );
+                        record_error(&mut errors, error_type, error_message, site);
                     }
                 }
                 q_idx += len;
                 t_idx += len;
             }
-            CigarOp::Insertion(len) => {
-                q_idx += *len as usize;
+            CigarOp::AlignmentMatch(len) => {
+                let len = *len as usize;
+                if options.require_extended_cigar {
+                    let error_message = format!(
+                        "M operation at operation {} (target {}:{}-{}) doesn't say whether its bases match; use = and X",
+                        op_idx,
+                        record.target_name,
+                        record.target_start + t_idx,
+                        record.target_start + t_idx + len
+                    );
+                    let site = ErrorSite::new(
+                        op_idx,
+                        record.query_start + q_idx,
+                        record.target_start + t_idx,
+                        homopolymer_run(target_seq, t_idx),
+                    );
+                    record_error(
+                        &mut errors,
+                        ErrorType::NonExtendedCigar,
+                        error_message,
+                        site,
+                    );
+                }
+                q_idx += len;
+                t_idx += len;
             }
-            CigarOp::Deletion(len) => {
-                t_idx += *len as usize;
+            CigarOp::Insertion(len) | CigarOp::Deletion(len) => {
+                let len = *len as usize;
+                let kind = IndelKind::of(op).unwrap();
+                let is_insertion = kind == IndelKind::Insertion;
+                if options.max_indel_len.is_some_and(|max| len > max) {
+                    let t_end = if is_insertion { t_idx } else { t_idx + len };
+                    let error_message = format!(
+                        "{}-bp {} at operation {} exceeds --max-indel-len (target {}:{}-{})",
+                        len,
+                        kind,
+                        op_idx,
+                        record.target_name,
+                        record.target_start + t_idx,
+                        record.target_start + t_end
+                    );
+                    let site = ErrorSite::new(
+                        op_idx,
+                        record.query_start + q_idx,
+                        record.target_start + t_idx,
+                        homopolymer_run(target_seq, t_idx),
+                    );
+                    record_error(&mut errors, ErrorType::LongIndel, error_message, site);
+                }
+                if is_insertion {
+                    q_idx += len;
+                } else {
+                    t_idx += len;
+                }
+            }
+            CigarOp::Skip(len) => {
+                let len = *len as usize;
+                if options.check_splice_sites {
+                    let donor = target_seq.get(t_idx..t_idx + 2.min(len));
+                    let acceptor = target_seq.get((t_idx + len).saturating_sub(2)..t_idx + len);
+                    if let (Some(donor), Some(acceptor)) = (donor, acceptor) {
+                        if !is_canonical_splice(donor, acceptor) {
+                            let error_message = format!(
+                                "Non-canonical splice junction at operation {}: {}-{} intron at target {}-{}",
+                                op_idx,
+                                String::from_utf8_lossy(donor),
+                                String::from_utf8_lossy(acceptor),
+                                record.target_start + t_idx,
+                                record.target_start + t_idx + len
+                            );
+                            let site = ErrorSite::new(
+                                op_idx,
+                                record.query_start + q_idx,
+                                record.target_start + t_idx,
+                                homopolymer_run(target_seq, t_idx),
+                            );
+                            record_error(
+                                &mut errors,
+                                ErrorType::NonCanonicalSplice,
+                                error_message,
+                                site,
+                            );
+                        }
+                    }
+                }
+                t_idx += len;
             }
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
         }
     }
 
-    if q_idx != query_seq.len() {
-        let error_type = ErrorType::LengthMismatch;
+    check_boundary_indels(record, &cigar_ops, target_seq, options, &mut errors);
+    if options.checks.contains(Check::Coordinates) {
+        check_clips(record, &cigar_ops, target_seq, &mut errors);
+    }
+
+    let end_site = ErrorSite::new(
+        cigar_ops.len(),
+        record.query_start + q_idx,
+        record.target_start + t_idx,
+        homopolymer_run(target_seq, t_idx),
+    );
+    // An overrun is reported instead of the length mismatch it implies.
+    let check_length = options.checks.contains(Check::Length) && !overrun;
+    if check_length && q_idx != query_seq.len() {
         let error_message = format!(
             "Query sequence length mismatch: CIGAR implies {}, actual length {}",
             q_idx,
             query_seq.len()
         );
-        errors
-            .entry(error_type)
-            .and_modify(|e| e.count += 1)
-            .or_insert(ErrorInfo {
-                first_message: error_message,
-                count: 1,
-            });
-    }
-    if t_idx != target_seq.len() {
-        let error_type = ErrorType::LengthMismatch;
+        record_error(
+            &mut errors,
+            ErrorType::LengthMismatch,
+            error_message,
+            end_site.clone(),
+        );
+    }
+    if check_length && t_idx != target_seq.len() {
         let error_message = format!(
             "Target sequence length mismatch: CIGAR implies {}, actual length {}",
             t_idx,
             target_seq.len()
         );
-        errors
-            .entry(error_type)
-            .and_modify(|e| e.count += 1)
-            .or_insert(ErrorInfo {
-                first_message: error_message,
-                count: 1,
-            });
+        record_error(
+            &mut errors,
+            ErrorType::LengthMismatch,
+            error_message,
+            end_site,
+        );
+    }
+
+    // `tag_errors` can't check the NM:i of CIGARs with `M` ops, whose
+    // mismatches only the sequences tell.
+    let nm = record
+        .tag("NM:i")
+        .filter(|_| options.checks.contains(Check::Tags) && !overrun);
+    if let Some(nm) = nm.filter(|_| {
+        cigar_ops
+            .iter()
+            .any(|op| matches!(op, CigarOp::AlignmentMatch(_)))
+    }) {
+        let edit_distance = sequence_stats(&cigar_ops, query_seq, target_seq)?.edit_distance();
+        if nm.parse::<usize>().ok() != Some(edit_distance) {
+            let error_message = format!(
+                "NM:i is {}, but the alignment has an edit distance of {}",
+                nm, edit_distance
+            );
+            let site = ErrorSite::new(
+                0,
+                record.query_start,
+                record.target_start,
+                homopolymer_run(target_seq, 0),
+            );
+            record_error(&mut errors, ErrorType::TagMismatch, error_message, site);
+        }
+    }
+
+    if let Some(min_identity) = options.min_identity.filter(|_| !overrun) {
+        let stats = sequence_stats(&cigar_ops, query_seq, target_seq)?;
+        let identity = stats.blast_identity();
+        if identity < min_identity {
+            let error_message = format!(
+                "Identity {:.4} is below the minimum of {}: {} identical bases in {} alignment columns",
+                identity,
+                min_identity,
+                stats.identical,
+                stats.aligned + stats.inserted_bases + stats.deleted_bases
+            );
+            let site = ErrorSite::new(
+                0,
+                record.query_start,
+                record.target_start,
+                homopolymer_run(target_seq, 0),
+            );
+            record_error(&mut errors, ErrorType::LowIdentity, error_message, site);
+        }
     }
 
     if !errors.is_empty() {
+        // The DUST pass is only worth doing for records that have errors to annotate.
+        let mask = low_complexity_mask(target_seq, DUST_WINDOW, DUST_THRESHOLD);
+        for site in errors.values_mut().flat_map(|info| info.sites.iter_mut()) {
+            let idx = site.target_pos - record.target_start;
+            site.low_complexity = mask.get(idx).copied().unwrap_or(false);
+            site.gc_fraction = local_gc_fraction(target_seq, idx, GC_WINDOW);
+        }
+        if options.context_bases > 0 {
+            for error_type in [
+                ErrorType::Mismatch,
+                ErrorType::LowQualityMismatch,
+                ErrorType::CigarMismatch,
+            ] {
+                let Some(info) = errors.get_mut(&error_type) else {
+                    continue;
+                };
+                for site in &mut info.sites {
+                    site.context = Some(SiteContext::around(
+                        query_seq,
+                        site.query_pos - record.query_start,
+                        target_seq,
+                        site.target_pos - record.target_start,
+                        options.context_bases,
+                    ));
+                }
+            }
+        }
+
         if error_mode == "report" {
             for (error_type, error_info) in &errors {
                 writeln!(output, "{:?}: {}", error_type, error_info.first_message)?;
@@ -177,15 +1472,566 @@ pub fn validate_record<W: Write>(
     }
 }
 
-fn reverse_complement(seq: &str) -> String {
+pub(crate) fn record_error(
+    errors: &mut HashMap<ErrorType, ErrorInfo>,
+    error_type: ErrorType,
+    message: String,
+    site: ErrorSite,
+) {
+    errors
+        .entry(error_type)
+        .and_modify(|e| {
+            e.count += 1;
+            e.sites.push(site.clone());
+        })
+        .or_insert(ErrorInfo {
+            first_message: message,
+            count: 1,
+            sites: vec![site],
+        });
+}
+
+/// Fetches the aligned query and target spans, uppercased, with the query
+/// reverse-complemented for `-` strand records.
+pub fn fetch_aligned_sequences(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (mut query_seq, mut target_seq) = fetch_aligned_sequences_with_case(record, fasta_reader)?;
+    query_seq.make_ascii_uppercase();
+    target_seq.make_ascii_uppercase();
+    Ok((query_seq, target_seq))
+}
+
+/// Like `fetch_aligned_sequences`, but keeps soft-masked bases lowercase.
+pub fn fetch_aligned_sequences_with_case(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let query_seq = fasta_reader
+        .fetch_query_sequence(&record.query_name, record.query_start, record.query_end)
+        .context(format!(
+            "Failed to fetch query sequence: {} ({}:{})",
+            record.query_name, record.query_start, record.query_end
+        ))?;
+    let target_seq = fasta_reader
+        .fetch_target_sequence(&record.target_name, record.target_start, record.target_end)
+        .context(format!(
+            "Failed to fetch target sequence: {} ({}:{})",
+            record.target_name, record.target_start, record.target_end
+        ))?;
+
+    let query_seq = if record.strand == '-' {
+        reverse_complement(&query_seq)
+    } else {
+        query_seq
+    };
+
+    Ok((query_seq.into_bytes(), target_seq.into_bytes()))
+}
+
+/// Messages for the aligned spans of a record that are entirely soft-masked.
+fn masked_span_errors(record: &PafRecord, query_seq: &[u8], target_seq: &[u8]) -> Vec<String> {
+    let is_masked =
+        |seq: &[u8]| !seq.is_empty() && seq.iter().all(|base| !base.is_ascii_uppercase());
+    let mut errors = Vec::new();
+    for (role, name, start, end, seq) in [
+        (
+            "Query",
+            &record.query_name,
+            record.query_start,
+            record.query_end,
+            query_seq,
+        ),
+        (
+            "Target",
+            &record.target_name,
+            record.target_start,
+            record.target_end,
+            target_seq,
+        ),
+    ] {
+        if is_masked(seq) {
+            errors.push(format!(
+                "{} span {}:{}-{} is entirely soft-masked",
+                role, name, start, end
+            ));
+        }
+    }
+    errors
+}
+
+/// Identity implied by a record's CIGAR next to the one estimated from the
+/// minimizers shared by its aligned spans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdentityCheck {
+    pub cigar: f64,
+    /// `None` if a span is too short to contain any minimizer.
+    pub estimated: Option<f64>,
+}
+
+impl IdentityCheck {
+    /// Whether the two identities differ by more than `max_diff`.
+    pub fn disagrees(&self, max_diff: f64) -> bool {
+        self.estimated
+            .is_some_and(|estimated| (estimated - self.cigar).abs() > max_diff)
+    }
+}
+
+/// Cheap sanity check of a record: compares the CIGAR's identity with an
+/// estimate from shared minimizers, without walking the alignment.
+pub fn cross_check_identity(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+) -> Result<IdentityCheck> {
+    let (query_seq, target_seq) = fetch_aligned_sequences(record, fasta_reader)?;
+    let cigar_ops = alignment_ops(record)?;
+    Ok(IdentityCheck {
+        cigar: cigar_identity(&cigar_ops),
+        estimated: minimizer_identity(&query_seq, &target_seq, MINIMIZER_K, MINIMIZER_W),
+    })
+}
+
+/// Column counts of a record recomputed from its sequences.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentStats {
+    /// `=`/`X` columns whose bases are identical.
+    pub identical: usize,
+    /// `=`/`X` columns.
+    pub aligned: usize,
+    pub insertions: usize,
+    pub inserted_bases: usize,
+    pub deletions: usize,
+    pub deleted_bases: usize,
+}
+
+impl AlignmentStats {
+    /// BLAST-like identity: identical columns over all columns, counting
+    /// every inserted and deleted base.
+    pub fn blast_identity(&self) -> f64 {
+        let columns = self.aligned + self.inserted_bases + self.deleted_bases;
+        if columns == 0 {
+            0.0
+        } else {
+            self.identical as f64 / columns as f64
+        }
+    }
+
+    /// Gap-compressed identity: identical columns over aligned columns plus
+    /// one per indel.
+    pub fn gap_compressed_identity(&self) -> f64 {
+        let columns = self.aligned + self.insertions + self.deletions;
+        if columns == 0 {
+            0.0
+        } else {
+            self.identical as f64 / columns as f64
+        }
+    }
+
+    /// Alignment block length, as in column 11: aligned columns plus
+    /// inserted and deleted bases.
+    pub fn block_length(&self) -> usize {
+        self.aligned + self.inserted_bases + self.deleted_bases
+    }
+
+    /// Edit distance, as in `NM:i`: differing columns plus inserted and
+    /// deleted bases.
+    pub fn edit_distance(&self) -> usize {
+        self.aligned - self.identical + self.inserted_bases + self.deleted_bases
+    }
+}
+
+/// Recomputes a record's column counts from its sequences rather than
+/// trusting the CIGAR's `=`/`X` labels.
+pub fn alignment_stats(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+) -> Result<AlignmentStats> {
+    let (query_seq, target_seq) = fetch_aligned_sequences(record, fasta_reader)?;
+    sequence_stats(&alignment_ops(record)?, &query_seq, &target_seq)
+}
+
+/// Column counts of the aligned (uppercased) sequences under `ops`.
+pub fn sequence_stats(
+    ops: &[CigarOp],
+    query_seq: &[u8],
+    target_seq: &[u8],
+) -> Result<AlignmentStats> {
+    let mut q_idx: usize = 0;
+    let mut t_idx: usize = 0;
+    let mut stats = AlignmentStats::default();
+    for op in ops {
+        match op {
+            CigarOp::AlignmentMatch(len) | CigarOp::Match(len) | CigarOp::Mismatch(len) => {
+                let len = *len as usize;
+                let (q_slice, t_slice) = match (
+                    query_seq.get(q_idx..q_idx + len),
+                    target_seq.get(t_idx..t_idx + len),
+                ) {
+                    (Some(q), Some(t)) => (q, t),
+                    _ => anyhow::bail!("CIGAR extends past the aligned sequences"),
+                };
+                stats.identical += q_slice.iter().zip(t_slice).filter(|(q, t)| q == t).count();
+                stats.aligned += len;
+                q_idx += len;
+                t_idx += len;
+            }
+            CigarOp::Insertion(len) => {
+                q_idx += *len as usize;
+                stats.insertions += 1;
+                stats.inserted_bases += *len as usize;
+            }
+            CigarOp::Deletion(len) => {
+                t_idx += *len as usize;
+                stats.deletions += 1;
+                stats.deleted_bases += *len as usize;
+            }
+            CigarOp::Skip(len) => t_idx += *len as usize,
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    Ok(stats)
+}
+
+/// Gap-compressed identity of a record recomputed from its sequences.
+pub fn recompute_identity(record: &PafRecord, fasta_reader: &mut MultiFastaReader) -> Result<f64> {
+    Ok(alignment_stats(record, fasta_reader)?.gap_compressed_identity())
+}
+
+/// Validates a random `fraction` of the aligned (`=`/`X`) bases of a record
+/// and estimates its per-base error rate from the sample.
+pub fn sample_record(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    fraction: f64,
+    rng: &mut SplitMix64,
+) -> Result<RateEstimate> {
+    let (query_seq, target_seq) = fetch_aligned_sequences(record, fasta_reader)?;
+    let cigar_ops = alignment_ops(record)?;
+
+    let mut q_idx: usize = 0;
+    let mut t_idx: usize = 0;
+    let mut sampled = 0;
+    let mut errors = 0;
+    for op in &cigar_ops {
+        match op {
+            CigarOp::Match(len) | CigarOp::Mismatch(len) => {
+                let len = *len as usize;
+                let expected_match = matches!(op, CigarOp::Match(_));
+                for i in 0..len {
+                    if rng.next_f64() >= fraction {
+                        continue;
+                    }
+                    let (q, t) = match (query_seq.get(q_idx + i), target_seq.get(t_idx + i)) {
+                        (Some(q), Some(t)) => (q, t),
+                        _ => anyhow::bail!("CIGAR extends past the aligned sequences"),
+                    };
+                    sampled += 1;
+                    if (q == t) != expected_match {
+                        errors += 1;
+                    }
+                }
+                q_idx += len;
+                t_idx += len;
+            }
+            CigarOp::AlignmentMatch(len) => {
+                q_idx += *len as usize;
+                t_idx += *len as usize;
+            }
+            CigarOp::Insertion(len) => q_idx += *len as usize,
+            CigarOp::Deletion(len) | CigarOp::Skip(len) => t_idx += *len as usize,
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    Ok(RateEstimate::from_counts(errors, sampled))
+}
+
+/// Spot-checks a record by comparing only `anchors` evenly spaced aligned
+/// columns plus the first and last one, fetching single bases instead of the
+/// whole spans. Returns `false` if any anchor disagrees with its CIGAR op, in
+/// which case the record should be fully validated.
+pub fn spot_check_record(
+    record: &PafRecord,
+    fasta_reader: &mut MultiFastaReader,
+    anchors: usize,
+) -> Result<bool> {
+    let cigar_ops = alignment_ops(record)?;
+
+    // (query offset, target offset, length, expected match) of each =/X run
+    let mut segments = Vec::new();
+    let mut q_idx = 0;
+    let mut t_idx = 0;
+    let mut aligned = 0;
+    for op in &cigar_ops {
+        match op {
+            CigarOp::Match(len) | CigarOp::Mismatch(len) => {
+                let len = *len as usize;
+                segments.push((q_idx, t_idx, len, matches!(op, CigarOp::Match(_))));
+                q_idx += len;
+                t_idx += len;
+                aligned += len;
+            }
+            CigarOp::AlignmentMatch(len) => {
+                q_idx += *len as usize;
+                t_idx += *len as usize;
+            }
+            CigarOp::Insertion(len) => q_idx += *len as usize,
+            CigarOp::Deletion(len) | CigarOp::Skip(len) => t_idx += *len as usize,
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+    }
+    if aligned == 0 {
+        return Ok(true);
+    }
+
+    let mut columns: Vec<usize> = (0..=anchors + 1)
+        .map(|i| i * (aligned - 1) / (anchors + 1))
+        .collect();
+    columns.dedup();
+
+    let mut segment_iter = segments.iter();
+    let mut segment = segment_iter.next();
+    let mut segment_start = 0;
+    for column in columns {
+        while let Some(&(_, _, len, _)) = segment {
+            if column < segment_start + len {
+                break;
+            }
+            segment_start += len;
+            segment = segment_iter.next();
+        }
+        let &(q_off, t_off, _, expected_match) = match segment {
+            Some(segment) => segment,
+            None => break,
+        };
+        let q_off = q_off + column - segment_start;
+        let t_off = t_off + column - segment_start;
+        if record.target_start + t_off >= record.target_end
+            || record.query_start + q_off >= record.query_end
+        {
+            return Ok(false);
+        }
+
+        let q_pos = if record.strand == '-' {
+            record.query_end - 1 - q_off
+        } else {
+            record.query_start + q_off
+        };
+        let q_base = fasta_reader.fetch_query_sequence(&record.query_name, q_pos, q_pos + 1)?;
+        let q_base = if record.strand == '-' {
+            reverse_complement(&q_base)
+        } else {
+            q_base
+        };
+        let t_pos = record.target_start + t_off;
+        let t_base = fasta_reader.fetch_target_sequence(&record.target_name, t_pos, t_pos + 1)?;
+
+        if q_base.eq_ignore_ascii_case(&t_base) != expected_match {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether an indel inserts query bases or deletes target bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndelKind {
+    Insertion,
+    Deletion,
+}
+
+impl IndelKind {
+    /// The kind of an `I` or `D` op, `None` for other ops.
+    pub fn of(op: &CigarOp) -> Option<Self> {
+        match op {
+            CigarOp::Insertion(_) => Some(IndelKind::Insertion),
+            CigarOp::Deletion(_) => Some(IndelKind::Deletion),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for IndelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            IndelKind::Insertion => "insertion",
+            IndelKind::Deletion => "deletion",
+        })
+    }
+}
+
+/// An insertion or deletion an alignment begins or ends with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalIndel {
+    pub op_index: usize,
+    /// Whether the alignment ends with it rather than begins with it.
+    pub at_end: bool,
+    pub kind: IndelKind,
+    pub length: usize,
+}
+
+/// The insertions and deletions an alignment begins or ends with. Most
+/// downstream tools expect these to be absorbed into the coordinates.
+pub fn terminal_indels(cigar_ops: &[CigarOp]) -> Vec<TerminalIndel> {
+    // The same ops `fix::trim_terminal_indels` strips: all insertions and
+    // deletions outside the aligned ops, clips aside.
+    let Some((first, last)) = aligned_range(cigar_ops) else {
+        return Vec::new();
+    };
+    (0..first)
+        .map(|op_index| (op_index, false))
+        .chain((last + 1..cigar_ops.len()).map(|op_index| (op_index, true)))
+        .filter_map(|(op_index, at_end)| {
+            let op = &cigar_ops[op_index];
+            Some(TerminalIndel {
+                op_index,
+                at_end,
+                kind: IndelKind::of(op)?,
+                length: op.count() as usize,
+            })
+        })
+        .collect()
+}
+
+/// Reports terminal `I`/`D` ops according to the boundary indel policy.
+fn check_boundary_indels(
+    record: &PafRecord,
+    cigar_ops: &[CigarOp],
+    target_seq: &[u8],
+    options: &ValidationOptions,
+    errors: &mut HashMap<ErrorType, ErrorInfo>,
+) {
+    if !options.flag_boundary_indels && options.max_terminal_indel.is_none() {
+        return;
+    }
+    let consumed = |ops: &[CigarOp]| {
+        ops.iter().fold((0, 0), |(q, t), op| {
+            let (q_len, t_len) = op.consumed();
+            (q + q_len, t + t_len)
+        })
+    };
+    for indel in terminal_indels(cigar_ops) {
+        let too_long = options
+            .max_terminal_indel
+            .is_some_and(|max| indel.length > max);
+        if !options.flag_boundary_indels && !too_long {
+            continue;
+        }
+        // Leading ops are placed from the start of the alignment, trailing
+        // ones from its end.
+        let (query_pos, target_pos, t_idx) = if indel.at_end {
+            let (q_len, t_len) = consumed(&cigar_ops[indel.op_index..]);
+            (
+                record.query_end.saturating_sub(q_len),
+                record.target_end.saturating_sub(t_len),
+                target_seq.len().saturating_sub(t_len),
+            )
+        } else {
+            let (q_off, t_off) = consumed(&cigar_ops[..indel.op_index]);
+            (
+                record.query_start + q_off,
+                record.target_start + t_off,
+                t_off,
+            )
+        };
+        let error_message = format!(
+            "Alignment {} with a {}-bp {} at operation {} (target pos {})",
+            if indel.at_end { "ends" } else { "begins" },
+            indel.length,
+            indel.kind,
+            indel.op_index,
+            target_pos
+        );
+        let site = ErrorSite::new(
+            indel.op_index,
+            query_pos,
+            target_pos,
+            homopolymer_run(target_seq, t_idx),
+        );
+        record_error(errors, ErrorType::BoundaryIndel, error_message, site);
+    }
+}
+
+/// Checks that clipped bases plus the aligned query span add up to the
+/// declared query length, and that the leading clip matches the query start.
+/// On the `-` strand the CIGAR's leading clip is at the end of the query.
+fn check_clips(
+    record: &PafRecord,
+    cigar_ops: &[CigarOp],
+    target_seq: &[u8],
+    errors: &mut HashMap<ErrorType, ErrorInfo>,
+) {
+    if !cigar_ops.iter().any(CigarOp::is_clip) {
+        return;
+    }
+    let (leading, trailing) = clip_lengths(cigar_ops);
+    let (clip_before, clip_after) = if record.strand == '-' {
+        (trailing, leading)
+    } else {
+        (leading, trailing)
+    };
+    let span = record.query_end.saturating_sub(record.query_start);
+    let implied_length = clip_before + span + clip_after;
+    let error_message = if implied_length != record.query_length {
+        format!(
+            "Clipped bases ({} + {}) plus aligned span {} imply query length {}, PAF declares {}",
+            clip_before, clip_after, span, implied_length, record.query_length
+        )
+    } else if clip_before != record.query_start {
+        format!(
+            "{} bases are clipped before the alignment but the query starts at {}",
+            clip_before, record.query_start
+        )
+    } else {
+        return;
+    };
+    let site = ErrorSite::new(
+        0,
+        record.query_start,
+        record.target_start,
+        homopolymer_run(target_seq, 0),
+    );
+    record_error(errors, ErrorType::ClipMismatch, error_message, site);
+}
+
+/// Whether an intron's donor and acceptor dinucleotides (on the target's
+/// forward strand) form a GT-AG, GC-AG or AT-AC junction on either strand.
+fn is_canonical_splice(donor: &[u8], acceptor: &[u8]) -> bool {
+    matches!(
+        (donor, acceptor),
+        (b"GT", b"AG")
+            | (b"GC", b"AG")
+            | (b"AT", b"AC")
+            | (b"CT", b"AC")
+            | (b"CT", b"GC")
+            | (b"GT", b"AT")
+    )
+}
+
+/// Returns the length of the run of identical bases in `seq` covering `idx`.
+fn homopolymer_run(seq: &[u8], idx: usize) -> usize {
+    let base = match seq.get(idx) {
+        Some(base) => *base,
+        None => return 0,
+    };
+    let left = seq[..idx].iter().rev().take_while(|&&b| b == base).count();
+    let right = seq[idx + 1..].iter().take_while(|&&b| b == base).count();
+    left + 1 + right
+}
+
+pub(crate) fn reverse_complement(seq: &str) -> String {
     seq.chars()
         .rev()
         .map(|base| match base {
-            'A' | 'a' => 'T',
-            'T' | 't' => 'A',
-            'G' | 'g' => 'C',
-            'C' | 'c' => 'G',
-            'N' | 'n' => 'N',
+            'A' => 'T',
+            'T' => 'A',
+            'G' => 'C',
+            'C' => 'G',
+            'a' => 't',
+            't' => 'a',
+            'g' => 'c',
+            'c' => 'g',
+            'n' => 'n',
             _ => 'N',
         })
         .collect()
@@ -197,6 +2043,35 @@ mod tests {
     use crate::paf_parser::PafRecord;
     use std::io::BufWriter;
 
+    #[test]
+    fn test_homopolymer_run() {
+        let seq = b"ACCCCGTTA";
+        assert_eq!(homopolymer_run(seq, 0), 1);
+        assert_eq!(homopolymer_run(seq, 2), 4);
+        assert_eq!(homopolymer_run(seq, 7), 2);
+        assert_eq!(homopolymer_run(seq, 9), 0);
+    }
+
+    #[test]
+    fn test_terminal_indels() {
+        let ops = parse_cigar("2S2I3D10=1X5=1D").unwrap();
+        let indel = |op_index, at_end, kind, length| TerminalIndel {
+            op_index,
+            at_end,
+            kind,
+            length,
+        };
+        assert_eq!(
+            terminal_indels(&ops),
+            [
+                indel(1, false, IndelKind::Insertion, 2),
+                indel(2, false, IndelKind::Deletion, 3),
+                indel(6, true, IndelKind::Deletion, 1)
+            ]
+        );
+        assert!(terminal_indels(&parse_cigar("5=1I5=").unwrap()).is_empty());
+    }
+
     #[test]
     fn test_false_mismatch_detection() {
         let query_fasta_content = ">query\nACTGACTGACTG";
@@ -213,7 +2088,11 @@ mod tests {
             target_length: 12,
             target_start: 0,
             target_end: 12,
+            residue_matches: 0,
+            block_length: 0,
+            mapq: Some(60),
             cigar: cigar.to_string(),
+            tags: Vec::new(),
         };
 
         let mut fasta_reader =
@@ -264,7 +2143,11 @@ mod tests {
             target_length: 12,
             target_start: 0,
             target_end: 12,
+            residue_matches: 0,
+            block_length: 0,
+            mapq: Some(60),
             cigar: cigar.to_string(),
+            tags: Vec::new(),
         };
 
         let mut fasta_reader =
@@ -0,0 +1,189 @@
+use crate::cigar_parser::CigarOp;
+use crate::fix::{left_align_indels, resegment_cigar};
+use anyhow::Result;
+use std::io::Write;
+
+/// A variant of the query against the target: a 0-based target position and
+/// the REF and ALT alleles of its VCF record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub position: usize,
+    pub reference: String,
+    pub alternate: String,
+}
+
+impl Variant {
+    fn new(position: usize, reference: &[u8], alternate: &[u8]) -> Self {
+        Variant {
+            position,
+            reference: String::from_utf8_lossy(reference).into_owned(),
+            alternate: String::from_utf8_lossy(alternate).into_owned(),
+        }
+    }
+}
+
+/// The SNVs and indels implied by `ops` over the aligned (uppercased)
+/// sequences of a record whose target interval starts at `target_start`.
+/// The `=`/`X` runs are recomputed from the sequences and indels
+/// left-aligned, so the variants are consistent with the sequences and
+/// normalized. Indels are anchored on the target base before them, which is
+/// `anchor` for an indel at the start of the alignment; without one (at the
+/// start of the target) they are anchored on the base after them instead,
+/// as VCF does at position 1.
+pub fn alignment_variants(
+    ops: &[CigarOp],
+    query_seq: &[u8],
+    target_seq: &[u8],
+    target_start: usize,
+    anchor: Option<u8>,
+) -> Result<Vec<Variant>> {
+    let ops = resegment_cigar(ops, query_seq, target_seq)?;
+    let ops = left_align_indels(&ops, query_seq, target_seq);
+    let mut variants = Vec::new();
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in &ops {
+        let (q_len, t_len) = op.consumed();
+        let inserted = &query_seq[q_idx..q_idx + q_len];
+        let deleted = &target_seq[t_idx..t_idx + t_len];
+        let before = match t_idx {
+            0 => anchor,
+            _ => Some(target_seq[t_idx - 1]),
+        };
+        match op {
+            CigarOp::Mismatch(_) => {
+                for (i, (q, t)) in inserted.iter().zip(deleted).enumerate() {
+                    variants.push(Variant::new(target_start + t_idx + i, &[*t], &[*q]));
+                }
+            }
+            CigarOp::Insertion(_) | CigarOp::Deletion(_) => {
+                let variant = match (before, target_seq.get(t_idx + t_len)) {
+                    (Some(base), _) => Variant::new(
+                        target_start + t_idx - 1,
+                        &[&[base], deleted].concat(),
+                        &[&[base], inserted].concat(),
+                    ),
+                    (None, Some(&base)) => Variant::new(
+                        target_start + t_idx,
+                        &[deleted, &[base]].concat(),
+                        &[inserted, &[base]].concat(),
+                    ),
+                    // An alignment of nothing but the indel has no base to anchor it.
+                    (None, None) => {
+                        anyhow::bail!("Indel at the start of the target can't be anchored")
+                    }
+                };
+                variants.push(variant);
+            }
+            _ => {}
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+    Ok(variants)
+}
+
+/// A variant with the target and query it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantRecord {
+    pub target_name: String,
+    pub variant: Variant,
+    pub query_name: String,
+    pub strand: char,
+}
+
+/// Writes a VCF 4.2 file of `records` against the `contigs` (name, length)
+/// of the target FASTA, sorted by contig (in `contigs` order, then by name)
+/// and position. Each record names the query it comes from in its INFO.
+pub fn write_vcf<W: Write>(
+    output: &mut W,
+    reference: &str,
+    contigs: &[(String, usize)],
+    mut records: Vec<VariantRecord>,
+) -> Result<()> {
+    writeln!(output, "##fileformat=VCFv4.2")?;
+    writeln!(output, "##source=pafcheck")?;
+    writeln!(output, "##reference={}", reference)?;
+    for (name, length) in contigs {
+        writeln!(output, "##contig=<ID={},length={}>", name, length)?;
+    }
+    writeln!(
+        output,
+        "##INFO=<ID=QNAME,Number=1,Type=String,Description=\"Query sequence of the alignment implying the variant\">"
+    )?;
+    writeln!(
+        output,
+        "##INFO=<ID=QSTRAND,Number=1,Type=Character,Description=\"Strand of the query in the alignment\">"
+    )?;
+    writeln!(output, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+
+    let contig_index = |name: &str| {
+        contigs
+            .iter()
+            .position(|(contig, _)| contig == name)
+            .unwrap_or(contigs.len())
+    };
+    records.sort_by(|a, b| {
+        (
+            contig_index(&a.target_name),
+            &a.target_name,
+            a.variant.position,
+        )
+            .cmp(&(
+                contig_index(&b.target_name),
+                &b.target_name,
+                b.variant.position,
+            ))
+    });
+    for record in &records {
+        writeln!(
+            output,
+            "{}\t{}\t.\t{}\t{}\t.\tPASS\tQNAME={};QSTRAND={}",
+            record.target_name,
+            record.variant.position + 1,
+            record.variant.reference,
+            record.variant.alternate,
+            record.query_name,
+            record.strand
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cigar_parser::parse_cigar;
+
+    fn variant(position: usize, reference: &str, alternate: &str) -> Variant {
+        Variant::new(position, reference.as_bytes(), alternate.as_bytes())
+    }
+
+    #[test]
+    fn test_alignment_variants() {
+        // An SNV, a T inserted after a TT run (left-aligned to its start),
+        // and a deletion of one A of AA.
+        let query = b"ACGTTTGACGCAGT";
+        let target = b"ACCTTGACGCAAGT";
+        let ops = parse_cigar("5M1I6M1D2M").unwrap();
+        assert_eq!(
+            alignment_variants(&ops, query, target, 100, Some(b'N')).unwrap(),
+            [
+                variant(102, "C", "G"),
+                variant(102, "C", "CT"),
+                variant(109, "CA", "C"),
+            ]
+        );
+
+        // A leading deletion is anchored on the base before the alignment,
+        // or at the start of the target on the base after it.
+        let ops = parse_cigar("2D2=").unwrap();
+        assert_eq!(
+            alignment_variants(&ops, b"GT", b"ACGT", 10, Some(b'T')).unwrap(),
+            [variant(9, "TAC", "T")]
+        );
+        assert_eq!(
+            alignment_variants(&ops, b"GT", b"ACGT", 0, None).unwrap(),
+            [variant(0, "ACG", "G")]
+        );
+    }
+}
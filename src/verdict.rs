@@ -0,0 +1,255 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// Tag added to each record by `--annotated-output`.
+pub const VERDICT_TAG: &str = "vd:Z:";
+
+#[derive(Debug, PartialEq)]
+enum LineKind {
+    Record,
+    /// A record left out by the record filters.
+    Skipped,
+    /// A line that isn't a record, written unchanged.
+    Other,
+}
+
+/// Which lines a `VerdictWriter` writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Selection {
+    /// Every line, with a verdict tag on each record.
+    All,
+    /// Records with errors or that couldn't be checked, tagged.
+    Failing,
+    /// Records checked without errors, and other lines, unchanged.
+    Passing,
+}
+
+#[derive(Debug)]
+struct QueuedLine {
+    line_number: usize,
+    text: String,
+    kind: LineKind,
+    /// Error counts per type, in upper snake case.
+    errors: Vec<(String, usize)>,
+    unchecked: bool,
+}
+
+/// Writes PAF lines back out with a `vd:Z` tag holding the validation
+/// result of each record: `PASS`, the error counts per type (e.g.
+/// `MISMATCH,3;LENGTH_MISMATCH,1`), `UNCHECKED` for records that could not be
+/// checked, or `SKIPPED` for filtered records.
+///
+/// Lines are queued until their records have been validated, so that records
+/// validated on other threads are still written in input order.
+pub struct VerdictWriter<W: Write> {
+    output: W,
+    queued: VecDeque<QueuedLine>,
+    selection: Selection,
+}
+
+impl<W: Write> VerdictWriter<W> {
+    pub fn new(output: W) -> Self {
+        VerdictWriter {
+            output,
+            queued: VecDeque::new(),
+            selection: Selection::All,
+        }
+    }
+
+    /// A writer that only writes the records with errors or that could not
+    /// be checked, for `--failing-output`.
+    pub fn failing_only(output: W) -> Self {
+        VerdictWriter {
+            selection: Selection::Failing,
+            ..Self::new(output)
+        }
+    }
+
+    /// A writer that passes through the records checked without errors,
+    /// untagged, and drops the others, for `--filter`.
+    pub fn passing_only(output: W) -> Self {
+        VerdictWriter {
+            selection: Selection::Passing,
+            ..Self::new(output)
+        }
+    }
+
+    pub fn push_record(&mut self, line_number: usize, text: &str) {
+        self.push(line_number, text, LineKind::Record);
+    }
+
+    pub fn push_skipped(&mut self, line_number: usize, text: &str) {
+        self.push(line_number, text, LineKind::Skipped);
+    }
+
+    pub fn push_other(&mut self, line_number: usize, text: &str) {
+        self.push(line_number, text, LineKind::Other);
+    }
+
+    fn push(&mut self, line_number: usize, text: &str, kind: LineKind) {
+        self.queued.push_back(QueuedLine {
+            line_number,
+            text: text.to_string(),
+            kind,
+            errors: Vec::new(),
+            unchecked: false,
+        });
+    }
+
+    /// Adds `count` errors of `error_type` (a report name like
+    /// `LengthMismatch`), or a failure to check the record if `None`, to the
+    /// queued record at `line_number`.
+    pub fn note(&mut self, line_number: usize, error_type: Option<&str>, count: usize) {
+        let Some(queued) = self
+            .queued
+            .iter_mut()
+            .rev()
+            .find(|queued| queued.line_number == line_number)
+        else {
+            return;
+        };
+        match error_type {
+            Some(error_type) => {
+                let label = upper_snake_case(error_type);
+                match queued.errors.iter_mut().find(|(l, _)| *l == label) {
+                    Some((_, total)) => *total += count,
+                    None => queued.errors.push((label, count)),
+                }
+            }
+            None => queued.unchecked = true,
+        }
+    }
+
+    /// Writes out all queued lines; call once their records are validated.
+    pub fn flush_queued(&mut self) -> Result<()> {
+        while let Some(queued) = self.queued.pop_front() {
+            let failed = !queued.errors.is_empty() || queued.unchecked;
+            let written = match self.selection {
+                Selection::All => true,
+                Selection::Failing => queued.kind == LineKind::Record && failed,
+                Selection::Passing => match queued.kind {
+                    LineKind::Record => !failed,
+                    LineKind::Skipped => false,
+                    LineKind::Other => true,
+                },
+            };
+            if !written {
+                continue;
+            }
+            if queued.kind == LineKind::Other || self.selection == Selection::Passing {
+                writeln!(self.output, "{}", queued.text)?;
+                continue;
+            }
+            let mut verdict: Vec<String> = queued
+                .errors
+                .iter()
+                .map(|(label, count)| format!("{},{}", label, count))
+                .collect();
+            if queued.unchecked {
+                verdict.push("UNCHECKED".to_string());
+            }
+            let verdict = match (&queued.kind, verdict.is_empty()) {
+                (LineKind::Skipped, _) => "SKIPPED".to_string(),
+                (_, true) => "PASS".to_string(),
+                (_, false) => verdict.join(";"),
+            };
+            let fields: Vec<&str> = queued
+                .text
+                .split('\t')
+                .filter(|field| !field.starts_with(VERDICT_TAG))
+                .collect();
+            writeln!(
+                self.output,
+                "{}\t{}{}",
+                fields.join("\t"),
+                VERDICT_TAG,
+                verdict
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_queued()?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+/// `LengthMismatch` -> `LENGTH_MISMATCH`.
+fn upper_snake_case(name: &str) -> String {
+    let mut label = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && !label.is_empty() {
+            label.push('_');
+        }
+        label.push(c.to_ascii_uppercase());
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict_tags() {
+        let mut writer = VerdictWriter::new(Vec::new());
+        writer.push_record(1, "q\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60\tcg:Z:12=");
+        writer.push_record(2, "q\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60\tvd:Z:PASS");
+        writer.push_skipped(3, "r\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.push_record(4, "s\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.note(2, Some("Mismatch"), 3);
+        writer.note(2, Some("LengthMismatch"), 1);
+        writer.note(2, Some("Mismatch"), 1);
+        writer.note(4, None, 1);
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let tags: Vec<&str> = output
+            .lines()
+            .map(|line| line.rsplit('\t').next().unwrap())
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                "vd:Z:PASS",
+                "vd:Z:MISMATCH,4;LENGTH_MISMATCH,1",
+                "vd:Z:SKIPPED",
+                "vd:Z:UNCHECKED"
+            ]
+        );
+        assert_eq!(output.matches("vd:Z:").count(), 4);
+    }
+
+    #[test]
+    fn test_failing_only() {
+        let mut writer = VerdictWriter::failing_only(Vec::new());
+        writer.push_other(1, "##PAF\tdetail");
+        writer.push_record(2, "q\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.push_record(3, "r\t12\t0\t12\t+\tt\t12\t0\t12\t11\t12\t60");
+        writer.push_skipped(4, "s\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.push_record(5, "u\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.note(3, Some("Mismatch"), 1);
+        writer.note(5, None, 1);
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "r\t12\t0\t12\t+\tt\t12\t0\t12\t11\t12\t60\tvd:Z:MISMATCH,1\n\
+             u\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60\tvd:Z:UNCHECKED\n"
+        );
+    }
+
+    #[test]
+    fn test_passing_only() {
+        let mut writer = VerdictWriter::passing_only(Vec::new());
+        writer.push_record(1, "q\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60\tvd:Z:PASS");
+        writer.push_record(2, "r\t12\t0\t12\t+\tt\t12\t0\t12\t11\t12\t60");
+        writer.push_skipped(3, "s\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60");
+        writer.note(2, Some("Mismatch"), 1);
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "q\t12\t0\t12\t+\tt\t12\t0\t12\t12\t12\t60\tvd:Z:PASS\n"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::cigar_parser::CigarOp;
+use crate::paf_parser::PafRecord;
+use anyhow::Result;
+
+/// Identity of one window of an alignment, with the target interval it
+/// spans and the query interval aligned to it (forward-strand coordinates).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityWindow {
+    pub target_start: usize,
+    pub target_end: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+    /// Identical columns over all columns in the window, counting each
+    /// inserted and deleted base.
+    pub identity: f64,
+}
+
+/// Identity in windows of `window` target bases, `step` bases apart, along
+/// the alignment of a record's aligned (uppercased) sequences under `ops`.
+/// The last window ends at the end of the alignment, and alignments shorter
+/// than `window` get one window. Bases are compared rather than trusting the
+/// `=`/`X` labels; insertions count towards the window of the target base
+/// they follow.
+pub fn identity_windows(
+    record: &PafRecord,
+    ops: &[CigarOp],
+    query_seq: &[u8],
+    target_seq: &[u8],
+    window: usize,
+    step: usize,
+) -> Result<Vec<IdentityWindow>> {
+    if window == 0 || step == 0 {
+        anyhow::bail!("Window size and step must be positive");
+    }
+    // Prefix sums per target offset: identical columns, all columns, and
+    // the query offset aligned to it.
+    let span = target_seq.len();
+    let mut identical = vec![0; span + 1];
+    let mut columns = vec![0; span + 1];
+    let mut query_offsets = vec![0; span + 1];
+    let (mut q_idx, mut t_idx) = (0, 0);
+    for op in ops {
+        let (q_len, t_len) = op.consumed();
+        if q_idx + q_len > query_seq.len() || t_idx + t_len > span {
+            anyhow::bail!("CIGAR extends past the aligned sequences");
+        }
+        match op {
+            CigarOp::AlignmentMatch(_) | CigarOp::Match(_) | CigarOp::Mismatch(_) => {
+                for i in 0..t_len {
+                    let same = query_seq[q_idx + i] == target_seq[t_idx + i];
+                    identical[t_idx + i + 1] = identical[t_idx + i] + usize::from(same);
+                    columns[t_idx + i + 1] = columns[t_idx + i] + 1;
+                    query_offsets[t_idx + i + 1] = q_idx + i + 1;
+                }
+            }
+            CigarOp::Insertion(_) => {
+                // Added to the column count of the last target base so far.
+                columns[t_idx] += q_len;
+                query_offsets[t_idx] += q_len;
+            }
+            CigarOp::Deletion(_) => {
+                for i in 0..t_len {
+                    identical[t_idx + i + 1] = identical[t_idx + i];
+                    columns[t_idx + i + 1] = columns[t_idx + i] + 1;
+                    query_offsets[t_idx + i + 1] = q_idx;
+                }
+            }
+            CigarOp::Skip(_) => {
+                for i in 0..t_len {
+                    identical[t_idx + i + 1] = identical[t_idx + i];
+                    columns[t_idx + i + 1] = columns[t_idx + i];
+                    query_offsets[t_idx + i + 1] = q_idx;
+                }
+            }
+            CigarOp::SoftClip(_) | CigarOp::HardClip(_) => {}
+        }
+        q_idx += q_len;
+        t_idx += t_len;
+    }
+
+    let mut starts: Vec<usize> = (0..span)
+        .step_by(step)
+        .take_while(|start| start + window <= span)
+        .collect();
+    if span > 0 && starts.last().is_none_or(|start| start + window < span) {
+        starts.push(span.saturating_sub(window));
+    }
+    Ok(starts
+        .into_iter()
+        .map(|start| {
+            let end = (start + window).min(span);
+            // A window from the start includes leading insertions.
+            let (identical_before, columns_before, query_before) = match start {
+                0 => (0, 0, 0),
+                _ => (identical[start], columns[start], query_offsets[start]),
+            };
+            let window_columns = columns[end] - columns_before;
+            let identity = if window_columns == 0 {
+                0.0
+            } else {
+                (identical[end] - identical_before) as f64 / window_columns as f64
+            };
+            let (query_start, query_end) = if record.strand == '-' {
+                (
+                    record.query_end - query_offsets[end],
+                    record.query_end - query_before,
+                )
+            } else {
+                (
+                    record.query_start + query_before,
+                    record.query_start + query_offsets[end],
+                )
+            };
+            IdentityWindow {
+                target_start: record.target_start + start,
+                target_end: record.target_start + end,
+                query_start,
+                query_end,
+                identity,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cigar_parser::parse_cigar;
+
+    #[test]
+    fn test_identity_windows() {
+        // A mismatch at target offset 1 and two bases inserted after offset 4.
+        let target = b"ACGTACGTAC";
+        let query = b"AGGTAGGCGTAC";
+        let ops = parse_cigar("5M2I5M").unwrap();
+        let record =
+            PafRecord::from_line("q\t12\t0\t12\t+\tt\t200\t100\t110\t9\t12\t60\tcg:Z:5M2I5M")
+                .unwrap();
+        let windows = identity_windows(&record, &ops, query, target, 4, 3).unwrap();
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|w| {
+                (
+                    w.target_start,
+                    w.target_end,
+                    w.query_start,
+                    w.query_end,
+                    format!("{:.3}", w.identity),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (100, 104, 0, 4, "0.750".to_string()),
+                (103, 107, 3, 9, "0.667".to_string()),
+                (106, 110, 8, 12, "1.000".to_string()),
+            ]
+        );
+
+        // Query coordinates of - strand records are on the forward strand;
+        // a short alignment gets one window.
+        let record =
+            PafRecord::from_line("q\t12\t0\t12\t-\tt\t200\t100\t110\t9\t12\t60\tcg:Z:5M2I5M")
+                .unwrap();
+        let windows = identity_windows(&record, &ops, query, target, 20, 5).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!((windows[0].target_start, windows[0].target_end), (100, 110));
+        assert_eq!((windows[0].query_start, windows[0].query_end), (0, 12));
+        let windows = identity_windows(&record, &ops, query, target, 4, 3).unwrap();
+        assert_eq!((windows[0].query_start, windows[0].query_end), (8, 12));
+    }
+}
@@ -0,0 +1,646 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tempfile::NamedTempFile;
+
+fn create_temp_fasta(sequences: &[(&str, &str)]) -> Result<NamedTempFile> {
+    let mut temp_file = NamedTempFile::new()?;
+    for (name, seq) in sequences {
+        writeln!(temp_file, ">{}", name)?;
+        writeln!(temp_file, "{}", seq)?;
+    }
+    Ok(temp_file)
+}
+
+fn create_temp_paf(entries: &[&str]) -> Result<NamedTempFile> {
+    let mut temp_file = NamedTempFile::new()?;
+    for entry in entries {
+        writeln!(temp_file, "{}", entry)?;
+    }
+    Ok(temp_file)
+}
+
+/// Runs the pafcheck binary with `args`, feeding it `stdin` if given. The
+/// report of `check` goes to stdout.
+fn pafcheck(args: &[&str], stdin: Option<&str>) -> Result<Output> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pafcheck"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.unwrap_or_default().as_bytes())?;
+    drop(input);
+    Ok(child.wait_with_output()?)
+}
+
+fn path(file: &NamedTempFile) -> &str {
+    file.path().to_str().unwrap()
+}
+
+/// A query, a target that is the query without its first base, and a record
+/// aligning them with a leading insertion: a `BoundaryIndel` warning and
+/// nothing else.
+fn boundary_indel_inputs() -> Result<(NamedTempFile, NamedTempFile, NamedTempFile)> {
+    Ok((
+        create_temp_fasta(&[("q", "ACGTACGTAC")])?,
+        create_temp_fasta(&[("t", "CGTACGTAC")])?,
+        create_temp_paf(&["q\t10\t0\t10\t+\tt\t9\t0\t9\t9\t10\t60\tcg:Z:1I9="])?,
+    ))
+}
+
+#[test]
+fn test_warnings_pass_by_default() -> Result<()> {
+    let (query, target, paf) = boundary_indel_inputs()?;
+    let output = pafcheck(
+        &["-q", path(&query), "-t", path(&target), "-p", path(&paf)],
+        None,
+    )?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(
+        stdout.contains("Warning at line 1: BoundaryIndel: Alignment begins with a 1-bp insertion")
+    );
+    assert!(stdout.contains(
+        "[pafcheck] Warnings (use --warnings-as-errors to fail on them):\n[pafcheck]   - BoundaryIndel: 1 warnings\n"
+    ));
+    assert!(stdout.contains("completed successfully with 1 warnings"));
+    Ok(())
+}
+
+#[test]
+fn test_warnings_as_errors() -> Result<()> {
+    let (query, target, paf) = boundary_indel_inputs()?;
+    for option in ["--warnings-as-errors", "--strict", "--flag-boundary-indels"] {
+        let output = pafcheck(
+            &[
+                "-q",
+                path(&query),
+                "-t",
+                path(&target),
+                "-p",
+                path(&paf),
+                option,
+            ],
+            None,
+        )?;
+        let stdout = String::from_utf8(output.stdout)?;
+        assert_eq!(output.status.code(), Some(4), "{}: {}", option, stdout);
+        assert!(
+            stdout.contains("Error at line 1: BoundaryIndel"),
+            "{}",
+            stdout
+        );
+        assert!(!stdout.contains("Warnings"), "{}", stdout);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_self_alignment_warning() -> Result<()> {
+    let sequences = create_temp_fasta(&[("s", "ACGTACGTAC")])?;
+    let paf = create_temp_paf(&["s\t10\t0\t10\t+\ts\t10\t0\t10\t10\t10\t60\tcg:Z:10="])?;
+    let args = ["-q", path(&sequences), "-p", path(&paf)];
+    let output = pafcheck(&args, None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(
+        stdout.contains("[pafcheck]   - SelfAlignment: 1 warnings"),
+        "{}",
+        stdout
+    );
+
+    let output = pafcheck(&[&args[..], &["--self-alignments", "error"]].concat(), None)?;
+    assert_eq!(output.status.code(), Some(4));
+    Ok(())
+}
+
+#[test]
+fn test_max_terminal_indel() -> Result<()> {
+    let (query, target, paf) = boundary_indel_inputs()?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", path(&paf)];
+
+    // The 1-bp insertion is short enough to pass without a warning...
+    let output = pafcheck(&[&args[..], &["--max-terminal-indel", "1"]].concat(), None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(!stdout.contains("BoundaryIndel"), "{}", stdout);
+
+    // ...but not a longer one.
+    let output = pafcheck(&[&args[..], &["--max-terminal-indel", "0"]].concat(), None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    assert!(
+        stdout.contains("Error at line 1: BoundaryIndel"),
+        "{}",
+        stdout
+    );
+    Ok(())
+}
+
+#[test]
+fn test_sampled_errors_are_typed() -> Result<()> {
+    let query = create_temp_fasta(&[("q", "ACGTACGTAC")])?;
+    let target = create_temp_fasta(&[("t", "ACGTTCGTAC")])?;
+    let paf = create_temp_paf(&["q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\tcg:Z:10="])?;
+    let output = pafcheck(
+        &[
+            "-q",
+            path(&query),
+            "-t",
+            path(&target),
+            "-p",
+            path(&paf),
+            "--sample-bases",
+            "1",
+        ],
+        None,
+    )?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    assert!(
+        stdout.contains("[pafcheck]   - Mismatch: 1 errors\n[pafcheck] Total errors: 1\n"),
+        "{}",
+        stdout
+    );
+    Ok(())
+}
+
+#[test]
+fn test_converters_take_validation_options() -> Result<()> {
+    let query = create_temp_fasta(&[("q", "ACGTACGTAC")])?;
+    let target = create_temp_fasta(&[("t", "ACGTTCGTAC")])?;
+    let paf = create_temp_paf(&["q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\tcg:Z:10="])?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", path(&paf)];
+    for (subcommand, skipped) in [
+        ("to-sam", "Wrote 0 SAM records; 1 records skipped"),
+        ("to-maf", "Wrote 0 MAF blocks; 1 records skipped"),
+        ("to-vcf", "Wrote 0 variants of 0 records; 1 records skipped"),
+    ] {
+        let output = pafcheck(&[&[subcommand][..], &args].concat(), None)?;
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(output.status.success(), "{}", stderr);
+        assert!(stderr.contains(skipped), "{}", stderr);
+
+        // The false match is no error with --ignore mismatch, or without the
+        // mismatch check.
+        for options in [&["--ignore", "mismatch"][..], &["--checks=-mismatch"]] {
+            let output = pafcheck(&[&[subcommand][..], &args, options].concat(), None)?;
+            let stderr = String::from_utf8(output.stderr)?;
+            assert!(stderr.contains("; 0 records skipped"), "{}", stderr);
+        }
+    }
+
+    let output = pafcheck(
+        &[
+            "to-sam",
+            "-q",
+            path(&query),
+            "-t",
+            "refget:http://localhost:1",
+            "-p",
+            path(&paf),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn test_report_filters() -> Result<()> {
+    let query = create_temp_fasta(&[("q", "ACGTACGTAC")])?;
+    let target = create_temp_fasta(&[("t", "ACGTTCGTAA")])?;
+    let record = "q\t10\t0\t10\t+\tt\t10\t0\t10\t10\t10\t60\tcg:Z:10=";
+    let paf = create_temp_paf(&[record, record])?;
+    let store = NamedTempFile::new()?;
+    let output = pafcheck(
+        &[
+            "-q",
+            path(&query),
+            "-t",
+            path(&target),
+            "-p",
+            path(&paf),
+            "--error-store",
+            path(&store),
+        ],
+        None,
+    )?;
+    assert_eq!(output.status.code(), Some(4));
+
+    let report = |args: &[&str]| -> Result<String> {
+        let output = pafcheck(&[&["report"][..], args].concat(), None)?;
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(output.status.success(), "{:?}: {}", args, stderr);
+        Ok(String::from_utf8(output.stdout)?)
+    };
+    let query = ["query", "-s", path(&store)];
+    assert_eq!(
+        report(&[&query[..], &["--line", "2", "--region", "t:6-10"]].concat())?,
+        "Line 2: Mismatch at t:9 (query q:9)\n"
+    );
+    assert_eq!(
+        report(&[&query[..], &["--type", "mismatch", "--line", "1"]].concat())?
+            .lines()
+            .count(),
+        2
+    );
+    assert_eq!(
+        report(&[&query[..], &["--error-type", "Length"]].concat())?,
+        ""
+    );
+    assert_eq!(
+        report(&[&query[..], &["--region", "t:5", "--format", "bed"]].concat())?,
+        "t\t4\t5\tMismatch:q:4\nt\t4\t5\tMismatch:q:4\n"
+    );
+    assert_eq!(
+        report(&["view", "-s", path(&store), "t:1-5"])?
+            .lines()
+            .count(),
+        2
+    );
+
+    let output = pafcheck(&[&["report"][..], &query, &["--line", "x"]].concat(), None)?;
+    assert!(String::from_utf8(output.stderr)?.contains("Failed to parse --line"));
+    let output = pafcheck(&["report-view", "-s", path(&store), "t"], None)?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn test_threads_match_serial_run() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    // Passing records and records with different errors, interleaved over
+    // several batches of the workers.
+    let records: Vec<String> = (0..300)
+        .map(|i| {
+            let (target, cigar) = match i % 5 {
+                0 => ("u", "10="),
+                1 => ("t", "10="),
+                2 => ("u", "9="),
+                3 => ("u", "4=1X5="),
+                _ => ("t", "4=1X5="),
+            };
+            format!(
+                "q\t10\t0\t10\t+\t{}\t10\t0\t10\t10\t10\t60\tcg:Z:{}",
+                target, cigar
+            )
+        })
+        .collect();
+    let paf = create_temp_paf(&records.iter().map(String::as_str).collect::<Vec<_>>())?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", path(&paf)];
+    for options in [
+        &["--format", "text"][..],
+        &["--format", "tsv"],
+        &["--format", "json"],
+        &["--max-errors", "7"],
+    ] {
+        let run = |threads: &str| {
+            pafcheck(
+                &[&args[..], options, &["--threads", threads]].concat(),
+                None,
+            )
+        };
+        let serial = run("1")?;
+        assert_eq!(serial.status.code(), Some(4));
+        for threads in ["2", "8"] {
+            let parallel = run(threads)?;
+            assert_eq!(parallel.status.code(), serial.status.code());
+            assert_eq!(
+                String::from_utf8(parallel.stdout)?,
+                String::from_utf8(serial.stdout.clone())?,
+                "--threads {} {:?}",
+                threads,
+                options
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A query, and targets `t` (one base off the query) and `u` (identical).
+fn query_and_targets() -> Result<(NamedTempFile, NamedTempFile)> {
+    Ok((
+        create_temp_fasta(&[("q", "ACGTACGTAC")])?,
+        create_temp_fasta(&[("t", "ACGTTCGTAC"), ("u", "ACGTACGTAC")])?,
+    ))
+}
+
+/// A record aligning `q` to `target` without errors if it is `u`, and with
+/// one mismatch if it is `t`.
+fn record(target: &str) -> String {
+    format!(
+        "q\t10\t0\t10\t+\t{}\t10\t0\t10\t10\t10\t60\tcg:Z:10=",
+        target
+    )
+}
+
+#[test]
+fn test_exit_codes() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    for (records, code, message) in [
+        (vec![record("u")], 0, None),
+        (
+            vec![record("u"), "q\t10\tx".to_string()],
+            2,
+            Some("Failed to parse PAF record at line 2"),
+        ),
+        (
+            vec![record("u"), record("v"), record("t")],
+            3,
+            Some(
+                "PAF validation failed with 2 errors; sequences of 1 records could not be fetched",
+            ),
+        ),
+        (
+            vec![record("t"), record("u")],
+            4,
+            Some("PAF validation failed with 1 errors"),
+        ),
+    ] {
+        let paf = create_temp_paf(&records.iter().map(String::as_str).collect::<Vec<_>>())?;
+        let output = pafcheck(
+            &["-q", path(&query), "-t", path(&target), "-p", path(&paf)],
+            None,
+        )?;
+        let stderr = String::from_utf8(output.stderr)?;
+        assert_eq!(output.status.code(), Some(code), "{}", stderr);
+        if let Some(message) = message {
+            assert!(
+                stderr.contains(&format!("[pafcheck] Error: {}", message)),
+                "{}",
+                stderr
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_best_alignments_keep_the_report() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let short_cigar = "q\t10\t0\t10\t+\tu\t10\t0\t10\t10\t10\t60\tcg:Z:9=";
+    let paf = create_temp_paf(&[&record("t"), short_cigar, &record("u"), &record("v")])?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", path(&paf)];
+    let plain = pafcheck(&args, None)?;
+    let table = NamedTempFile::new()?;
+    let with_table = pafcheck(
+        &[&args[..], &["--best-alignments", path(&table)]].concat(),
+        None,
+    )?;
+    assert_eq!(with_table.status.code(), plain.status.code());
+    let report = String::from_utf8(with_table.stdout)?;
+    let report: Vec<&str> = report
+        .lines()
+        .filter(|line| !line.starts_with("[pafcheck] Wrote best alignment"))
+        .collect();
+    assert_eq!(report.join("\n") + "\n", String::from_utf8(plain.stdout)?);
+    // The record whose target can't be fetched has no identity.
+    let tsv = std::fs::read_to_string(table.path())?;
+    let row: Vec<&str> = tsv.lines().nth(1).unwrap().split('\t').collect();
+    assert_eq!(&row[..4], ["q", "3", "3", "u"], "{}", tsv);
+    Ok(())
+}
+
+#[test]
+fn test_subcommand_exit_codes() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    for (records, code) in [
+        (vec![record("u")], 0),
+        (vec![record("u"), "q\t10\tx".to_string()], 2),
+        (vec![record("u"), record("v")], 3),
+    ] {
+        let paf = create_temp_paf(&records.iter().map(String::as_str).collect::<Vec<_>>())?;
+        let output = pafcheck(
+            &[
+                "annotate",
+                "-q",
+                path(&query),
+                "-t",
+                path(&target),
+                "-p",
+                path(&paf),
+            ],
+            None,
+        )?;
+        let stderr = String::from_utf8(output.stderr)?;
+        assert_eq!(output.status.code(), Some(code), "{}", stderr);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_paf_from_stdin() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", "-"];
+    let output = pafcheck(&args, Some(&format!("{}\n{}\n", record("u"), record("t"))))?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    assert!(
+        stdout.contains("[pafcheck] Error at line 2: Mismatch"),
+        "{}",
+        stdout
+    );
+
+    let output = pafcheck(&args, Some(&format!("{}\n", record("u"))))?;
+    assert_eq!(output.status.code(), Some(0));
+    Ok(())
+}
+
+#[test]
+fn test_multiple_pafs() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let first = create_temp_paf(&[&record("u"), &record("t")])?;
+    let second = create_temp_paf(&[&record("t"), &record("t"), &record("u")])?;
+    let output = pafcheck(
+        &[
+            "-q",
+            path(&query),
+            "-t",
+            path(&target),
+            "-p",
+            path(&first),
+            "-p",
+            path(&second),
+        ],
+        None,
+    )?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    // Line numbers start over in each file.
+    assert!(stdout.contains(&format!(
+        "[pafcheck] Validating {}\n[pafcheck] Error at line 1: Mismatch",
+        path(&second)
+    )));
+    assert!(stdout.contains(&format!(
+        "[pafcheck]   - {}: 2 records, 1 errors\n[pafcheck]   - {}: 3 records, 2 errors\n",
+        path(&first),
+        path(&second)
+    )));
+    assert!(
+        stdout.contains("[pafcheck] Total errors: 3\n"),
+        "{}",
+        stdout
+    );
+    Ok(())
+}
+
+#[test]
+fn test_max_errors_and_limit() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let paf = create_temp_paf(&[&record("t"), &record("u"), &record("t"), &record("t")])?;
+    let args = ["-q", path(&query), "-t", path(&target), "-p", path(&paf)];
+
+    let output = pafcheck(&[&args[..], &["--max-errors", "2"]].concat(), None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    assert!(stdout.contains(&format!(
+        "[pafcheck] Stopped after 2 errors (--max-errors 2); records from line 4 of {} on were not checked",
+        path(&paf)
+    )));
+    assert!(
+        stdout.contains("[pafcheck] Total errors: 2\n"),
+        "{}",
+        stdout
+    );
+
+    let output = pafcheck(&[&args[..], &["--limit", "2"]].concat(), None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    assert!(stdout.contains("[pafcheck] Stopped after the first 2 records (--limit)"));
+    assert!(
+        stdout.contains("[pafcheck] Total errors: 1\n"),
+        "{}",
+        stdout
+    );
+
+    // The summary marks both as truncated.
+    for option in ["--max-errors", "--limit"] {
+        let output = pafcheck(
+            &[&args[..], &[option, "1", "--format", "json"]].concat(),
+            None,
+        )?;
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains("\"truncated\": true"), "{}", stdout);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sample_extrapolation() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let records = vec![record("t"); 100];
+    let paf = create_temp_paf(&records.iter().map(String::as_str).collect::<Vec<_>>())?;
+    let args = [
+        "-q",
+        path(&query),
+        "-t",
+        path(&target),
+        "-p",
+        path(&paf),
+        "--sample",
+        "0.5",
+    ];
+    let output = pafcheck(&args, None)?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    let sampled: usize = stdout
+        .split("[pafcheck] Sampled ")
+        .nth(1)
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap()
+        .parse()?;
+    assert!(sampled > 0 && sampled < 100, "{}", stdout);
+    // Every record has one error, so the extrapolation is exact.
+    assert!(stdout.contains(&format!(
+        "Sampled {} of 100 records (--sample 0.5): {} with errors",
+        sampled, sampled
+    )));
+    assert!(
+        stdout.contains("[pafcheck] Extrapolated errors in all 100 records: 100 ("),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(&format!("[pafcheck] Total errors: {}\n", sampled)));
+
+    // The same seed samples the same records.
+    assert_eq!(pafcheck(&args, None)?.stdout, stdout.as_bytes());
+    Ok(())
+}
+
+#[test]
+fn test_ndjson_output() -> Result<()> {
+    let (query, target) = query_and_targets()?;
+    let paf = create_temp_paf(&[&record("u"), &record("t")])?;
+    let output = pafcheck(
+        &[
+            "-q",
+            path(&query),
+            "-t",
+            path(&target),
+            "-p",
+            path(&paf),
+            "--format",
+            "ndjson",
+        ],
+        None,
+    )?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(4), "{}", stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(lines[0].starts_with(
+        "{\"line\":2,\"query\":\"q\",\"target\":\"t\",\"type\":\"Mismatch\",\"category\":\"correctness\",\"severity\":\"error\",\"count\":1,\"query_pos\":4,\"target_pos\":4,"
+    ), "{}", lines[0]);
+    assert!(lines[1].starts_with(
+        "{\"summary\": {\"records\": 2, \"total_errors\": 1, \"total_warnings\": 0, \"passed\": false, \"truncated\": false}, \"error_counts\": {\"Mismatch\": 1}, \"warning_counts\": {}, \"inputs\": [{\"role\": \"paf\""
+    ), "{}", lines[1]);
+    Ok(())
+}
+
+#[test]
+fn test_checks_help_lists_all_checks() -> Result<()> {
+    for subcommand in [&["check"][..], &["to-sam"]] {
+        let output = pafcheck(&[subcommand, &["--help"]].concat(), None)?;
+        let help = String::from_utf8(output.stdout)?
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(
+            help.contains("(mismatch, cigar-mismatch, length, coordinates, tags, cigar)"),
+            "{}",
+            help
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_doctor_reads_paf_from_stdin() -> Result<()> {
+    // The FASTAs and their indexes in a directory that is removed even if an
+    // assertion fails.
+    let dir = tempfile::tempdir()?;
+    let write = |name: &str, contents: &str| -> Result<String> {
+        let path = dir.path().join(name).to_str().unwrap().to_string();
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    };
+    let query = write("query.fa", ">q\nACGTACGTAC\n")?;
+    let target = write("target.fa", ">t\nACGTTCGTAC\n>u\nACGTACGTAC\n")?;
+    write("query.fa.fai", "q\t10\t3\t10\t11\n")?;
+    write("target.fa.fai", "t\t10\t3\t10\t11\nu\t10\t17\t10\t11\n")?;
+    let args = ["doctor", "-q", &query, "-t", &target];
+    let output = pafcheck(&args, Some(&format!("{}\n{}\n", record("t"), record("u"))))?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains("[pafcheck] OK   PAF is read from stdin\n"));
+    assert!(stdout.contains("[pafcheck] OK   PAF references 1 query and 2 target sequences\n"));
+
+    let output = pafcheck(&args, Some(&record("v")))?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("[pafcheck] FAIL 1 of 1 target names in the PAF are missing"));
+    Ok(())
+}
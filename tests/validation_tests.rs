@@ -1,7 +1,14 @@
 use anyhow::Result;
-use pafcheck::fasta_reader::MultiFastaReader;
-use pafcheck::paf_parser::PafRecord;
-use pafcheck::validator::validate_record;
+use pafcheck::bed::BedRegions;
+use pafcheck::fasta_reader::{MultiFastaReader, SequenceSource};
+use pafcheck::fix::{annotate_line, fix_line, FixOptions};
+use pafcheck::paf_parser::{negative_columns, PafRecord};
+use pafcheck::validator::{
+    alignment_stats, cigar_errors, column_errors, coordinate_errors, cs_errors, is_self_alignment,
+    policy_errors, spot_check_record, tag_errors, tag_syntax_errors, validate_record,
+    validate_record_with_options, CheckSet, ErrorType, LowercasePolicy, SelfAlignmentPolicy,
+    Severity, ValidationOptions,
+};
 use std::io::{BufWriter, Write};
 use tempfile::NamedTempFile;
 
@@ -31,7 +38,11 @@ fn test_mismatch_detection() -> Result<()> {
         target_length: 5,
         target_start: 0,
         target_end: 5,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
         cigar: "4=1X".to_string(),
+        tags: Vec::new(),
     };
 
     // Create MultiFastaReader
@@ -75,7 +86,11 @@ fn test_false_match_detection() -> Result<()> {
         target_length: 5,
         target_start: 0,
         target_end: 5,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
         cigar: "5=".to_string(),
+        tags: Vec::new(),
     };
 
     let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
@@ -118,7 +133,11 @@ fn test_false_mismatch_detection() -> Result<()> {
         target_length: 5,
         target_start: 0,
         target_end: 5,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
         cigar: "4=1X".to_string(),
+        tags: Vec::new(),
     };
 
     let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
@@ -161,7 +180,11 @@ fn test_mixed_match_mismatch_errors() -> Result<()> {
         target_length: 8,
         target_start: 0,
         target_end: 8,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
         cigar: "4=1X3=".to_string(),
+        tags: Vec::new(),
     };
 
     let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
@@ -188,3 +211,811 @@ fn test_mixed_match_mismatch_errors() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_spot_check_anchors() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTC")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+
+    let mut paf_record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 5,
+        query_start: 0,
+        query_end: 5,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 5,
+        target_start: 0,
+        target_end: 5,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "4=1X".to_string(),
+        tags: Vec::new(),
+    };
+    assert!(spot_check_record(&paf_record, &mut fasta_reader, 2)?);
+
+    // The last base is always an anchor, so a false match there is caught.
+    paf_record.cigar = "5=".to_string();
+    assert!(!spot_check_record(&paf_record, &mut fasta_reader, 0)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_excluded_region_skips_checks() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTC")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+
+    let paf_record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 5,
+        query_start: 0,
+        query_end: 5,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 5,
+        target_start: 0,
+        target_end: 5,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "5=".to_string(),
+        tags: Vec::new(),
+    };
+    let options = ValidationOptions {
+        exclude_regions: Some(BedRegions::from_lines(["target1\t4\t5"])?),
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    let result = validate_record_with_options(
+        &paf_record,
+        &mut fasta_reader,
+        "omit",
+        &options,
+        &mut output,
+    );
+    assert!(
+        result.is_ok(),
+        "Expected the masked mismatch to be skipped, but got: {:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_spliced_alignment_splice_sites() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("read1", "ACGTACGT")])?;
+    // ACGT + GTCCAG intron + ACGT, then the same with a GGCCTT intron
+    let target_fasta = create_temp_fasta(&[
+        ("canonical", "ACGTGTCCAGACGT"),
+        ("noncanonical", "ACGTGGCCTTACGT"),
+    ])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let options = ValidationOptions {
+        check_splice_sites: true,
+        ..Default::default()
+    };
+
+    for (target, expect_ok) in [("canonical", true), ("noncanonical", false)] {
+        let record = PafRecord {
+            query_name: "read1".to_string(),
+            query_length: 8,
+            query_start: 0,
+            query_end: 8,
+            strand: '+',
+            target_name: target.to_string(),
+            target_length: 14,
+            target_start: 0,
+            target_end: 14,
+            residue_matches: 0,
+            block_length: 0,
+            mapq: Some(60),
+            cigar: "4=6N4=".to_string(),
+            tags: Vec::new(),
+        };
+        let mut output = Vec::new();
+        let result =
+            validate_record_with_options(&record, &mut fasta_reader, "omit", &options, &mut output);
+        assert_eq!(
+            result.is_ok(),
+            expect_ok,
+            "unexpected result for {}",
+            target
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lowercase_policy() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "acgtacgt")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '-',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "8=".to_string(),
+        tags: Vec::new(),
+    };
+
+    for (policy, expected) in [
+        ("ignore", None),
+        ("mismatch", Some("Mismatch:")),
+        ("warn", Some("MaskedAlignment: Query span query1:0-8")),
+    ] {
+        let options = ValidationOptions {
+            lowercase: policy.parse()?,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        validate_record_with_options(&record, &mut fasta_reader, "report", &options, &mut output)?;
+        let output = String::from_utf8(output)?;
+        match expected {
+            None => assert!(output.is_empty(), "{}: {}", policy, output),
+            Some(prefix) => assert!(output.starts_with(prefix), "{}: {}", policy, output),
+        }
+    }
+    assert!("upper".parse::<LowercasePolicy>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_n_policy() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTNCGT")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let mut record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: String::new(),
+        tags: Vec::new(),
+    };
+
+    for (cigar, policy, expected) in [
+        ("8=", "mismatch", Some("Mismatch:")),
+        ("8=", "match-any", None),
+        ("8=", "skip", None),
+        ("4=1X3=", "mismatch", None),
+        ("4=1X3=", "match-any", Some("CigarMismatch:")),
+        ("4=1X3=", "skip", None),
+    ] {
+        record.cigar = cigar.to_string();
+        let options = ValidationOptions {
+            n_policy: policy.parse()?,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        validate_record_with_options(&record, &mut fasta_reader, "report", &options, &mut output)?;
+        let output = String::from_utf8(output)?;
+        match expected {
+            None => assert!(output.is_empty(), "{} {}: {}", cigar, policy, output),
+            Some(prefix) => assert!(
+                output.starts_with(prefix),
+                "{} {}: {}",
+                cigar,
+                policy,
+                output
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_min_base_quality() -> Result<()> {
+    let mut query_fastq = tempfile::Builder::new().suffix(".fq").tempfile()?;
+    writeln!(query_fastq, "@query1\nACGTACGT\n+\nIIII#III")?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTTCGA")])?;
+    let mut fasta_reader = MultiFastaReader::from_sources(
+        SequenceSource::open(query_fastq.path().to_str().unwrap(), None)?,
+        SequenceSource::open(target_fasta.path().to_str().unwrap(), None)?,
+    );
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 8,
+        block_length: 8,
+        mapq: Some(60),
+        cigar: "8=".to_string(),
+        tags: Vec::new(),
+    };
+
+    let options = ValidationOptions {
+        min_base_quality: Some(10),
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    validate_record_with_options(&record, &mut fasta_reader, "report", &options, &mut output)?;
+    let output = String::from_utf8(output)?;
+    // The mismatch at position 4 is on a base of quality 2, the one at
+    // position 7 on a base of quality 40.
+    assert!(
+        output.contains("LowQualityMismatch: CIGAR mismatch at operation 0: query char 'A' at pos 4 vs target char 'T' at pos 4 (query base quality 2)"),
+        "{}",
+        output
+    );
+    assert!(
+        output.lines().any(|line| line
+            .starts_with("Mismatch: CIGAR mismatch at operation 0: query char 'T' at pos 7")),
+        "{}",
+        output
+    );
+    Ok(())
+}
+
+#[test]
+fn test_require_extended_cigar() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTACGT")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTTCGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 7,
+        block_length: 8,
+        mapq: Some(60),
+        cigar: "8M".to_string(),
+        tags: vec!["NM:i:1".to_string()],
+    };
+    assert!(column_errors(&record).is_empty());
+    assert!(tag_errors(&record, 0.01).is_empty());
+
+    let mut output = Vec::new();
+    validate_record(&record, &mut fasta_reader, "report", &mut output)?;
+    assert!(output.is_empty(), "{}", String::from_utf8(output)?);
+
+    let options = ValidationOptions {
+        require_extended_cigar: true,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    validate_record_with_options(&record, &mut fasta_reader, "report", &options, &mut output)?;
+    assert!(String::from_utf8(output)?.starts_with("NonExtendedCigar: M operation at operation 0"));
+
+    // The NM:i of an M CIGAR is checked against the sequences.
+    let record = PafRecord {
+        tags: vec!["NM:i:0".to_string()],
+        ..record
+    };
+    let mut output = Vec::new();
+    validate_record(&record, &mut fasta_reader, "report", &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        "TagMismatch: NM:i is 0, but the alignment has an edit distance of 1\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_min_identity() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTACGTAC")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTTCGTAC")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let record =
+        PafRecord::from_line("query1\t10\t0\t10\t+\ttarget1\t10\t0\t10\t9\t10\t60\tcg:Z:10M")?;
+
+    for (min_identity, expected) in [(0.9, ""), (0.95, "LowIdentity: Identity 0.9000 is below the minimum of 0.95: 9 identical bases in 10 alignment columns\n")] {
+        let options = ValidationOptions {
+            min_identity: Some(min_identity),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        validate_record_with_options(&record, &mut fasta_reader, "report", &options, &mut output)?;
+        assert_eq!(String::from_utf8(output)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cigar_overrun() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTACGT")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let mut record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "6=4I".to_string(),
+        tags: Vec::new(),
+    };
+
+    let mut output = Vec::new();
+    validate_record(&record, &mut fasta_reader, "report", &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        "CigarOverrunsQuery: CIGAR overruns the query at operation 1 (4I): it reaches 10 of the 8 bases of query1:0-8\n"
+    );
+
+    record.cigar = "10=".to_string();
+    let mut output = Vec::new();
+    validate_record(&record, &mut fasta_reader, "report", &mut output)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("CigarOverrunsQuery:"), "{}", output);
+    assert!(output.contains("CigarOverrunsTarget:"), "{}", output);
+    assert!(!output.contains("LengthMismatch"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_cs_tag_validation() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTTCGTA")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let line = "query1\t9\t0\t9\t+\ttarget1\t9\t0\t9\t7\t10\t60\tcs:Z:";
+    for (cs, expected) in [
+        (":4*at:2-g:1+a", None),
+        (":9", Some("Mismatch:")),
+        (
+            ":4*at:2-c:1+a",
+            Some("CsBaseMismatch: cs segment 3 has target bases c"),
+        ),
+        (":4*at:2-g:2", Some("Cigar")),
+    ] {
+        let record = PafRecord::from_line(&format!("{}{}", line, cs))?;
+        let mut output = Vec::new();
+        validate_record(&record, &mut fasta_reader, "report", &mut output)?;
+        let output = String::from_utf8(output)?;
+        match expected {
+            None => assert!(output.is_empty(), "{}: {}", cs, output),
+            Some(prefix) => assert!(output.starts_with(prefix), "{}: {}", cs, output),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_boundary_indel_policy() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTACGT")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGTAA")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 10,
+        target_start: 0,
+        target_end: 10,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "8=2D".to_string(),
+        tags: Vec::new(),
+    };
+
+    let mut output = Vec::new();
+    let lenient = ValidationOptions {
+        max_terminal_indel: Some(2),
+        ..Default::default()
+    };
+    assert!(validate_record_with_options(
+        &record,
+        &mut fasta_reader,
+        "omit",
+        &lenient,
+        &mut output
+    )
+    .is_ok());
+
+    let strict = ValidationOptions {
+        flag_boundary_indels: true,
+        ..Default::default()
+    };
+    let err =
+        validate_record_with_options(&record, &mut fasta_reader, "omit", &strict, &mut output)
+            .unwrap_err();
+    assert!(err.to_string().contains("BoundaryIndel"));
+    Ok(())
+}
+
+#[test]
+fn test_clip_consistency() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "TTACGTACGTGG")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGT")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let mut record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 12,
+        query_start: 2,
+        query_end: 10,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 8,
+        target_start: 0,
+        target_end: 8,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "2S8=2H".to_string(),
+        tags: Vec::new(),
+    };
+
+    let mut output = Vec::new();
+    assert!(validate_record(&record, &mut fasta_reader, "omit", &mut output).is_ok());
+
+    // A converter that dropped the hard clip from the declared length
+    record.query_length = 10;
+    let err = validate_record(&record, &mut fasta_reader, "omit", &mut output).unwrap_err();
+    assert!(err.to_string().contains("ClipMismatch"));
+    Ok(())
+}
+
+#[test]
+fn test_selected_checks() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTTCGT")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGTAA")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    // One false match at position 4, and a target span two bases longer than the CIGAR.
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 8,
+        query_start: 0,
+        query_end: 8,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 10,
+        target_start: 0,
+        target_end: 10,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "8=".to_string(),
+        tags: Vec::new(),
+    };
+    let validate = |checks: &str, fasta_reader: &mut MultiFastaReader| {
+        let options = ValidationOptions {
+            checks: CheckSet::parse(checks).unwrap(),
+            ..Default::default()
+        };
+        validate_record_with_options(&record, fasta_reader, "omit", &options, &mut Vec::new())
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default()
+    };
+
+    let all = validate("all", &mut fasta_reader);
+    assert!(all.contains("Mismatch") && all.contains("LengthMismatch"));
+    let mismatch_only = validate("mismatch", &mut fasta_reader);
+    assert!(mismatch_only.contains("Mismatch") && !mismatch_only.contains("LengthMismatch"));
+    let without_length = validate("-length,-mismatch", &mut fasta_reader);
+    assert!(without_length.is_empty());
+
+    assert!(CheckSet::parse("mismatch,bogus").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_error_type_names() {
+    for error_type in ErrorType::ALL {
+        assert_eq!(
+            error_type.cli_name().parse::<ErrorType>().unwrap(),
+            error_type
+        );
+    }
+    assert_eq!(ErrorType::CigarMismatch.cli_name(), "cigar-mismatch");
+    assert_eq!(
+        "InconsistentQueryLength".parse::<ErrorType>().unwrap(),
+        ErrorType::InconsistentQueryLength
+    );
+    assert!("cigar_mismatch".parse::<ErrorType>().is_err());
+}
+
+#[test]
+fn test_coordinate_sanity() -> Result<()> {
+    let record = PafRecord::from_line("q1\t100\t50\t40\t+\tt1\t200\t10\t210\t0\t0\t60")?;
+    let errors: Vec<ErrorType> = coordinate_errors(&record)
+        .into_iter()
+        .map(|(error_type, _)| error_type)
+        .collect();
+    assert_eq!(
+        errors,
+        [ErrorType::InvalidInterval, ErrorType::CoordinateOutOfRange]
+    );
+    let record = PafRecord::from_line("q1\t100\t0\t100\t+\tt1\t200\t10\t110\t0\t0\t60")?;
+    assert!(coordinate_errors(&record).is_empty());
+    assert_eq!(record.mapq, Some(60));
+    let record = PafRecord::from_line("q1\t100\t0\t100\t+\tt1\t200\t10\t110\t0\t0\t256")?;
+    assert_eq!(record.mapq, None);
+
+    let line = "q1\t100\t-5\t40\t+\tt1\t200\t10\t-1\t0\t0\t60";
+    assert!(PafRecord::from_line(line).is_err());
+    assert_eq!(
+        negative_columns(line),
+        [("query start", "-5"), ("target end", "-1")]
+    );
+    assert!(negative_columns("q1\t100\tx\t40\t+\tt1\t200\t10\t-\t0\t0\t60").is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_self_alignment() -> Result<()> {
+    for (line, expected) in [
+        ("c1\t100\t0\t50\t+\tc1\t100\t40\t90\t50\t50\t60", true),
+        ("c1\t100\t0\t50\t-\tc1\t100\t0\t50\t50\t50\t60", true),
+        // A repeat copy elsewhere in the same sequence is a genuine alignment.
+        ("c1\t100\t0\t50\t+\tc1\t100\t50\t100\t50\t50\t60", false),
+        ("c1\t100\t0\t50\t+\tc2\t100\t0\t50\t50\t50\t60", false),
+    ] {
+        assert_eq!(
+            is_self_alignment(&PafRecord::from_line(line)?),
+            expected,
+            "{}",
+            line
+        );
+    }
+    assert_eq!(
+        "skip".parse::<SelfAlignmentPolicy>()?,
+        SelfAlignmentPolicy::Skip
+    );
+    assert!("ignore".parse::<SelfAlignmentPolicy>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_policy_errors() -> Result<()> {
+    let record = PafRecord::from_line("q1\t1000\t100\t400\t+\tt1\t500\t0\t300\t290\t300\t60")?;
+    assert!(policy_errors(&record, None, None).is_empty());
+    assert!(policy_errors(&record, Some(300), Some(0.3)).is_empty());
+    let errors: Vec<ErrorType> = policy_errors(&record, Some(301), Some(0.5))
+        .into_iter()
+        .map(|(error_type, _)| error_type)
+        .collect();
+    assert_eq!(
+        errors,
+        [ErrorType::ShortBlock, ErrorType::LowRecordQueryCoverage]
+    );
+    assert!(errors.iter().all(ErrorType::is_policy));
+    assert!(!ErrorType::Mismatch.is_policy());
+    Ok(())
+}
+
+#[test]
+fn test_column_errors() -> Result<()> {
+    let record = PafRecord::from_line("q1\t12\t0\t12\t+\tt1\t12\t0\t12\t11\t12\t60\tcg:Z:6=1X5=")?;
+    assert!(column_errors(&record).is_empty());
+    let record = PafRecord::from_line("q1\t12\t0\t12\t+\tt1\t12\t0\t12\t12\t12\t60\tcg:Z:6=1X5=")?;
+    let errors = column_errors(&record);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, ErrorType::MatchCountMismatch);
+    // A truncated CIGAR falls short of the block length.
+    let record = PafRecord::from_line("q1\t12\t0\t12\t+\tt1\t12\t0\t12\t6\t12\t60\tcg:Z:6=")?;
+    let errors = column_errors(&record);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, ErrorType::BlockLengthMismatch);
+    Ok(())
+}
+
+#[test]
+fn test_non_normalized_cigar() -> Result<()> {
+    let line = "q1\t12\t0\t12\t+\tt1\t12\t0\t12\t12\t12\t60\tcg:Z:";
+    for (cigar, expected) in [
+        ("2H3S12=4S", 0),
+        ("5=3=4=", 2),
+        ("6=2S6=", 1),
+        ("3S2H12=", 1),
+        ("12=1H1S", 1),
+    ] {
+        let record = PafRecord::from_line(&format!("{}{}", line, cigar))?;
+        let errors = cigar_errors(&record);
+        assert_eq!(errors.len(), expected, "{}: {:?}", cigar, errors);
+        assert!(errors
+            .iter()
+            .all(|(error_type, _)| *error_type == ErrorType::NonNormalizedCigar));
+    }
+    let record = PafRecord::from_line(&format!("{}5=3=4=", line))?;
+    assert_eq!(
+        cigar_errors(&record)[0].1,
+        "Operations 0 and 1 (5=3=) have the same type and should be merged"
+    );
+    // A legal CIGAR: reported, but as a warning.
+    assert_eq!(ErrorType::NonNormalizedCigar.severity(), Severity::Warning);
+    assert_eq!(ErrorType::MalformedCigar.severity(), Severity::Error);
+    assert_eq!(ErrorType::ConflictingPrimary.severity(), Severity::Error);
+    assert_eq!(ErrorType::OrphanSecondary.severity(), Severity::Error);
+    Ok(())
+}
+
+#[test]
+fn test_nm_tag() -> Result<()> {
+    let line = "q1\t12\t0\t12\t+\tt1\t13\t0\t13\t10\t16\t60\tNM:i:4\tcg:Z:6=1X2I4=3D";
+    let record = PafRecord::from_line(line)?;
+    assert_eq!(record.tag("NM:i"), Some("4"));
+    assert_eq!(record.tag("cg:Z"), Some("6=1X2I4=3D"));
+    assert_eq!(record.tag("de:f"), None);
+    let errors = tag_errors(&record, 0.01);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, ErrorType::TagMismatch);
+    assert_eq!(
+        errors[0].1,
+        "NM:i is 4, but the CIGAR has an edit distance of 6"
+    );
+    let record = PafRecord::from_line(&line.replace("NM:i:4", "NM:i:6"))?;
+    assert!(tag_errors(&record, 0.01).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_identity_tags() -> Result<()> {
+    // 10 identical of 11 aligned columns, one 2-bp insertion and one 3-bp
+    // deletion: gap-compressed identity 10/13, block identity 10/16.
+    let line = "q1\t12\t0\t12\t+\tt1\t13\t0\t13\t10\t16\t60\tgi:f:0.7692\tbi:f:0.625\tde:f:0.2308\tcg:Z:6=1X2I4=3D";
+    let record = PafRecord::from_line(line)?;
+    assert!(tag_errors(&record, 0.001).is_empty());
+    let record = PafRecord::from_line(&line.replace("de:f:0.2308", "de:f:0.1"))?;
+    let errors = tag_errors(&record, 0.01);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].1.starts_with("de:f is 0.1"));
+    assert!(tag_errors(&record, 0.2).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_cs_against_cigar() -> Result<()> {
+    let line = "q1\t12\t0\t12\t+\tt1\t12\t0\t12\t11\t12\t60\tcs:Z:";
+    for (tags, expected) in [
+        (":6*ag:5\tcg:Z:6=1X5=", None),
+        (":6*ag:5\tcg:Z:1S12M", None),
+        (":6*ag:5\tcg:Z:7=1X4=", Some("cs:Z and cg:Z differ at query offset 6 and target offset 6: cs:Z has X where cg:Z has =")),
+        (":6*ag:5\tcg:Z:6=1X4=", Some("cs:Z covers 12 query and 12 target bases, but cg:Z covers 11 and 11")),
+        (":6*ag:5", None),
+    ] {
+        let record = PafRecord::from_line(&format!("{}{}", line, tags))?;
+        let errors = cs_errors(&record);
+        match expected {
+            None => assert!(errors.is_empty(), "{}: {:?}", tags, errors),
+            Some(message) => {
+                assert_eq!(errors.len(), 1, "{}", tags);
+                assert_eq!(errors[0].0, ErrorType::CsCigarMismatch);
+                assert_eq!(errors[0].1, message);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_tag_syntax() -> Result<()> {
+    let line = "q1\t12\t0\t12\t+\tt1\t12\t0\t12\t12\t12\t60\t";
+    let valid = "tp:A:P\tNM:i:-3\tde:f:1.5e-3\tcs:Z:a b\tXH:H:1AFF\tXB:B:i,1,-2\tcg:Z:12=";
+    let record = PafRecord::from_line(&format!("{}{}", line, valid))?;
+    assert!(tag_syntax_errors(&record).is_empty());
+
+    let invalid = "tp:A:PS\tNM:i:1.0\tde:f:nan\tXH:H:ABC\tXB:B:q,1\tNM:i:3\tcg:12=\tX:Z:a\tXY:Q:1";
+    let record = PafRecord::from_line(&format!("{}{}", line, invalid))?;
+    let errors = tag_syntax_errors(&record);
+    assert!(errors
+        .iter()
+        .all(|(error_type, _)| *error_type == ErrorType::TagSyntax));
+    let messages: Vec<&str> = errors.iter().map(|(_, message)| message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Column 13 has a value that isn't of type A: tp:A:PS",
+            "Column 14 has a value that isn't of type i: NM:i:1.0",
+            "Column 15 has a value that isn't of type f: de:f:nan",
+            "Column 16 has a value that isn't of type H: XH:H:ABC",
+            "Column 17 has a value that isn't of type B: XB:B:q,1",
+            "Column 18 repeats the tag NM",
+            "Column 19 is not TAG:TYPE:VALUE: cg:12=",
+            "Column 20 has an invalid tag name: X:Z:a",
+            "Column 21 has an unknown type Q: XY:Q:1",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_alignment_stats_identities() -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("query1", "ACGTTCGTAA")])?;
+    let target_fasta = create_temp_fasta(&[("target1", "ACGTACGTCCC")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let record = PafRecord {
+        query_name: "query1".to_string(),
+        query_length: 10,
+        query_start: 0,
+        query_end: 10,
+        strand: '+',
+        target_name: "target1".to_string(),
+        target_length: 11,
+        target_start: 0,
+        target_end: 11,
+        residue_matches: 0,
+        block_length: 0,
+        mapq: Some(60),
+        cigar: "4=1X3=2I3D".to_string(),
+        tags: Vec::new(),
+    };
+    let stats = alignment_stats(&record, &mut fasta_reader)?;
+    assert_eq!((stats.identical, stats.aligned), (7, 8));
+    assert!((stats.blast_identity() - 7.0 / 13.0).abs() < 1e-9);
+    assert!((stats.gap_compressed_identity() - 0.7).abs() < 1e-9);
+    Ok(())
+}
+
+/// Rewrites tests/data/roundtrip.paf line by line and compares the result
+/// with the golden file `expected`.
+fn assert_round_trip(
+    expected: &str,
+    mut rewrite: impl FnMut(&str, &PafRecord, &mut MultiFastaReader) -> Result<Vec<String>>,
+) -> Result<()> {
+    let query_fasta = create_temp_fasta(&[("q1", "ACGTTTTACGTA")])?;
+    let target_fasta = create_temp_fasta(&[("t1", "ACGTTTAACGTA")])?;
+    let mut fasta_reader = MultiFastaReader::new(query_fasta.path(), target_fasta.path())?;
+    let data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let mut output = String::new();
+    for line in std::fs::read_to_string(data.join("roundtrip.paf"))?.lines() {
+        let record = PafRecord::from_line(line)?;
+        for line in rewrite(line, &record, &mut fasta_reader)? {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    assert_eq!(output, std::fs::read_to_string(data.join(expected))?);
+    Ok(())
+}
+
+#[test]
+fn test_fix_round_trip() -> Result<()> {
+    // Records that need no fix come back byte for byte, and fixed ones keep
+    // their other fields and the order of their tags.
+    assert_round_trip("roundtrip.fixed.paf", |line, record, fasta_reader| {
+        Ok(fix_line(line, record, fasta_reader, &FixOptions::default())?.0)
+    })
+}
+
+#[test]
+fn test_annotate_round_trip() -> Result<()> {
+    assert_round_trip("roundtrip.annotated.paf", |line, record, fasta_reader| {
+        Ok(vec![annotate_line(line, record, fasta_reader)?])
+    })
+}